@@ -0,0 +1,263 @@
+//! Node.js bindings of the dupefiles scanning engine
+//!
+//! Built with napi-rs into a native module, for Electron file managers and backup
+//! dashboards to find duplicates without running the command:
+//!
+//! ```js
+//! const { scan, summarize } = require('dupefiles')
+//!
+//! const groups = await scan('/home/alice/Pictures', { extensions: ['jpg', 'png'] }, (progress) => {
+//!   console.log(`${progress.phase}: ${progress.filesDone}/${progress.filesTotal} files`)
+//! })
+//! console.log(`${summarize(groups, '/home/alice/Pictures').wastedBytes} bytes wasted`)
+//! ```
+//!
+//! Scans run on the libuv thread pool, so the event loop keeps running while they
+//! hash. Paths that are not valid UTF-8 are passed to JavaScript lossily.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use napi::bindgen_prelude::AsyncTask;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, Error, Result, Task};
+use napi_derive::napi;
+use dupefiles::find_duplicates::find_duplicates;
+use dupefiles::localize::message;
+use dupefiles::scan_progress::{ScanPhase, ScanProgress, PROGRESS_INTERVAL};
+use dupefiles::scan_summary::summarize as summarize_groups;
+
+/// The device reports are written to, since the groups are returned instead
+#[cfg(windows)]
+const NULL_DEVICE: &str = "NUL";
+#[cfg(not(windows))]
+const NULL_DEVICE: &str = "/dev/null";
+
+/// The options of a scan, all optional.
+#[napi(object)]
+#[derive(Default)]
+pub struct ScanOptions {
+    /// Only scan files with one of these extensions, such as `['jpg', 'png']`.
+    pub extensions: Option<Vec<String>>,
+    /// Skip files with one of these extensions.
+    pub exclude_extensions: Option<Vec<String>>,
+    /// Skip files smaller than this many bytes (1 by default, skipping empty files).
+    pub min_size: Option<i64>,
+    /// Only return groups with at least this many copies.
+    pub min_group_size: Option<u32>,
+    /// Compare MP3 and FLAC files by their audio frames only, ignoring tags.
+    pub audio_content: Option<bool>,
+    /// Compare images by their pixel data only, ignoring metadata.
+    pub image_content: Option<bool>,
+    /// Compare email messages by their Message-ID and body only.
+    pub email_content: Option<bool>,
+    /// Compare text files ignoring line endings, a byte order mark and trailing whitespace.
+    pub normalize_text: Option<bool>,
+    /// Also compare the files stored in zip, tar and tar.gz archives.
+    pub scan_archives: Option<bool>,
+    /// Skip the directories on network file systems, except the one scanned.
+    pub skip_network: Option<bool>,
+    /// Stop hashing after this many files, returning the groups found so far.
+    pub max_files: Option<u32>,
+    /// Stop hashing after this many seconds, returning the groups found so far.
+    pub max_seconds: Option<f64>,
+}
+
+impl ScanOptions {
+    /// The options of the engine these options stand for
+    fn to_engine(&self) -> Result<dupefiles::scan_options::ScanOptions> {
+        let lowercase = |extensions: &Vec<String>| extensions.iter().map(|ext| ext.trim_start_matches('.').to_lowercase()).collect();
+        let max_runtime = self.max_seconds
+            .map(|seconds| Duration::try_from_secs_f64(seconds).map_err(|e| Error::from_reason(message("binding-invalid-max-seconds", &[("error", &e)]))))
+            .transpose()?;
+        Ok(dupefiles::scan_options::ScanOptions {
+            extensions: self.extensions.as_ref().map(lowercase),
+            exclude_extensions: self.exclude_extensions.as_ref().map(lowercase),
+            min_size: u64::try_from(self.min_size.unwrap_or(1)).map_err(|_| Error::from_reason(message("binding-invalid-min-size", &[])))?,
+            min_group_size: self.min_group_size.unwrap_or(2) as usize,
+            audio_content: self.audio_content.unwrap_or(false),
+            image_content: self.image_content.unwrap_or(false),
+            email_content: self.email_content.unwrap_or(false),
+            normalize_text: self.normalize_text.unwrap_or(false),
+            scan_archives: self.scan_archives.unwrap_or(false),
+            skip_network: self.skip_network.unwrap_or(false),
+            max_files: self.max_files.map(|max_files| max_files as usize),
+            max_runtime,
+            ..Default::default()
+        })
+    }
+}
+
+/// A set of files with the same content.
+#[napi(object)]
+pub struct DuplicateGroup {
+    /// A short identifier of the group, the same in every scan for the same content.
+    pub id: String,
+    /// The SHA256 hash of the content, prefixed by the content mode when one is used,
+    /// such as `audio:`.
+    pub hash: String,
+    /// The size in bytes of the first copy.
+    pub size: i64,
+    /// The copies, in the order they were found.
+    pub files: Vec<String>,
+    /// The copies sharing all their data with the first one on disk, such as reflinks,
+    /// so removing them frees no space.
+    pub reflinked: Vec<String>,
+    /// Bytes freed by keeping the first copy only.
+    pub wasted_bytes: i64,
+}
+
+impl From<&dupefiles::duplicate_group::DuplicateGroup> for DuplicateGroup {
+    fn from(group: &dupefiles::duplicate_group::DuplicateGroup) -> Self {
+        let paths = |paths: &[PathBuf]| paths.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        DuplicateGroup {
+            id: group.id(),
+            hash: group.hash.clone(),
+            size: group.size as i64,
+            files: paths(&group.files),
+            reflinked: paths(&group.reflinked),
+            wasted_bytes: group.wasted_bytes() as i64,
+        }
+    }
+}
+
+impl From<&DuplicateGroup> for dupefiles::duplicate_group::DuplicateGroup {
+    fn from(group: &DuplicateGroup) -> Self {
+        dupefiles::duplicate_group::DuplicateGroup {
+            hash: group.hash.clone(),
+            size: group.size.max(0) as u64,
+            files: group.files.iter().map(PathBuf::from).collect(),
+            reflinked: group.reflinked.iter().map(PathBuf::from).collect(),
+            digests: Default::default(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// How far a scan has progressed, passed to the progress callback of `scan` about
+/// once a second.
+#[napi(object)]
+pub struct Progress {
+    /// `walking` while the files are listed, then `hashing`.
+    pub phase: String,
+    /// Files hashed so far.
+    pub files_done: u32,
+    /// Files to hash, or found so far while walking.
+    pub files_total: u32,
+    /// Bytes hashed so far.
+    pub bytes_hashed: i64,
+    /// Bytes to hash, or found so far while walking.
+    pub bytes_total: i64,
+    /// The file being walked or hashed.
+    pub current_path: String,
+    /// Seconds the hashing is expected to take still, once known.
+    pub eta_seconds: Option<f64>,
+}
+
+impl From<&ScanProgress> for Progress {
+    fn from(progress: &ScanProgress) -> Self {
+        Progress {
+            phase: match progress.phase {
+                ScanPhase::Walking => "walking",
+                ScanPhase::Hashing => "hashing",
+            }.to_string(),
+            files_done: progress.files_done as u32,
+            files_total: progress.files_total as u32,
+            bytes_hashed: progress.bytes_hashed as i64,
+            bytes_total: progress.bytes_total as i64,
+            current_path: progress.current_path.to_string_lossy().into_owned(),
+            eta_seconds: progress.eta().map(|eta| eta.as_secs_f64()),
+        }
+    }
+}
+
+/// The totals of a set of duplicate groups.
+#[napi(object)]
+pub struct Summary {
+    /// Number of groups.
+    pub duplicate_groups: u32,
+    /// Number of copies besides the first of each group.
+    pub duplicate_files: u32,
+    /// Bytes freed by keeping a single copy of every group.
+    pub wasted_bytes: i64,
+    /// Bytes wasted under each directory directly below the root, or the root itself
+    /// for the copies directly in it.
+    pub wasted_by_directory: HashMap<String, i64>,
+}
+
+/// A scan run on the libuv thread pool
+pub struct ScanTask {
+    directory: PathBuf,
+    options: dupefiles::scan_options::ScanOptions,
+    on_progress: Option<ThreadsafeFunction<Progress, ErrorStrategy::Fatal>>,
+}
+
+impl Task for ScanTask {
+    type Output = Vec<dupefiles::duplicate_group::DuplicateGroup>;
+    type JsValue = Vec<DuplicateGroup>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = dupefiles::scan_options::ScanOptions { shared_progress: Some(progress.clone()), ..self.options.clone() };
+        let mut null_output = File::options().write(true).open(NULL_DEVICE)?;
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let reporter = self.on_progress.as_ref().map(|on_progress| scope.spawn(|| {
+                loop {
+                    thread::park_timeout(PROGRESS_INTERVAL);
+                    let finished = done.load(Ordering::Relaxed);
+                    let event = Progress::from(&*progress.lock().unwrap_or_else(|e| e.into_inner()));
+                    on_progress.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+                    if finished {
+                        break;
+                    }
+                }
+            }));
+            let groups = find_duplicates(&self.directory, &options, Some(&mut null_output));
+            done.store(true, Ordering::Relaxed);
+            if let Some(reporter) = reporter {
+                reporter.thread().unpark();
+            }
+            groups
+        }).map_err(|e| Error::from_reason(format!("{:#}", e)))
+    }
+
+    fn resolve(&mut self, _env: Env, groups: Self::Output) -> Result<Self::JsValue> {
+        Ok(groups.iter().map(DuplicateGroup::from).collect())
+    }
+}
+
+/// Finds the duplicate files below a directory.
+///
+/// Resolves to the groups of files with the same content, each listing its copies in
+/// the order they were found. `onProgress` is called with a `Progress` about once a
+/// second while the scan runs, and once more when it ends.
+#[napi(ts_return_type = "Promise<Array<DuplicateGroup>>")]
+pub fn scan(
+    directory: String,
+    options: Option<ScanOptions>,
+    #[napi(ts_arg_type = "(progress: Progress) => void")] on_progress: Option<ThreadsafeFunction<Progress, ErrorStrategy::Fatal>>,
+) -> Result<AsyncTask<ScanTask>> {
+    let options = options.unwrap_or_default().to_engine()?;
+    Ok(AsyncTask::new(ScanTask { directory: PathBuf::from(directory), options, on_progress }))
+}
+
+/// Adds up the duplicates of groups returned by `scan`, with the space they waste
+/// under each directory directly below `root`.
+#[napi]
+pub fn summarize(groups: Vec<DuplicateGroup>, root: String) -> Summary {
+    let groups: Vec<dupefiles::duplicate_group::DuplicateGroup> = groups.iter().map(Into::into).collect();
+    let summary = summarize_groups(&groups, &PathBuf::from(root));
+    Summary {
+        duplicate_groups: summary.duplicate_groups as u32,
+        duplicate_files: summary.duplicate_files as u32,
+        wasted_bytes: summary.wasted_bytes as i64,
+        wasted_by_directory: summary.wasted_by_directory.iter()
+            .map(|(directory, wasted)| (directory.to_string_lossy().into_owned(), *wasted as i64))
+            .collect(),
+    }
+}
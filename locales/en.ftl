@@ -0,0 +1,267 @@
+# Messages of dupefiles in English, also shown for any message a translation lacks.
+# Each line is `id = text`, and `{ $name }` is replaced by a value. To translate
+# dupefiles, copy this file to <language>.ftl (such as de.ftl or pt_BR.ftl) in the
+# locale directory and translate the text after each `=`.
+
+## Scan summary
+summary-stopped = Scan stopped early ({ $reason }), partial results:
+summary-totals = { $groups } duplicate groups, { $files } duplicate files, { $wasted } wasted
+summary-savings = Space freed by deleting: { $delete }, hard linking: { $hardlink }, reflinking: { $reflink }
+summary-min-size = Files smaller than { $size } were skipped (use --min-size 0 to include them)
+summary-memory = Memory used by the file indexes: about { $memory }
+summary-unique = { $files } files have no duplicate, holding { $size }
+summary-spilled = The files found were spilled to disk to stay within --memory-limit
+summary-by-directory = Wasted space by directory:
+summary-directory = { $directory }: { $wasted } ({ $percent }%)
+summary-by-extension = Duplicates by extension:
+summary-extension = { $extension }: { $files } files, { $wasted }
+summary-no-extension = (none)
+stop-interrupted = interrupted
+stop-max-runtime = runtime limit reached
+stop-max-files = file limit reached
+stop-max-bytes = byte limit reached
+notification-finished = Scan of { $directory } finished
+
+## Actions and their confirmation
+action-delete = delete
+action-trash = trash
+action-hardlink = hard link
+action-reflink = clone
+action-dedupe = deduplicate
+impact-totals = { $files } files, { $size } ({ $bytes } bytes)
+impact-directory = { $directory }: { $files } files, { $size }
+plan-skip-probable = Skipping group of { $file }: probable duplicates compared by samples only
+plan-skip-content-match = Skipping group of { $file }: will not { $action } copies matched by their decoded content, as their bytes differ
+plan-skip-protected = Skipping group of { $file }: all copies are protected
+plan-skip-manifest = Skipping group of { $file }: no copy is on disk, all are listed in manifests
+plan-skip-raw-pairs = Skipping group of { $file }: every copy is half of a RAW+JPEG pair
+plan-would = Would { $action } { $duplicate } (keeping { $keeper })
+plan-would-remove-sidecar = Would { $action } its sidecar { $sidecar }
+plan-would-move-sidecar = Would move its sidecar { $sidecar } next to { $file }
+plan-dry-run = Dry run, would { $action } { $impact }
+plan-about-to = About to { $action } { $impact }
+plan-about-to-content-match = These copies only match the kept ones by their decoded content, their bytes differ. About to { $action } { $impact }
+plan-skip-content-matches = Skipping the copies matched by their decoded content.
+# The answer itself is always "yes", whatever the language
+plan-confirm = Type "yes" to continue:
+plan-aborted = Aborted, no files were changed.
+plan-skip-group-changed = Skipping group of { $file }: { $changed } changed since it was scanned
+plan-skip-group-differs = Skipping group of { $file }: { $duplicate } is no longer identical to { $keeper }
+plan-skip-group-unreadable = Skipping group of { $file }: cannot check { $changed } again: { $error }
+plan-skip-same-file = Skipping { $duplicate }: it is the same file as { $keeper }
+exec-would = Would run { $command } for the group of { $file }
+exec-failed = Command failed for the group of { $file }: { $status }
+
+## Undoing the changes of a journal
+undo-would = Would restore { $file } from { $keeper }
+undo-restored = Restored { $file } from { $keeper }
+undo-already = Skipping { $file }: already restored
+undo-failed = Cannot restore { $file }: { $error }
+undo-summary = Restored { $restored } of { $changes } changes
+
+## Mounting the groups of a report
+mount-ready = Showing { $groups } duplicate groups in { $mountpoint }; press Ctrl-C or unmount it to stop
+
+## Runs sharing a cache or journal
+lock-busy = { $file } is in use by another dupefiles run (process { $process }); wait for it to finish, or pass --wait to wait for it
+lock-waiting = Waiting for another dupefiles run (process { $process }) to finish using { $file }
+
+## Warnings
+warning-network = Warning: { $directory } is on a network file system; scanning it may be slow
+warning-alternate-streams = Warning: --alternate-streams only has an effect on Windows
+warning-drop-cache = Warning: --drop-cache only has an effect on Linux
+warning-open-files = Warning: this process may only open { $limit } files, so { $slots } files are hashed at once instead of --jobs { $jobs }; raise the limit with `ulimit -n`
+warning-prefilter = Warning: --prefilter has no effect with --audio-content, --image-content, --email-content, --normalize-text or --decompress
+warning-memory-limit = Warning: --memory-limit has no effect with --audio-content, --image-content, --email-content, --normalize-text or --decompress
+warning-quick = Warning: --quick and --quick-check have no effect with --audio-content, --image-content, --email-content, --normalize-text or --decompress
+warning-filter-remote = Warning: --filter has no effect on remote sources
+
+## Scanning
+scan-skip-network = Skipping network file system: { $directory }
+scan-skip-broken-symlink = Skipping broken symlink: { $file } -> { $target }
+scan-symlink-unreadable = Failed to read symlink: { $file }
+scan-metadata-failed = Error accessing metadata for { $file }: { $error }
+scan-streams-failed = Failed to list alternate data streams of { $file }: { $error }
+scan-members-failed = Failed to read the files of { $file }: { $error }
+scan-directory-unreadable = Failed to read directory { $directory }: { $error }
+scan-spill-write-failed = Failed to write the files found to a spill file: { $error }
+scan-spill-read-failed = Failed to read the files found back from their spill file: { $error }
+scan-busy-locked = Skipping busy file { $file }: locked by another process
+scan-busy-modified = Skipping busy file { $file }: modified while it was hashed
+scan-invalid-hash = Invalid hash for { $file }: { $hash }
+scan-invalid-hash-value = invalid hash { $hash }
+scan-hash-too-many-files = Failed to compute hash for { $file }: too many open files, the limit of this process being { $limit }; raise it with `ulimit -n`
+scan-hash-failed = Failed to compute hash for { $file }: { $error }
+scan-fuzzy-hash-failed = Failed to compute fuzzy hash of { $file }: { $error }
+scan-compare-failed = Error checking for duplicate file: { $error }
+scan-elapsed = Elapsed time: { $time }
+compare-metadata-failed = Error accessing metadata of { $file }: { $error }
+compare-hash-failed = Error computing SHA256 for { $file }: { $error }
+status-walking = Walking: { $files } files found ({ $size }), elapsed { $elapsed }, current { $file }
+status-hashing = Hashing: { $done }/{ $files } files, { $hashed }/{ $size }, { $throughput }/s, elapsed { $elapsed }, current { $file }
+similar-pairs = Similar files: { $pairs } pairs
+
+## Estimates and checks of a report
+estimate-files = { $files } files, { $size }
+estimate-candidates = { $files } files to hash, { $size }
+estimate-throughput = Hashing throughput: { $throughput }/s
+estimate-time = Estimated scan time: { $time }
+estimate-time-unknown = Estimated scan time: unknown
+stale-missing = missing
+stale-not-a-file = not a regular file
+stale-size-changed = size changed from { $expected } to { $actual }
+stale-modified = modified since the report was written
+stale-content-changed = content changed
+stale-unreadable = cannot be read: { $error }
+verify-summary = { $stale } of { $checked } files checked are stale
+clean-skip-stale = Skipping { $file }: { $reason }
+
+## Hash cache
+cache-info = { $files } files, { $size }
+cache-pruned = Pruned { $pruned } entries, { $left } left
+cache-missing = Hash cache does not exist: { $file }
+cache-invalid = Invalid hash cache { $file }: { $error }
+cache-save-failed = Failed to save hash cache { $file }: { $error }
+
+## Capabilities
+capabilities-features = Features: { $features }
+capabilities-no-features = none
+capabilities-sha256 = SHA256: { $implementation }
+
+## Reports
+report-no-duplicates = No duplicate files found.
+report-read-failed = Failed to read report { $file }
+report-parse-failed = Failed to parse report { $file }
+report-unterminated-field = line { $line }: unterminated quoted field
+report-line-error = line { $line }: { $error }
+report-field-count = line { $line }: expected { $expected } fields, found { $found }
+report-invalid-size = line { $line }: invalid size '{ $size }'
+stats-format-unsupported = statistics cannot be written in { $format } format
+unique-format-unsupported = unique files cannot be written in { $format } format
+treemap-write-failed = Failed to write treemap { $file }: { $error }
+metrics-write-failed = Failed to write metrics { $file }: { $error }
+similar-write-failed = Failed to write similar files { $file }: { $error }
+manifest-read-failed = Failed to read manifest { $file }
+manifest-invalid-line = Invalid line { $line } in manifest { $file }
+manifest-not-sha256 = { $line }: not a SHA256 hash
+manifest-expected-tagged = expected SHA256 (NAME) = HASH
+manifest-expected-line = expected HASH  NAME
+manifest-no-sha256 = the manifest has no sha256 column
+manifest-expected-columns = expected { $columns }
+manifest-invalid-size = invalid size
+
+## Changing files
+action-delete-failed = Failed to delete { $file }
+action-trash-failed = Failed to move { $file } to trash
+action-hardlink-failed = Failed to hard link { $file } to { $keeper }
+action-reflink-failed = Failed to replace { $file } with a clone of { $keeper }
+action-dedupe-failed = Failed to deduplicate { $file } against { $keeper }
+clone-macos-only = copy-on-write clones are only supported on macOS
+clone-unsupported-volume = the volume does not support copy-on-write clones
+dedupe-linux-only = kernel deduplication is only supported on Linux
+dedupe-size-differs = the files differ in size
+dedupe-no-bytes = the kernel deduplicated no bytes
+dedupe-content-differs = the file contents differ
+sidecar-list-failed = Failed to list { $directory }
+sidecar-target-differs = Kept sidecar { $sidecar }: { $target } already exists and differs
+sidecar-move-failed = Failed to move { $sidecar } to { $target }
+journal-open-failed = Failed to open journal { $file }
+journal-write-failed = Failed to write journal { $file }
+journal-read-failed = Failed to read journal { $file }
+journal-invalid-entry = Invalid journal entry on line { $line } of { $file }
+query-failed = Failed to query { $file }
+undo-not-same-bytes = { $file } did not have the same bytes as { $keeper }
+undo-keeper-changed = { $keeper } no longer has the content of { $file }
+undo-owner-failed = Failed to restore the owner of { $file }: { $error }
+undo-restore-failed = Failed to restore { $file } from { $keeper }
+undo-move-back-failed = Failed to move { $keeper } back to { $file }
+protect-invalid-pattern = Invalid protect pattern '{ $pattern }'
+protect-read-failed = Failed to read protect list { $file }
+webhook-failed = Failed to notify { $url }
+notification-failed = Failed to show the desktop notification
+mount-thread-panicked = the file system thread panicked
+
+## Reading files
+error-path-missing = Path does not exist
+content-not-flac = Not a FLAC file
+content-binary = File looks binary
+content-not-email = Not an email message
+content-not-image = Not a JPEG or PNG file
+content-jpeg-marker = Invalid JPEG segment marker
+iso-truncated = the image is truncated
+iso-not-image = not an ISO 9660 image
+archive-unsupported = not a tar or zip archive
+archive-truncated = the archive is truncated
+archive-tar-checksum = invalid tar header checksum
+archive-tar-size = invalid tar file size
+archive-tar-metadata = tar metadata too large
+open-files-unsupported = no limit on open files on this platform
+s3-invalid-url = Invalid S3 URL: { $url }
+s3-not-in-bucket = Not in this bucket: { $file }
+sftp-invalid-url = Invalid SFTP URL: { $url }
+sftp-not-on-server = Not on this server: { $file }
+sftp-no-host-key = the server sent no host key
+sftp-host-key-check-failed = failed to check the host key
+sftp-host-key-mismatch = the host key of { $host } does not match ~/.ssh/known_hosts
+sftp-host-unknown = { $host } is not in ~/.ssh/known_hosts; connect once with ssh to add it
+sftp-auth-failed = authentication failed for { $user }
+
+## Command line values
+invalid-size = invalid size '{ $value }'
+invalid-size-unit = invalid size unit in '{ $value }' (expected B, K, M, G or T)
+invalid-duration = invalid duration '{ $value }'
+invalid-duration-unit = invalid duration unit in '{ $value }' (expected ms, s, m, h or d)
+invalid-keep-policy = invalid keep policy '{ $value }' (expected first, newest or oldest)
+invalid-quick-check = invalid quick check '{ $value }' (expected sampled or full)
+invalid-output-format = invalid output format '{ $value }' (expected csv, json, ncdu or hashdeep)
+invalid-pair-mode = invalid pair mode '{ $value }' (expected canonical or all)
+invalid-progress-format = invalid progress format '{ $value }' (expected json)
+invalid-sidecar-policy = invalid sidecar policy '{ $value }' (expected remove or move)
+invalid-hash-algorithm = invalid hash algorithm '{ $value }' (expected md5 or sha1)
+invalid-capabilities-format = invalid capabilities format '{ $value }' (expected text or json)
+invalid-compression = invalid compression format '{ $value }' (expected gz)
+invalid-escaped-path = invalid escaped path '{ $value }'
+invalid-base64-path = invalid base64 path '{ $value }': { $error }
+invalid-fuzzy-hash = invalid fuzzy hash '{ $value }' (expected BLOCKSIZE:SIGNATURE:SIGNATURE)
+filter-expected = expected { $what }
+filter-want-field = a field (size, mtime, age, ext, name or path)
+filter-unknown-field = unknown field '{ $field }' (expected size, mtime, age, ext, name or path)
+filter-expected-operator = expected an operator after '{ $field }'
+filter-want-value-after = a value after { $token }
+filter-text-operators = text fields only support ==, !=, in and contains
+filter-want-list = '(' after 'in'
+filter-want-list-value = a value in the list
+filter-want-list-end = ')' at the end of the list
+filter-invalid-date = invalid date '{ $value }' (expected YYYY-MM-DD)
+filter-trailing-input = unexpected input after the expression
+
+## Daemon serving metrics
+metrics-serving = Serving metrics at http://{ $address }/metrics
+metrics-server-failed = Stopped serving metrics: { $error }
+daemon-scan-failed = Scan failed, trying again at the next interval: { $error }
+
+## Errors
+error = Error: { $error }
+error-canonicalize = Failed to canonicalize directory path: { $error }
+error-directory-missing = Directory does not exist: { $directory }
+error-not-directory = Not a directory: { $directory }
+error-preferred-directory = Preferred directory { $directory }: { $error }
+error-manifest = Manifest { $file }: { $error }
+error-lock-failed = Failed to lock { $file }: { $error }
+error-create-output = Failed to create output file: { $error }
+error-create-treemap = Failed to create treemap file: { $error }
+error-create-metrics = Failed to create metrics file: { $error }
+error-create-similar = Failed to create similar files file: { $error }
+error-serve-metrics = Failed to serve metrics at { $address }: { $error }
+error-remote-action = --delete, --trash, --hardlink, --reflink and --dedupe-ioctl only apply to local files
+error-hashdeep-content-mode = --format hashdeep cannot be combined with the content modes or --sample-above
+error-local-only = { $option } only applies to local files
+error-csv-json-only = { $option } writes CSV or JSON only
+error-no-clones = { $directory } is not on a volume that supports copy-on-write clones (APFS on macOS)
+error-missing-feature = dupefiles was built without { $support } support; rebuild it with --features { $feature }
+translation-ignored = Ignoring translation { $file }: { $error }
+panic = thread '{ $thread }' panicked at '{ $message }', { $file }:{ $line }:{ $column }
+
+## Node.js bindings
+binding-invalid-max-seconds = Invalid maxSeconds: { $error }
+binding-invalid-min-size = Invalid minSize
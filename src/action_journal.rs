@@ -0,0 +1,496 @@
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::actions::{temp_path, Action};
+use crate::compute_sha256::compute_sha256;
+use crate::file_entry::file_id;
+use crate::hash_cache::unix_time;
+use crate::path_encoding::{deserialize_path, serialize_path};
+use crate::localize::message;
+
+/// What was done to a file recorded in an `ActionJournal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalAction {
+    /// The file was deleted.
+    Delete,
+    /// The file was moved to the trash.
+    Trash,
+    /// The file was replaced with a hard link to the kept copy.
+    Hardlink,
+    /// The file was replaced with a copy-on-write clone of the kept copy.
+    Reflink,
+    /// The kernel made the file share its data with the kept copy.
+    Dedupe,
+    /// The file, a sidecar, was renamed to the path of `JournalEntry::keeper`.
+    Move,
+}
+
+impl From<Action> for JournalAction {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Delete => JournalAction::Delete,
+            Action::Trash => JournalAction::Trash,
+            Action::Hardlink => JournalAction::Hardlink,
+            Action::Reflink => JournalAction::Reflink,
+            Action::Dedupe => JournalAction::Dedupe,
+        }
+    }
+}
+
+/// How far a change recorded in an `ActionJournal` got.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// The change was about to be made, and whether it was is unknown, as the process
+    /// was killed before recording its outcome.
+    Pending,
+    /// The change was made. Journals written before changes had a status only hold
+    /// changes that were made.
+    #[default]
+    Done,
+    /// The change failed, leaving the file as it was.
+    Failed,
+}
+
+/// A change made to a file, with the metadata the file had before, as recorded in an
+/// `ActionJournal`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// When the change was made, in seconds since the Unix epoch.
+    pub time: u64,
+    /// What was done.
+    pub action: JournalAction,
+    /// Whether it was done.
+    #[serde(default)]
+    pub status: JournalStatus,
+    /// The file changed.
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub path: PathBuf,
+    /// The copy kept in its place, whose content the file had, or where it was moved to.
+    /// For a sidecar removed with its file, the copy of that file that was kept, which
+    /// does not have its content.
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub keeper: PathBuf,
+    /// The hash the file and the kept copy shared, as in `DuplicateGroup::hash`, or the
+    /// SHA256 hash of a sidecar.
+    pub hash: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The permission bits and file type of the file (`st_mode`). On Windows, only
+    /// whether it is writable, as 0o644 or 0o444.
+    pub mode: u32,
+    /// The owner of the file, 0 on Windows.
+    pub uid: u32,
+    /// The group of the file, 0 on Windows.
+    pub gid: u32,
+    /// The last modification time of the file, in seconds since the Unix epoch.
+    pub mtime: i64,
+    /// The nanoseconds of `mtime`.
+    pub mtime_nsec: i64,
+}
+
+impl JournalEntry {
+    /// Describes a change about to be made to `path` from its current metadata, without
+    /// following a symbolic link.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - What is about to be done.
+    /// * `path` - The file about to be changed.
+    /// * `keeper` - The copy kept in its place, or where it is about to be moved to.
+    /// * `hash` - The hash the file shares with the kept copy.
+    ///
+    /// # Returns
+    ///
+    /// The entry, timed now and pending, or an error if `path` cannot be queried.
+    pub fn new(action: JournalAction, path: &Path, keeper: &Path, hash: &str) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(path)?;
+        #[cfg(unix)]
+        let (mode, uid, gid) = {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.mode(), metadata.uid(), metadata.gid())
+        };
+        #[cfg(not(unix))]
+        let (mode, uid, gid) = (if metadata.permissions().readonly() { 0o444 } else { 0o644 }, 0, 0);
+        let (mtime, mtime_nsec) = metadata.modified().map_or((0, 0), unix_time);
+        Ok(JournalEntry {
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()),
+            action,
+            status: JournalStatus::Pending,
+            path: path.to_path_buf(),
+            keeper: keeper.to_path_buf(),
+            hash: hash.to_string(),
+            size: metadata.len(),
+            mode,
+            uid,
+            gid,
+            mtime,
+            mtime_nsec,
+        })
+    }
+}
+
+/// An append-only journal of the changes made by actions, one `JournalEntry` per line
+/// as a JSON object, so there is a complete record of every file removed or replaced,
+/// of the copy kept in its place and of the metadata it had.
+///
+/// Every entry is written and flushed to disk as pending before its change is made, and
+/// written again once it is done or failed, so the journal lists every change even when
+/// the process is killed: at worst the last one stays pending. Several runs can append
+/// to the same journal.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::action_journal::{journaled, ActionJournal, JournalAction};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (keeper, duplicate) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+/// fs::write(&keeper, "copy").unwrap();
+/// fs::write(&duplicate, "copy").unwrap();
+///
+/// let mut journal = ActionJournal::open(&dir.path().join("journal.jsonl")).unwrap();
+/// journaled(Some(&mut journal), JournalAction::Delete, &duplicate, &keeper, "ed7002b4", || {
+///     Ok(fs::remove_file(&duplicate)?)
+/// }).unwrap();
+/// let text = fs::read_to_string(dir.path().join("journal.jsonl")).unwrap();
+/// assert!(text.contains(r#""action":"delete""#) && text.ends_with('\n'));
+/// ```
+#[derive(Debug)]
+pub struct ActionJournal {
+    path: PathBuf,
+    file: File,
+}
+
+impl ActionJournal {
+    /// Opens a journal for appending, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::options().create(true).append(true).open(path)
+            .with_context(|| message("journal-open-failed", &[("file", &path.display())]))?;
+        Ok(ActionJournal { path: path.to_path_buf(), file })
+    }
+
+    /// Appends an entry to the journal and flushes it to disk.
+    pub fn record(&mut self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+            .and_then(|_| self.file.sync_data())
+            .with_context(|| message("journal-write-failed", &[("file", &self.path.display())]))
+    }
+}
+
+/// Makes a change to a file with `change`, recording it in `journal`, when there is one,
+/// with the metadata the file had before: as pending before it is made, then as done or
+/// failed.
+///
+/// # Arguments
+///
+/// * `journal` - The journal to record the change in, or `None` to only make it.
+/// * `action` - What `change` does.
+/// * `path` - The file changed.
+/// * `keeper` - The copy kept in its place, or where it is moved to.
+/// * `hash` - The hash the file shares with the kept copy.
+/// * `change` - Makes the change.
+///
+/// # Returns
+///
+/// The error of `change`, or an error if the file cannot be queried beforehand or the
+/// journal cannot be written, in which case the change is not made, or only once it has
+/// been recorded as pending.
+pub fn journaled(
+    journal: Option<&mut ActionJournal>,
+    action: JournalAction,
+    path: &Path,
+    keeper: &Path,
+    hash: &str,
+    change: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let Some(journal) = journal else {
+        return change();
+    };
+    // An unrecorded change could not be reviewed or undone
+    let mut entry = JournalEntry::new(action, path, keeper, hash)
+        .with_context(|| message("query-failed", &[("file", &path.display())]))?;
+    journal.record(&entry)?;
+    let changed = change();
+    entry.status = if changed.is_ok() { JournalStatus::Done } else { JournalStatus::Failed };
+    let recorded = journal.record(&entry);
+    changed.and(recorded)
+}
+
+/// Reads the entries of a journal written by `ActionJournal`, in the order they were
+/// recorded, one per change with its latest status.
+///
+/// # Returns
+///
+/// The entries, or an error naming the first line that is not a `JournalEntry`.
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+    let text = fs::read_to_string(path).with_context(|| message("journal-read-failed", &[("file", &path.display())]))?;
+    let mut entries: Vec<JournalEntry> = Vec::new();
+    for (index, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let entry: JournalEntry = serde_json::from_str(line)
+            .with_context(|| message("journal-invalid-entry", &[("line", &(index + 1)), ("file", &path.display())]))?;
+        // The journal is locked while changes are made, so the outcome of a change is
+        // the line right after it
+        match entries.last_mut() {
+            Some(last) if last.status == JournalStatus::Pending && entry.status != JournalStatus::Pending
+                && JournalEntry { status: entry.status, ..last.clone() } == entry => last.status = entry.status,
+            _ => entries.push(entry),
+        }
+    }
+    Ok(entries)
+}
+
+/// What `undo_entry` did with a change, or would do with `dry_run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoOutcome {
+    /// The file was restored as an independent copy of the kept one with its original
+    /// metadata, or moved back.
+    Restored,
+    /// The file is already back as an independent file, so it was left alone.
+    AlreadyUndone,
+    /// The change left the file in place as an independent file, as reflinks and
+    /// deduplication do, so there is nothing to undo.
+    NothingToUndo,
+}
+
+/// Checks that the kept copy of an entry still has the content the changed file had, so
+/// copying it back restores that file
+fn check_keeper(entry: &JournalEntry) -> Result<()> {
+    // Members compared by content only, such as by their audio frames, differ in bytes
+    if entry.hash.is_empty() || entry.hash.contains(':') {
+        bail!("{}", message("undo-not-same-bytes", &[("file", &entry.path.display()), ("keeper", &entry.keeper.display())]));
+    }
+    // Groups read from CSV reports only know the start of their hash
+    if !compute_sha256(&entry.keeper)?.starts_with(&entry.hash) {
+        bail!("{}", message("undo-keeper-changed", &[("file", &entry.path.display()), ("keeper", &entry.keeper.display())]));
+    }
+    Ok(())
+}
+
+/// The modification time of a journal entry
+fn entry_mtime(entry: &JournalEntry) -> Option<SystemTime> {
+    let seconds = u64::try_from(entry.mtime).ok()?;
+    UNIX_EPOCH.checked_add(Duration::new(seconds, entry.mtime_nsec.clamp(0, 999_999_999) as u32))
+}
+
+/// Copies the kept copy of an entry over its path, with the permissions, owner and
+/// modification time the path had
+fn restore_copy(entry: &JournalEntry) -> Result<()> {
+    if let Some(parent) = entry.path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = temp_path(&entry.path);
+    let restored = (|| -> io::Result<()> {
+        let mut copy = File::options().write(true).create_new(true).open(&temp_path)?;
+        io::copy(&mut File::open(&entry.keeper)?, &mut copy)?;
+        #[cfg(unix)]
+        {
+            use std::fs::Permissions;
+            use std::os::unix::fs::{fchown, PermissionsExt};
+
+            // Only root can give files away, so a file keeps the owner running the undo otherwise
+            if let Err(e) = fchown(&copy, Some(entry.uid), Some(entry.gid)) {
+                eprintln!("{}", message("undo-owner-failed", &[("file", &entry.path.display()), ("error", &e)]));
+            }
+            copy.set_permissions(Permissions::from_mode(entry.mode & 0o7777))?;
+        }
+        #[cfg(not(unix))]
+        {
+            let mut permissions = copy.metadata()?.permissions();
+            permissions.set_readonly(entry.mode & 0o222 == 0);
+            copy.set_permissions(permissions)?;
+        }
+        if let Some(mtime) = entry_mtime(entry) {
+            copy.set_modified(mtime)?;
+        }
+        fs::rename(&temp_path, &entry.path)
+    })();
+    restored.inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    }).with_context(|| message("undo-restore-failed", &[("file", &entry.path.display()), ("keeper", &entry.keeper.display())]))
+}
+
+/// Reverses a change recorded in a journal, so a cleanup that turned out to be a mistake
+/// can be undone.
+///
+/// A file deleted or trashed is copied back from the kept copy, and a file replaced with
+/// a hard link is replaced again with a copy of its own, each with the permissions,
+/// owner and modification time it had; trashed files stay in the trash. A sidecar moved
+/// next to the kept copy is moved back. Files that were reflinked or deduplicated are
+/// already independent and left alone. The kept copy is hashed first and nothing is
+/// restored from it when it no longer has the content of the file, or never had exactly
+/// the same bytes, as for sidecars removed with their file and members of groups
+/// compared by content only.
+///
+/// # Arguments
+///
+/// * `entry` - The change to reverse, as read by `read_journal`.
+/// * `dry_run` - Only check that the change can be reversed, without changing any file.
+///
+/// # Returns
+///
+/// What was done, or an error if the change cannot be reversed.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::action_journal::{journaled, read_journal, undo_entry, ActionJournal, JournalAction, UndoOutcome};
+/// use dupefiles::compute_sha256::compute_sha256;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (keeper, duplicate) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+/// fs::write(&keeper, "copy").unwrap();
+/// fs::write(&duplicate, "copy").unwrap();
+/// let hash = compute_sha256(&keeper).unwrap();
+///
+/// let mut journal = ActionJournal::open(&dir.path().join("journal.jsonl")).unwrap();
+/// journaled(Some(&mut journal), JournalAction::Delete, &duplicate, &keeper, &hash, || {
+///     Ok(fs::remove_file(&duplicate)?)
+/// }).unwrap();
+///
+/// let entries = read_journal(&dir.path().join("journal.jsonl")).unwrap();
+/// assert_eq!(undo_entry(&entries[0], false).unwrap(), UndoOutcome::Restored);
+/// assert_eq!(fs::read_to_string(&duplicate).unwrap(), "copy");
+/// assert_eq!(undo_entry(&entries[0], false).unwrap(), UndoOutcome::AlreadyUndone);
+/// ```
+pub fn undo_entry(entry: &JournalEntry, dry_run: bool) -> Result<UndoOutcome> {
+    let current = match fs::symlink_metadata(&entry.path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| message("query-failed", &[("file", &entry.path.display())])),
+    };
+    match entry.action {
+        _ if entry.status == JournalStatus::Failed => Ok(UndoOutcome::NothingToUndo),
+        JournalAction::Reflink | JournalAction::Dedupe => Ok(UndoOutcome::NothingToUndo),
+        JournalAction::Delete | JournalAction::Trash if current.is_some() => Ok(UndoOutcome::AlreadyUndone),
+        JournalAction::Move if current.is_some() => Ok(UndoOutcome::AlreadyUndone),
+        JournalAction::Hardlink if current.as_ref().is_some_and(|current| {
+            fs::metadata(&entry.keeper).map_or(true, |keeper| file_id(&keeper) != file_id(current))
+        }) => Ok(UndoOutcome::AlreadyUndone),
+        JournalAction::Move => {
+            check_keeper(entry)?;
+            if !dry_run && fs::rename(&entry.keeper, &entry.path).is_err() {
+                // Another file system
+                fs::copy(&entry.keeper, &entry.path)
+                    .and_then(|_| fs::remove_file(&entry.keeper))
+                    .with_context(|| message("undo-move-back-failed", &[("file", &entry.path.display()), ("keeper", &entry.keeper.display())]))?;
+            }
+            Ok(UndoOutcome::Restored)
+        }
+        JournalAction::Delete | JournalAction::Trash | JournalAction::Hardlink => {
+            check_keeper(entry)?;
+            if !dry_run {
+                restore_copy(entry)?;
+            }
+            Ok(UndoOutcome::Restored)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::fs::Permissions;
+    #[cfg(unix)]
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use tempfile::Builder;
+
+    #[test]
+    fn test_failed_change_recorded_as_failed() {
+        let tmp_dir = Builder::new().prefix("action_journal").tempdir().unwrap();
+        let (keeper, duplicate) = (tmp_dir.path().join("a.txt"), tmp_dir.path().join("b.txt"));
+        fs::write(&keeper, "copy").unwrap();
+        fs::write(&duplicate, "copy").unwrap();
+        let journal_path = tmp_dir.path().join("journal.jsonl");
+        let mut journal = ActionJournal::open(&journal_path).unwrap();
+
+        let failed = journaled(Some(&mut journal), JournalAction::Hardlink, &duplicate, &keeper, "ed7002b4", || {
+            anyhow::bail!("no links here")
+        });
+        assert!(failed.is_err());
+        journaled(Some(&mut journal), JournalAction::Delete, &duplicate, &keeper, "ed7002b4", || {
+            Ok(fs::remove_file(&duplicate)?)
+        }).unwrap();
+
+        let text = fs::read_to_string(&journal_path).unwrap();
+        let lines: Vec<JournalEntry> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let statuses: Vec<JournalStatus> = lines.iter().map(|line| line.status).collect();
+        assert_eq!(statuses, [JournalStatus::Pending, JournalStatus::Failed, JournalStatus::Pending, JournalStatus::Done]);
+        let entries = read_journal(&journal_path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].action, entries[0].status), (JournalAction::Hardlink, JournalStatus::Failed));
+        assert_eq!((entries[1].action, entries[1].status), (JournalAction::Delete, JournalStatus::Done));
+        assert_eq!((&entries[1].path, &entries[1].keeper, entries[1].size), (&duplicate, &keeper, 4));
+        assert_eq!(undo_entry(&entries[0], false).unwrap(), UndoOutcome::NothingToUndo);
+    }
+
+    #[test]
+    fn test_change_pending_until_done() {
+        let tmp_dir = Builder::new().prefix("action_journal_killed").tempdir().unwrap();
+        let (keeper, duplicate) = (tmp_dir.path().join("a.txt"), tmp_dir.path().join("b.txt"));
+        fs::write(&keeper, "copy").unwrap();
+        fs::write(&duplicate, "copy").unwrap();
+        let journal_path = tmp_dir.path().join("journal.jsonl");
+        let mut journal = ActionJournal::open(&journal_path).unwrap();
+
+        // What a kill in the middle of the change would leave
+        journaled(Some(&mut journal), JournalAction::Delete, &duplicate, &keeper, "ed7002b4", || {
+            let entries = read_journal(&journal_path).unwrap();
+            assert_eq!((entries.len(), entries[0].status), (1, JournalStatus::Pending));
+            Ok(fs::remove_file(&duplicate)?)
+        }).unwrap();
+        // Lines of journals written before changes had a status are done
+        let old_line = r#"{"time":1,"action":"delete","path":"/data/b.txt","keeper":"/data/a.txt","hash":"ed7002b4","size":4,"mode":33188,"uid":0,"gid":0,"mtime":1,"mtime_nsec":0}"#;
+        fs::write(&journal_path, format!("{}\n{}\n", fs::read_to_string(&journal_path).unwrap(), old_line)).unwrap();
+
+        let statuses: Vec<JournalStatus> = read_journal(&journal_path).unwrap().iter().map(|entry| entry.status).collect();
+        assert_eq!(statuses, [JournalStatus::Done, JournalStatus::Done]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_undo_hardlink() {
+        let tmp_dir = Builder::new().prefix("undo_hardlink").tempdir().unwrap();
+        let (keeper, duplicate) = (tmp_dir.path().join("a.txt"), tmp_dir.path().join("b.txt"));
+        fs::write(&keeper, "copy").unwrap();
+        fs::write(&duplicate, "copy").unwrap();
+        fs::set_permissions(&duplicate, Permissions::from_mode(0o640)).unwrap();
+        let mtime = UNIX_EPOCH + Duration::new(1_000_000_000, 500);
+        File::options().write(true).open(&duplicate).unwrap().set_modified(mtime).unwrap();
+        let entry = JournalEntry::new(JournalAction::Hardlink, &duplicate, &keeper, &compute_sha256(&keeper).unwrap()).unwrap();
+        crate::actions::apply_action(Action::Hardlink, &keeper, &duplicate).unwrap();
+
+        assert_eq!(undo_entry(&entry, true).unwrap(), UndoOutcome::Restored);
+        assert_eq!(fs::metadata(&duplicate).unwrap().ino(), fs::metadata(&keeper).unwrap().ino());
+        assert_eq!(undo_entry(&entry, false).unwrap(), UndoOutcome::Restored);
+        let restored = fs::metadata(&duplicate).unwrap();
+        assert_ne!(restored.ino(), fs::metadata(&keeper).unwrap().ino());
+        assert_eq!((restored.mode() & 0o7777, restored.modified().unwrap()), (0o640, mtime));
+        assert_eq!(undo_entry(&entry, false).unwrap(), UndoOutcome::AlreadyUndone);
+    }
+
+    #[test]
+    fn test_undo_refused_when_keeper_changed() {
+        let tmp_dir = Builder::new().prefix("undo_changed").tempdir().unwrap();
+        let (keeper, duplicate) = (tmp_dir.path().join("a.txt"), tmp_dir.path().join("b.txt"));
+        fs::write(&keeper, "copy").unwrap();
+        fs::write(&duplicate, "copy").unwrap();
+        let entry = JournalEntry::new(JournalAction::Delete, &duplicate, &keeper, &compute_sha256(&keeper).unwrap()).unwrap();
+        fs::remove_file(&duplicate).unwrap();
+        fs::write(&keeper, "edited").unwrap();
+
+        assert!(undo_entry(&entry, false).is_err());
+        assert!(!duplicate.exists());
+        let audio = JournalEntry { hash: format!("audio:{}", compute_sha256(&keeper).unwrap()), ..entry };
+        assert!(undo_entry(&audio, false).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use crate::clone_file::{clone_file, clone_supported};
+use crate::dedupe_range::dedupe_range;
+use crate::localize::message;
+
+/// An action applied to each duplicate file once it has been identified.
+///
+/// The first-seen copy of every duplicate set is always kept; actions are only
+/// applied to the later copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Permanently unlink the duplicate.
+    Delete,
+    /// Move the duplicate to the freedesktop Trash (or the platform equivalent),
+    /// so it can be restored from the desktop environment.
+    Trash,
+    /// Replace the duplicate with a hard link to the kept copy.
+    Hardlink,
+    /// Replace the duplicate with a copy-on-write clone of the kept copy, which shares
+    /// its data blocks but remains a separate file (APFS on macOS only).
+    Reflink,
+    /// Have the kernel share the duplicate's data with the kept copy after checking that
+    /// it is identical, leaving both files in place (Btrfs and XFS on Linux only).
+    Dedupe,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Delete => write!(f, "{}", message("action-delete", &[])),
+            Action::Trash => write!(f, "{}", message("action-trash", &[])),
+            Action::Hardlink => write!(f, "{}", message("action-hardlink", &[])),
+            Action::Reflink => write!(f, "{}", message("action-reflink", &[])),
+            Action::Dedupe => write!(f, "{}", message("action-dedupe", &[])),
+        }
+    }
+}
+
+/// Applies an action to a duplicate file.
+///
+/// # Arguments
+///
+/// * `action` - The `Action` to apply.
+/// * `keeper` - The path of the copy that is kept.
+/// * `duplicate` - The path of the duplicate file to act upon.
+///
+/// # Returns
+///
+/// Result
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::actions::{apply_action, Action};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let keeper = dir.path().join("original.txt");
+/// let path = dir.path().join("copy.txt");
+/// fs::write(&keeper, "duplicate content").unwrap();
+/// fs::write(&path, "duplicate content").unwrap();
+///
+/// apply_action(Action::Delete, &keeper, &path).unwrap();
+/// assert!(!path.exists());
+/// ```
+pub fn apply_action(action: Action, keeper: &Path, duplicate: &Path) -> Result<()> {
+    match action {
+        Action::Delete => fs::remove_file(duplicate)
+            .with_context(|| message("action-delete-failed", &[("file", &duplicate.display())])),
+        Action::Trash => trash::delete(duplicate)
+            .with_context(|| message("action-trash-failed", &[("file", &duplicate.display())])),
+        Action::Hardlink => replace_with_hard_link(keeper, duplicate)
+            .with_context(|| message("action-hardlink-failed", &[("file", &duplicate.display()), ("keeper", &keeper.display())])),
+        Action::Reflink => replace_with_clone(keeper, duplicate)
+            .with_context(|| message("action-reflink-failed", &[("file", &duplicate.display()), ("keeper", &keeper.display())])),
+        Action::Dedupe => dedupe_range(keeper, duplicate)
+            .with_context(|| message("action-dedupe-failed", &[("file", &duplicate.display()), ("keeper", &keeper.display())])),
+    }
+}
+
+/// Returns the temporary name a replacement for `duplicate` is created under.
+pub(crate) fn temp_path(duplicate: &Path) -> PathBuf {
+    let file_name = duplicate.file_name().unwrap_or_default().to_string_lossy();
+    duplicate.with_file_name(format!(".{}.dupefiles-tmp", file_name))
+}
+
+/// Links the keeper under a temporary name next to the duplicate, then renames it
+/// over the duplicate so the duplicate path is never missing.
+fn replace_with_hard_link(keeper: &Path, duplicate: &Path) -> std::io::Result<()> {
+    let temp_path = temp_path(duplicate);
+    fs::hard_link(keeper, &temp_path)?;
+    fs::rename(&temp_path, duplicate).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+/// Clones the keeper under a temporary name next to the duplicate, then renames it
+/// over the duplicate, once the volume is known to support clones.
+fn replace_with_clone(keeper: &Path, duplicate: &Path) -> std::io::Result<()> {
+    if !clone_supported(duplicate)? {
+        return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, message("clone-unsupported-volume", &[])));
+    }
+    let temp_path = temp_path(duplicate);
+    clone_file(keeper, &temp_path)?;
+    fs::rename(&temp_path, duplicate).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::MetadataExt;
+    use tempfile::Builder;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_replaces_duplicate() {
+        let tmp_dir = Builder::new().prefix("hardlink_action").tempdir().unwrap();
+        let keeper = tmp_dir.path().join("keeper.txt");
+        let duplicate = tmp_dir.path().join("duplicate.txt");
+        fs::write(&keeper, "duplicate content").unwrap();
+        fs::write(&duplicate, "duplicate content").unwrap();
+
+        apply_action(Action::Hardlink, &keeper, &duplicate).unwrap();
+
+        assert_eq!(fs::metadata(&keeper).unwrap().ino(), fs::metadata(&duplicate).unwrap().ino());
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_reflink_unsupported_keeps_duplicate() {
+        let tmp_dir = Builder::new().prefix("reflink_action").tempdir().unwrap();
+        let keeper = tmp_dir.path().join("keeper.txt");
+        let duplicate = tmp_dir.path().join("duplicate.txt");
+        fs::write(&keeper, "duplicate content").unwrap();
+        fs::write(&duplicate, "duplicate content").unwrap();
+
+        assert!(apply_action(Action::Reflink, &keeper, &duplicate).is_err());
+        assert_eq!(fs::read_to_string(&duplicate).unwrap(), "duplicate content");
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 2);
+    }
+}
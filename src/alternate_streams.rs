@@ -0,0 +1,86 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Lists the NTFS alternate data streams of a file.
+///
+/// Each stream is returned as a path using the `file:stream` syntax, which Windows
+/// accepts wherever a file path is expected, together with its size in bytes. The
+/// default unnamed stream holding the file's regular content is not listed. On other
+/// platforms, which have no alternate data streams, the list is always empty.
+///
+/// # Arguments
+///
+/// * `path` - The file whose streams are listed.
+///
+/// # Returns
+///
+/// The path and size of every named data stream, or an error if they cannot be listed.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::alternate_streams::alternate_streams;
+///
+/// let file = tempfile::NamedTempFile::new().unwrap();
+/// let streams = alternate_streams(file.path()).unwrap();
+/// # #[cfg(not(windows))]
+/// assert!(streams.is_empty());
+/// ```
+pub fn alternate_streams(path: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    #[cfg(windows)]
+    {
+        find_streams(path)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Ok(Vec::new())
+    }
+}
+
+/// List the named data streams with FindFirstStreamW and FindNextStreamW
+#[cfg(windows)]
+fn find_streams(path: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard, WIN32_FIND_STREAM_DATA,
+    };
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+    let data_ptr = &mut data as *mut WIN32_FIND_STREAM_DATA as *mut _;
+    let handle = unsafe { FindFirstStreamW(wide_path.as_ptr(), FindStreamInfoStandard, data_ptr, 0) };
+    if handle == INVALID_HANDLE_VALUE {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(code) if code == ERROR_HANDLE_EOF as i32 => Ok(Vec::new()),
+            _ => Err(error),
+        };
+    }
+
+    let mut streams = Vec::new();
+    loop {
+        // Names look like ":name:$DATA", and "::$DATA" for the default stream
+        let len = data.cStreamName.iter().position(|&c| c == 0).unwrap_or(data.cStreamName.len());
+        let name = String::from_utf16_lossy(&data.cStreamName[..len]);
+        if let Some(name) = name.strip_prefix(':').and_then(|name| name.strip_suffix(":$DATA")) {
+            if !name.is_empty() {
+                let mut stream_path = OsString::from(path.as_os_str());
+                stream_path.push(":");
+                stream_path.push(name);
+                streams.push((PathBuf::from(stream_path), data.StreamSize as u64));
+            }
+        }
+        if unsafe { FindNextStreamW(handle, data_ptr) } == 0 {
+            break;
+        }
+    }
+    let error = io::Error::last_os_error();
+    unsafe { FindClose(handle) };
+    match error.raw_os_error() {
+        Some(code) if code == ERROR_HANDLE_EOF as i32 => Ok(streams),
+        _ => Err(error),
+    }
+}
@@ -0,0 +1,537 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use sha2::{Digest, Sha256};
+use crate::iso_image::IsoMember;
+use crate::localize::message;
+
+/// Size of a tar header and of the blocks tar pads every file to
+const TAR_BLOCK_SIZE: u64 = 512;
+/// Bytes at the end of a zip archive searched for its end of central directory record:
+/// the record itself and the longest comment it can be followed by
+const ZIP_TAIL_SIZE: u64 = 22 + 65535;
+/// Signature of the local header before the data of every file of a zip archive
+const ZIP_LOCAL_HEADER: &[u8; 4] = b"PK\x03\x04";
+/// Signature of a file header in the central directory of a zip archive
+const ZIP_CENTRAL_HEADER: &[u8; 4] = b"PK\x01\x02";
+/// Signature of the end of central directory record of a zip archive
+const ZIP_END_OF_DIRECTORY: &[u8; 4] = b"PK\x05\x06";
+/// Long names and pax headers larger than this are not read, so a corrupt size cannot
+/// exhaust memory
+const MAX_TAR_METADATA_SIZE: u64 = 1024 * 1024;
+
+/// How the content of an `ArchiveMember` is stored in its archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberEncoding {
+    /// As is, at `offset` in the archive: files in disc images, tar archives and zip
+    /// archives without compression.
+    Stored,
+    /// Compressed with deflate at `offset` in the archive, taking this many bytes, as in
+    /// most zip archives.
+    Deflated(u64),
+    /// As is, at `offset` in the decompressed content of a gzip-compressed tar archive.
+    Gzipped,
+    /// As is, at `offset` in an mbox mailbox: one email message, listed by
+    /// `mbox_messages`.
+    Message,
+}
+
+/// A file stored in an archive or a disc image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveMember {
+    /// The path of the file inside the archive, such as `photos/2003/img_001.jpg`.
+    pub path: PathBuf,
+    /// The position of the file's content in the archive, in bytes.
+    pub offset: u64,
+    /// The size of the file in bytes, once decompressed.
+    pub size: u64,
+    /// How the content is stored.
+    pub encoding: MemberEncoding,
+}
+
+impl From<IsoMember> for ArchiveMember {
+    fn from(member: IsoMember) -> Self {
+        ArchiveMember { path: member.path, offset: member.offset, size: member.size, encoding: MemberEncoding::Stored }
+    }
+}
+
+/// The kinds of archives `archive_members` reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Tar,
+    GzippedTar,
+    Zip,
+}
+
+/// The kind of archive `path` is by its name
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::GzippedTar)
+    } else if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else {
+        None
+    }
+}
+
+/// Returns whether `path` is a tar or zip archive: a file named `.tar`, `.tar.gz`,
+/// `.tgz` or `.zip` starting like one.
+///
+/// # Arguments
+///
+/// * `path` - The file to check.
+///
+/// # Returns
+///
+/// `true` if the file can be read with `archive_members`, `false` otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::archive_members::is_archive;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (zip, fake) = (dir.path().join("photos.zip"), dir.path().join("notes.zip"));
+/// fs::write(&zip, b"PK\x05\x06\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0").unwrap();
+/// fs::write(&fake, "not an archive").unwrap();
+/// assert!(is_archive(&zip));
+/// assert!(!is_archive(&fake));
+/// ```
+pub fn is_archive(path: &Path) -> bool {
+    let Some(kind) = archive_kind(path) else {
+        return false;
+    };
+    let mut head = [0; TAR_BLOCK_SIZE as usize];
+    let Ok(length) = File::open(path).and_then(|file| file.take(TAR_BLOCK_SIZE).read(&mut head)) else {
+        return false;
+    };
+    match kind {
+        ArchiveKind::Tar => length == head.len() && tar_header_valid(&head),
+        ArchiveKind::GzippedTar => head[..length].starts_with(b"\x1f\x8b"),
+        ArchiveKind::Zip => head[..length].starts_with(ZIP_LOCAL_HEADER) || head[..length].starts_with(ZIP_END_OF_DIRECTORY),
+    }
+}
+
+/// Lists the regular files stored in a tar or zip archive without extracting it.
+///
+/// Tar archives may be gzip-compressed, and their GNU long names and pax paths and sizes
+/// are used. Zip files stored without compression or compressed with deflate are listed;
+/// encrypted files, other compression methods and ZIP64 archives are skipped. Leading
+/// `/` and `..` components of the names are dropped, so every member path stays inside
+/// the archive.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to read.
+///
+/// # Returns
+///
+/// Every file in the archive, or an error if it cannot be read as an archive.
+pub fn archive_members(archive: &Path) -> io::Result<Vec<ArchiveMember>> {
+    let file = File::open(archive)?;
+    match archive_kind(archive) {
+        Some(ArchiveKind::Tar) => tar_members(BufReader::new(file), MemberEncoding::Stored, |reader, length| {
+            reader.seek_relative(length as i64)
+        }),
+        Some(ArchiveKind::GzippedTar) => tar_members(MultiGzDecoder::new(BufReader::new(file)), MemberEncoding::Gzipped, |reader, length| {
+            io::copy(&mut reader.take(length), &mut io::sink()).map(|_| ())
+        }),
+        Some(ArchiveKind::Zip) => zip_members(file),
+        None => Err(io::Error::new(io::ErrorKind::InvalidInput, message("archive-unsupported", &[]))),
+    }
+}
+
+/// Computes the SHA256 hash of a file stored in an archive or a disc image.
+///
+/// # Arguments
+///
+/// * `archive` - The archive holding the file.
+/// * `member` - The file, as listed by `archive_members`.
+///
+/// # Returns
+///
+/// The SHA256 hash of the decompressed content as a hexadecimal string, or an error if
+/// the archive cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::archive_members::{archive_members, compute_archive_member_sha256};
+/// use dupefiles::compute_sha256::compute_sha256;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let file = dir.path().join("notes.txt");
+/// fs::write(&file, "archived notes").unwrap();
+/// // A tar archive holding notes.txt
+/// let mut tar = vec![0; 512];
+/// tar[..9].copy_from_slice(b"notes.txt");
+/// tar[124..136].copy_from_slice(b"00000000016\0");
+/// tar[148..156].copy_from_slice(b"        ");
+/// tar[156] = b'0';
+/// let checksum: u32 = tar.iter().map(|&byte| byte as u32).sum();
+/// tar[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+/// tar.extend(b"archived notes");
+/// tar.resize(2048, 0);
+/// fs::write(dir.path().join("notes.tar"), tar).unwrap();
+///
+/// let members = archive_members(&dir.path().join("notes.tar")).unwrap();
+/// assert_eq!(members[0].path.to_str(), Some("notes.txt"));
+/// assert_eq!(compute_archive_member_sha256(&dir.path().join("notes.tar"), &members[0]).unwrap(), compute_sha256(&file).unwrap());
+/// ```
+pub fn compute_archive_member_sha256(archive: &Path, member: &ArchiveMember) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    if io::copy(&mut open_archive_member(archive, member)?, &mut hasher)? < member.size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message("archive-truncated", &[])));
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Opens a file stored in an archive or a disc image for reading.
+///
+/// # Arguments
+///
+/// * `archive` - The archive holding the file.
+/// * `member` - The file, as listed by `archive_members`.
+///
+/// # Returns
+///
+/// A reader of the decompressed content, ending after `member.size` bytes or earlier if
+/// the archive is truncated, or an error if the archive cannot be opened.
+pub fn open_archive_member(archive: &Path, member: &ArchiveMember) -> io::Result<Box<dyn Read>> {
+    let mut file = File::open(archive)?;
+    Ok(match member.encoding {
+        MemberEncoding::Stored | MemberEncoding::Message => {
+            file.seek(SeekFrom::Start(member.offset))?;
+            Box::new(BufReader::new(file).take(member.size))
+        }
+        MemberEncoding::Deflated(compressed_size) => {
+            file.seek(SeekFrom::Start(member.offset))?;
+            Box::new(DeflateDecoder::new(BufReader::new(file.take(compressed_size))).take(member.size))
+        }
+        MemberEncoding::Gzipped => {
+            let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+            io::copy(&mut (&mut decoder).take(member.offset), &mut io::sink())?;
+            Box::new(decoder.take(member.size))
+        }
+    })
+}
+
+/// The path of a member named `name`, without the root and parent components that
+/// would take it out of the archive
+pub(crate) fn member_path(name: &str) -> PathBuf {
+    Path::new(name).components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether a tar header block has a valid checksum: the sum of its bytes, with the
+/// checksum field counted as spaces
+fn tar_header_valid(header: &[u8]) -> bool {
+    let sum: u64 = header.iter().enumerate()
+        .map(|(index, &byte)| if (148..156).contains(&index) { b' ' as u64 } else { byte as u64 })
+        .sum();
+    tar_number(&header[148..156]) == Some(sum)
+}
+
+/// Parse a numeric field of a tar header: octal digits, or a big-endian binary number
+/// when its first byte has the high bit set, as GNU tar writes large sizes
+fn tar_number(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|byte| byte & 0x80 != 0) {
+        return Some(field[1..].iter().fold(0, |number, &byte| (number << 8) | byte as u64));
+    }
+    let text = std::str::from_utf8(field).ok()?;
+    let digits = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// A NUL-terminated text field of a tar header
+fn tar_text(field: &[u8]) -> String {
+    let end = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Read the records of a pax extended header, such as `30 path=some/long/name\n`
+fn pax_records(data: &[u8]) -> Vec<(String, String)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&byte| byte == b' ') {
+        let length: usize = match std::str::from_utf8(&rest[..space]).ok().and_then(|length| length.parse().ok()) {
+            Some(length) if length > space && length <= rest.len() => length,
+            _ => break,
+        };
+        let record = String::from_utf8_lossy(&rest[space + 1..length]);
+        if let Some((key, value)) = record.trim_end_matches('\n').split_once('=') {
+            records.push((key.to_string(), value.to_string()));
+        }
+        rest = &rest[length..];
+    }
+    records
+}
+
+/// List the regular files of the tar archive read from `reader`, skipping `length`
+/// bytes of it with `skip`. Offsets are positions in what `reader` reads
+fn tar_members<R, F>(mut reader: R, encoding: MemberEncoding, mut skip: F) -> io::Result<Vec<ArchiveMember>>
+where
+    R: Read,
+    F: FnMut(&mut R, u64) -> io::Result<()>,
+{
+    let mut members = Vec::new();
+    let mut position = 0;
+    // The name and size given by a GNU long name or a pax header for the next file
+    let mut long_name: Option<String> = None;
+    let mut pax_size: Option<u64> = None;
+    let mut header = [0; TAR_BLOCK_SIZE as usize];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            // Some writers leave out the end-of-archive blocks
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && !members.is_empty() => break,
+            Err(e) => return Err(e),
+        }
+        position += TAR_BLOCK_SIZE;
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        if !tar_header_valid(&header) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message("archive-tar-checksum", &[])));
+        }
+        let size = pax_size.take().or_else(|| tar_number(&header[124..136]))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, message("archive-tar-size", &[])))?;
+        let padded = size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+        match header[156] {
+            b'L' | b'x' => {
+                if size > MAX_TAR_METADATA_SIZE {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message("archive-tar-metadata", &[])));
+                }
+                let mut data = vec![0; padded as usize];
+                reader.read_exact(&mut data)?;
+                data.truncate(size as usize);
+                if header[156] == b'L' {
+                    long_name = Some(tar_text(&data));
+                } else {
+                    for (key, value) in pax_records(&data) {
+                        match key.as_str() {
+                            "path" => long_name = Some(value),
+                            "size" => pax_size = value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            kind => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let name = tar_text(&header[..100]);
+                    match &header[257..262] == b"ustar" && header[345] != 0 {
+                        true => format!("{}/{}", tar_text(&header[345..500]), name),
+                        false => name,
+                    }
+                });
+                // Regular and contiguous files; links, directories and devices hold no content
+                let path = member_path(&name);
+                if matches!(kind, b'0' | b'\0' | b'7') && !path.as_os_str().is_empty() {
+                    members.push(ArchiveMember { path, offset: position, size, encoding });
+                }
+                skip(&mut reader, padded)?;
+            }
+        }
+        position += padded;
+    }
+    Ok(members)
+}
+
+/// Read a little-endian number of `N` bytes at `offset` in `data`
+fn le_number<const N: usize>(data: &[u8], offset: usize) -> u64 {
+    data[offset..offset + N].iter().rev().fold(0, |number, &byte| (number << 8) | byte as u64)
+}
+
+/// List the files of a zip archive from its central directory
+fn zip_members(mut file: File) -> io::Result<Vec<ArchiveMember>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let length = file.metadata()?.len();
+    let tail_start = length.saturating_sub(ZIP_TAIL_SIZE);
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(tail_start))?;
+    (&mut file).take(ZIP_TAIL_SIZE).read_to_end(&mut tail)?;
+    let end = tail.windows(4).rposition(|window| window == ZIP_END_OF_DIRECTORY)
+        .filter(|&end| end + 22 <= tail.len())
+        .ok_or_else(|| invalid("no zip end of central directory record"))?;
+    let (directory_size, directory_offset) = (le_number::<4>(&tail, end + 12), le_number::<4>(&tail, end + 16));
+    if directory_size == 0xFFFF_FFFF || directory_offset == 0xFFFF_FFFF {
+        return Err(invalid("ZIP64 archives are not supported"));
+    }
+    if directory_offset + directory_size > length {
+        return Err(invalid("the zip central directory is out of the archive"));
+    }
+    let mut directory = vec![0; directory_size as usize];
+    file.seek(SeekFrom::Start(directory_offset))?;
+    file.read_exact(&mut directory)?;
+
+    let mut members = Vec::new();
+    let mut rest = directory.as_slice();
+    while rest.len() >= 46 && rest.starts_with(ZIP_CENTRAL_HEADER) {
+        let flags = le_number::<2>(rest, 8);
+        let method = le_number::<2>(rest, 10);
+        let (compressed_size, size) = (le_number::<4>(rest, 20), le_number::<4>(rest, 24));
+        let name_length = le_number::<2>(rest, 28) as usize;
+        let record_length = 46 + name_length + le_number::<2>(rest, 30) as usize + le_number::<2>(rest, 32) as usize;
+        let header_offset = le_number::<4>(rest, 42);
+        if rest.len() < record_length {
+            return Err(invalid("truncated zip central directory"));
+        }
+        let name = String::from_utf8_lossy(&rest[46..46 + name_length]).into_owned();
+        rest = &rest[record_length..];
+
+        let encoding = match method {
+            0 => MemberEncoding::Stored,
+            8 => MemberEncoding::Deflated(compressed_size),
+            _ => continue,
+        };
+        let encrypted = flags & 1 != 0;
+        let zip64 = [compressed_size, size, header_offset].contains(&0xFFFF_FFFF);
+        let path = member_path(&name);
+        if encrypted || zip64 || name.ends_with('/') || path.as_os_str().is_empty() {
+            continue;
+        }
+        // The data follows the local header, whose name and extra field may differ from
+        // those of the central directory
+        let mut local = [0; 30];
+        file.seek(SeekFrom::Start(header_offset))?;
+        file.read_exact(&mut local)?;
+        if !local.starts_with(ZIP_LOCAL_HEADER) {
+            return Err(invalid("invalid zip local header"));
+        }
+        let offset = header_offset + 30 + le_number::<2>(&local, 26) + le_number::<2>(&local, 28);
+        members.push(ArchiveMember { path, offset, size, encoding });
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use flate2::Compression;
+    use tempfile::Builder;
+
+    /// A tar header block for a file of `size` bytes named `name`
+    fn tar_header(name: &str, size: u64, kind: u8) -> Vec<u8> {
+        let mut header = vec![0; TAR_BLOCK_SIZE as usize];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        header[148..156].copy_from_slice(b"        ");
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        let checksum: u64 = header.iter().map(|&byte| byte as u64).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        header
+    }
+
+    /// A tar entry: its header and its data padded to whole blocks
+    pub(crate) fn tar_entry(name: &str, data: &[u8], kind: u8) -> Vec<u8> {
+        let mut entry = tar_header(name, data.len() as u64, kind);
+        entry.extend(data);
+        entry.resize(entry.len().div_ceil(TAR_BLOCK_SIZE as usize) * TAR_BLOCK_SIZE as usize, 0);
+        entry
+    }
+
+    #[test]
+    fn test_tar_members() {
+        let tmp_dir = Builder::new().prefix("archive_tar").tempdir().unwrap();
+        let long_name = format!("{}/report.txt", "nested".repeat(20));
+        let mut tar = tar_entry("docs/", b"", b'5');
+        tar.extend(tar_entry("docs/a.txt", b"first file", b'0'));
+        tar.extend(tar_entry("././@LongLink", format!("{}\0", long_name).as_bytes(), b'L'));
+        tar.extend(tar_entry("truncated", b"second file", b'0'));
+        tar.extend(tar_entry("PaxHeaders/c.txt", b"17 path=../c.txt\n", b'x'));
+        tar.extend(tar_entry("c.txt", b"third", b'0'));
+        tar.extend(tar_entry("link", b"", b'2'));
+        tar.extend([0; 1024]);
+        let archive = tmp_dir.path().join("docs.tar");
+        fs::write(&archive, &tar).unwrap();
+        let compressed = tmp_dir.path().join("docs.tar.gz");
+        let mut encoder = GzEncoder::new(fs::File::create(&compressed).unwrap(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        encoder.finish().unwrap();
+
+        for archive in [archive, compressed] {
+            assert!(is_archive(&archive));
+            let members = archive_members(&archive).unwrap();
+            let paths: Vec<_> = members.iter().map(|member| member.path.to_string_lossy().into_owned()).collect();
+            assert_eq!(paths, ["docs/a.txt", long_name.as_str(), "c.txt"]);
+            let hash = compute_archive_member_sha256(&archive, &members[1]).unwrap();
+            assert_eq!(hash, format!("{:x}", Sha256::digest(b"second file")));
+        }
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let tmp_dir = Builder::new().prefix("archive_zip").tempdir().unwrap();
+        let content = b"zipped content ".repeat(100);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&content).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        let mut zip = Vec::new();
+        let mut directory = Vec::new();
+        for (name, method, data) in [("stored.txt", 0u16, &content[..]), ("dir/deflated.txt", 8, &deflated[..])] {
+            let offset = zip.len() as u32;
+            let mut fields = Vec::new();
+            fields.extend(method.to_le_bytes());
+            fields.extend([0; 8]); // Time, date and CRC-32, which are not checked
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((content.len() as u32).to_le_bytes());
+            fields.extend((name.len() as u16).to_le_bytes());
+            zip.extend(b"PK\x03\x04\x14\0\0\0");
+            zip.extend(&fields);
+            zip.extend([0, 0]);
+            zip.extend(name.as_bytes());
+            zip.extend(data);
+            directory.extend(b"PK\x01\x02\x14\0\x14\0\0\0");
+            directory.extend(&fields);
+            directory.extend([0; 12]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let directory_offset = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(b"PK\x05\x06\0\0\0\0\x02\0\x02\0");
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(directory_offset.to_le_bytes());
+        zip.extend([0, 0]);
+        let archive = tmp_dir.path().join("content.zip");
+        fs::write(&archive, &zip).unwrap();
+
+        assert!(is_archive(&archive));
+        let members = archive_members(&archive).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[1].path, Path::new("dir/deflated.txt"));
+        assert_eq!(members[1].encoding, MemberEncoding::Deflated(deflated.len() as u64));
+        let expected = format!("{:x}", Sha256::digest(&content));
+        for member in &members {
+            assert_eq!(compute_archive_member_sha256(&archive, member).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_archives() {
+        let tmp_dir = Builder::new().prefix("archive_invalid").tempdir().unwrap();
+        let tar = tmp_dir.path().join("broken.tar");
+        fs::write(&tar, [b'x'; 1024]).unwrap();
+        assert!(!is_archive(&tar));
+        assert!(archive_members(&tar).is_err());
+        let zip = tmp_dir.path().join("broken.zip");
+        fs::write(&zip, "PK\x03\x04 but nothing else").unwrap();
+        assert!(archive_members(&zip).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Walks a directory tree level by level: the root first, then every entry directly
+/// inside it, then every entry two levels down, and so on.
+///
+/// Entries are yielded as `walkdir` entries, so the walk can replace a depth-first
+/// `WalkDir`. The depth of every entry below the root is 1. Like `filter_entry`, the
+/// predicate decides which entries are yielded, and directories it rejects are not
+/// walked. Symbolic links to directories are not followed.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::breadth_first::BreadthFirst;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// fs::create_dir_all(dir.path().join("a/b")).unwrap();
+/// fs::write(dir.path().join("a/b/deep.txt"), "deep").unwrap();
+/// fs::write(dir.path().join("top.txt"), "top").unwrap();
+///
+/// let files: Vec<_> = BreadthFirst::new(dir.path(), |_| true)
+///     .filter_map(|entry| entry.ok())
+///     .filter(|entry| entry.file_type().is_file())
+///     .map(|entry| entry.file_name().to_owned())
+///     .collect();
+/// assert_eq!(files, ["top.txt", "deep.txt"]);
+/// ```
+pub struct BreadthFirst<P> {
+    current: walkdir::IntoIter,
+    queue: VecDeque<PathBuf>,
+    predicate: P,
+}
+
+impl<P: FnMut(&DirEntry) -> bool> BreadthFirst<P> {
+    /// Starts a walk of `root`, keeping the entries for which `predicate` returns `true`.
+    pub fn new(root: &Path, predicate: P) -> Self {
+        BreadthFirst {
+            current: WalkDir::new(root).max_depth(0).into_iter(),
+            queue: VecDeque::new(),
+            predicate,
+        }
+    }
+}
+
+impl<P: FnMut(&DirEntry) -> bool> Iterator for BreadthFirst<P> {
+    type Item = walkdir::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current.next() {
+                Some(Ok(entry)) => {
+                    if !(self.predicate)(&entry) {
+                        continue;
+                    }
+                    if entry.file_type().is_dir() {
+                        self.queue.push_back(entry.path().to_path_buf());
+                    }
+                    return Some(Ok(entry));
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    let directory = self.queue.pop_front()?;
+                    self.current = WalkDir::new(directory).min_depth(1).max_depth(1).into_iter();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_rejected_directories_not_walked() {
+        let tmp_dir = Builder::new().prefix("breadth_first").tempdir().unwrap();
+        fs::create_dir_all(tmp_dir.path().join("skip/inner")).unwrap();
+        fs::write(tmp_dir.path().join("skip/inner/file.txt"), "skipped").unwrap();
+        fs::write(tmp_dir.path().join("kept.txt"), "kept").unwrap();
+
+        let paths: Vec<PathBuf> = BreadthFirst::new(tmp_dir.path(), |entry| entry.file_name() != "skip")
+            .map(|entry| entry.unwrap().into_path())
+            .collect();
+        assert_eq!(paths, [tmp_dir.path().to_path_buf(), tmp_dir.path().join("kept.txt")]);
+    }
+}
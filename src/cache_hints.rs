@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Tells the kernel that `file` will be read from start to end, so it reads further ahead.
+///
+/// This is only a hint: on platforms without `posix_fadvise` it does nothing.
+///
+/// # Arguments
+///
+/// * `file` - The open file, whose reads through this handle are affected.
+///
+/// # Returns
+///
+/// Result, with an error if the kernel rejects the hint.
+pub fn advise_sequential(file: &File) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::fadvise(file, libc::POSIX_FADV_SEQUENTIAL)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = file;
+        Ok(())
+    }
+}
+
+/// Asks the kernel to drop the cached pages of the file at `path`, so reading a whole
+/// disk once does not evict the page cache of the programs running alongside.
+///
+/// Pages of other files are untouched, and pages still waiting to be written stay
+/// cached. On platforms without `posix_fadvise` it does nothing.
+///
+/// # Arguments
+///
+/// * `path` - The file just read.
+///
+/// # Returns
+///
+/// Result, with an error if the file cannot be opened or the kernel rejects the hint.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::cache_hints::drop_cached_pages;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("scanned.txt");
+/// fs::write(&path, "read once").unwrap();
+/// assert_eq!(fs::read_to_string(&path).unwrap(), "read once");
+///
+/// drop_cached_pages(&path).unwrap();
+/// ```
+pub fn drop_cached_pages(path: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    #[cfg(target_os = "linux")]
+    {
+        linux::fadvise(&file, libc::POSIX_FADV_DONTNEED)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        drop(file);
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    /// Give `advice` for the whole file
+    pub fn fadvise(file: &File, advice: libc::c_int) -> io::Result<()> {
+        // posix_fadvise returns the error number instead of setting errno
+        match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) } {
+            0 => Ok(()),
+            errno => Err(io::Error::from_raw_os_error(errno)),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::str::FromStr;
+use serde::Serialize;
+use crate::compute_sha256::sha256_implementation;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::localize::message;
+
+/// How `--capabilities` prints the capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CapabilitiesFormat {
+    /// The version, the optional features and the SHA256 implementation, for people.
+    #[default]
+    Text,
+    /// Every field of `Capabilities` as a JSON object, for wrapper tools.
+    Json,
+}
+
+impl FromStr for CapabilitiesFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(CapabilitiesFormat::Text),
+            "json" => Ok(CapabilitiesFormat::Json),
+            _ => Err(message("invalid-capabilities-format", &[("value", &s)])),
+        }
+    }
+}
+
+/// What this build of dupefiles supports on the platform it runs on, for wrapper tools
+/// to adapt to the installed binary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// The version of dupefiles.
+    pub version: &'static str,
+    /// The operating system, as in `std::env::consts::OS`.
+    pub os: &'static str,
+    /// The CPU architecture, as in `std::env::consts::ARCH`.
+    pub arch: &'static str,
+    /// The optional cargo features built in.
+    pub features: Vec<&'static str>,
+    /// The SHA256 implementation used on this CPU.
+    pub sha256: &'static str,
+    /// The hash of every group, then the other digests `--also-hash` accepts.
+    pub hash_algorithms: Vec<&'static str>,
+    /// The content modes comparing part of the content of files.
+    pub content_modes: Vec<&'static str>,
+    /// The report formats.
+    pub output_formats: Vec<&'static str>,
+    /// The disc image and archive formats whose files can be compared with `--scan-images`
+    /// and `--scan-archives`.
+    pub archive_formats: Vec<&'static str>,
+    /// The URL schemes scanned as remote sources.
+    pub remote_sources: Vec<&'static str>,
+    /// The actions that can be applied to duplicates on this platform.
+    pub actions: Vec<&'static str>,
+    /// Whether copies sharing their data blocks are detected, so they are not counted as wasted.
+    pub shared_extents: bool,
+    /// Whether network file systems are detected, for `--skip-network`.
+    pub network_detection: bool,
+    /// Whether files are ordered by their first block on disk with `--physical-order`,
+    /// rather than by inode number.
+    pub physical_order: bool,
+    /// Whether `--drop-cache` drops hashed files from the page cache.
+    pub drop_cache: bool,
+    /// Whether `--direct-io` bypasses the page cache.
+    pub direct_io: bool,
+    /// Whether `--alternate-streams` compares NTFS alternate data streams.
+    pub alternate_streams: bool,
+}
+
+/// Describes what this build supports on the platform it runs on.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::capabilities::capabilities;
+///
+/// let capabilities = capabilities();
+/// assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+/// assert_eq!(capabilities.hash_algorithms, ["sha256", "md5", "sha1"]);
+/// ```
+pub fn capabilities() -> Capabilities {
+    let enabled = |options: &[(bool, &'static str)]| -> Vec<&'static str> {
+        options.iter().filter(|(enabled, _)| *enabled).map(|(_, name)| *name).collect()
+    };
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        features: enabled(&[
+            (cfg!(feature = "s3"), "s3"),
+            (cfg!(feature = "sftp"), "sftp"),
+            (cfg!(feature = "webhook"), "webhook"),
+            (cfg!(feature = "desktop"), "desktop"),
+            (cfg!(feature = "fuse"), "fuse"),
+            (cfg!(feature = "asm"), "asm"),
+            (cfg!(feature = "actions"), "actions"),
+            (cfg!(feature = "reports"), "reports"),
+        ]),
+        sha256: sha256_implementation(),
+        hash_algorithms: ["sha256"].into_iter()
+            .chain([HashAlgorithm::Md5, HashAlgorithm::Sha1].map(HashAlgorithm::name))
+            .collect(),
+        content_modes: vec!["audio", "image", "email", "text", "gz"],
+        output_formats: vec!["csv", "json", "ncdu", "hashdeep"],
+        archive_formats: vec!["iso", "tar", "tar.gz", "zip"],
+        remote_sources: enabled(&[(cfg!(feature = "s3"), "s3"), (cfg!(feature = "sftp"), "sftp")]),
+        actions: enabled(&[
+            (cfg!(feature = "actions"), "delete"),
+            (cfg!(feature = "actions"), "trash"),
+            (cfg!(feature = "actions"), "hardlink"),
+            (cfg!(all(feature = "actions", target_os = "macos")), "reflink"),
+            (cfg!(all(feature = "actions", target_os = "linux")), "dedupe"),
+        ]),
+        shared_extents: cfg!(target_os = "linux"),
+        network_detection: cfg!(any(target_os = "linux", target_os = "macos")),
+        physical_order: cfg!(any(target_os = "linux", target_os = "macos")),
+        drop_cache: cfg!(target_os = "linux"),
+        direct_io: cfg!(any(target_os = "linux", target_os = "macos")),
+        alternate_streams: cfg!(windows),
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use crate::compute_sha256::compute_sha256;
+use crate::duplicate_group::DuplicateGroup;
+use crate::human_readable_size::human_readable_size;
+use crate::localize::message;
+
+/// Why a file listed in a report no longer matches it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Staleness {
+    /// The file no longer exists.
+    Missing,
+    /// The path no longer names a regular file.
+    NotAFile,
+    /// The file no longer has the size of its group.
+    SizeChanged { expected: u64, actual: u64 },
+    /// The file was modified after the report was written.
+    Modified,
+    /// The file no longer has the hash of its group.
+    ContentChanged,
+    /// The file could not be queried or read, for the reason given.
+    Unreadable(String),
+}
+
+impl fmt::Display for Staleness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Staleness::Missing => write!(f, "{}", message("stale-missing", &[])),
+            Staleness::NotAFile => write!(f, "{}", message("stale-not-a-file", &[])),
+            Staleness::SizeChanged { expected, actual } => write!(f, "{}", message("stale-size-changed", &[
+                ("expected", &human_readable_size(*expected)),
+                ("actual", &human_readable_size(*actual)),
+            ])),
+            Staleness::Modified => write!(f, "{}", message("stale-modified", &[])),
+            Staleness::ContentChanged => write!(f, "{}", message("stale-content-changed", &[])),
+            Staleness::Unreadable(reason) => write!(f, "{}", message("stale-unreadable", &[("error", reason)])),
+        }
+    }
+}
+
+/// What `check_report` checks.
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    /// Only check this many members of the groups, chosen at random, instead of all of them.
+    pub sample: Option<usize>,
+    /// Also hash the members checked and compare them with the hash of their group.
+    /// Groups compared by content only, such as `audio:` groups, are not hashed again.
+    pub rehash: bool,
+    /// When the report was written, so members modified later are reported as stale.
+    pub report_time: Option<SystemTime>,
+}
+
+/// The outcome of `check_report`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Number of members checked.
+    pub checked: usize,
+    /// The members that no longer match the report, in report order.
+    pub stale: Vec<(PathBuf, Staleness)>,
+}
+
+/// Checks the members of the groups of an older report against the file system, so a
+/// report is not acted upon after its files changed.
+///
+/// Every member is queried for its type, size and modification time, and hashed again
+/// with `CheckOptions::rehash`. Members whose group hash is only known by its identifier,
+/// as in CSV reports, are compared with that identifier.
+///
+/// # Arguments
+///
+/// * `groups` - The groups of the report, as read by `read_report`.
+/// * `options` - The `CheckOptions` selecting the members checked and how.
+///
+/// # Returns
+///
+/// The number of members checked and those found stale, with the first reason found.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::check_report::{check_report, CheckOptions, Staleness};
+/// use dupefiles::duplicate_group::DuplicateGroup;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+/// fs::write(&a, "copy").unwrap();
+/// let group = DuplicateGroup {
+///     hash: "ed6b1d0c4a6bb2bc".to_string(),
+///     size: 4,
+///     files: vec![a, b.clone()],
+///     reflinked: Vec::new(),
+///     digests: Default::default(),
+///     entries: Vec::new(),
+/// };
+///
+/// let result = check_report(&[group], &CheckOptions::default());
+/// assert_eq!(result.checked, 2);
+/// assert_eq!(result.stale, vec![(b, Staleness::Missing)]);
+/// ```
+pub fn check_report(groups: &[DuplicateGroup], options: &CheckOptions) -> CheckResult {
+    let mut members: Vec<(&DuplicateGroup, &PathBuf)> = groups.iter()
+        .flat_map(|group| group.files.iter().map(move |file| (group, file)))
+        .collect();
+    if let Some(sample) = options.sample.filter(|sample| *sample < members.len()) {
+        // Hashing with random keys shuffles the members without a random number generator
+        let random = RandomState::new();
+        let mut indices: Vec<usize> = (0..members.len()).collect();
+        indices.sort_by_cached_key(|index| random.hash_one(index));
+        indices.truncate(sample);
+        indices.sort_unstable();
+        members = indices.into_iter().map(|index| members[index]).collect();
+    }
+
+    let stale = members.iter()
+        .filter_map(|(group, file)| check_member(group, file, options).map(|staleness| (file.to_path_buf(), staleness)))
+        .collect();
+    CheckResult { checked: members.len(), stale }
+}
+
+/// Why `file`, a member of `group`, is stale, if it is
+fn check_member(group: &DuplicateGroup, file: &PathBuf, options: &CheckOptions) -> Option<Staleness> {
+    let metadata = match fs::metadata(file) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Some(Staleness::Missing),
+        Err(e) => return Some(Staleness::Unreadable(e.to_string())),
+    };
+    if !metadata.is_file() {
+        return Some(Staleness::NotAFile);
+    }
+    // Members compared by content only may differ in size and hash
+    let content_only = group.hash.contains(':');
+    if !content_only && metadata.len() != group.size {
+        return Some(Staleness::SizeChanged { expected: group.size, actual: metadata.len() });
+    }
+    if let (Some(report_time), Ok(modified)) = (options.report_time, metadata.modified()) {
+        if modified > report_time {
+            return Some(Staleness::Modified);
+        }
+    }
+    if options.rehash && !content_only {
+        // A CSV report only gives the start of the hash
+        return match compute_sha256(file) {
+            Ok(hash) if hash.starts_with(&group.hash) => None,
+            Ok(_) => Some(Staleness::ContentChanged),
+            Err(e) => Some(Staleness::Unreadable(e.to_string())),
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_rehash_and_sample() {
+        let tmp_dir = Builder::new().prefix("check_report").tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.txt", "b.txt", "c.txt"].iter().map(|name| tmp_dir.path().join(name)).collect();
+        for file in &files {
+            fs::write(file, "copy").unwrap();
+        }
+        // The copy edited in place keeps its size
+        fs::write(&files[2], "edit").unwrap();
+        let group = DuplicateGroup {
+            hash: compute_sha256(&files[0]).unwrap(),
+            size: 4,
+            files: files.clone(),
+            reflinked: Vec::new(),
+            digests: Default::default(),
+            entries: Vec::new(),
+        };
+
+        let result = check_report(std::slice::from_ref(&group), &CheckOptions::default());
+        assert!(result.stale.is_empty());
+        let options = CheckOptions { rehash: true, ..Default::default() };
+        let result = check_report(std::slice::from_ref(&group), &options);
+        assert_eq!(result.stale, vec![(files[2].clone(), Staleness::ContentChanged)]);
+
+        let options = CheckOptions { sample: Some(2), ..Default::default() };
+        assert_eq!(check_report(&[group], &options).checked, 2);
+    }
+
+    #[test]
+    fn test_modified_since_report() {
+        let tmp_dir = Builder::new().prefix("check_report_modified").tempdir().unwrap();
+        let file = tmp_dir.path().join("a.txt");
+        fs::write(&file, "copy").unwrap();
+        let group = DuplicateGroup {
+            hash: String::new(),
+            size: 4,
+            files: vec![file.clone()],
+            reflinked: Vec::new(),
+            digests: Default::default(),
+            entries: Vec::new(),
+        };
+        let options = CheckOptions { report_time: Some(SystemTime::UNIX_EPOCH), ..Default::default() };
+        assert_eq!(check_report(&[group], &options).stale, vec![(file, Staleness::Modified)]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::io;
+use std::path::Path;
+use crate::localize::message;
+
+/// Returns whether the volume holding `path` supports copy-on-write clones.
+///
+/// Only APFS volumes on macOS are detected as supporting clones; on other platforms
+/// the answer is always `false`.
+///
+/// # Arguments
+///
+/// * `path` - Any path on the volume.
+///
+/// # Returns
+///
+/// Whether `clone_file` can be used on the volume, or an error if it cannot be queried.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::clone_file::clone_supported;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let supported = clone_supported(dir.path()).unwrap();
+/// # #[cfg(not(target_os = "macos"))]
+/// assert!(!supported);
+/// ```
+pub fn clone_supported(path: &Path) -> io::Result<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        macos::clone_supported(path)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = path;
+        Ok(false)
+    }
+}
+
+/// Returns whether the file system holding `path` can share data blocks between files,
+/// so the duplicates on it can be reflinked or deduplicated rather than removed.
+///
+/// APFS volumes are detected with `clone_supported` on macOS, and Btrfs, XFS and bcachefs
+/// by their file system type on Linux, where an XFS file system made without reflink
+/// support is still reported as supporting it. On other platforms the answer is always
+/// `false`.
+///
+/// # Arguments
+///
+/// * `path` - Any path on the file system.
+///
+/// # Returns
+///
+/// Whether the file system supports copy-on-write, or an error if it cannot be queried.
+pub fn copy_on_write_supported(path: &Path) -> io::Result<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        macos::clone_supported(path)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        /// BTRFS_SUPER_MAGIC, XFS_SUPER_MAGIC and BCACHEFS_SUPER_MAGIC (from linux/magic.h)
+        const COPY_ON_WRITE_MAGICS: [u32; 3] = [0x9123_683e, 0x5846_5342, 0xca45_1a4e];
+        // f_type is 32 bits wide on some architectures, where the larger magics are negative
+        let magic = crate::network_filesystem::statfs(path)?.f_type as u32;
+        Ok(COPY_ON_WRITE_MAGICS.contains(&magic))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = path;
+        Ok(false)
+    }
+}
+
+/// Creates `destination` as a copy-on-write clone of `source`, sharing its data blocks
+/// until either file is modified.
+///
+/// The clone also takes the permissions and timestamps of `source`. Both paths must be
+/// on the same volume and `destination` must not exist.
+///
+/// # Arguments
+///
+/// * `source` - The file to clone.
+/// * `destination` - The path of the clone.
+///
+/// # Returns
+///
+/// Result, with an `Unsupported` error on platforms other than macOS.
+pub fn clone_file(source: &Path, destination: &Path) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        macos::clone_file(source, destination)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (source, destination);
+        Err(io::Error::new(io::ErrorKind::Unsupported, message("clone-macos-only", &[])))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Do not follow a symbolic link at the source path (from sys/clonefile.h)
+    const CLONE_NOFOLLOW: u32 = 0x0001;
+
+    /// The buffer filled by getattrlist when asking for the volume capabilities
+    #[repr(C, packed(4))]
+    struct VolumeCapabilities {
+        length: u32,
+        capabilities: libc::vol_capabilities_attr_t,
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    pub fn clone_supported(path: &Path) -> io::Result<bool> {
+        // Volume attributes can only be read from the volume's mount point
+        let path = c_path(path)?;
+        let mut stats: libc::statfs = unsafe { mem::zeroed() };
+        if unsafe { libc::statfs(path.as_ptr(), &mut stats) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mount_point = unsafe { CStr::from_ptr(stats.f_mntonname.as_ptr()) };
+
+        let mut request = libc::attrlist {
+            bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+            reserved: 0,
+            commonattr: 0,
+            volattr: libc::ATTR_VOL_INFO | libc::ATTR_VOL_CAPABILITIES,
+            dirattr: 0,
+            fileattr: 0,
+            forkattr: 0,
+        };
+        let mut reply: VolumeCapabilities = unsafe { mem::zeroed() };
+        let result = unsafe {
+            libc::getattrlist(
+                mount_point.as_ptr(),
+                &mut request as *mut libc::attrlist as *mut libc::c_void,
+                &mut reply as *mut VolumeCapabilities as *mut libc::c_void,
+                mem::size_of::<VolumeCapabilities>(),
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let capabilities = reply.capabilities;
+        let interfaces = libc::VOL_CAPABILITIES_INTERFACES;
+        Ok(capabilities.valid[interfaces] & capabilities.capabilities[interfaces] & libc::VOL_CAP_INT_CLONE != 0)
+    }
+
+    pub fn clone_file(source: &Path, destination: &Path) -> io::Result<()> {
+        let (source, destination) = (c_path(source)?, c_path(destination)?);
+        if unsafe { libc::clonefile(source.as_ptr(), destination.as_ptr(), CLONE_NOFOLLOW) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
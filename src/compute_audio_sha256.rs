@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::localize::message;
+
+/// Extensions of the audio formats whose tags `compute_audio_sha256` can skip.
+pub const AUDIO_EXTENSIONS: [&str; 2] = ["mp3", "flac"];
+
+/// Returns `true` if the path has one of the `AUDIO_EXTENSIONS`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::compute_audio_sha256::is_audio_file;
+///
+/// assert!(is_audio_file(Path::new("track.MP3")));
+/// assert!(!is_audio_file(Path::new("cover.jpg")));
+/// ```
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Computes the SHA256 hash of the audio frames of an MP3 or FLAC file.
+///
+/// For MP3 files, leading ID3v2 tags and trailing APEv2 and ID3v1 tags are skipped.
+/// For FLAC files, every metadata block (including Vorbis comments and embedded
+/// pictures) is skipped. The same track with different tags or artwork therefore
+/// produces the same hash.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the MP3 or FLAC file to hash.
+///
+/// # Returns
+///
+/// * `Result<String>` - The SHA256 hash of the audio frames as a hexadecimal string,
+///   or an error if the file cannot be read or is not a recognized audio file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use dupefiles::compute_audio_sha256::compute_audio_sha256;
+///
+/// match compute_audio_sha256(Path::new("track.mp3")) {
+///     Ok(hash) => println!("Audio SHA256: {}", hash),
+///     Err(e) => eprintln!("Error: {}", e),
+/// }
+/// ```
+pub fn compute_audio_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+    let (start, end) = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("flac")) {
+        (flac_audio_start(&mut file)?, length)
+    } else {
+        (id3v2_end(&mut file)?, mp3_audio_end(&mut file, length)?)
+    };
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = file.take(end.saturating_sub(start));
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns the offset just past any ID3v2 tags at the start of the file.
+fn id3v2_end(file: &mut File) -> Result<u64> {
+    let mut offset = 0;
+    loop {
+        let mut header = [0u8; 10];
+        file.seek(SeekFrom::Start(offset))?;
+        if file.read_exact(&mut header).is_err() || &header[..3] != b"ID3" {
+            return Ok(offset);
+        }
+        // The tag size is a 28-bit "synchsafe" integer and excludes the header and footer
+        let size = header[6..10].iter().fold(0u64, |size, byte| (size << 7) | (byte & 0x7f) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        offset += 10 + size + footer;
+    }
+}
+
+/// Returns the offset where trailing APEv2 and ID3v1 tags begin, or `length` if there are none.
+fn mp3_audio_end(file: &mut File, length: u64) -> Result<u64> {
+    let mut end = length;
+    if end >= 128 {
+        let mut tag = [0u8; 3];
+        file.seek(SeekFrom::Start(end - 128))?;
+        file.read_exact(&mut tag)?;
+        if &tag == b"TAG" {
+            end -= 128;
+        }
+    }
+    if end >= 32 {
+        let mut footer = [0u8; 32];
+        file.seek(SeekFrom::Start(end - 32))?;
+        file.read_exact(&mut footer)?;
+        if &footer[..8] == b"APETAGEX" {
+            // The size covers the items and footer; a header of 32 bytes may precede them
+            let size = u32::from_le_bytes([footer[12], footer[13], footer[14], footer[15]]) as u64;
+            let header = if footer[23] & 0x80 != 0 { 32 } else { 0 };
+            end = end.saturating_sub(size + header);
+        }
+    }
+    Ok(end)
+}
+
+/// Returns the offset of the first FLAC audio frame, just past the metadata blocks.
+fn flac_audio_start(file: &mut File) -> Result<u64> {
+    let mut marker = [0u8; 4];
+    file.read_exact(&mut marker)?;
+    if &marker != b"fLaC" {
+        return Err(Error::new(ErrorKind::InvalidData, message("content-not-flac", &[])));
+    }
+    let mut offset = 4;
+    loop {
+        let mut header = [0u8; 4];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        offset += 4 + length;
+        if header[0] & 0x80 != 0 {
+            return Ok(offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    const FRAMES: &[u8] = b"\xff\xfb\x90\x64 pretend these are mpeg audio frames";
+
+    fn id3v2_tag(title: &[u8]) -> Vec<u8> {
+        let mut tag = b"ID3\x03\x00\x00\x00\x00\x00".to_vec();
+        tag.push(title.len() as u8);
+        tag.extend_from_slice(title);
+        tag
+    }
+
+    fn id3v1_tag(title: &[u8]) -> Vec<u8> {
+        let mut tag = b"TAG".to_vec();
+        tag.extend_from_slice(title);
+        tag.resize(128, 0);
+        tag
+    }
+
+    #[test]
+    fn test_mp3_tags_are_ignored() {
+        let tmp_dir = Builder::new().prefix("audio_mp3").tempdir().unwrap();
+        let tagged = tmp_dir.path().join("tagged.mp3");
+        let retagged = tmp_dir.path().join("retagged.mp3");
+        let bare = tmp_dir.path().join("bare.mp3");
+        fs::write(&tagged, [id3v2_tag(b"Title"), FRAMES.to_vec(), id3v1_tag(b"Title")].concat()).unwrap();
+        fs::write(&retagged, [id3v2_tag(b"Another title"), FRAMES.to_vec()].concat()).unwrap();
+        fs::write(&bare, FRAMES).unwrap();
+
+        let hash = compute_audio_sha256(&tagged).unwrap();
+        assert_eq!(hash, compute_audio_sha256(&retagged).unwrap());
+        assert_eq!(hash, compute_audio_sha256(&bare).unwrap());
+    }
+
+    #[test]
+    fn test_flac_metadata_is_ignored() {
+        let tmp_dir = Builder::new().prefix("audio_flac").tempdir().unwrap();
+        let first = tmp_dir.path().join("first.flac");
+        let second = tmp_dir.path().join("second.flac");
+        let streaminfo = [&[0x00, 0x00, 0x00, 0x04][..], b"info"].concat();
+        fs::write(&first, [&b"fLaC"[..], &streaminfo, &[0x84, 0x00, 0x00, 0x02], b"ab", FRAMES].concat()).unwrap();
+        fs::write(&second, [&b"fLaC"[..], &streaminfo, &[0x84, 0x00, 0x00, 0x03], b"xyz", FRAMES].concat()).unwrap();
+
+        assert_eq!(compute_audio_sha256(&first).unwrap(), compute_audio_sha256(&second).unwrap());
+    }
+
+    #[test]
+    fn test_not_a_flac_file() {
+        let tmp_dir = Builder::new().prefix("audio_not_flac").tempdir().unwrap();
+        let path = tmp_dir.path().join("fake.flac");
+        fs::write(&path, FRAMES).unwrap();
+
+        assert!(compute_audio_sha256(&path).is_err());
+    }
+}
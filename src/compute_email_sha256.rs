@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result};
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use crate::archive_members::{ArchiveMember, MemberEncoding};
+use crate::localize::message;
+
+/// The line starting every message of an mbox mailbox
+const MBOX_SEPARATOR: &[u8] = b"From ";
+
+/// Returns `true` if the path is an email message file: a `.eml` file, or a file in the
+/// `cur`, `new` or `tmp` folder of a Maildir.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::compute_email_sha256::is_email_file;
+///
+/// let maildir = tempfile::tempdir().unwrap();
+/// for folder in ["cur", "new", "tmp"] {
+///     fs::create_dir(maildir.path().join(folder)).unwrap();
+/// }
+/// assert!(is_email_file(&maildir.path().join("cur/1700000000.M1P2.host:2,S")));
+/// assert!(is_email_file(std::path::Path::new("/saved/invoice.EML")));
+/// assert!(!is_email_file(std::path::Path::new("/photos/cur/img_001.jpg")));
+/// ```
+pub fn is_email_file(path: &Path) -> bool {
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("eml")) {
+        return true;
+    }
+    let Some(folder) = path.parent() else {
+        return false;
+    };
+    folder.file_name().is_some_and(|name| name == "cur" || name == "new" || name == "tmp")
+        && folder.parent().is_some_and(|maildir| maildir.join("cur").is_dir() && maildir.join("new").is_dir())
+}
+
+/// Returns `true` if the file is an mbox mailbox: a file starting with a `From ` line.
+pub fn is_mbox(path: &Path) -> bool {
+    let mut head = [0; MBOX_SEPARATOR.len()];
+    File::open(path).and_then(|mut file| file.read_exact(&mut head)).is_ok() && head == MBOX_SEPARATOR
+}
+
+/// Lists the messages of an mbox mailbox, so they can be compared like the files of an
+/// archive.
+///
+/// Every message starts with a `From ` line at the start of the file or after an empty
+/// line, which is not part of the message, and ends before the empty line preceding the
+/// next one. Messages are named by their position in the mailbox, from 1.
+///
+/// # Arguments
+///
+/// * `mailbox` - The mbox file to read.
+///
+/// # Returns
+///
+/// Every message of the mailbox, or an error if it cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::compute_email_sha256::mbox_messages;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let mailbox = dir.path().join("inbox");
+/// fs::write(&mailbox, "From a@example.com Mon Jan  1 00:00:00 2024\nSubject: one\n\nFirst\n\n\
+///                      From b@example.com Mon Jan  1 00:00:00 2024\nSubject: two\n\nSecond\n").unwrap();
+/// let messages = mbox_messages(&mailbox).unwrap();
+/// assert_eq!(messages.len(), 2);
+/// assert_eq!(messages[1].path.to_str(), Some("2"));
+/// assert_eq!(messages[0].size, "Subject: one\n\nFirst\n".len() as u64);
+/// ```
+pub fn mbox_messages(mailbox: &Path) -> Result<Vec<ArchiveMember>> {
+    let mut reader = BufReader::with_capacity(1024 * 1024, File::open(mailbox)?);
+    let mut messages = Vec::new();
+    let mut line = Vec::new();
+    let mut position = 0;
+    // Where the current message starts, and where it ends if the next line starts another
+    let mut start = None;
+    let mut end = 0;
+    let mut previous_empty = true;
+    loop {
+        line.clear();
+        let length = reader.read_until(b'\n', &mut line)? as u64;
+        if length == 0 {
+            break;
+        }
+        if previous_empty && line.starts_with(MBOX_SEPARATOR) {
+            if let Some(start) = start {
+                messages.push(mbox_message(messages.len(), start, end));
+            }
+            start = Some(position + length);
+            end = position + length;
+        } else if !is_empty_line(&line) {
+            end = position + length;
+        }
+        previous_empty = is_empty_line(&line);
+        position += length;
+    }
+    if let Some(start) = start {
+        messages.push(mbox_message(messages.len(), start, end));
+    }
+    Ok(messages)
+}
+
+/// The `index`th message of a mailbox, from `start` to `end`, keeping the empty lines
+/// of its body but the last ones, which separate it from the next message
+fn mbox_message(index: usize, start: u64, end: u64) -> ArchiveMember {
+    ArchiveMember { path: PathBuf::from((index + 1).to_string()), offset: start, size: end - start, encoding: MemberEncoding::Message }
+}
+
+/// Whether a line holds nothing but its line ending
+fn is_empty_line(line: &[u8]) -> bool {
+    line.iter().all(|&byte| byte == b'\n' || byte == b'\r')
+}
+
+/// Computes the SHA256 hash of an email message over its `Message-ID` and its body only.
+///
+/// The other headers, such as `Received`, `Delivered-To` and the flags mail clients
+/// store in `Status` headers, are ignored, so the same message saved by different
+/// clients or delivered to several folders produces the same hash. The body is
+/// normalized like `compute_text_sha256` does, with CRLF line endings, spaces at the
+/// end of lines and empty lines at the end of the body ignored, and with the `>` that
+/// mbox mailboxes add before body lines starting with `From ` removed.
+///
+/// # Arguments
+///
+/// * `reader` - The message, headers first.
+///
+/// # Returns
+///
+/// * `Result<String>` - The SHA256 hash as a hexadecimal string, or an error if the
+///   message cannot be read or does not start with headers.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::compute_email_sha256::compute_email_sha256;
+///
+/// let delivered = "Received: from mx1\r\nMessage-ID: <1@example.com>\r\nSubject: Hi\r\n\r\nHello\r\n";
+/// let saved = "Status: RO\nMessage-ID:\n <1@example.com>\nSubject: Hi\n\nHello\n\n";
+/// let hash = |message: &str| compute_email_sha256(message.as_bytes()).unwrap();
+/// assert_eq!(hash(delivered), hash(saved));
+/// assert_ne!(hash(delivered), hash("Message-ID: <2@example.com>\n\nHello\n"));
+/// assert!(compute_email_sha256("just some text".as_bytes()).is_err());
+/// ```
+pub fn compute_email_sha256(mut reader: impl BufRead) -> Result<String> {
+    let not_a_message = || Error::new(ErrorKind::InvalidData, message("content-not-email", &[]));
+    let mut line = Vec::new();
+    let mut message_id = String::new();
+    let mut in_message_id = false;
+    let mut headers = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 || is_empty_line(&line) {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if text.starts_with([' ', '\t']) {
+            // A header folded on several lines
+            if headers == 0 {
+                return Err(not_a_message());
+            }
+            if in_message_id {
+                message_id.push_str(text.trim());
+            }
+            continue;
+        }
+        let (name, value) = text.split_once(':').ok_or_else(not_a_message)?;
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(not_a_message());
+        }
+        in_message_id = name.eq_ignore_ascii_case("message-id");
+        if in_message_id {
+            message_id = value.trim().to_string();
+        }
+        headers += 1;
+    }
+    if headers == 0 {
+        return Err(not_a_message());
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"Message-ID: ");
+    hasher.update(message_id.trim_start_matches('<').trim_end_matches('>').as_bytes());
+    hasher.update(b"\n\n");
+    // Empty lines are only hashed once a line follows them
+    let mut empty_lines = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let mut end = line.len();
+        while end > 0 && matches!(line[end - 1], b'\n' | b'\r' | b' ' | b'\t') {
+            end -= 1;
+        }
+        let mut body_line = &line[..end];
+        if body_line.is_empty() {
+            empty_lines += 1;
+            continue;
+        }
+        let quotes = body_line.iter().take_while(|&&byte| byte == b'>').count();
+        if quotes > 0 && body_line[quotes..].starts_with(MBOX_SEPARATOR) {
+            body_line = &body_line[1..];
+        }
+        for _ in 0..empty_lines {
+            hasher.update(b"\n");
+        }
+        empty_lines = 0;
+        hasher.update(body_line);
+        hasher.update(b"\n");
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+    use crate::archive_members::open_archive_member;
+
+    #[test]
+    fn test_mbox_messages_match_maildir() {
+        let tmp_dir = Builder::new().prefix("email_mbox").tempdir().unwrap();
+        let mailbox = tmp_dir.path().join("archive.mbox");
+        fs::write(&mailbox, "From a@example.com Mon Jan  1 00:00:00 2024\n\
+            Message-ID: <1@example.com>\nX-Status: A\n\nQuoted below:\n>From the top\n\n\n\
+            From b@example.com Mon Jan  1 00:00:00 2024\nMessage-ID: <2@example.com>\n\nOther\n").unwrap();
+        let maildir = tmp_dir.path().join("1700000000.M1P2.host:2,S");
+        fs::write(&maildir, "Message-ID: <1@example.com>\r\nReceived: from mx1\r\n\r\nQuoted below:\r\nFrom the top\r\n").unwrap();
+
+        let messages = mbox_messages(&mailbox).unwrap();
+        assert_eq!(messages.len(), 2);
+        let hash = |message: &ArchiveMember| compute_email_sha256(BufReader::new(open_archive_member(&mailbox, message).unwrap())).unwrap();
+        let expected = compute_email_sha256(BufReader::new(File::open(&maildir).unwrap())).unwrap();
+        assert_eq!(hash(&messages[0]), expected);
+        assert_ne!(hash(&messages[1]), expected);
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io::{self, BufReader, Result};
+use std::path::Path;
+use std::str::FromStr;
+use flate2::read::MultiGzDecoder;
+use sha2::{Digest, Sha256};
+use crate::localize::message;
+
+/// A compression format whose files can be compared by their decompressed content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionFormat {
+    /// gzip, as written by `gzip` and `logrotate`, in `.gz` and `.tgz` files.
+    Gzip,
+}
+
+impl CompressionFormat {
+    /// Returns the name of the format, as accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressionFormat::Gzip => "gz",
+        }
+    }
+}
+
+impl FromStr for CompressionFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gz" | "gzip" => Ok(CompressionFormat::Gzip),
+            _ => Err(message("invalid-compression", &[("value", &s)])),
+        }
+    }
+}
+
+/// Extensions of the files `compute_gzip_sha256` decompresses.
+pub const GZIP_EXTENSIONS: [&str; 2] = ["gz", "tgz"];
+
+/// Returns `true` if the path has one of the `GZIP_EXTENSIONS`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::compute_gzip_sha256::is_gzip_file;
+///
+/// assert!(is_gzip_file(Path::new("/var/log/syslog.2.GZ")));
+/// assert!(!is_gzip_file(Path::new("/var/log/syslog.1")));
+/// ```
+pub fn is_gzip_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| GZIP_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Computes the SHA256 hash of the decompressed content of a gzip file.
+///
+/// Files made of several gzip members, as written by appending to a `.gz` file, are
+/// decompressed whole. The hash is that of the decompressed bytes, so it equals the
+/// SHA256 hash of the original file, whatever the compression level and the name and
+/// time stored in the gzip header.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the gzip file to hash.
+///
+/// # Returns
+///
+/// * `Result<String>` - The SHA256 hash of the decompressed content as a hexadecimal
+///   string, or an error if the file cannot be read or is not valid gzip.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Write;
+/// use flate2::{write::GzEncoder, Compression};
+/// use dupefiles::compute_gzip_sha256::compute_gzip_sha256;
+/// use dupefiles::compute_sha256::compute_sha256;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let log = dir.path().join("app.log.1");
+/// let compressed = dir.path().join("app.log.1.gz");
+/// fs::write(&log, "first line\nsecond line\n").unwrap();
+/// let mut encoder = GzEncoder::new(fs::File::create(&compressed).unwrap(), Compression::best());
+/// encoder.write_all(b"first line\nsecond line\n").unwrap();
+/// encoder.finish().unwrap();
+///
+/// assert_eq!(compute_gzip_sha256(&compressed).unwrap(), compute_sha256(&log).unwrap());
+/// assert!(compute_gzip_sha256(&log).is_err());
+/// ```
+pub fn compute_gzip_sha256(path: &Path) -> Result<String> {
+    let file = File::open(path)?;
+    let mut decoder = MultiGzDecoder::new(BufReader::with_capacity(1024 * 1024, file));
+    let mut hasher = Sha256::new();
+    io::copy(&mut decoder, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tempfile::Builder;
+
+    /// Write `content` to `path` gzip-compressed at `level`
+    fn write_gzip(path: &Path, content: &[u8], level: u32) {
+        let mut encoder = GzEncoder::new(fs::File::create(path).unwrap(), Compression::new(level));
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_compression_levels() {
+        let tmp_dir = Builder::new().prefix("gzip_levels").tempdir().unwrap();
+        let (fast, best) = (tmp_dir.path().join("fast.gz"), tmp_dir.path().join("best.gz"));
+        let content = b"repeated line\n".repeat(1000);
+        write_gzip(&fast, &content, 1);
+        write_gzip(&best, &content, 9);
+
+        assert_ne!(fs::read(&fast).unwrap(), fs::read(&best).unwrap());
+        assert_eq!(compute_gzip_sha256(&fast).unwrap(), compute_gzip_sha256(&best).unwrap());
+    }
+
+    #[test]
+    fn test_multiple_members() {
+        let tmp_dir = Builder::new().prefix("gzip_members").tempdir().unwrap();
+        let (whole, appended) = (tmp_dir.path().join("whole.gz"), tmp_dir.path().join("appended.gz"));
+        write_gzip(&whole, b"first\nsecond\n", 6);
+        write_gzip(&appended, b"first\n", 6);
+        let first = fs::read(&appended).unwrap();
+        write_gzip(&appended, b"second\n", 6);
+        let second = fs::read(&appended).unwrap();
+        fs::write(&appended, [first, second].concat()).unwrap();
+
+        assert_eq!(compute_gzip_sha256(&whole).unwrap(), compute_gzip_sha256(&appended).unwrap());
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Result};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::localize::message;
+
+/// Extensions of the image formats whose metadata `compute_image_sha256` can skip.
+pub const IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// PNG chunks that affect how the image is rendered; every other chunk is metadata.
+const PNG_IMAGE_CHUNKS: [&[u8; 4]; 5] = [b"IHDR", b"PLTE", b"tRNS", b"IDAT", b"IEND"];
+
+/// Returns `true` if the path has one of the `IMAGE_EXTENSIONS`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::compute_image_sha256::is_image_file;
+///
+/// assert!(is_image_file(Path::new("photo.JPG")));
+/// assert!(!is_image_file(Path::new("track.mp3")));
+/// ```
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Computes the SHA256 hash of the image payload of a JPEG or PNG file.
+///
+/// For JPEG files, APPn segments (EXIF, XMP, ICC profiles, thumbnails) and comments
+/// are skipped. For PNG files, only the IHDR, PLTE, tRNS, IDAT and IEND chunks are
+/// hashed. A photo re-saved with edited or stripped metadata therefore produces the
+/// same hash as the original, as long as the compressed image data is unchanged.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the JPEG or PNG file to hash.
+///
+/// # Returns
+///
+/// * `Result<String>` - The SHA256 hash of the image payload as a hexadecimal string,
+///   or an error if the file cannot be read or is not a JPEG or PNG file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use dupefiles::compute_image_sha256::compute_image_sha256;
+///
+/// match compute_image_sha256(Path::new("photo.jpg")) {
+///     Ok(hash) => println!("Image SHA256: {}", hash),
+///     Err(e) => eprintln!("Error: {}", e),
+/// }
+/// ```
+pub fn compute_image_sha256(path: &Path) -> Result<String> {
+    let mut reader = BufReader::with_capacity(1024 * 1024, File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature[..2])?;
+    if signature[..2] == [0xff, 0xd8] {
+        hash_jpeg_payload(&mut reader, &mut hasher)?;
+    } else {
+        reader.read_exact(&mut signature[2..])?;
+        if &signature != b"\x89PNG\r\n\x1a\n" {
+            return Err(Error::new(ErrorKind::InvalidData, message("content-not-image", &[])));
+        }
+        hash_png_payload(&mut reader, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hashes every JPEG segment except APPn and COM, then all the data from the first scan on.
+fn hash_jpeg_payload<R: Read>(reader: &mut R, hasher: &mut Sha256) -> Result<()> {
+    loop {
+        let mut marker = [0u8; 2];
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(Error::new(ErrorKind::InvalidData, message("content-jpeg-marker", &[])));
+        }
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length)?;
+        let length = u16::from_be_bytes(length) as u64;
+        let mut segment = Vec::new();
+        reader.take(length.saturating_sub(2)).read_to_end(&mut segment)?;
+        let is_metadata = (0xe0..=0xef).contains(&marker[1]) || marker[1] == 0xfe;
+        if !is_metadata {
+            hasher.update(marker);
+            hasher.update(&segment);
+        }
+        // Start of scan: the entropy-coded data and any later scans run to the end of the file
+        if marker[1] == 0xda {
+            std::io::copy(reader, hasher)?;
+            return Ok(());
+        }
+    }
+}
+
+/// Hashes the type and data of the PNG chunks listed in `PNG_IMAGE_CHUNKS`.
+fn hash_png_payload<R: Read>(reader: &mut R, hasher: &mut Sha256) -> Result<()> {
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let chunk_type = &header[4..8];
+        // Chunk data is followed by a 4-byte CRC
+        let mut chunk = reader.take(length + 4);
+        if PNG_IMAGE_CHUNKS.iter().any(|t| &t[..] == chunk_type) {
+            hasher.update(chunk_type);
+            std::io::copy(&mut (&mut chunk).take(length), hasher)?;
+        }
+        std::io::copy(&mut chunk, &mut std::io::sink())?;
+        if chunk_type == b"IEND" {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    fn jpeg(app1: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xff, 0xd8];
+        data.extend_from_slice(&[0xff, 0xe1, 0x00, (app1.len() + 2) as u8]);
+        data.extend_from_slice(app1);
+        data.extend_from_slice(&[0xff, 0xdb, 0x00, 0x05, 1, 2, 3]);
+        data.extend_from_slice(&[0xff, 0xda, 0x00, 0x04, 4, 5]);
+        data.extend_from_slice(b"entropy coded data\xff\xd9");
+        data
+    }
+
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0, 0, 0, 0]);
+        chunk
+    }
+
+    fn png(text: &[u8]) -> Vec<u8> {
+        [
+            b"\x89PNG\r\n\x1a\n".to_vec(),
+            png_chunk(b"IHDR", b"header"),
+            png_chunk(b"tEXt", text),
+            png_chunk(b"IDAT", b"pixels"),
+            png_chunk(b"IEND", b""),
+        ].concat()
+    }
+
+    #[test]
+    fn test_jpeg_exif_is_ignored() {
+        let tmp_dir = Builder::new().prefix("image_jpeg").tempdir().unwrap();
+        let original = tmp_dir.path().join("original.jpg");
+        let edited = tmp_dir.path().join("edited.jpg");
+        fs::write(&original, jpeg(b"Exif\0\0orientation=1 gps=here")).unwrap();
+        fs::write(&edited, jpeg(b"Exif\0\0orientation=6")).unwrap();
+
+        assert_eq!(compute_image_sha256(&original).unwrap(), compute_image_sha256(&edited).unwrap());
+    }
+
+    #[test]
+    fn test_png_text_is_ignored() {
+        let tmp_dir = Builder::new().prefix("image_png").tempdir().unwrap();
+        let original = tmp_dir.path().join("original.png");
+        let edited = tmp_dir.path().join("edited.png");
+        fs::write(&original, png(b"Comment\0original")).unwrap();
+        fs::write(&edited, png(b"Comment\0edited in another tool")).unwrap();
+
+        assert_eq!(compute_image_sha256(&original).unwrap(), compute_image_sha256(&edited).unwrap());
+    }
+
+    #[test]
+    fn test_not_an_image() {
+        let tmp_dir = Builder::new().prefix("image_invalid").tempdir().unwrap();
+        let path = tmp_dir.path().join("fake.png");
+        fs::write(&path, "plain text").unwrap();
+
+        assert!(compute_image_sha256(&path).is_err());
+    }
+}
@@ -1,10 +1,39 @@
-use std::fs::File;
-use std::io::{BufReader, Read, Result,Error, ErrorKind};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, Metadata};
+use std::io::{Read, Result, Error, ErrorKind, Seek, SeekFrom};
 use std::path::Path;
+use md5::Md5;
+use sha1::Sha1;
 use sha2::{Sha256, Digest};
+use crate::cache_hints::advise_sequential;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::physical_order::is_rotational;
+use crate::file_entry::file_id;
+use crate::localize::message;
+
+/// Alignment of the buffer and reads of `compute_sha256_direct`, a multiple of the block
+/// size of common disks
+const DIRECT_ALIGNMENT: usize = 4096;
+/// Sizes of the read buffer: small files are read whole, large ones in chunks of the
+/// largest size for their disk
+const MIN_BUFFER_SIZE: usize = 4096;
+const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+const MAX_ROTATIONAL_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+thread_local! {
+    /// The read buffer of this thread, reused for every file and grown as needed
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    /// Whether each device met is a rotational disk, so each is only checked once
+    static ROTATIONAL_DEVICES: RefCell<HashMap<u64, bool>> = RefCell::new(HashMap::new());
+}
 
 /// Computes the SHA256 hash of a file at the given path.
 ///
+/// The kernel is told the file is read sequentially, so it reads further ahead. Small
+/// files are read whole and large ones in chunks of 1 MiB, or 4 MiB on rotational disks,
+/// into a buffer reused by every file hashed on the same thread.
+///
 /// # Arguments
 ///
 /// * `path` - A reference to a `Path` representing the file to hash.
@@ -30,29 +59,267 @@ use sha2::{Sha256, Digest};
 pub fn compute_sha256(path: &Path) -> Result<String> {
     if ! path.try_exists()? {
         // Path does not exist, return an error
-        return Err(Error::new(ErrorKind::NotFound, "Path does not exist"))
+        return Err(Error::new(ErrorKind::NotFound, message("error-path-missing", &[])))
     }
     let file = File::open(path)?;
-    let mut reader = BufReader::with_capacity(1024 * 1024, file); // 1MB buffer
+    // Only a hint, so hashing goes on without it
+    let _ = advise_sequential(&file);
+    hash_file(path, file)
+}
+
+/// Computes the SHA256 hash of a file like `compute_sha256`, along with other digests
+/// computed while reading the file once.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the file to hash.
+/// * `algorithms` - The other digests to compute.
+///
+/// # Returns
+///
+/// The SHA256 hash and each other digest as hexadecimal strings, or an error if the file
+/// cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::compute_sha256::compute_sha256_with_digests;
+/// use dupefiles::hash_algorithm::HashAlgorithm;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("hello.txt");
+/// fs::write(&path, "Hello, world!").unwrap();
+///
+/// let (sha256, digests) = compute_sha256_with_digests(&path, &[HashAlgorithm::Md5]).unwrap();
+/// assert_eq!(sha256, "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3");
+/// assert_eq!(digests[&HashAlgorithm::Md5], "6cd3556deb0da54bca060b4c39479839");
+/// ```
+pub fn compute_sha256_with_digests(path: &Path, algorithms: &[HashAlgorithm]) -> Result<(String, BTreeMap<HashAlgorithm, String>)> {
+    let file = File::open(path)?;
+    // Only a hint, so hashing goes on without it
+    let _ = advise_sequential(&file);
+    let mut sha256 = Sha256::new();
+    let mut md5 = algorithms.contains(&HashAlgorithm::Md5).then(Md5::new);
+    let mut sha1 = algorithms.contains(&HashAlgorithm::Sha1).then(Sha1::new);
+    read_chunks(path, file, |chunk| {
+        sha256.update(chunk);
+        if let Some(md5) = &mut md5 {
+            md5.update(chunk);
+        }
+        if let Some(sha1) = &mut sha1 {
+            sha1.update(chunk);
+        }
+    })?;
+
+    let mut digests = BTreeMap::new();
+    if let Some(md5) = md5 {
+        digests.insert(HashAlgorithm::Md5, format!("{:x}", md5.finalize()));
+    }
+    if let Some(sha1) = sha1 {
+        digests.insert(HashAlgorithm::Sha1, format!("{:x}", sha1.finalize()));
+    }
+    Ok((format!("{:x}", sha256.finalize()), digests))
+}
+
+/// Computes a SHA256 hash of the size of a file and of its first, middle and last
+/// `sample_size` bytes, reading a fixed amount of even the largest files.
+///
+/// Files with the same sampled hash are only probable duplicates: they may differ in the
+/// bytes between the samples. Files no larger than three samples are hashed whole.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the file to hash.
+/// * `sample_size` - The bytes read at each of the three places.
+///
+/// # Returns
+///
+/// The hash as a hexadecimal string, or an error if the file cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::compute_sha256::compute_sampled_sha256;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b) = (dir.path().join("a.img"), dir.path().join("b.img"));
+/// let mut data = vec![0u8; 10_000];
+/// fs::write(&a, &data).unwrap();
+/// // Between the samples
+/// data[2000] = 1;
+/// fs::write(&b, &data).unwrap();
+/// assert_eq!(compute_sampled_sha256(&a, 1000).unwrap(), compute_sampled_sha256(&b, 1000).unwrap());
+/// ```
+pub fn compute_sampled_sha256(path: &Path, sample_size: u64) -> Result<String> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
     let mut hasher = Sha256::new();
-    let mut buffer = [0; 1024 * 1024]; // Also increase the read buffer to 1MB
+    hasher.update(size.to_le_bytes());
+    if size <= sample_size.saturating_mul(3) {
+        read_chunks(path, file, |chunk| hasher.update(chunk))?;
+    } else {
+        let mut sample = vec![0; sample_size as usize];
+        for offset in [0, size / 2 - sample_size / 2, size - sample_size] {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut sample)?;
+            hasher.update(&sample);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-    loop {
-        let bytes_read = reader.read(&mut buffer)?;
-        if bytes_read == 0 { 
-            break;
+/// Returns the name of the SHA256 implementation hashing files on this CPU.
+///
+/// The CPU's SHA instructions are detected at run time: SHA-NI on x86 and x86_64 in
+/// every build, and the ARMv8 SHA2 extensions on aarch64 in builds with the `asm`
+/// feature. Otherwise the hashing is done by assembly code with the `asm` feature on
+/// those architectures, or by portable Rust code.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::compute_sha256::sha256_implementation;
+///
+/// println!("SHA256: {}", sha256_implementation());
+/// ```
+pub fn sha256_implementation() -> &'static str {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        let sha_ni = is_x86_feature_detected!("sha")
+            && is_x86_feature_detected!("sse2")
+            && is_x86_feature_detected!("ssse3")
+            && is_x86_feature_detected!("sse4.1");
+        if sha_ni {
+            return "SHA-NI";
+        }
+        if cfg!(feature = "asm") {
+            return "assembly";
         }
-        hasher.update(&buffer[..bytes_read]);
-    }   
+    }
+    #[cfg(all(feature = "asm", target_arch = "aarch64"))]
+    if std::arch::is_aarch64_feature_detected!("sha2") {
+        return "ARMv8 SHA2";
+    }
+    "portable"
+}
+
+/// Computes the SHA256 hash of a file like `compute_sha256`, reading it around the page
+/// cache: with `O_DIRECT` on Linux and `F_NOCACHE` on macOS.
+///
+/// Reads go straight from the disk into an aligned buffer, so hashing a dataset larger
+/// than memory neither copies every byte through the cache nor evicts what is cached.
+/// On file systems that refuse direct I/O, such as tmpfs, the file is read normally.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the file to hash.
+///
+/// # Returns
+///
+/// * `Result<String>` - The SHA256 hash as a hexadecimal string if successful, or an error if the file
+///   doesn't exist or cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::compute_sha256::{compute_sha256, compute_sha256_direct};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("large.bin");
+/// fs::write(&path, vec![7u8; 5000]).unwrap();
+/// assert_eq!(compute_sha256_direct(&path).unwrap(), compute_sha256(&path).unwrap());
+/// ```
+pub fn compute_sha256_direct(path: &Path) -> Result<String> {
+    match open_direct(path)? {
+        Some(file) => hash_file(path, file),
+        None => compute_sha256(path),
+    }
+}
 
+/// Hash an open file with SHA256
+fn hash_file(path: &Path, file: File) -> Result<String> {
+    let mut hasher = Sha256::new();
+    read_chunks(path, file, |chunk| hasher.update(chunk))?;
     Ok(format!("{:x}", hasher.finalize()))
 }
 
+/// Read an open file to the end into this thread's buffer, in chunks sized by
+/// `buffer_size`, passing each chunk to `update`
+fn read_chunks(path: &Path, mut file: File, mut update: impl FnMut(&[u8])) -> Result<()> {
+    let size = buffer_size(path, &file.metadata()?);
+    BUFFER.with(|buffer| {
+        // Slice an aligned buffer, as direct I/O needs, out of a slightly larger one
+        let mut storage = buffer.borrow_mut();
+        if storage.len() < size + DIRECT_ALIGNMENT {
+            storage.resize(size + DIRECT_ALIGNMENT, 0);
+        }
+        let offset = storage.as_ptr().align_offset(DIRECT_ALIGNMENT);
+        let buffer = &mut storage[offset..offset + size];
+        loop {
+            let bytes_read = file.read(buffer)?;
+            if bytes_read == 0 {
+                return Ok(());
+            }
+            update(&buffer[..bytes_read]);
+        }
+    })
+}
+
+/// The size of the reads of a file: the whole file when it is small, up to larger
+/// chunks on rotational disks, where every read of a large file may cost a seek
+fn buffer_size(path: &Path, metadata: &Metadata) -> usize {
+    let device = file_id(metadata).map_or(0, |(dev, _)| dev);
+    let rotational = ROTATIONAL_DEVICES.with(|devices| {
+        *devices.borrow_mut().entry(device).or_insert_with(|| is_rotational(path).unwrap_or(false))
+    });
+    let max_size = if rotational { MAX_ROTATIONAL_BUFFER_SIZE } else { MAX_BUFFER_SIZE };
+    // One byte more than the file, so a single read reaches the end
+    usize::try_from(metadata.len().saturating_add(1)).unwrap_or(usize::MAX)
+        .clamp(MIN_BUFFER_SIZE, max_size)
+        .next_multiple_of(DIRECT_ALIGNMENT)
+}
+
+/// Open a file for reads bypassing the page cache, or `None` if its file system refuses
+fn open_direct(path: &Path) -> Result<Option<File>> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::fs::OpenOptions;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        match OpenOptions::new().read(true).custom_flags(libc::O_DIRECT).open(path) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path)?;
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+            return Ok(None);
+        }
+        Ok(Some(file))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = path;
+        Ok(None)
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::needless_borrows_for_generic_args, clippy::needless_borrow)]
 mod tests {
     use super::*;
     use std::fs;
 
+    // Writes to /tmp, which only Unix-like systems have
+    #[cfg(unix)]
     #[test]
     fn test_compute_sha256() {
         // Create a temporary file for testing
@@ -69,6 +336,30 @@ mod tests {
         // Clean up the test file
         fs::remove_file(&test_file_path).expect("Unable to delete test file");
     }
+    #[test]
+    fn test_compute_sha256_direct_multiple_buffers() {
+        let tmp_dir = tempfile::Builder::new().prefix("compute_sha256_direct").tempdir().unwrap();
+        let path = tmp_dir.path().join("large.bin");
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 123).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, data).unwrap();
+
+        assert_eq!(compute_sha256_direct(&path).unwrap(), compute_sha256(&path).unwrap());
+        assert!(compute_sha256_direct(Path::new("/non/existing/file.txt")).is_err());
+    }
+
+    #[test]
+    fn test_buffer_size_follows_file_size() {
+        let tmp_dir = tempfile::Builder::new().prefix("compute_sha256_buffer").tempdir().unwrap();
+        let small = tmp_dir.path().join("small.txt");
+        fs::write(&small, "small").unwrap();
+        let large = tmp_dir.path().join("large.bin");
+        File::create(&large).unwrap().set_len(100 * 1024 * 1024).unwrap();
+
+        assert_eq!(buffer_size(&small, &fs::metadata(&small).unwrap()), MIN_BUFFER_SIZE);
+        let size = buffer_size(&large, &fs::metadata(&large).unwrap());
+        assert!(size == MAX_BUFFER_SIZE || size == MAX_ROTATIONAL_BUFFER_SIZE);
+    }
+
     #[test]
     fn test_compute_sha_256_checksum_non_existing_file() {
         let path = Path::new("/non/existing/file.txt");
@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::localize::message;
+
+/// Number of leading bytes inspected to decide whether a file is text, as git does.
+const TEXT_SNIFF_LENGTH: usize = 8000;
+
+/// Computes the SHA256 hash of a text file with normalized line endings.
+///
+/// Every line is hashed as if it ended with a single LF, so files checked out with
+/// CRLF and LF line endings produce the same hash. A missing newline at the end of
+/// the file is treated the same way. Files containing a NUL byte in their first
+/// 8000 bytes are considered binary and rejected.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the text file to hash.
+/// * `trim_trailing_whitespace` - Also ignore spaces and tabs at the end of each line.
+///
+/// # Returns
+///
+/// * `Result<String>` - The SHA256 hash of the normalized text as a hexadecimal string,
+///   or an error if the file cannot be read or looks binary.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::compute_text_sha256::compute_text_sha256;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let unix = dir.path().join("unix.txt");
+/// let windows = dir.path().join("windows.txt");
+/// fs::write(&unix, "first line\nsecond line\n").unwrap();
+/// fs::write(&windows, "first line\r\nsecond line\r\n").unwrap();
+///
+/// assert_eq!(compute_text_sha256(&unix, false).unwrap(), compute_text_sha256(&windows, false).unwrap());
+/// ```
+pub fn compute_text_sha256(path: &Path, trim_trailing_whitespace: bool) -> Result<String> {
+    let mut reader = BufReader::with_capacity(1024 * 1024, File::open(path)?);
+    let head = reader.fill_buf()?;
+    if head[..head.len().min(TEXT_SNIFF_LENGTH)].contains(&0) {
+        return Err(Error::new(ErrorKind::InvalidData, message("content-binary", &[])));
+    }
+
+    let mut hasher = Sha256::new();
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let mut end = line.len();
+        while end > 0 && matches!(line[end - 1], b'\n' | b'\r') {
+            end -= 1;
+        }
+        while trim_trailing_whitespace && end > 0 && matches!(line[end - 1], b' ' | b'\t') {
+            end -= 1;
+        }
+        hasher.update(&line[..end]);
+        hasher.update(b"\n");
+        line.clear();
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let tmp_dir = Builder::new().prefix("text_whitespace").tempdir().unwrap();
+        let clean = tmp_dir.path().join("clean.txt");
+        let padded = tmp_dir.path().join("padded.txt");
+        fs::write(&clean, "line\nother line").unwrap();
+        fs::write(&padded, "line  \r\nother line\t\r\n").unwrap();
+
+        assert_ne!(compute_text_sha256(&clean, false).unwrap(), compute_text_sha256(&padded, false).unwrap());
+        assert_eq!(compute_text_sha256(&clean, true).unwrap(), compute_text_sha256(&padded, true).unwrap());
+    }
+
+    #[test]
+    fn test_binary_rejected() {
+        let tmp_dir = Builder::new().prefix("text_binary").tempdir().unwrap();
+        let path = tmp_dir.path().join("binary.txt");
+        fs::write(&path, b"text\0with a nul byte").unwrap();
+
+        assert!(compute_text_sha256(&path, false).is_err());
+    }
+}
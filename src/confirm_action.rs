@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, Result};
+use std::path::{Path, PathBuf};
+use crate::human_readable_size::human_readable_size;
+use crate::localize::message;
+
+/// The files and bytes a destructive action is about to touch.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImpactSummary {
+    /// Number of files that will be affected.
+    pub files: usize,
+    /// Total size of those files in bytes.
+    pub bytes: u64,
+    /// Files and bytes per parent directory.
+    pub by_directory: BTreeMap<PathBuf, (usize, u64)>,
+}
+
+/// Summarizes the impact of applying an action to the given files.
+///
+/// Files whose metadata cannot be read are counted with a size of zero.
+///
+/// # Arguments
+///
+/// * `paths` - The files the action will be applied to.
+///
+/// # Returns
+///
+/// An `ImpactSummary` with totals and a per-directory breakdown.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::confirm_action::summarize_impact;
+///
+/// let summary = summarize_impact(&[Path::new("/non/existing/file.txt")]);
+/// assert_eq!(summary.files, 1);
+/// assert_eq!(summary.bytes, 0);
+/// ```
+pub fn summarize_impact(paths: &[&Path]) -> ImpactSummary {
+    let mut summary = ImpactSummary::default();
+    for path in paths {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let entry = summary.by_directory.entry(directory).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += size;
+        summary.files += 1;
+        summary.bytes += size;
+    }
+    summary
+}
+
+impl fmt::Display for ImpactSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", message("impact-totals", &[
+            ("files", &self.files),
+            ("size", &human_readable_size(self.bytes)),
+            ("bytes", &self.bytes),
+        ]))?;
+        for (directory, (files, bytes)) in &self.by_directory {
+            writeln!(f, "  {}", message("impact-directory", &[
+                ("directory", &directory.display()),
+                ("files", files),
+                ("size", &human_readable_size(*bytes)),
+            ]))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a line from `input` and returns whether it is an explicit "yes".
+///
+/// Anything else, including "y" and end of input, is treated as a refusal.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::confirm_action::confirm;
+///
+/// assert!(confirm("yes\n".as_bytes()).unwrap());
+/// assert!(!confirm("y\n".as_bytes()).unwrap());
+/// ```
+pub fn confirm<R: BufRead>(mut input: R) -> Result<bool> {
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_summarize_impact_by_directory() {
+        let tmp_dir = Builder::new().prefix("impact_summary").tempdir().unwrap();
+        let sub_dir = tmp_dir.path().join("sub");
+        fs::create_dir(&sub_dir).unwrap();
+        let file1 = tmp_dir.path().join("a.txt");
+        let file2 = sub_dir.join("b.txt");
+        let file3 = sub_dir.join("c.txt");
+        fs::write(&file1, "1234").unwrap();
+        fs::write(&file2, "123456").unwrap();
+        fs::write(&file3, "12").unwrap();
+
+        let summary = summarize_impact(&[&file1, &file2, &file3]);
+
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.bytes, 12);
+        assert_eq!(summary.by_directory[tmp_dir.path()], (1, 4));
+        assert_eq!(summary.by_directory[&sub_dir], (2, 8));
+    }
+
+    #[test]
+    fn test_confirm_rejects_empty_input() {
+        assert!(!confirm("".as_bytes()).unwrap());
+    }
+}
@@ -0,0 +1,128 @@
+use std::io;
+use std::path::Path;
+
+/// Asks the kernel to share the data of `destination` with `source` using the
+/// FIDEDUPERANGE ioctl.
+///
+/// The kernel locks both files, compares their contents and only shares the extents
+/// that are identical at that moment, so a file modified concurrently is never
+/// corrupted: the call fails instead. Both files stay separate files with their own
+/// metadata. This is supported by Btrfs and XFS (with reflink enabled) on Linux.
+///
+/// # Arguments
+///
+/// * `source` - The file whose extents are kept.
+/// * `destination` - The file whose extents are replaced by those of `source`.
+///
+/// # Returns
+///
+/// Result, with an error if the files differ, the file system does not support
+/// deduplication or the platform is not Linux.
+pub fn dedupe_range(source: &Path, destination: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::dedupe_range(source, destination)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (source, destination);
+        Err(io::Error::new(io::ErrorKind::Unsupported, crate::localize::message("dedupe-linux-only", &[])))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    /// _IOWR(0x94, 54, struct file_dedupe_range) from linux/fs.h
+    const FIDEDUPERANGE: u64 = 0xC018_9436;
+    /// The ranges were identical and have been deduplicated
+    const FILE_DEDUPE_RANGE_SAME: i32 = 0;
+    /// The ranges differ, so nothing was changed
+    const FILE_DEDUPE_RANGE_DIFFERS: i32 = 1;
+    /// Bytes requested per call; file systems may cap a single request below the file size
+    const CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+    /// struct file_dedupe_range_info from linux/fs.h
+    #[repr(C)]
+    struct FileDedupeRangeInfo {
+        dest_fd: i64,
+        dest_offset: u64,
+        bytes_deduped: u64,
+        status: i32,
+        reserved: u32,
+    }
+
+    /// struct file_dedupe_range from linux/fs.h, with a single destination
+    #[repr(C)]
+    struct FileDedupeRange {
+        src_offset: u64,
+        src_length: u64,
+        dest_count: u16,
+        reserved1: u16,
+        reserved2: u32,
+        info: [FileDedupeRangeInfo; 1],
+    }
+
+    pub fn dedupe_range(source: &Path, destination: &Path) -> io::Result<()> {
+        let source = File::open(source)?;
+        let destination = OpenOptions::new().write(true).open(destination)?;
+        let length = source.metadata()?.len();
+        if destination.metadata()?.len() != length {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, crate::localize::message("dedupe-size-differs", &[])));
+        }
+
+        let mut offset = 0;
+        while offset < length {
+            let mut range = FileDedupeRange {
+                src_offset: offset,
+                src_length: CHUNK_SIZE.min(length - offset),
+                dest_count: 1,
+                reserved1: 0,
+                reserved2: 0,
+                info: [FileDedupeRangeInfo {
+                    dest_fd: destination.as_raw_fd() as i64,
+                    dest_offset: offset,
+                    bytes_deduped: 0,
+                    status: 0,
+                    reserved: 0,
+                }],
+            };
+            if unsafe { libc::ioctl(source.as_raw_fd(), FIDEDUPERANGE as _, &mut range) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let info = &range.info[0];
+            match info.status {
+                FILE_DEDUPE_RANGE_SAME if info.bytes_deduped > 0 => offset += info.bytes_deduped,
+                FILE_DEDUPE_RANGE_SAME => return Err(io::Error::other(crate::localize::message("dedupe-no-bytes", &[]))),
+                FILE_DEDUPE_RANGE_DIFFERS => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, crate::localize::message("dedupe-content-differs", &[])));
+                }
+                status => return Err(io::Error::from_raw_os_error(-status)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_size_mismatch_rejected() {
+        let tmp_dir = Builder::new().prefix("dedupe_range").tempdir().unwrap();
+        let source = tmp_dir.path().join("source.txt");
+        let destination = tmp_dir.path().join("destination.txt");
+        fs::write(&source, "content").unwrap();
+        fs::write(&destination, "other content").unwrap();
+
+        assert!(dedupe_range(&source, &destination).is_err());
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "other content");
+    }
+}
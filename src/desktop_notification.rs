@@ -0,0 +1,57 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use notify_rust::Notification;
+use crate::human_readable_size::human_readable_size;
+use crate::localize::message;
+use crate::scan_summary::ScanSummary;
+
+/// The body of the notification of a scan, with the totals of the summary and why the
+/// scan stopped early, if it did
+fn notification_body(summary: &ScanSummary) -> String {
+    let totals = message("summary-totals", &[
+        ("groups", &summary.duplicate_groups),
+        ("files", &summary.duplicate_files),
+        ("wasted", &human_readable_size(summary.wasted_bytes)),
+    ]);
+    match summary.stopped {
+        Some(reason) => format!("{}\n{}", message("summary-stopped", &[("reason", &reason)]), totals),
+        None => totals,
+    }
+}
+
+/// Shows a desktop notification telling that the scan of a directory is done, with the
+/// duplicates it found, so users can switch away from the terminal during long scans.
+///
+/// # Arguments
+///
+/// * `directory` - The scanned directory, or the URL of a remote source.
+/// * `summary` - The summary of the scan.
+///
+/// # Returns
+///
+/// An error if the notification server of the desktop cannot be reached, such as in a
+/// session without one.
+pub fn show_scan_notification(directory: &Path, summary: &ScanSummary) -> Result<()> {
+    Notification::new()
+        .appname("dupefiles")
+        .summary(&message("notification-finished", &[("directory", &directory.display())]))
+        .body(&notification_body(summary))
+        .show()
+        .with_context(|| message("notification-failed", &[]))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan_summary::StopReason;
+
+    #[test]
+    fn test_notification_body() {
+        let summary = ScanSummary { duplicate_groups: 2, duplicate_files: 3, wasted_bytes: 2048, ..Default::default() };
+        assert_eq!(notification_body(&summary), format!("2 duplicate groups, 3 duplicate files, {} wasted", human_readable_size(2048)));
+
+        let summary = ScanSummary { stopped: Some(StopReason::Interrupted), ..summary };
+        assert!(notification_body(&summary).starts_with("Scan stopped early (interrupted), partial results:\n2 duplicate groups"));
+    }
+}
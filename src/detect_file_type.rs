@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io::{Read, Result};
+use std::path::Path;
+
+/// Magic byte signatures: offset, signature and the extensions of the matching type.
+/// The first extension of each entry is the canonical one.
+const SIGNATURES: &[(usize, &[u8], &[&str])] = &[
+    (0, b"\xff\xd8\xff", &["jpg", "jpeg", "jpe"]),
+    (0, b"\x89PNG\r\n\x1a\n", &["png"]),
+    (0, b"GIF87a", &["gif"]),
+    (0, b"GIF89a", &["gif"]),
+    (0, b"BM", &["bmp"]),
+    (0, b"II*\0", &["tif", "tiff"]),
+    (0, b"MM\0*", &["tif", "tiff"]),
+    (8, b"WEBP", &["webp"]),
+    (0, b"%PDF-", &["pdf"]),
+    (0, b"PK\x03\x04", &["zip", "docx", "xlsx", "pptx", "odt", "jar", "epub"]),
+    (0, b"\x1f\x8b", &["gz", "tgz"]),
+    (0, b"BZh", &["bz2"]),
+    (0, b"\xfd7zXZ\0", &["xz"]),
+    (0, b"7z\xbc\xaf\x27\x1c", &["7z"]),
+    (0, b"Rar!\x1a\x07", &["rar"]),
+    (257, b"ustar", &["tar"]),
+    (0, b"ID3", &["mp3"]),
+    (0, b"\xff\xfb", &["mp3"]),
+    (0, b"fLaC", &["flac"]),
+    (0, b"OggS", &["ogg", "oga", "ogv", "opus"]),
+    (8, b"WAVE", &["wav"]),
+    (8, b"AVI ", &["avi"]),
+    (8, b"qt  ", &["mov"]),
+    (8, b"M4A ", &["m4a"]),
+    (4, b"ftyp", &["mp4", "m4v", "mov", "3gp"]),
+    (0, b"\x1a\x45\xdf\xa3", &["mkv", "webm"]),
+];
+
+/// Number of leading bytes needed to check every signature.
+const HEADER_LENGTH: usize = 262;
+
+/// Detects the type of a file from its leading magic bytes.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the file to inspect.
+///
+/// # Returns
+///
+/// * `Result<Option<&[&str]>>` - The extensions used for the detected type, with the
+///   canonical one first, `None` if no signature matches, or an error if the file
+///   cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::detect_file_type::detect_file_type;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("photo.dat");
+/// fs::write(&path, b"\xff\xd8\xff\xe0 rest of a jpeg").unwrap();
+///
+/// assert_eq!(detect_file_type(&path).unwrap().map(|exts| exts[0]), Some("jpg"));
+/// ```
+pub fn detect_file_type(path: &Path) -> Result<Option<&'static [&'static str]>> {
+    let mut header = Vec::with_capacity(HEADER_LENGTH);
+    File::open(path)?.take(HEADER_LENGTH as u64).read_to_end(&mut header)?;
+    Ok(SIGNATURES.iter()
+        .find(|(offset, signature, _)| header.get(*offset..offset + signature.len()) == Some(signature))
+        .map(|(_, _, extensions)| *extensions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_detect_mp4_by_offset() {
+        let tmp_dir = Builder::new().prefix("detect_mp4").tempdir().unwrap();
+        let path = tmp_dir.path().join("video.bin");
+        fs::write(&path, b"\0\0\0\x18ftypisom\0\0\x02\0").unwrap();
+
+        assert_eq!(detect_file_type(&path).unwrap().map(|exts| exts[0]), Some("mp4"));
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        let tmp_dir = Builder::new().prefix("detect_unknown").tempdir().unwrap();
+        let path = tmp_dir.path().join("notes.jpg");
+        fs::write(&path, "plain text").unwrap();
+
+        assert_eq!(detect_file_type(&path).unwrap(), None);
+    }
+}
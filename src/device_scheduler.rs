@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use crate::physical_order::is_rotational_device;
+
+/// How many items are worked on at a time on a rotational disk, whose head would seek
+/// back and forth between the files of more streams instead of reading them in order
+pub const ROTATIONAL_STREAMS: usize = 1;
+
+/// How many items of a device are worked on at a time: `ROTATIONAL_STREAMS` on a
+/// rotational disk, `jobs` on a solid-state one, and one for items of an unknown
+/// device, such as the files of disc images and archives, read from a single file.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::device_scheduler::device_streams;
+///
+/// assert_eq!(device_streams(None, 8), 1);
+/// assert!((1..=8).contains(&device_streams(Some(0), 8)));
+/// ```
+pub fn device_streams(device: Option<u64>, jobs: usize) -> usize {
+    match device {
+        Some(device) if is_rotational_device(device) => ROTATIONAL_STREAMS.min(jobs),
+        Some(_) => jobs,
+        None => 1,
+    }
+}
+
+/// Runs `work` on every item in parallel, with a queue per device worked on by as
+/// many threads as the device can serve, as given by `device_streams`: a hard drive
+/// is read one file at a time in the order of `items` while an NVMe drive is read by
+/// `jobs` threads, so a mixed-storage scan neither thrashes the slow disk nor starves
+/// the fast one.
+///
+/// The results are handed to `consume` on the calling thread in the order of `items`,
+/// whichever device finished first; results of fast devices are held until those of
+/// the items before them arrive. Once `consume` breaks, no other item is started, and
+/// the items being worked on are finished and dropped.
+///
+/// # Arguments
+///
+/// * `items` - The items to work on, in the order their results are consumed.
+/// * `jobs` - How many items of a solid-state device are worked on at a time. With one
+///   or none, every item is worked on and consumed in turn on the calling thread.
+/// * `device` - The device number holding an item, or `None` when it is unknown.
+/// * `work` - The work done on each item, on a thread of its device.
+/// * `consume` - Called with each item and its result.
+///
+/// # Examples
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use dupefiles::device_scheduler::run_by_device;
+///
+/// let sizes = [4, 8, 15, 16, 23, 42];
+/// let mut doubled = Vec::new();
+/// run_by_device(&sizes, 4, |size| Some(size % 2), |size| size * 2, |_, result| {
+///     doubled.push(result);
+///     ControlFlow::Continue(())
+/// });
+/// assert_eq!(doubled, [8, 16, 30, 32, 46, 84]);
+/// ```
+pub fn run_by_device<T, R, D, W, C>(items: &[T], jobs: usize, device: D, work: W, mut consume: C)
+where
+    T: Sync,
+    R: Send,
+    D: Fn(&T) -> Option<u64>,
+    W: Fn(&T) -> R + Sync,
+    C: FnMut(&T, R) -> ControlFlow<()>,
+{
+    if jobs <= 1 {
+        for item in items {
+            if consume(item, work(item)).is_break() {
+                break;
+            }
+        }
+        return;
+    }
+
+    // The indices of the items of each device, in order, and the next one to start
+    let mut by_device: HashMap<Option<u64>, Vec<usize>> = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        by_device.entry(device(item)).or_default().push(index);
+    }
+    let queues: Vec<(usize, Vec<usize>, AtomicUsize)> = by_device.into_iter()
+        .map(|(device, queue)| (device_streams(device, jobs), queue, AtomicUsize::new(0)))
+        .collect();
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for (streams, queue, next) in &queues {
+            for _ in 0..*streams {
+                let (sender, stop, work) = (sender.clone(), &stop, &work);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let Some(&index) = queue.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+                        if sender.send((index, work(&items[index]))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+        drop(sender);
+
+        let mut pending: HashMap<usize, R> = HashMap::new();
+        'items: for (index, item) in items.iter().enumerate() {
+            let result = loop {
+                if let Some(result) = pending.remove(&index) {
+                    break result;
+                }
+                match receiver.recv() {
+                    Ok((done, result)) => {
+                        pending.insert(done, result);
+                    }
+                    // Every worker ended, which only happens early when one panicked
+                    Err(_) => break 'items,
+                }
+            };
+            if consume(item, result).is_break() {
+                break;
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn test_break_stops_workers() {
+        let items: Vec<usize> = (0..200).collect();
+        let started = AtomicUsize::new(0);
+        let mut consumed = Vec::new();
+        run_by_device(&items, 4, |item| Some(*item as u64 % 3), |item| {
+            started.fetch_add(1, Ordering::Relaxed);
+            thread::sleep(Duration::from_millis(1));
+            *item
+        }, |_, result| {
+            consumed.push(result);
+            if consumed.len() == 10 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        });
+
+        assert_eq!(consumed, (0..10).collect::<Vec<_>>());
+        assert!(started.load(Ordering::Relaxed) < items.len());
+    }
+}
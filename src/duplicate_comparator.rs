@@ -0,0 +1,110 @@
+use std::fmt;
+use std::io;
+use crate::file_entry::FileEntry;
+use crate::is_duplicate_file::same_content;
+
+/// Confirms or refutes that two files with equal hashes are duplicates, so embedders can
+/// apply their own notion of equality on top of the hash comparison of a scan.
+///
+/// A scan calls the comparator with the first member of a group and every other file
+/// hashing alike; files it refutes are left out of the group. Set one in
+/// `ScanOptions::comparator`; scans without one use `DefaultComparator`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io;
+/// use std::sync::Arc;
+/// use dupefiles::duplicate_comparator::{DefaultComparator, DuplicateComparator};
+/// use dupefiles::file_entry::FileEntry;
+/// use dupefiles::find_duplicates::find_duplicates;
+/// use dupefiles::scan_options::ScanOptions;
+///
+/// /// Small files are expected to repeat, so only larger ones are duplicates
+/// #[derive(Debug)]
+/// struct LargeOnly;
+///
+/// impl DuplicateComparator for LargeOnly {
+///     fn is_duplicate(&self, first: &FileEntry, second: &FileEntry) -> io::Result<bool> {
+///         Ok(first.size >= 1024 && DefaultComparator.is_duplicate(first, second)?)
+///     }
+/// }
+///
+/// let dir = tempfile::Builder::new().prefix("comparator").tempdir().unwrap();
+/// fs::write(dir.path().join("a.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("b.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("c.bin"), vec![1; 2048]).unwrap();
+/// fs::write(dir.path().join("d.bin"), vec![1; 2048]).unwrap();
+///
+/// let options = ScanOptions { comparator: Some(Arc::new(LargeOnly)), ..Default::default() };
+/// let mut output = tempfile::tempfile().unwrap();
+/// let groups = find_duplicates(dir.path(), &options, Some(&mut output)).unwrap();
+/// assert_eq!(groups.len(), 1);
+/// assert_eq!(groups[0].size, 2048);
+/// ```
+pub trait DuplicateComparator: fmt::Debug + Send + Sync {
+    /// Returns whether `second` is a duplicate of `first`. Both have the same hash, and
+    /// the same size unless they were compared by part of their content only.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The member the group was started with.
+    /// * `second` - The file hashing like `first`.
+    ///
+    /// # Returns
+    ///
+    /// `true` when `second` belongs in the group of `first`, or an error reported on
+    /// stderr, in which case `second` is left out.
+    fn is_duplicate(&self, first: &FileEntry, second: &FileEntry) -> io::Result<bool>;
+}
+
+/// The comparison used by scans without a `ScanOptions::comparator`.
+///
+/// Hard links to the same file are never duplicates. Files hashed in full must have
+/// the same size and are hashed again before being confirmed, going by the metadata
+/// in their entries rather than reading it again; files compared by part of
+/// their content only, such as their audio frames or samples, are confirmed by their
+/// hash. Files of which only the size and hash are known, such as the files in disc
+/// images and remote objects, are trusted by their hash too.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::duplicate_comparator::{DefaultComparator, DuplicateComparator};
+/// use dupefiles::file_entry::FileEntry;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b, link) = (dir.path().join("a.txt"), dir.path().join("b.txt"), dir.path().join("link.txt"));
+/// fs::write(&a, "copy").unwrap();
+/// fs::write(&b, "copy").unwrap();
+/// fs::hard_link(&a, &link).unwrap();
+///
+/// let hash = "audio:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+/// let entry = |path| FileEntry::from_path(path, 4, hash);
+/// assert!(DefaultComparator.is_duplicate(&entry(&a), &entry(&b)).unwrap());
+/// #[cfg(unix)]
+/// assert!(!DefaultComparator.is_duplicate(&entry(&a), &entry(&link)).unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefaultComparator;
+
+impl DuplicateComparator for DefaultComparator {
+    fn is_duplicate(&self, first: &FileEntry, second: &FileEntry) -> io::Result<bool> {
+        if first.inode.is_none() || second.inode.is_none() {
+            return Ok(true);
+        }
+        if first.is_same_file(second) {
+            return Ok(false);
+        }
+        // Partial hashes carry the prefix of their content mode, such as `audio:`
+        if first.hash.contains(':') {
+            return Ok(true);
+        }
+        if first.size != second.size {
+            return Ok(false);
+        }
+        same_content(&first.path, &second.path)
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::clone_file::copy_on_write_supported;
+use crate::file_entry::FileEntry;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::path_encoding::{deserialize_paths, serialize_paths};
+
+/// A set of files that share the same content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    /// The SHA256 hash of the shared content, as a hexadecimal string. Hashes of
+    /// audio frames only are prefixed with `audio:`, hashes of image payloads only
+    /// with `image:`, hashes of normalized text with `text:`, hashes of decompressed
+    /// content with `gz:`, hashes of email messages with `email:` and hashes of samples
+    /// of large files with `sample:`.
+    pub hash: String,
+    /// The size in bytes of the first member. Members compared by content only
+    /// (such as audio frames, image payloads, normalized text, decompressed content or
+    /// email messages) may differ in size.
+    pub size: u64,
+    /// The members of the group, in the order they were found.
+    #[serde(serialize_with = "serialize_paths", deserialize_with = "deserialize_paths")]
+    pub files: Vec<PathBuf>,
+    /// The members, other than the first, that already share all their data on disk
+    /// with the first member (such as reflinked copies), so removing them frees no space.
+    #[serde(serialize_with = "serialize_paths", deserialize_with = "deserialize_paths", default)]
+    pub reflinked: Vec<PathBuf>,
+    /// Other digests of the shared content, such as MD5, computed on request along with
+    /// the SHA256 hash and written in JSON reports next to it.
+    #[serde(flatten)]
+    pub digests: BTreeMap<HashAlgorithm, String>,
+    /// The members with their size, modification time, device and inode as found by the
+    /// scan, in the order of `files`. Reports do not record them, so groups read back
+    /// from a report have none.
+    #[serde(skip)]
+    pub entries: Vec<FileEntry>,
+}
+
+/// The action suggested for the duplicates of a group by `DuplicateGroup::suggested_action`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestedAction {
+    /// Replace the duplicates with copy-on-write clones, which free the space while
+    /// leaving separate files.
+    Reflink,
+    /// Replace the duplicates with hard links, where clones cannot be made.
+    Hardlink,
+    /// Delete the duplicates, as they cannot be linked to the first member or only
+    /// match it by their decoded content.
+    Delete,
+    /// Compare the members in full first, as they are only probable duplicates.
+    Verify,
+    /// Leave the group alone, as removing its duplicates would free no space.
+    #[serde(rename = "none")]
+    Nothing,
+}
+
+impl DuplicateGroup {
+    /// Returns a short identifier of the group: its hash cut to 16 hexadecimal digits,
+    /// keeping the prefix of its content mode, such as `audio:`. It only depends on the shared
+    /// content, so the same set of files has the same identifier in every report.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: "audio:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03".to_string(),
+    ///     size: 100,
+    ///     files: Vec::new(),
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert_eq!(group.id(), "audio:5891b5b522d5df08");
+    /// ```
+    pub fn id(&self) -> String {
+        let digest_start = self.hash.rfind(':').map_or(0, |colon| colon + 1);
+        self.hash[..self.hash.len().min(digest_start + 16)].to_string()
+    }
+
+    /// Returns whether the members are only probable duplicates, compared by samples of
+    /// their content rather than all of it, so they must not be removed unchecked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: "sample:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03".to_string(),
+    ///     size: 100,
+    ///     files: Vec::new(),
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert!(group.is_probable());
+    /// ```
+    pub fn is_probable(&self) -> bool {
+        self.hash.starts_with("sample:")
+    }
+
+    /// Returns whether the members were matched by their decoded content, such as the
+    /// audio frames or the decompressed stream, rather than by their bytes, so each must
+    /// keep its own data: they may be removed but never linked to each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let mut group = DuplicateGroup {
+    ///     hash: "gz:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03".to_string(),
+    ///     size: 100,
+    ///     files: Vec::new(),
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert!(group.is_content_match());
+    /// group.hash = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03".to_string();
+    /// assert!(!group.is_content_match());
+    /// ```
+    pub fn is_content_match(&self) -> bool {
+        ["audio:", "image:", "text:", "gz:", "email:"].iter().any(|prefix| self.hash.starts_with(prefix))
+    }
+
+    /// Returns whether the members live in more than one directory, rather than all
+    /// next to each other. Files of an archive or disc image are in the directory they
+    /// have inside it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let mut group = DuplicateGroup {
+    ///     hash: String::new(),
+    ///     size: 100,
+    ///     files: vec![PathBuf::from("/exports/report-v1.pdf"), PathBuf::from("/exports/report-v2.pdf")],
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert!(!group.spans_directories());
+    /// group.files.push(PathBuf::from("/backup/report.pdf"));
+    /// assert!(group.spans_directories());
+    /// ```
+    pub fn spans_directories(&self) -> bool {
+        let mut directories = self.files.iter().map(|file| file.parent());
+        let first = directories.next();
+        directories.any(|directory| Some(directory) != first)
+    }
+
+    /// Splits the group into one group per directory holding at least two members,
+    /// dropping the members alone in their directory. Each group keeps the order of
+    /// the members, and the members sharing data with the first member only stay
+    /// listed in the group of that member. The groups share the hash, and so the
+    /// identifier, of the original group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: String::new(),
+    ///     size: 100,
+    ///     files: ["/photos/a.jpg", "/backup/a.jpg", "/photos/a (1).jpg"].map(PathBuf::from).to_vec(),
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// let groups = group.split_by_directory();
+    /// assert_eq!(groups.len(), 1);
+    /// assert_eq!(groups[0].files, ["/photos/a.jpg", "/photos/a (1).jpg"].map(PathBuf::from));
+    /// ```
+    pub fn split_by_directory(&self) -> Vec<DuplicateGroup> {
+        let mut groups: Vec<DuplicateGroup> = Vec::new();
+        for (index, file) in self.files.iter().enumerate() {
+            let directory = file.parent();
+            let position = match groups.iter().position(|group| group.files[0].parent() == directory) {
+                Some(position) => position,
+                None => {
+                    groups.push(DuplicateGroup {
+                        hash: self.hash.clone(),
+                        size: self.size,
+                        files: Vec::new(),
+                        reflinked: Vec::new(),
+                        digests: self.digests.clone(),
+                        entries: Vec::new(),
+                    });
+                    groups.len() - 1
+                }
+            };
+            let group = &mut groups[position];
+            group.files.push(file.clone());
+            // Sharing data with the first member says nothing about the first of another directory
+            if position == 0 && self.reflinked.contains(file) {
+                group.reflinked.push(file.clone());
+            }
+            if let Some(entry) = self.entries.get(index).filter(|_| self.entries.len() == self.files.len()) {
+                group.entries.push(entry.clone());
+            }
+        }
+        groups.retain(|group| group.files.len() > 1);
+        groups
+    }
+
+    /// Returns the members, other than the first, whose removal would free space: those
+    /// not sharing their data with the first member (see `reflinked`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: String::new(),
+    ///     size: 100,
+    ///     files: vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")],
+    ///     reflinked: vec![PathBuf::from("c")],
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert_eq!(group.wasting_files().collect::<Vec<_>>(), vec![&PathBuf::from("b")]);
+    /// ```
+    pub fn wasting_files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().skip(1).filter(|file| !self.reflinked.contains(file))
+    }
+
+    /// Returns the bytes that would be freed by keeping a single copy, not counting
+    /// members that already share their data with the first member.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: String::new(),
+    ///     size: 100,
+    ///     files: vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")],
+    ///     reflinked: vec![PathBuf::from("c")],
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert_eq!(group.wasted_bytes(), 100);
+    /// ```
+    pub fn wasted_bytes(&self) -> u64 {
+        self.size * self.wasting_files().count() as u64
+    }
+
+    /// Returns whether every member listed by `wasting_files` is on the device of the
+    /// first member, so it can be replaced by a hard link to it, or `None` when a device
+    /// is unknown, as for groups without `entries`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    /// use dupefiles::file_entry::FileEntry;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let files = vec![dir.path().join("a"), dir.path().join("b")];
+    /// fs::write(&files[0], "copy").unwrap();
+    /// fs::write(&files[1], "copy").unwrap();
+    ///
+    /// let mut group = DuplicateGroup {
+    ///     hash: String::new(),
+    ///     size: 4,
+    ///     entries: files.iter().map(|file| FileEntry::from_path(file, 4, "")).collect(),
+    ///     files,
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    /// };
+    /// #[cfg(unix)]
+    /// assert_eq!(group.hardlink_possible(), Some(true));
+    /// group.entries.clear();
+    /// assert_eq!(group.hardlink_possible(), None);
+    /// ```
+    pub fn hardlink_possible(&self) -> Option<bool> {
+        let device = |path: &PathBuf| self.entries.iter().find(|entry| &entry.path == path).and_then(|entry| entry.dev);
+        let keeper_dev = device(self.files.first()?)?;
+        let mut possible = true;
+        for duplicate in self.wasting_files() {
+            possible &= device(duplicate)? == keeper_dev;
+        }
+        Some(possible)
+    }
+
+    /// Returns whether every member listed by `wasting_files` can be replaced by a
+    /// copy-on-write clone of the first member: they can be hard linked to it and their
+    /// file system supports copy-on-write (see `copy_on_write_supported`). `None` when a
+    /// device is unknown or the file system cannot be queried.
+    pub fn reflink_possible(&self) -> Option<bool> {
+        match self.hardlink_possible()? {
+            true => copy_on_write_supported(&self.files[0]).ok(),
+            false => Some(false),
+        }
+    }
+
+    /// Returns the action suggested for the duplicates: clones where possible, as they
+    /// keep separate files, otherwise hard links, otherwise deletion, unless the members
+    /// are only probable duplicates (see `is_probable`) or removing them frees no space.
+    /// Members matched by their decoded content (see `is_content_match`) are never
+    /// linked, so deletion is suggested for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use dupefiles::duplicate_group::{DuplicateGroup, SuggestedAction};
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: String::new(),
+    ///     size: 100,
+    ///     files: vec![PathBuf::from("a"), PathBuf::from("b")],
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// assert_eq!(group.suggested_action(), SuggestedAction::Delete);
+    ///
+    /// let group = DuplicateGroup { hash: format!("gz:{}", "0".repeat(64)), ..group };
+    /// assert_eq!(group.suggested_action(), SuggestedAction::Delete);
+    /// ```
+    pub fn suggested_action(&self) -> SuggestedAction {
+        if self.wasted_bytes() == 0 {
+            SuggestedAction::Nothing
+        } else if self.is_probable() {
+            SuggestedAction::Verify
+        } else if self.is_content_match() {
+            SuggestedAction::Delete
+        } else if self.reflink_possible() == Some(true) {
+            SuggestedAction::Reflink
+        } else if self.hardlink_possible() == Some(true) {
+            SuggestedAction::Hardlink
+        } else {
+            SuggestedAction::Delete
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use fuser::{FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request};
+use crate::duplicate_group::DuplicateGroup;
+use crate::localize::message;
+
+/// The inode of the root directory
+const ROOT: u64 = fuser::FUSE_ROOT_ID;
+
+/// How long the kernel may cache entries and attributes, which never change
+const TTL: Duration = Duration::from_secs(3600);
+
+/// How often `mount_groups` checks whether to unmount
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A directory or symlink of the tree
+#[derive(Debug)]
+enum Node {
+    /// A directory listing the inodes of its entries
+    Directory(Vec<u64>),
+    /// A symlink to a member of a group
+    Link(PathBuf),
+}
+
+/// The read-only tree shown by `mount_groups`: a directory per duplicate group below
+/// the root, holding a symlink to each of its members.
+///
+/// The directory of a group is named after the file name of its first member and its
+/// ID, such as `IMG_0001.jpg (7b5fbea173697cb3)`, and the symlinks after the position
+/// and file name of each member, such as `1 IMG_0001.jpg`, so members with the same
+/// name in different directories can be told apart.
+#[derive(Debug)]
+pub struct DuplicateTree {
+    /// The parent, name and content of every node, indexed by inode minus one
+    nodes: Vec<(u64, OsString, Node)>,
+    /// When the tree was built, the time of every node
+    time: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+/// The file name of a path, or the whole path when it has none
+fn file_name(path: &Path) -> &OsStr {
+    path.file_name().unwrap_or(path.as_os_str())
+}
+
+impl DuplicateTree {
+    /// Builds the tree of `groups`, owned by the user running dupefiles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use dupefiles::duplicate_group::DuplicateGroup;
+    /// use dupefiles::duplicate_mount::DuplicateTree;
+    ///
+    /// let group = DuplicateGroup {
+    ///     hash: "7b5fbea173697cb3cdf068f72ac153291fe2904d01fafa055797dc6d72baa1cb".to_string(),
+    ///     size: 4000,
+    ///     files: vec![PathBuf::from("/a/IMG_0001.jpg"), PathBuf::from("/b/IMG_0001.jpg")],
+    ///     reflinked: Vec::new(),
+    ///     digests: Default::default(),
+    ///     entries: Vec::new(),
+    /// };
+    /// let tree = DuplicateTree::new(&[group]);
+    /// let link = tree.resolve(Path::new("IMG_0001.jpg (7b5fbea173697cb3)/2 IMG_0001.jpg")).unwrap();
+    /// assert_eq!(tree.link_target(link), Some(Path::new("/b/IMG_0001.jpg")));
+    /// ```
+    pub fn new(groups: &[DuplicateGroup]) -> Self {
+        let mut nodes = vec![(ROOT, OsString::new(), Node::Directory(Vec::new()))];
+        let mut names = HashSet::new();
+        for (index, group) in groups.iter().enumerate() {
+            let Some(first) = group.files.first() else { continue };
+            let mut name = file_name(first).to_owned();
+            name.push(format!(" ({})", group.id()));
+            // Groups compared in several content modes may share an ID
+            if !names.insert(name.clone()) {
+                name.push(format!(" {}", index + 1));
+                names.insert(name.clone());
+            }
+            let directory = nodes.len() as u64 + 1;
+            nodes.push((ROOT, name, Node::Directory(Vec::new())));
+            let mut links = Vec::new();
+            for (position, file) in group.files.iter().enumerate() {
+                let mut name = OsString::from(format!("{} ", position + 1));
+                name.push(file_name(file));
+                links.push(nodes.len() as u64 + 1);
+                nodes.push((directory, name, Node::Link(file.clone())));
+            }
+            nodes[directory as usize - 1].2 = Node::Directory(links);
+            if let Node::Directory(entries) = &mut nodes[0].2 {
+                entries.push(directory);
+            }
+        }
+        // SAFETY: getuid and getgid cannot fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        DuplicateTree { nodes, time: SystemTime::now(), uid, gid }
+    }
+
+    /// The node with inode `ino`
+    fn node(&self, ino: u64) -> Option<&(u64, OsString, Node)> {
+        self.nodes.get(usize::try_from(ino).ok()?.checked_sub(1)?)
+    }
+
+    /// The inode of the entry named `name` in the directory `parent`
+    fn lookup_child(&self, parent: u64, name: &OsStr) -> Option<u64> {
+        match self.node(parent)? {
+            (_, _, Node::Directory(entries)) => entries.iter().copied().find(|&ino| self.nodes[ino as usize - 1].1 == name),
+            _ => None,
+        }
+    }
+
+    /// The inode of the node at `path`, relative to the root of the tree.
+    pub fn resolve(&self, path: &Path) -> Option<u64> {
+        path.iter().try_fold(ROOT, |parent, name| self.lookup_child(parent, name))
+    }
+
+    /// The member of a group the symlink with inode `ino` points to, or `None` if it
+    /// is not a symlink.
+    pub fn link_target(&self, ino: u64) -> Option<&Path> {
+        match self.node(ino)? {
+            (_, _, Node::Link(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// The attributes of the node with inode `ino`
+    fn attr(&self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, size, nlink) = match &self.node(ino)?.2 {
+            Node::Directory(entries) => (FileType::Directory, 0o555, 0, 2 + entries.len() as u32 * u32::from(ino == ROOT)),
+            Node::Link(target) => (FileType::Symlink, 0o777, target.as_os_str().len() as u64, 1),
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: 0,
+            atime: self.time,
+            mtime: self.time,
+            ctime: self.time,
+            crtime: self.time,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+}
+
+impl Filesystem for DuplicateTree {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_child(parent, name).and_then(|ino| self.attr(ino)) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        match self.link_target(ino) {
+            Some(target) => reply.data(target.as_os_str().as_bytes()),
+            None => reply.error(libc::EINVAL),
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        let Some((parent, _, Node::Directory(entries))) = self.node(ino) else {
+            reply.error(libc::ENOTDIR);
+            return;
+        };
+        let listing = [(ino, FileType::Directory, OsStr::new(".")), (*parent, FileType::Directory, OsStr::new(".."))]
+            .into_iter()
+            .chain(entries.iter().map(|&child| {
+                let (_, name, node) = &self.nodes[child as usize - 1];
+                let kind = match node {
+                    Node::Directory(_) => FileType::Directory,
+                    Node::Link(_) => FileType::Symlink,
+                };
+                (child, kind, name.as_os_str())
+            }));
+        // Each offset is the position of the entry following it
+        for (position, (child, kind, name)) in listing.enumerate().skip(offset.max(0) as usize) {
+            if reply.add(child, position as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mounts a read-only file system at `mountpoint` showing each duplicate group as a
+/// directory of symlinks to its members, as laid out by `DuplicateTree`, so the
+/// duplicates can be browsed, compared and removed with a file manager. Removing a
+/// symlink is refused; the files are reached through them.
+///
+/// The call blocks until `stop` is set, then unmounts the file system, or until it is
+/// unmounted by other means such as `fusermount -u`.
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups to show.
+/// * `mountpoint` - An empty directory to mount the file system on.
+/// * `stop` - Set to unmount the file system and return.
+///
+/// # Returns
+///
+/// An error if the file system cannot be mounted, such as when FUSE is unavailable.
+pub fn mount_groups(groups: &[DuplicateGroup], mountpoint: &Path, stop: &AtomicBool) -> io::Result<()> {
+    let options = [MountOption::RO, MountOption::FSName("dupefiles".to_string()), MountOption::DefaultPermissions];
+    let session = fuser::spawn_mount2(DuplicateTree::new(groups), mountpoint, &options)?;
+    while !stop.load(Ordering::Relaxed) && !session.guard.is_finished() {
+        thread::sleep(POLL_INTERVAL);
+    }
+    if session.guard.is_finished() {
+        return session.guard.join().unwrap_or_else(|_| Err(io::Error::other(message("mount-thread-panicked", &[]))));
+    }
+    // Dropping the session unmounts the file system
+    drop(session);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(hash: &str, files: &[&str]) -> DuplicateGroup {
+        DuplicateGroup {
+            hash: hash.to_string(),
+            size: 10,
+            files: files.iter().map(PathBuf::from).collect(),
+            reflinked: Vec::new(),
+            digests: Default::default(),
+            entries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_names_unique() {
+        let hash = "4f".repeat(32);
+        let groups = [
+            group(&hash, &["/a/song.mp3", "/b/song.mp3"]),
+            group(&hash, &["/c/song.mp3", "/d/other.mp3"]),
+        ];
+        let tree = DuplicateTree::new(&groups);
+
+        let first = tree.resolve(Path::new("song.mp3 (4f4f4f4f4f4f4f4f)")).unwrap();
+        let second = tree.resolve(Path::new("song.mp3 (4f4f4f4f4f4f4f4f) 2")).unwrap();
+        assert_ne!(first, second);
+        let link = tree.resolve(Path::new("song.mp3 (4f4f4f4f4f4f4f4f) 2/2 other.mp3")).unwrap();
+        assert_eq!(tree.link_target(link), Some(Path::new("/d/other.mp3")));
+        assert_eq!(tree.link_target(second), None);
+        assert_eq!(tree.resolve(Path::new("song.mp3 (4f4f4f4f4f4f4f4f)/3 song.mp3")), None);
+        assert_eq!(tree.attr(ROOT).unwrap().nlink, 4);
+        assert_eq!(tree.attr(link).unwrap().size, "/d/other.mp3".len() as u64);
+    }
+}
@@ -1,4 +1,5 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use serde::{Serialize, Serializer};
 
 /// Measures the elapsed time of a given function and returns a formatted string representation.
 ///
@@ -74,4 +75,66 @@ pub fn format_duration(duration: std::time::Duration) -> String {
     } else {
         format!("{}ms", milliseconds)
     }
-}
\ No newline at end of file
+}
+
+/// Serializes a duration as a number of seconds
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// How long each phase of a scan took and how much data went through it, so the
+/// performance of scans can be tracked from one run or version to the next.
+///
+/// A scan fills it in `ScanProgress::timings` as each phase ends.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use dupefiles::elapsed_time::TimingReport;
+///
+/// let timings = TimingReport { hash: Duration::from_millis(1500), hash_bytes: 4096, ..Default::default() };
+/// let json = timings.to_json();
+/// assert!(json.contains("\"hash_seconds\":1.5,"));
+/// assert!(json.contains("\"hash_bytes\":4096,"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TimingReport {
+    /// Time spent walking the tree and choosing the files to hash, including the
+    /// prefilter and quick check passes.
+    #[serde(rename = "walk_seconds", serialize_with = "serialize_seconds")]
+    pub walk: Duration,
+    /// Number of files found while walking, before those without a possible duplicate
+    /// are left out.
+    pub walk_files: usize,
+    /// Total size of the files found while walking.
+    pub walk_bytes: u64,
+    /// Time spent hashing files, not counting the comparisons of `verify`.
+    #[serde(rename = "hash_seconds", serialize_with = "serialize_seconds")]
+    pub hash: Duration,
+    /// Number of files hashed, including those whose hash was reused from a cache.
+    pub hash_files: usize,
+    /// Bytes read to hash files.
+    pub hash_bytes: u64,
+    /// Time spent confirming that files with the same hash are duplicates, such as by
+    /// comparing them byte for byte.
+    #[serde(rename = "verify_seconds", serialize_with = "serialize_seconds")]
+    pub verify: Duration,
+    /// Number of files compared with the first member of their group.
+    pub verify_files: usize,
+    /// Total size of the files compared, counting both files of every comparison.
+    pub verify_bytes: u64,
+    /// Time spent filtering the groups and writing the report.
+    #[serde(rename = "output_seconds", serialize_with = "serialize_seconds")]
+    pub output: Duration,
+    /// Time from the start of the scan until the report was written.
+    #[serde(rename = "total_seconds", serialize_with = "serialize_seconds")]
+    pub total: Duration,
+}
+
+impl TimingReport {
+    /// The report as a single-line JSON object, with the durations in seconds.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
@@ -0,0 +1,63 @@
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use crate::duplicate_group::DuplicateGroup;
+use crate::write_report::JsonGroup;
+
+/// The placeholder of an `--exec` command replaced by the paths of the group.
+pub const GROUP_PLACEHOLDER: &str = "{group}";
+
+/// Runs a user command for a duplicate group, so custom actions can be applied to the
+/// groups of a scan.
+///
+/// The command is run by `sh -c`. When it holds `{group}`, the placeholder is replaced
+/// by the paths of the members, passed as separate arguments so names with spaces or
+/// quotes need no escaping. Otherwise the group is written to the standard input of the
+/// command as a JSON object, as in JSON reports.
+///
+/// # Arguments
+///
+/// * `command` - The shell command.
+/// * `group` - The duplicate group.
+///
+/// # Returns
+///
+/// The exit status of the command, or an error if it could not be started.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use dupefiles::duplicate_group::DuplicateGroup;
+/// use dupefiles::exec_hook::run_exec;
+///
+/// let group = DuplicateGroup {
+///     hash: "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03".to_string(),
+///     size: 4,
+///     files: vec![PathBuf::from("/data/a b.txt"), PathBuf::from("/data/c.txt")],
+///     reflinked: Vec::new(),
+///     digests: Default::default(),
+///     entries: Vec::new(),
+/// };
+/// assert!(run_exec("test $# -eq 2 && test \"$1\" = '/data/a b.txt' # {group}", &group).unwrap().success());
+/// assert!(run_exec("grep -q '\"files\":\\[\"/data/a b.txt\"'", &group).unwrap().success());
+/// ```
+pub fn run_exec(command: &str, group: &DuplicateGroup) -> io::Result<ExitStatus> {
+    let mut shell = Command::new("sh");
+    if command.contains(GROUP_PLACEHOLDER) {
+        // `$0` of the script, then the members as its positional parameters
+        shell.arg("-c").arg(command.replace(GROUP_PLACEHOLDER, "\"$@\""))
+            .arg("dupefiles")
+            .args(&group.files);
+        return shell.status();
+    }
+    let mut child = shell.arg("-c").arg(command).stdin(Stdio::piped()).spawn()?;
+    let json = serde_json::to_vec(&JsonGroup::new(group))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Commands that do not read the group close their input early
+        match stdin.write_all(&json) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()
+}
@@ -0,0 +1,208 @@
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A member of a duplicate group with the metadata it had when the group was formed,
+/// so callers can decide what to do with it without querying every file again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// The path of the member, as in `DuplicateGroup::files`.
+    pub path: PathBuf,
+    /// The size of the member in bytes. Members compared by content only may differ
+    /// in size from the rest of their group.
+    pub size: u64,
+    /// The space allocated to the member on disk in bytes (`st_blocks` * 512), less than
+    /// `size` for sparse files and on compressing file systems such as btrfs and ZFS, or
+    /// `None` when unknown.
+    pub allocated: Option<u64>,
+    /// The last modification time, or `None` when unknown, as for remote sources and
+    /// the files in disc images.
+    pub mtime: Option<SystemTime>,
+    /// The device holding the member, or `None` when unknown.
+    pub dev: Option<u64>,
+    /// The inode number of the member on `dev`, or `None` when unknown. Members with
+    /// the same `dev` and `inode` are hard links to the same file.
+    pub inode: Option<u64>,
+    /// The hash of the member's content, as in `DuplicateGroup::hash`.
+    pub hash: String,
+}
+
+impl FileEntry {
+    /// Describes the local file `path` from its current metadata, following symbolic
+    /// links. When the file cannot be queried, as for the files in disc images, only
+    /// `size` and `hash` are known.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The member.
+    /// * `size` - The size of the member, used when it cannot be queried.
+    /// * `hash` - The hash of the member's content.
+    ///
+    /// # Returns
+    ///
+    /// The entry of `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use dupefiles::file_entry::FileEntry;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("a.txt");
+    /// fs::write(&path, "content").unwrap();
+    ///
+    /// let entry = FileEntry::from_path(&path, 7, "ed7002b4");
+    /// assert_eq!(entry.size, 7);
+    /// assert!(entry.mtime.is_some());
+    /// #[cfg(unix)]
+    /// assert!(entry.inode.is_some() && entry.allocated.is_some());
+    ///
+    /// let missing = FileEntry::from_path(&dir.path().join("missing.txt"), 7, "ed7002b4");
+    /// assert_eq!((missing.mtime, missing.inode), (None, None));
+    /// ```
+    pub fn from_path(path: &Path, size: u64, hash: &str) -> Self {
+        match fs::metadata(path) {
+            Ok(metadata) => FileEntry::from_metadata(path, &metadata, hash),
+            Err(_) => FileEntry::unknown(path, size, hash),
+        }
+    }
+
+    /// Describes the local file `path` from metadata already read, so a file is only
+    /// queried once however many times its entry is used. Scans read the metadata of
+    /// each file while walking the tree, with a single `statx` call on Linux, and keep
+    /// it in the entry of the file from then on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use dupefiles::file_entry::FileEntry;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("a.txt");
+    /// fs::write(&path, "content").unwrap();
+    ///
+    /// let entry = FileEntry::from_metadata(&path, &fs::metadata(&path).unwrap(), "");
+    /// assert_eq!(entry, FileEntry::from_path(&path, 0, ""));
+    /// ```
+    pub fn from_metadata(path: &Path, metadata: &Metadata, hash: &str) -> Self {
+        FileEntry {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            allocated: allocated_size(metadata),
+            mtime: metadata.modified().ok(),
+            dev: file_id(metadata).map(|(dev, _)| dev),
+            inode: file_id(metadata).map(|(_, inode)| inode),
+            hash: hash.to_string(),
+        }
+    }
+
+    /// Describes a member of which only the size and hash are known, such as an object
+    /// of a remote source.
+    pub fn unknown(path: &Path, size: u64, hash: &str) -> Self {
+        FileEntry { path: path.to_path_buf(), size, allocated: None, mtime: None, dev: None, inode: None, hash: hash.to_string() }
+    }
+
+    /// Whether both entries are the same file on disk, reached through two paths: hard
+    /// links, symbolic links, or names differing in case only on case-insensitive file
+    /// systems such as APFS, NTFS and FAT. Entries whose device or inode is unknown are
+    /// never the same file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use dupefiles::file_entry::FileEntry;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let (a, b, link) = (dir.path().join("a.txt"), dir.path().join("b.txt"), dir.path().join("link.txt"));
+    /// fs::write(&a, "copy").unwrap();
+    /// fs::write(&b, "copy").unwrap();
+    /// fs::hard_link(&a, &link).unwrap();
+    ///
+    /// let entry = |path| FileEntry::from_path(path, 4, "5891b5b5");
+    /// #[cfg(unix)]
+    /// assert!(entry(&a).is_same_file(&entry(&link)));
+    /// assert!(!entry(&a).is_same_file(&entry(&b)));
+    /// ```
+    pub fn is_same_file(&self, other: &FileEntry) -> bool {
+        self.dev.is_some() && self.inode.is_some() && self.dev == other.dev && self.inode == other.inode
+    }
+
+    /// Returns whether the file still has the size, modification time, device and inode
+    /// of the entry, following symbolic links, so an action is not applied to a file
+    /// modified or replaced since it was scanned. Fields that are unknown are not compared.
+    ///
+    /// # Returns
+    ///
+    /// Whether the file is unchanged, or an error if it can no longer be queried, such as
+    /// when it was removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use dupefiles::file_entry::FileEntry;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("a.txt");
+    /// fs::write(&path, "content").unwrap();
+    /// let entry = FileEntry::from_path(&path, 7, "ed7002b4");
+    /// assert!(entry.is_unchanged().unwrap());
+    ///
+    /// fs::write(&path, "changed content").unwrap();
+    /// assert!(!entry.is_unchanged().unwrap());
+    /// fs::remove_file(&path).unwrap();
+    /// assert!(entry.is_unchanged().is_err());
+    /// ```
+    pub fn is_unchanged(&self) -> io::Result<bool> {
+        let metadata = fs::metadata(&self.path)?;
+        let id = file_id(&metadata);
+        Ok(metadata.len() == self.size
+            && self.mtime.is_none_or(|mtime| metadata.modified().ok() == Some(mtime))
+            && self.dev.is_none_or(|dev| id.map(|(d, _)| d) == Some(dev))
+            && self.inode.is_none_or(|inode| id.map(|(_, i)| i) == Some(inode)))
+    }
+}
+
+/// The device and inode number of a file, or `None` on platforms whose standard
+/// library does not expose them, such as Windows.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::file_entry::file_id;
+///
+/// let metadata = fs::metadata("Cargo.toml").unwrap();
+/// assert_eq!(file_id(&metadata), file_id(&fs::metadata("./Cargo.toml").unwrap()));
+/// ```
+pub fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// The space allocated to a file on disk in bytes (`st_blocks` * 512), or `None` on
+/// platforms that do not report it.
+pub fn allocated_size(metadata: &Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.blocks() * 512)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
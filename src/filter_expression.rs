@@ -0,0 +1,413 @@
+use std::fs::Metadata;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use crate::parse_duration::parse_duration;
+use crate::parse_size::parse_size;
+use crate::unicode_paths::nfc_str;
+use crate::walk_filter::{FilterDecision, WalkFilter};
+use crate::localize::message;
+
+/// A comparison operator of a filter expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// Whether `left` compares to `right` as required
+    fn holds<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+/// A text attribute of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextField {
+    /// The lowercase extension, empty without one
+    Ext,
+    /// The file name
+    Name,
+    /// The whole path
+    Path,
+}
+
+/// A test of a text attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TextTest {
+    Eq(String),
+    Ne(String),
+    In(Vec<String>),
+    Contains(String),
+}
+
+/// A parsed filter expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Size(Comparison, u64),
+    Mtime(Comparison, SystemTime),
+    Age(Comparison, Duration),
+    Text(TextField, TextTest),
+}
+
+/// A token of a filter expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    And,
+    Or,
+    Not,
+    Compare(Comparison),
+    Word(String),
+}
+
+/// Split an expression into tokens. Words are runs of characters other than spaces,
+/// parentheses, commas and operators, or text in single or double quotes
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Compare(Comparison::Ne),
+            '!' => Token::Not,
+            '=' => {
+                chars.next_if_eq(&'=');
+                Token::Compare(Comparison::Eq)
+            }
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Compare(Comparison::Le),
+            '<' => Token::Compare(Comparison::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Compare(Comparison::Ge),
+            '>' => Token::Compare(Comparison::Gt),
+            '"' | '\'' => {
+                let word: String = chars.by_ref().take_while(|&next| next != c).collect();
+                Token::Word(word)
+            }
+            '&' | '|' => return Err(message("filter-expected", &[("what", &format!("'{}{}'", c, c))])),
+            c => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|&next| !next.is_whitespace() && !"(),&|!=<>\"'".contains(next)) {
+                    word.push(next);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of an expression
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => Err(message("filter-expected", &[("what", &what)])),
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            _ => Err(message("filter-expected", &[("what", &what)])),
+        }
+    }
+
+    /// or := and ('||' and)*
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// and := unary ('&&' unary)*
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    /// unary := '!' unary | '(' or ')' | condition
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Open) => {
+                self.next();
+                let expr = self.or()?;
+                self.expect(Token::Close, "')'")?;
+                Ok(expr)
+            }
+            _ => self.condition(),
+        }
+    }
+
+    /// condition := field operator value | field 'in' '(' value (',' value)* ')' | field 'contains' value
+    fn condition(&mut self) -> Result<Expr, String> {
+        let field = self.word(&message("filter-want-field", &[]))?;
+        let text_field = match field.to_lowercase().as_str() {
+            "ext" => Some(TextField::Ext),
+            "name" => Some(TextField::Name),
+            "path" => Some(TextField::Path),
+            "size" | "mtime" | "age" => None,
+            _ => return Err(message("filter-unknown-field", &[("field", &field)])),
+        };
+        let comparison = match (self.next(), text_field) {
+            (Some(Token::Compare(comparison)), _) => comparison,
+            (Some(Token::Word(word)), Some(field)) if matches!(word.as_str(), "in" | "contains") => {
+                let test = if word == "in" {
+                    TextTest::In(self.list()?.into_iter().map(|value| normalize(field, value)).collect())
+                } else {
+                    TextTest::Contains(normalize(field, self.word(&message("filter-want-value-after", &[("token", &"'contains'")]))?))
+                };
+                return Ok(Expr::Text(field, test));
+            }
+            _ => return Err(message("filter-expected-operator", &[("field", &field)])),
+        };
+        let value = self.word(&message("filter-want-value-after", &[("token", &format!("'{}'", field))]))?;
+        match (field.to_lowercase().as_str(), text_field) {
+            (_, Some(field)) => {
+                let value = normalize(field, value);
+                match comparison {
+                    Comparison::Eq => Ok(Expr::Text(field, TextTest::Eq(value))),
+                    Comparison::Ne => Ok(Expr::Text(field, TextTest::Ne(value))),
+                    _ => Err(message("filter-text-operators", &[])),
+                }
+            }
+            ("size", _) => Ok(Expr::Size(comparison, parse_size(&value)?)),
+            ("mtime", _) => Ok(Expr::Mtime(comparison, parse_date(&value)?)),
+            _ => Ok(Expr::Age(comparison, parse_duration(&value)?)),
+        }
+    }
+
+    /// list := '(' value (',' value)* ')'
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        self.expect(Token::Open, &message("filter-want-list", &[]))?;
+        let mut values = vec![self.word(&message("filter-want-list-value", &[]))?];
+        while self.peek() == Some(&Token::Comma) {
+            self.next();
+            values.push(self.word(&message("filter-want-value-after", &[("token", &"','")]))?);
+        }
+        self.expect(Token::Close, &message("filter-want-list-end", &[]))?;
+        Ok(values)
+    }
+}
+
+/// Text is compared in Unicode NFC, and extensions in lowercase and without their
+/// leading dot
+fn normalize(field: TextField, value: String) -> String {
+    let value = nfc_str(&value).into_owned();
+    match field {
+        TextField::Ext => value.trim_start_matches('.').to_lowercase(),
+        _ => value,
+    }
+}
+
+/// Parse a `YYYY-MM-DD` date as midnight UTC
+fn parse_date(input: &str) -> Result<SystemTime, String> {
+    let invalid = || message("filter-invalid-date", &[("value", &input)]);
+    let parts: Vec<i64> = input.split('-')
+        .map(|part| part.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    // Days since 1970-01-01 in the proleptic Gregorian calendar
+    let (year, month) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let seconds = Duration::from_secs(days.unsigned_abs() * 86400);
+    Ok(if days >= 0 { SystemTime::UNIX_EPOCH + seconds } else { SystemTime::UNIX_EPOCH - seconds })
+}
+
+/// A condition on the files of a scan, such as
+/// `size > 100MiB && ext in (mp4, mkv) && mtime < 2023-01-01`, so complex selections
+/// need not be spelled out with many options.
+///
+/// Conditions compare a field of the file with a value:
+///
+/// * `size` with a size such as `512`, `10K` or `1.5GiB`,
+/// * `mtime`, the last modification time, with a `YYYY-MM-DD` date (midnight UTC),
+/// * `age`, the time since the last modification, with a duration such as `30d`,
+/// * `ext`, `name` and `path` with text, bare or in quotes, using `==` and `!=`,
+///   `in (a, b, ...)` or `contains`. Extensions are compared in lowercase, and
+///   accented letters match whether the name writes them as one character or as a
+///   letter and its accent, as macOS does.
+///
+/// Numbers and dates support `==`, `!=`, `<`, `<=`, `>` and `>=`. Conditions are
+/// combined with `&&`, `||`, `!` and parentheses; `&&` binds tighter than `||`.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::filter_expression::FilterExpression;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("movie.MKV");
+/// std::fs::write(&path, vec![0; 2048]).unwrap();
+/// let metadata = std::fs::metadata(&path).unwrap();
+///
+/// let filter: FilterExpression = "size > 1K && ext in (mp4, mkv) && mtime >= 2023-01-01".parse().unwrap();
+/// assert!(filter.matches(&path, &metadata));
+/// let filter: FilterExpression = "!(name contains movie) || age > 1d".parse().unwrap();
+/// assert!(!filter.matches(&path, &metadata));
+/// assert!("size > lots".parse::<FilterExpression>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterExpression {
+    expr: Expr,
+}
+
+impl FromStr for FilterExpression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(s)?, position: 0 };
+        let expr = parser.or()?;
+        if parser.peek().is_some() {
+            return Err(message("filter-trailing-input", &[]));
+        }
+        Ok(FilterExpression { expr })
+    }
+}
+
+impl FilterExpression {
+    /// Returns whether the file `path` with `metadata` satisfies the expression. Files
+    /// whose modification time is unknown fail every `mtime` and `age` condition.
+    pub fn matches(&self, path: &Path, metadata: &Metadata) -> bool {
+        evaluate(&self.expr, path, metadata)
+    }
+
+    /// Wraps the expression in a `WalkFilter` skipping the files it does not match,
+    /// for `ScanOptions::walk_filter`. Directories are always walked, and the files
+    /// matched are still subject to the built-in filters.
+    pub fn walk_filter(self) -> WalkFilter {
+        WalkFilter::new(move |path, metadata| {
+            if metadata.is_dir() || self.matches(path, metadata) {
+                FilterDecision::Default
+            } else {
+                FilterDecision::Exclude
+            }
+        })
+    }
+}
+
+/// Evaluate an expression for a file
+fn evaluate(expr: &Expr, path: &Path, metadata: &Metadata) -> bool {
+    match expr {
+        Expr::And(left, right) => evaluate(left, path, metadata) && evaluate(right, path, metadata),
+        Expr::Or(left, right) => evaluate(left, path, metadata) || evaluate(right, path, metadata),
+        Expr::Not(expr) => !evaluate(expr, path, metadata),
+        Expr::Size(comparison, size) => comparison.holds(metadata.len(), *size),
+        Expr::Mtime(comparison, time) => metadata.modified().is_ok_and(|modified| comparison.holds(modified, *time)),
+        Expr::Age(comparison, age) => metadata.modified().ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|elapsed| comparison.holds(elapsed, *age)),
+        Expr::Text(field, test) => {
+            let text = match field {
+                TextField::Ext => path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default(),
+                TextField::Name => path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                TextField::Path => path.to_string_lossy().into_owned(),
+            };
+            let text = nfc_str(&text);
+            match test {
+                TextTest::Eq(value) => text == value.as_str(),
+                TextTest::Ne(value) => text != value.as_str(),
+                TextTest::In(values) => values.iter().any(|value| text == value.as_str()),
+                TextTest::Contains(value) => text.contains(value.as_str()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence() {
+        let parsed: FilterExpression = "ext == jpg || ext == png && size > 1K".parse().unwrap();
+        let expected: FilterExpression = "ext == jpg || (ext == png && size > 1K)".parse().unwrap();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-02"), Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(86400)));
+        assert_eq!(parse_date("2023-01-01"), Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(1672531200)));
+        assert!(parse_date("2023-13-01").is_err());
+    }
+
+    #[test]
+    fn test_decomposed_names() {
+        let dir = tempfile::Builder::new().prefix("filter_expression").tempdir().unwrap();
+        let path = dir.path().join("cafe\u{301}.jpg");
+        std::fs::write(&path, "copy").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        let filter: FilterExpression = "name == \"caf\u{e9}.jpg\"".parse().unwrap();
+        assert!(filter.matches(&path, &metadata));
+        let filter: FilterExpression = "name contains \"cafe\u{301}\"".parse().unwrap();
+        assert!(filter.matches(&dir.path().join("caf\u{e9}.jpg"), &metadata));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!("size >".parse::<FilterExpression>().is_err());
+        assert!("colour == red".parse::<FilterExpression>().is_err());
+        assert!("ext < mp4".parse::<FilterExpression>().is_err());
+        assert!("(size > 1K".parse::<FilterExpression>().is_err());
+        assert!("size > 1K size".parse::<FilterExpression>().is_err());
+        assert!("size > 1K & ext == mp4".parse::<FilterExpression>().is_err());
+    }
+}
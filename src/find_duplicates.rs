@@ -1,170 +1,1959 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::ops::ControlFlow;
+use std::time::Instant;
 use anyhow::Result;
-use std::io::Write;
-use walkdir::WalkDir;
-use crate::is_duplicate_file::is_duplicate_file;
-use crate::compute_sha256::compute_sha256;
+use walkdir::{DirEntry, WalkDir};
+use crate::breadth_first::BreadthFirst;
+use crate::hash_key::HashKey;
+use crate::elapsed_time::TimingReport;
+use crate::prefilter::Prefilter;
+use crate::physical_order::physical_position;
+use crate::device_scheduler::run_by_device;
+use crate::open_files::{is_too_many_open_files, open_file_limit, OpenFileBudget};
+use crate::memory_usage::{entry_memory, map_memory, SpillFile};
+use crate::cache_hints::drop_cached_pages;
+use crate::quick_check::{quick_checksum, QuickCheck};
+use crate::duplicate_comparator::{DefaultComparator, DuplicateComparator};
+use crate::compute_sha256::{compute_sampled_sha256, compute_sha256, compute_sha256_direct, compute_sha256_with_digests};
+use crate::hash_algorithm::HashAlgorithm;
+use crate::retry::retry;
+use crate::locked_file::{changed_since, is_locked};
+use crate::compute_audio_sha256::{compute_audio_sha256, is_audio_file};
+use crate::compute_image_sha256::{compute_image_sha256, is_image_file};
+use crate::compute_text_sha256::compute_text_sha256;
+use crate::compute_gzip_sha256::{compute_gzip_sha256, is_gzip_file, CompressionFormat};
+use crate::compute_email_sha256::{compute_email_sha256, is_email_file, is_mbox, mbox_messages};
+use crate::detect_file_type::detect_file_type;
 use crate::is_hidden::is_hidden;
-use crate::human_readable_size::human_readable_size;
+use crate::file_entry::{file_id, FileEntry};
+use crate::redact_paths::{redact_directory, redact_file, redact_groups};
+use crate::unicode_paths::{nfc_path, nfc_str, normalize_groups};
+use crate::walk_filter::FilterDecision;
+use crate::long_path::long_path;
+use crate::alternate_streams::alternate_streams;
+use crate::shared_extents::shares_extents;
+use crate::network_filesystem::is_network_filesystem;
+use crate::iso_image::{is_iso_image, iso_members};
+use crate::archive_members::{archive_members, compute_archive_member_sha256, is_archive, open_archive_member, ArchiveMember, MemberEncoding};
+use crate::scan_source::is_filtered_out;
+use crate::duplicate_group::DuplicateGroup;
+use crate::scan_options::ScanOptions;
+use crate::scan_summary::{summarize, StopReason};
+use crate::write_report::{group_entries, write_csv, write_hashdeep, write_json, write_ncdu, write_stats, write_unique, OutputFormat};
+use crate::scan_progress::{ProgressFormat, ScanError, ScanProgress};
 use std::io::ErrorKind;
-use std::io::Error;
+use std::io::{BufReader, Error};
+use crate::localize::message;
 
-/// Write a line either to the output file or stdout
-fn write_line(output_file: &mut Option<&mut fs::File>, line: &str) -> Result<()> {
-    match output_file {
-        Some(file) => writeln!(file, "{}", line)?,
-        None => println!("{}", line),
+/// Bytes hashed at the start, middle and end of files above `ScanOptions::sample_above`
+const SAMPLE_SIZE: u64 = 1024 * 1024;
+
+/// Why the scan started at `started` must stop now, if it must
+pub(crate) fn stop_reason(options: &ScanOptions, started: Instant) -> Option<StopReason> {
+    if options.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+        Some(StopReason::Interrupted)
+    } else if options.max_runtime.is_some_and(|max_runtime| started.elapsed() >= max_runtime) {
+        Some(StopReason::MaxRuntime)
+    } else {
+        None
     }
-    Ok(())
+}
+
+/// Lock the shared progress, recovering it if a thread reading it panicked
+pub(crate) fn lock_progress(progress: &Mutex<ScanProgress>) -> MutexGuard<'_, ScanProgress> {
+    progress.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The content modes that affect hashes, so cached hashes are only reused by scans
+/// hashing the same way
+pub(crate) fn hash_mode(options: &ScanOptions) -> String {
+    let modes = [
+        (options.audio_content, "audio"),
+        (options.image_content, "image"),
+        (options.email_content, "email"),
+        (options.normalize_text, "text"),
+        (options.trim_trailing_whitespace, "trim"),
+        (options.decompress.contains(&CompressionFormat::Gzip), "gz"),
+    ];
+    modes.iter().filter(|(enabled, _)| *enabled).map(|(_, mode)| *mode).collect::<Vec<_>>().join(",")
+}
+
+/// Hash a file like `hash_file`, reusing and updating `ScanOptions::hash_cache` when set.
+/// The bytes read from the file are added to `bytes_read`.
+fn cached_hash_file(file: &FileEntry, options: &ScanOptions, mode: &str, bytes_read: &mut u64) -> std::io::Result<(String, bool)> {
+    let (path, size) = (file.path.as_path(), file.size);
+    let Some(cache) = &options.hash_cache else {
+        let hashed = hash_file(path, options, size)?;
+        *bytes_read += hashed_bytes(options, size);
+        return Ok(hashed);
+    };
+    // Sampled hashes are only reused for files sampled again
+    let mode = if is_sampled(options, size) {
+        [mode, "sample"].iter().filter(|part| !part.is_empty()).copied().collect::<Vec<_>>().join(",")
+    } else {
+        mode.to_string()
+    };
+    if let Some(hashed) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(file, &mode) {
+        return Ok(hashed);
+    }
+    let (hash, content_only) = hash_file(path, options, size)?;
+    *bytes_read += hashed_bytes(options, size);
+    cache.lock().unwrap_or_else(|e| e.into_inner()).insert(file, &mode, &hash, content_only);
+    Ok((hash, content_only))
+}
+
+/// Hash a file stored in a disc image or an archive, or a message of an mbox mailbox
+/// over its `Message-ID` and body like `hash_file` hashes email messages. Messages that
+/// cannot be parsed get a hash of their bytes
+fn hash_member(image: &Path, member: &ArchiveMember) -> std::io::Result<(String, bool)> {
+    if member.encoding == MemberEncoding::Message {
+        let message = open_archive_member(image, member).and_then(|reader| compute_email_sha256(BufReader::new(reader)));
+        if let Ok(hash) = message {
+            return Ok((format!("email:{}", hash), true));
+        }
+    }
+    compute_archive_member_sha256(image, member).map(|hash| (hash, false))
+}
+
+/// Whether a file of `size` bytes is compared by a sampled hash, set by `ScanOptions::sample_above`
+fn is_sampled(options: &ScanOptions, size: u64) -> bool {
+    options.sample_above.is_some_and(|threshold| size > threshold)
+}
+
+/// How many bytes of a file of `size` bytes `hash_file` reads
+fn hashed_bytes(options: &ScanOptions, size: u64) -> u64 {
+    if is_sampled(options, size) {
+        size.min(3 * SAMPLE_SIZE)
+    } else {
+        size
+    }
+}
+
+/// Hash a file, over its audio frames, image payload, email message, normalized text or
+/// decompressed content only when the matching content mode is enabled. The flag is
+/// true when the hash covers part of the content only, in which case equal hashes do
+/// not imply identical bytes. Files that cannot be parsed in a content mode fall back to a
+/// whole-file hash. Files above `ScanOptions::sample_above` get a sampled hash, which
+/// is partial too.
+fn hash_file(path: &Path, options: &ScanOptions, size: u64) -> std::io::Result<(String, bool)> {
+    let content_hash = if is_sampled(options, size) {
+        Some(compute_sampled_sha256(path, SAMPLE_SIZE).map(|h| format!("sample:{}", h))?)
+    } else if options.audio_content && is_audio_file(path) {
+        compute_audio_sha256(path).map(|h| format!("audio:{}", h)).ok()
+    } else if options.image_content && is_image_file(path) {
+        compute_image_sha256(path).map(|h| format!("image:{}", h)).ok()
+    } else if options.email_content && is_email_file(path) {
+        fs::File::open(path).and_then(|file| compute_email_sha256(BufReader::new(file))).map(|h| format!("email:{}", h)).ok()
+    } else if options.normalize_text {
+        compute_text_sha256(path, options.trim_trailing_whitespace).map(|h| format!("text:{}", h)).ok()
+    } else if options.decompress.contains(&CompressionFormat::Gzip) {
+        // Uncompressed files are hashed whole, to match the decompressed content of the others
+        let decompressed = is_gzip_file(path).then(|| compute_gzip_sha256(path).ok()).flatten();
+        Some(format!("gz:{}", decompressed.map_or_else(|| compute_sha256(path), Ok)?))
+    } else {
+        None
+    };
+    match content_hash {
+        Some(hash) => Ok((hash, true)),
+        None if options.direct_io => compute_sha256_direct(path).map(|hash| (hash, false)),
+        None => compute_sha256(path).map(|hash| (hash, false)),
+    }
+}
+
+/// The canonical path of `directory`, which every path walked is joined onto
+pub(crate) fn scan_root(directory: &Path) -> Result<PathBuf> {
+    let current_dir = env::current_dir()?;
+    let absolute_path = current_dir.join(directory);
+    let canonical_directory = absolute_path.canonicalize().map_err(|e| {
+        Error::new(ErrorKind::NotFound, message("error-canonicalize", &[("error", &e)]))
+    })?;
+
+    // Every path walked is joined onto the root, so this lifts the path length limit for all of them
+    let canonical_directory = long_path(&canonical_directory);
+
+    if !canonical_directory.exists() {
+        return Err(Error::new(ErrorKind::NotFound, message("error-directory-missing", &[("directory", &directory.display())])).into());
+    }
+    Ok(canonical_directory)
+}
+
+/// A file found by the walk, with the metadata read while walking and, when it is stored
+/// in a disc image or an archive, the image or archive holding it and its location there
+pub(crate) struct Candidate {
+    /// The file, without a hash yet
+    pub(crate) entry: FileEntry,
+    pub(crate) image: Option<(PathBuf, ArchiveMember)>,
+}
+
+/// Walk the tree below `root`, calling `visit` with every file to compare, including
+/// alternate streams and the files in disc images when enabled, until `visit` breaks.
+/// Returns the reason the walk stopped early, if `stop_reason` stopped it
+pub(crate) fn walk_files<F>(root: &Path, options: &ScanOptions, started: Instant, mut visit: F) -> Option<StopReason>
+where
+    F: FnMut(Candidate) -> ControlFlow<()>,
+{
+    // Whether each device met is a network file system, so each mount is only checked once
+    let mut network_devices: HashMap<u64, bool> = HashMap::new();
+    // The directory asked for is scanned even when it is on a network file system
+    if let Ok(metadata) = fs::metadata(root) {
+        network_devices.insert(file_id(&metadata).map_or(0, |(dev, _)| dev), false);
+    }
+    let walk_entry = |e: &DirEntry| {
+        if e.depth() == 0 || !e.file_type().is_dir() {
+            return true;
+        }
+        let Ok(metadata) = e.metadata() else {
+            return true;
+        };
+        if let Some(filter) = &options.walk_filter {
+            if filter.decide(e.path(), &metadata) == FilterDecision::Exclude {
+                return false;
+            }
+        }
+        if !options.skip_network {
+            return true;
+        }
+        let network = *network_devices.entry(file_id(&metadata).map_or(0, |(dev, _)| dev)).or_insert_with(|| {
+            let network = is_network_filesystem(e.path()).unwrap_or(false);
+            if network {
+                eprintln!("{}", message("scan-skip-network", &[("directory", &e.path().display())]));
+            }
+            network
+        });
+        !network
+    };
+    let count_error = |error| {
+        if let Some(progress) = &options.shared_progress {
+            lock_progress(progress).count_error(error);
+        }
+    };
+    let walk: Box<dyn Iterator<Item = walkdir::Result<DirEntry>>> = if options.breadth_first {
+        Box::new(BreadthFirst::new(root, walk_entry))
+    } else {
+        Box::new(WalkDir::new(root).into_iter().filter_entry(walk_entry))
+    };
+    for entry in walk.filter_map(|e| e.ok()) {
+        if let Some(reason) = stop_reason(options, started) {
+            return Some(reason);
+        }
+        let path = entry.path();
+        // Without a filter that may include them, hidden files are skipped before their metadata is read
+        if options.walk_filter.is_none() && is_hidden(path) {
+            continue;
+        }
+
+        // Skip symlinks that point to non-existent targets; the type of the entry comes
+        // with the directory listing, so other files are only queried once
+        if entry.path_is_symlink() {
+            if let Ok(link_target) = fs::read_link(path) {
+                if !link_target.exists() {
+                    eprintln!("{}", message("scan-skip-broken-symlink", &[("file", &path.display()), ("target", &link_target.display())]));
+                    continue;
+                }
+            } else {
+                eprintln!("{}", message("scan-symlink-unreadable", &[("file", &path.display())]));
+                count_error(ScanError::Metadata);
+                continue;
+            }
+        }
+
+        // Get file metadata
+        let metadata = match retry(options.retries, options.retry_delay, || fs::metadata(path)) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{}", message("scan-metadata-failed", &[("file", &path.display()), ("error", &e)]));
+                count_error(ScanError::Metadata);
+                continue;
+            }
+        };
+
+        // Skip if not a file
+        if !metadata.is_file() {
+            continue;
+        }
+        let decision = options.walk_filter.as_ref()
+            .map_or(FilterDecision::Default, |filter| filter.decide(path, &metadata));
+        let builtin_filters = match decision {
+            FilterDecision::Exclude => continue,
+            FilterDecision::Include => false,
+            FilterDecision::Default => true,
+        };
+        if builtin_filters && is_hidden(path) {
+            continue;
+        }
+        // Streams are scanned whatever the name and size of the file they are attached to
+        if options.alternate_streams {
+            match alternate_streams(path) {
+                Ok(streams) => {
+                    for (stream_path, size) in streams.into_iter().filter(|(_, size)| *size >= options.min_size.max(1)) {
+                        if visit(Candidate { entry: FileEntry::unknown(&stream_path, size, ""), image: None }).is_break() {
+                            return None;
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", message("scan-streams-failed", &[("file", &path.display()), ("error", &e)]));
+                    count_error(ScanError::Listing);
+                }
+            }
+        }
+        // Like streams, the files of an image or archive are filtered by their own names
+        let members = if options.scan_images && is_iso_image(path) {
+            Some(iso_members(path).map(|members| members.into_iter().map(ArchiveMember::from).collect::<Vec<_>>()))
+        } else if options.scan_archives && is_archive(path) {
+            Some(archive_members(path))
+        } else if options.email_content && is_mbox(path) {
+            Some(mbox_messages(path))
+        } else {
+            None
+        };
+        if let Some(members) = members {
+            match members {
+                Ok(members) => {
+                    for member in members {
+                        let member_path = path.join(&member.path);
+                        if member.size < options.min_size.max(1) || is_hidden(&member_path) || is_filtered_out(&member_path, options) {
+                            continue;
+                        }
+                        let candidate = Candidate { entry: FileEntry::unknown(&member_path, member.size, ""), image: Some((path.to_path_buf(), member)) };
+                        if visit(candidate).is_break() {
+                            return None;
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", message("scan-members-failed", &[("file", &path.display()), ("error", &e)]));
+                    count_error(ScanError::Listing);
+                }
+            }
+        }
+        if builtin_filters && (metadata.len() < options.min_size.max(1) || !matches_extensions(path, options)) {
+            continue;
+        }
+
+        // The metadata is kept, so the file is never queried again during the scan
+        if visit(Candidate { entry: FileEntry::from_metadata(path, &metadata, ""), image: None }).is_break() {
+            return None;
+        }
+    }
+    None
+
+}
+
+/// Whether `path` passes the `extensions` and `exclude_extensions` filters of `options`
+fn matches_extensions(path: &Path, options: &ScanOptions) -> bool {
+    // Check file extension if filters are specified, or the type detected from the
+    // magic bytes when classifying by content (falling back to the extension when
+    // the type is unknown)
+    let detected = if options.by_content && options.extensions.is_some() {
+        detect_file_type(path).ok().flatten()
+    } else {
+        None
+    };
+    if let Some(exts) = &options.extensions {
+        if let Some(types) = detected {
+            if !exts.iter().any(|e| types.contains(&e.as_str())) {
+                return false;
+            }
+        } else if let Some(ext) = path.extension() {
+            let ext_str = ext.to_string_lossy().to_lowercase();
+            if !exts.iter().any(|e| nfc_str(e) == nfc_str(&ext_str)) {
+                return false;
+            }
+        } else {
+            return false; // Skip files without extensions when filtering
+        }
+    }
+    if let (Some(exts), Some(ext)) = (&options.exclude_extensions, path.extension()) {
+        let ext_str = ext.to_string_lossy().to_lowercase();
+        if exts.iter().any(|e| nfc_str(e) == nfc_str(&ext_str)) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Keep only the candidates sharing their size and their quick checksum with another
+/// candidate, moving the others to `ruled_out`. Files in disc images and files whose
+/// checksum cannot be computed are kept, and so are the files left unchecked when
+/// `stop_reason` stops the check
+fn quick_check_candidates(
+    candidates: &mut Vec<FileEntry>,
+    ruled_out: &mut Vec<FileEntry>,
+    image_members: &HashMap<PathBuf, (PathBuf, ArchiveMember)>,
+    quick_check: QuickCheck,
+    options: &ScanOptions,
+    started: Instant,
+) -> Option<StopReason> {
+    let mut stopped = None;
+    let checksums: Vec<Option<u32>> = candidates.iter().map(|candidate| {
+        stopped = stopped.or_else(|| stop_reason(options, started));
+        if stopped.is_some() || image_members.contains_key(&candidate.path) {
+            return None;
+        }
+        // Unreadable files are reported when hashed
+        quick_checksum(&candidate.path, quick_check).ok()
+    }).collect();
+    let mut checksum_counts: HashMap<(u64, u32), usize> = HashMap::new();
+    for (candidate, checksum) in candidates.iter().zip(&checksums) {
+        if let Some(checksum) = checksum {
+            *checksum_counts.entry((candidate.size, *checksum)).or_default() += 1;
+        }
+    }
+    let mut checksums = checksums.into_iter();
+    let (kept, dropped) = candidates.drain(..).partition(|candidate| match checksums.next().flatten() {
+        Some(checksum) => checksum_counts[&(candidate.size, checksum)] > 1,
+        None => true,
+    });
+    *candidates = kept;
+    ruled_out.extend::<Vec<_>>(dropped);
+    stopped
 }
 
 /// This function takes a directory Path value and prints duplicates identified to the specified output.
 /// It skips zero byte files as well as hidden files and hidden directories.
+/// The tree is walked first and only the files that may have a duplicate are kept, such
+/// as those sharing their size with another file, so progress is reported against the
+/// totals to hash; they are then hashed in the order set by the scheduling options.
+/// Results are written once the scan is complete, or once it stops early through
+/// `ScanOptions::cancel`, `ScanOptions::max_runtime`, `ScanOptions::max_files` or
+/// `ScanOptions::max_bytes`, in which case the reason is
+/// recorded in the progress and the summary. With `ScanOptions::skip_network`, network
+/// file systems mounted below `directory` are not scanned. With `ScanOptions::scan_images`,
+/// the files in ISO 9660 images are compared too, and with `ScanOptions::scan_archives`,
+/// the files in tar and zip archives. With `ScanOptions::breadth_first`, the
+/// tree is walked level by level instead of depth first. With `ScanOptions::largest_first`,
+/// the largest files sharing their size with another file are hashed first.
 /// 
 /// # Arguments
 ///
 /// * `directory` - The directory Path where the search for duplicates begins
-/// * `extensions` - Optional list of file extensions to filter by (e.g., ["mp4", "jpg"])
+/// * `options` - The `ScanOptions` filtering the files scanned and the groups reported,
+///   and selecting the output format
 /// * `output_file` - Optional file to write results to (if None, writes to stdout)
 ///
 /// # Returns
 ///
-/// Result
+/// The reported duplicate groups, each listing its members in the order they were found.
 ///
 /// # Example
 ///
 /// ```no_run
 /// use std::path::Path;
 /// use dupefiles::find_duplicates::find_duplicates;
+/// use dupefiles::scan_options::ScanOptions;
 /// 
 /// # fn main() -> anyhow::Result<()> {
 /// let directory = Path::new("test_data");
-/// let extensions = Some(vec!["txt".to_string()]);
+/// let options = ScanOptions {
+///     extensions: Some(vec!["txt".to_string()]),
+///     ..Default::default()
+/// };
 /// let mut output_file = None;
 /// 
-/// find_duplicates(directory, extensions.as_ref(), output_file.as_mut())?;
+/// let groups = find_duplicates(directory, &options, output_file.as_mut())?;
+/// println!("{} duplicate groups found", groups.len());
 /// # Ok(())
 /// # }
 /// ```
-pub fn find_duplicates(directory: &Path, extensions: Option<&Vec<String>>, mut output_file: Option<&mut fs::File>) -> Result<()> {
-    static mut HEADER_PRINTED_ONCE: bool = false;
-    let mut hash_map: HashMap<String, PathBuf> = HashMap::new();
-    let mut found_duplicates = false;
+pub fn find_duplicates(directory: &Path, options: &ScanOptions, output_file: Option<&mut fs::File>) -> Result<Vec<DuplicateGroup>> {
+    find_duplicates_with(directory, options, output_file, |_, _| {})
+}
 
-    let current_dir = env::current_dir()?;
-    let absolute_path = current_dir.join(directory);
-    let canonical_directory = absolute_path.canonicalize().map_err(|e| {
-        Error::new(ErrorKind::NotFound, format!("Failed to canonicalize directory path: {}", e))
-    })?;
+/// Finds duplicates like `find_duplicates`, calling `on_duplicate` as soon as each
+/// duplicate is confirmed rather than only returning the groups once the scan is done.
+///
+/// `on_duplicate` receives the group as known so far, whose last member is the file
+/// just found to duplicate the others, and the path of that file. It is called for
+/// every duplicate found, before the groups are split by `same_dir_only` and filtered
+/// by `min_group_size`, `min_wasted` and `cross_dir_only`, and before `DuplicateGroup::reflinked` is filled in.
+///
+/// # Arguments
+///
+/// * `directory` - The directory Path where the search for duplicates begins
+/// * `options` - The `ScanOptions` filtering the files scanned and the groups reported,
+///   and selecting the output format
+/// * `output_file` - Optional file to write results to (if None, writes to stdout)
+/// * `on_duplicate` - Called with the group and the new member for every duplicate found
+///
+/// # Returns
+///
+/// The reported duplicate groups, each listing its members in the order they were found.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// use dupefiles::find_duplicates::find_duplicates_with;
+/// use dupefiles::scan_options::ScanOptions;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let mut output_file = None;
+/// find_duplicates_with(Path::new("test_data"), &ScanOptions::default(), output_file.as_mut(), |group, duplicate| {
+///     println!("{} duplicates {}", duplicate.display(), group.files[0].display());
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn find_duplicates_with<F>(directory: &Path, options: &ScanOptions, output_file: Option<&mut fs::File>, on_duplicate: F) -> Result<Vec<DuplicateGroup>>
+where
+    F: FnMut(&DuplicateGroup, &Path),
+{
+    let started = Instant::now();
+    let canonical_directory = scan_root(directory)?;
 
-    if !canonical_directory.exists() {
-        return Err(Error::new(ErrorKind::NotFound, "Directory does not exist").into());
+    // Collect the files to hash first, so progress can be reported against the totals
+    let shared_progress = options.shared_progress.clone().unwrap_or_default();
+    let enumeration = enumerate_candidates(&canonical_directory, options, started, &shared_progress, false)?;
+    lock_progress(&shared_progress).timings.walk = started.elapsed();
+    let (mut groups, hashed, mut stopped, _) = hash_candidates(&enumeration, options, started, &shared_progress, on_duplicate);
+
+    if enumeration.file_limit_reached {
+        stopped = stopped.or(Some(StopReason::MaxFiles));
+    }
+    lock_progress(&shared_progress).stopped = stopped;
+    if options.progress == Some(ProgressFormat::Json) {
+        eprintln!("{}", lock_progress(&shared_progress).to_json());
     }
+    if options.extracted_copies {
+        keep_extracted_copies(&mut groups, &enumeration.image_members);
+    }
+
+    // Copies that already share their data with the first member waste no space
+    for group in &mut groups {
+        let (first, duplicates) = group.files.split_first().expect("groups have at least two members");
+        group.reflinked = duplicates.iter()
+            .filter(|duplicate| shares_extents(first, duplicate).unwrap_or(false))
+            .cloned()
+            .collect();
+    }
+
+    let output_started = Instant::now();
+    let progress = lock_progress(&shared_progress).clone();
+    report_groups(&mut groups, Some(&hashed), &canonical_directory, &progress, options, output_file)?;
+    let mut progress = lock_progress(&shared_progress);
+    progress.timings.output = output_started.elapsed();
+    progress.timings.total = started.elapsed();
+    drop(progress);
+    Ok(groups)
+}
 
-    // Write CSV header if needed
-    unsafe {
-        if !HEADER_PRINTED_ONCE {
-            write_line(&mut output_file, "DUPE1.NAME,DUPE1.SIZE,DUPE1.HRSIZE,DUPE2.NAME,DUPE2.SIZE,DUPE2.HRSIZE")?;
-            HEADER_PRINTED_ONCE = true;
+/// Finds the files below `directory` that have no duplicate anywhere in the tree, such
+/// as the files a drive about to be wiped holds the only copy of, and writes them to the
+/// specified output as CSV or JSON with `write_unique`.
+///
+/// Files are scanned with the filters of `options` and are unique when no other file
+/// scanned has their content: files of a size no other file has, and those ruled out by
+/// `ScanOptions::prefilter` or `ScanOptions::quick_check`, are unique without being
+/// hashed. Files that cannot be read are never listed. When the scan stops early only
+/// the files proven unique are listed: none if the walk did not finish, and only those
+/// ruled out before hashing if the hashing did not. Group filters such as
+/// `min_group_size` and `same_dir_only` do not apply.
+///
+/// # Arguments
+///
+/// * `directory` - The directory Path where the search begins
+/// * `options` - The `ScanOptions` filtering the files scanned and selecting the output format
+/// * `output_file` - Optional file to write results to (if None, writes to stdout)
+///
+/// # Returns
+///
+/// The unique files sorted by path, with their hash when they were hashed.
+///
+/// # Example
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::find_duplicates::find_unique_files;
+/// use dupefiles::scan_options::ScanOptions;
+///
+/// // Hidden directories, such as the default `.tmp` ones, are not scanned
+/// let dir = tempfile::Builder::new().prefix("drive").tempdir().unwrap();
+/// fs::write(dir.path().join("a.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("b.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("c.txt"), "only copy").unwrap();
+///
+/// let mut output = tempfile::tempfile().unwrap();
+/// let options = ScanOptions { min_size: 0, ..Default::default() };
+/// let unique = find_unique_files(dir.path(), &options, Some(&mut output)).unwrap();
+/// assert_eq!(unique.len(), 1);
+/// assert!(unique[0].path.ends_with("c.txt"));
+/// ```
+pub fn find_unique_files(directory: &Path, options: &ScanOptions, output_file: Option<&mut fs::File>) -> Result<Vec<FileEntry>> {
+    let started = Instant::now();
+    let canonical_directory = scan_root(directory)?;
+
+    let shared_progress = options.shared_progress.clone().unwrap_or_default();
+    let enumeration = enumerate_candidates(&canonical_directory, options, started, &shared_progress, true)?;
+    lock_progress(&shared_progress).timings.walk = started.elapsed();
+    let (_, _, stopped, hashed_unique) = hash_candidates(&enumeration, options, started, &shared_progress, |_, _| {});
+    let walk_finished = enumeration.stopped.is_none() && !enumeration.file_limit_reached;
+
+    // A file is only unique once every file that could match it was compared with it
+    let mut unique = Vec::new();
+    if walk_finished {
+        unique.extend(enumeration.unique);
+        if stopped.is_none() {
+            unique.extend(hashed_unique);
         }
     }
+    unique.sort_by(|a, b| a.path.cmp(&b.path));
+    let stopped = stopped.or(enumeration.file_limit_reached.then_some(StopReason::MaxFiles));
+    lock_progress(&shared_progress).stopped = stopped;
+    if options.progress == Some(ProgressFormat::Json) {
+        eprintln!("{}", lock_progress(&shared_progress).to_json());
+    }
 
-    for entry in WalkDir::new(&canonical_directory)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| !is_hidden(e.path()))
-    {
-        let path = entry.path();
+    let output_started = Instant::now();
+    let mut output_file = output_file;
+    let written: Vec<FileEntry> = unique.iter().map(|file| reported_file(file, options)).collect();
+    write_unique(&mut output_file, &written, stopped, options.format)?;
+    let mut progress = lock_progress(&shared_progress);
+    progress.timings.output = output_started.elapsed();
+    progress.timings.total = started.elapsed();
+    drop(progress);
+    Ok(unique)
+}
 
-        // Skip symlinks that point to non-existent targets
-        if path.is_symlink() {
-            if let Ok(link_target) = fs::read_link(path) {
-                if !link_target.exists() {
-                    eprintln!("Skipping broken symlink: {} -> {}", path.display(), link_target.display());
-                    continue;
+/// Keep only the groups holding both files on disk and files stored in an archive or a
+/// disc image, with the stored copies first so actions keeping the first copy remove
+/// the files on disk
+fn keep_extracted_copies(groups: &mut Vec<DuplicateGroup>, image_members: &HashMap<PathBuf, (PathBuf, ArchiveMember)>) {
+    groups.retain_mut(|group| {
+        let stored = |path: &PathBuf| image_members.contains_key(path);
+        let stored_count = group.files.iter().filter(|path| stored(path)).count();
+        if stored_count == 0 || stored_count == group.files.len() {
+            return false;
+        }
+        // Stable, so both kinds of copies keep the order they were found in
+        group.files.sort_by_key(|path| !stored(path));
+        group.entries.sort_by_key(|entry| !stored(&entry.path));
+        true
+    });
+}
+
+/// The files to hash, found by the enumeration phase of a scan
+struct Enumeration {
+    /// The files to hash, in the order they are to be hashed
+    candidates: Vec<FileEntry>,
+    /// The image or archive and location of every candidate stored in one
+    image_members: HashMap<PathBuf, (PathBuf, ArchiveMember)>,
+    /// Why the enumeration stopped early, if `stop_reason` stopped it
+    stopped: Option<StopReason>,
+    /// Whether files were left out to stay within `ScanOptions::max_files`
+    file_limit_reached: bool,
+    /// The files ruled out for having no duplicate without being hashed, when asked for
+    unique: Vec<FileEntry>,
+    /// Whether the files hashed without a duplicate are to be returned too
+    collect_unique: bool,
+}
+
+/// Enumeration phase of a scan: walk the tree below `root` and keep the files that may
+/// have a duplicate, ordered by the scheduling options. Without a content mode, files of
+/// a size no other file has are dropped, then those ruled out by the prefilter or the
+/// quick check, unless every file is listed in a hashdeep manifest; with `collect_unique`
+/// they are kept as unique. Past half the memory limit, the files found wait in a spill
+/// file until their sizes are all known
+fn enumerate_candidates(
+    root: &Path,
+    options: &ScanOptions,
+    started: Instant,
+    shared_progress: &Mutex<ScanProgress>,
+    collect_unique: bool,
+) -> Result<Enumeration> {
+    let mut stopped = None;
+    let mut file_limit_reached = false;
+    let mut candidates: Vec<FileEntry> = Vec::new();
+    let mut unique: Vec<FileEntry> = Vec::new();
+    let mut image_members: HashMap<PathBuf, (PathBuf, ArchiveMember)> = HashMap::new();
+    let mode = hash_mode(options);
+    // Files matching a file of a manifest have no duplicate on disk to be found with
+    let known_hashes = options.known_hashes.as_deref();
+    // Manifests list the files without a duplicate too
+    let every_file = !mode.is_empty() || options.format == OutputFormat::Hashdeep;
+
+    // Files differing in size or in their first bytes are only duplicates when compared by content
+    let prefilter = options.prefilter.filter(|_| !every_file && known_hashes.is_none()).map(|memory| {
+        let mut prefilter = Prefilter::new(memory);
+        stopped = walk_files(root, options, started, |candidate| {
+            if candidate.image.is_none() {
+                // Files that cannot be read are kept, and reported when hashed
+                let _ = prefilter.insert(&candidate.entry.path, candidate.entry.size);
+            }
+            ControlFlow::Continue(())
+        });
+        prefilter
+    });
+    // Files of different sizes only have the same hash when compared by content
+    let mut size_counts: HashMap<u64, usize> = HashMap::new();
+    let (mut found_files, mut found_bytes) = (0, 0);
+    // Every file is hashed in the content modes, so spilling would save nothing
+    let spill_above = options.memory_limit.filter(|_| !every_file).map(|limit| limit / 2);
+    let mut spill: Option<SpillFile> = None;
+    let mut spill_error = None;
+    let mut walk_memory = 0;
+    let walk_stopped = walk_files(root, options, started, |candidate| {
+        if let Some(prefilter) = &prefilter {
+            if candidate.image.is_none() && !prefilter.may_have_duplicate(&candidate.entry.path, candidate.entry.size).unwrap_or(true) {
+                if collect_unique {
+                    unique.push(candidate.entry);
                 }
-            } else {
-                eprintln!("Failed to read symlink: {}", path.display());
-                continue;
+                return ControlFlow::Continue(());
             }
         }
+        // Hash the files found so far, but note that the rest of the tree was not scanned
+        if options.max_files.is_some_and(|max_files| found_files >= max_files) {
+            file_limit_reached = true;
+            return ControlFlow::Break(());
+        }
+        lock_progress(shared_progress).found_file(&candidate.entry.path, candidate.entry.size);
+        if let Some(image) = candidate.image {
+            image_members.insert(candidate.entry.path.clone(), image);
+        }
+        found_files += 1;
+        found_bytes += candidate.entry.size;
+        *size_counts.entry(candidate.entry.size).or_insert(0) += 1;
+        let index_memory = walk_memory + map_memory::<u64, usize>(size_counts.len());
+        if spill.is_none() && spill_above.is_some_and(|spill_above| index_memory >= spill_above) {
+            // The files found so far go first, so the spill file keeps the walk order
+            let spilled = SpillFile::new().and_then(|mut file| {
+                candidates.drain(..).try_for_each(|candidate| file.push(&candidate))?;
+                Ok(file)
+            });
+            match spilled {
+                Ok(file) => {
+                    let mut progress = lock_progress(shared_progress);
+                    progress.note_index_memory(index_memory);
+                    progress.spilled = true;
+                    candidates.shrink_to_fit();
+                    spill = Some(file);
+                }
+                Err(e) => {
+                    spill_error = Some(e);
+                    return ControlFlow::Break(());
+                }
+            }
+        }
+        match &mut spill {
+            Some(file) => {
+                if let Err(e) = file.push(&candidate.entry) {
+                    spill_error = Some(e);
+                    return ControlFlow::Break(());
+                }
+            }
+            None => {
+                walk_memory += entry_memory(&candidate.entry);
+                candidates.push(candidate.entry);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    if let Some(e) = spill_error {
+        anyhow::bail!("{}", message("scan-spill-write-failed", &[("error", &e)]));
+    }
+    stopped = stopped.or(walk_stopped);
+    let may_have_duplicate = |candidate: &FileEntry| {
+        size_counts[&candidate.size] > 1 || known_hashes.is_some_and(|known| known.may_match(candidate.size))
+    };
+    if let Some(file) = spill {
+        file.read_back(|candidate| {
+            if may_have_duplicate(&candidate) {
+                candidates.push(candidate);
+            } else if collect_unique {
+                unique.push(candidate);
+            }
+        }).map_err(|e| anyhow::anyhow!("{}", message("scan-spill-read-failed", &[("error", &e)])))?;
+    }
+    let mut progress = lock_progress(shared_progress);
+    progress.timings.walk_files = found_files;
+    progress.timings.walk_bytes = found_bytes;
+    progress.note_index_memory(walk_memory + map_memory::<u64, usize>(size_counts.len()));
+    drop(progress);
 
-        // Get file metadata
-        let metadata = match fs::metadata(path) {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!("Error accessing metadata for {}: {}", path.display(), e);
-                continue;
+    if !every_file {
+        let (kept, dropped) = candidates.into_iter().partition(may_have_duplicate);
+        candidates = kept;
+        if collect_unique {
+            unique.extend::<Vec<_>>(dropped);
+        }
+    }
+    if options.physical_order {
+        // Files stored in a disc image are read from the image, and files that cannot be
+        // located are hashed last
+        candidates.sort_by_cached_key(|candidate| {
+            let file = image_members.get(&candidate.path).map_or(&candidate.path, |(image, _)| image);
+            physical_position(file).unwrap_or((u64::MAX, u64::MAX))
+        });
+    }
+    if options.largest_first {
+        // Files of a unique size can only match in a content mode, so they come last; the
+        // stable sort keeps files of the same size in walk order
+        candidates.sort_by_key(|candidate| Reverse((size_counts[&candidate.size] > 1, candidate.size)));
+    }
+    if let Some(quick_check) = options.quick_check.filter(|_| !every_file && known_hashes.is_none() && stopped.is_none()) {
+        let mut ruled_out = Vec::new();
+        stopped = quick_check_candidates(&mut candidates, &mut ruled_out, &image_members, quick_check, options, started);
+        if collect_unique {
+            unique.append(&mut ruled_out);
+        }
+    }
+
+    Ok(Enumeration { candidates, image_members, stopped, file_limit_reached, unique, collect_unique })
+}
+
+/// What hashing a candidate gave
+enum Hashed {
+    /// The hash of the file, and the other digests computed while reading it, if any
+    Hash(HashKey, Option<BTreeMap<HashAlgorithm, String>>),
+    /// The file was skipped or could not be hashed, which was reported
+    Skipped,
+    /// The scan stopped before the file was hashed
+    Stopped(StopReason),
+}
+
+/// Hashing phase of a scan: hash the candidates of `enumeration` in order and group the
+/// files with the same content, calling `on_duplicate` for every duplicate confirmed.
+/// Returns the groups, every file hashed when writing a hashdeep manifest, why the scan
+/// stopped early, if it did, and the files hashed without a duplicate when the
+/// enumeration collects unique files
+fn hash_candidates<F>(
+    enumeration: &Enumeration,
+    options: &ScanOptions,
+    started: Instant,
+    shared_progress: &Mutex<ScanProgress>,
+    mut on_duplicate: F,
+) -> (Vec<DuplicateGroup>, Vec<FileEntry>, Option<StopReason>, Vec<FileEntry>)
+where
+    F: FnMut(&DuplicateGroup, &Path),
+{
+    let Enumeration { candidates, image_members, .. } = enumeration;
+    let mut manifest: Vec<FileEntry> = Vec::new();
+    let mut stopped = enumeration.stopped;
+    // Keyed by binary hashes, which take a fraction of the memory of their text form
+    let mut hash_map: HashMap<HashKey, FileEntry> = HashMap::new();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut group_index: HashMap<HashKey, usize> = HashMap::new();
+    // The digests asked for in `ScanOptions::also_hash` of each content hashed
+    let mut digests: HashMap<HashKey, BTreeMap<HashAlgorithm, String>> = HashMap::new();
+    let mode = hash_mode(options);
+    let comparator: &dyn DuplicateComparator = options.comparator.as_deref().unwrap_or(&DefaultComparator);
+    // Files of manifests come first, so they lead the group of any file matching them.
+    // Their entries are unknown, so the comparator trusts their hash
+    let mut known: HashSet<HashKey> = HashSet::new();
+    for file in options.known_hashes.iter().flat_map(|known| known.files()) {
+        if let Some(hash) = HashKey::parse(&file.sha256) {
+            hash_map.entry(hash).or_insert_with(|| FileEntry::unknown(&file.path, 0, ""));
+            known.insert(hash);
+        }
+    }
+
+    lock_progress(shared_progress).start_hashing(candidates.len(), candidates.iter().map(|candidate| candidate.size).sum());
+    let hashing_started = Instant::now();
+    let mut timings = TimingReport::default();
+    // Counted as files are read, which may be ahead of the files consumed with several jobs
+    let bytes_read = AtomicU64::new(0);
+    // Files hashed at once never need more descriptors than the process may open
+    let file_limit = open_file_limit().unwrap_or(u64::MAX);
+    let open_files = OpenFileBudget::new(file_limit);
+    let hash_candidate = |candidate: &FileEntry| {
+        if let Some(reason) = enumeration.stopped.or_else(|| stop_reason(options, started)) {
+            return Hashed::Stopped(reason);
+        }
+        if options.max_bytes.is_some_and(|max_bytes| bytes_read.load(Ordering::Relaxed) >= max_bytes) {
+            return Hashed::Stopped(StopReason::MaxBytes);
+        }
+        let (path, size) = (candidate.path.as_path(), candidate.size);
+        let _slot = open_files.acquire();
+
+        // Files being written would be hashed as a moving target
+        let before = if options.skip_busy && !image_members.contains_key(path) {
+            if is_locked(path).unwrap_or(false) {
+                eprintln!("{}", message("scan-busy-locked", &[("file", &path.display())]));
+                lock_progress(shared_progress).count_error(ScanError::Busy);
+                lock_progress(shared_progress).finish_file(size);
+                return Hashed::Skipped;
             }
+            fs::metadata(path).ok()
+        } else {
+            None
         };
 
-        // Skip if not a file or zero size
-        if !metadata.is_file() || metadata.len() == 0 {
-            continue;
+        // Compute file hash
+        lock_progress(shared_progress).start_file(path);
+        let mut file_digests = None;
+        let mut read = 0;
+        let hashed = retry(options.retries, options.retry_delay, || match image_members.get(path) {
+            Some((image, member)) => hash_member(image, member).inspect(|_| read += size),
+            // The other digests are computed while reading the file, so its cached hash is not enough
+            None if !options.also_hash.is_empty() => compute_sha256_with_digests(path, &options.also_hash)
+                .map(|(hash, computed)| {
+                    read += size;
+                    file_digests = Some(computed);
+                    (hash, false)
+                }),
+            None => cached_hash_file(candidate, options, &mode, &mut read),
+        });
+        bytes_read.fetch_add(read, Ordering::Relaxed);
+        if options.drop_cache && !image_members.contains_key(path) {
+            let _ = drop_cached_pages(path);
         }
-
-        // Check file extension if filters are specified
-        if let Some(exts) = extensions {
-            if let Some(ext) = path.extension() {
-                let ext_str = ext.to_string_lossy().to_lowercase();
-                if !exts.iter().any(|e| e == &ext_str) {
-                    continue;
+        let mut progress = lock_progress(shared_progress);
+        progress.finish_file(size);
+        if options.progress == Some(ProgressFormat::Json) && progress.report_due() {
+            eprintln!("{}", progress.to_json());
+        }
+        drop(progress);
+        let hash = match hashed {
+            // Partial hashes keep the prefix of their content mode, which the comparator checks
+            Ok((hash, _)) => match HashKey::parse(&hash) {
+                Some(hash) => hash,
+                None => {
+                    eprintln!("{}", message("scan-invalid-hash", &[("file", &path.display()), ("hash", &hash)]));
+                    lock_progress(shared_progress).count_error(ScanError::Hash);
+                    return Hashed::Skipped;
                 }
-            } else {
-                continue; // Skip files without extensions when filtering
+            },
+            // Descriptors held by the caller or other processes count against the limit too
+            Err(e) if is_too_many_open_files(&e) => {
+                eprintln!("{}", message("scan-hash-too-many-files", &[("file", &path.display()), ("limit", &file_limit)]));
+                lock_progress(shared_progress).count_error(ScanError::Hash);
+                return Hashed::Skipped;
+            }
+            Err(e) => {
+                eprintln!("{}", message("scan-hash-failed", &[("file", &path.display()), ("error", &e)]));
+                lock_progress(shared_progress).count_error(ScanError::Hash);
+                return Hashed::Skipped;
             }
+        };
+        if before.is_some_and(|before| changed_since(path, &before)) {
+            eprintln!("{}", message("scan-busy-modified", &[("file", &path.display())]));
+            lock_progress(shared_progress).count_error(ScanError::Busy);
+            return Hashed::Skipped;
         }
+        Hashed::Hash(hash, file_digests)
+    };
 
-        // Compute file hash
-        let hash = match compute_sha256(path) {
-            Ok(h) => h,
-            Err(e) => {
-                eprintln!("Failed to compute hash for {}: {}", path.display(), e);
-                continue;
+    // Files are hashed on the threads of their device, and grouped here in order
+    run_by_device(candidates, options.jobs, |candidate| candidate.dev, hash_candidate, |candidate, hashed| {
+        let (hash, file_digests) = match hashed {
+            Hashed::Hash(hash, file_digests) => (hash, file_digests),
+            Hashed::Skipped => return ControlFlow::Continue(()),
+            Hashed::Stopped(reason) => {
+                stopped = stopped.or(Some(reason));
+                return ControlFlow::Break(());
             }
         };
+        let (path, size) = (candidate.path.as_path(), &candidate.size);
+        if let Some(file_digests) = file_digests {
+            digests.entry(hash).or_insert(file_digests);
+        }
+        if options.format == OutputFormat::Hashdeep {
+            manifest.push(FileEntry::unknown(path, *size, &hash.to_string()));
+        }
 
         // Check for duplicates
-        if let Some(existing_path) = hash_map.get(&hash) {
-            match is_duplicate_file(existing_path, path) {
+        if let Some(first) = hash_map.get(&hash) {
+            let existing_path = &first.path;
+            let hash_text = hash.to_string();
+            // Files in disc images and manifests are described by their size and hash
+            // only, and the files of manifests list no size
+            let existing = match first.inode {
+                Some(_) => FileEntry { hash: hash_text.clone(), ..first.clone() },
+                None => FileEntry::unknown(existing_path, *size, &hash_text),
+            };
+            let entry = FileEntry { hash: hash_text.clone(), ..candidate.clone() };
+            // The comparator only sees the first member, but the file may be another
+            // name of any of them, such as a different case on a case-insensitive volume
+            let group = group_index.get(&hash).map(|&index| &groups[index]);
+            if group.is_some_and(|group| group.entries.iter().any(|member| member.is_same_file(&entry))) {
+                return ControlFlow::Continue(());
+            }
+            let verify_started = Instant::now();
+            let is_duplicate = comparator.is_duplicate(&existing, &entry);
+            timings.verify += verify_started.elapsed();
+            timings.verify_files += 1;
+            timings.verify_bytes += 2 * size;
+            // Checking the bytes read both files again
+            if options.drop_cache && !image_members.contains_key(path) && !image_members.contains_key(existing_path) {
+                let _ = drop_cached_pages(existing_path);
+                let _ = drop_cached_pages(path);
+            }
+            match is_duplicate {
                 Ok(is_duplicate) => {
                     if !is_duplicate {
-                        continue;
+                        return ControlFlow::Continue(());
                     }
                 },
                 Err(e) => {
-                    eprintln!("Error checking for duplicate file: {}", e);
-                    continue;
+                    eprintln!("{}", message("scan-compare-failed", &[("error", &e)]));
+                    lock_progress(shared_progress).count_error(ScanError::Compare);
+                    return ControlFlow::Continue(());
                 }
             }
 
-            let existing_size = match fs::metadata(existing_path) {
-                Ok(m) => m.len(),
-                Err(e) => {
-                    eprintln!("Error accessing metadata for {}: {}", existing_path.display(), e);
-                    continue;
+            let index = match group_index.get(&hash) {
+                Some(&index) => {
+                    groups[index].files.push(path.to_path_buf());
+                    groups[index].entries.push(entry);
+                    index
+                }
+                None => {
+                    group_index.insert(hash, groups.len());
+                    groups.push(DuplicateGroup {
+                        files: vec![existing_path.clone(), path.to_path_buf()],
+                        entries: vec![existing, entry],
+                        hash: hash_text,
+                        size: *size,
+                        reflinked: Vec::new(),
+                        digests: BTreeMap::new(),
+                    });
+                    groups.len() - 1
                 }
             };
-
-            let current_size = metadata.len();
-            found_duplicates = true;
-            
-            // Write duplicate file information
-            let output = format!("\"{}\",{},\"{}\",\"{}\",{},\"{}\"",
-                existing_path.display(), existing_size, human_readable_size(existing_size),
-                path.display(), current_size, human_readable_size(current_size));
-            write_line(&mut output_file, &output)?;
+            // Files in disc images have no other digests, so take them from any other member
+            if groups[index].digests.is_empty() {
+                if let Some(group_digests) = digests.get(&hash) {
+                    groups[index].digests = group_digests.clone();
+                }
+            }
+            on_duplicate(&groups[index], path);
         } else {
-            hash_map.insert(hash, path.to_path_buf());
+            hash_map.insert(hash, candidate.clone());
         }
-    }
+        ControlFlow::Continue(())
+    });
+
+    // The candidates stay listed while the indexes only grow, so the peak is at the end;
+    // entries are counted with their paths, inline in the map
+    let index_memory = candidates.iter().map(entry_memory).sum::<u64>()
+        + hash_map.values().map(entry_memory).sum::<u64>()
+        + map_memory::<HashKey, ()>(hash_map.len())
+        + map_memory::<HashKey, usize>(group_index.len())
+        + map_memory::<HashKey, BTreeMap<HashAlgorithm, String>>(digests.len());
+    let mut progress = lock_progress(shared_progress);
+    progress.note_index_memory(index_memory);
+    progress.timings = TimingReport {
+        hash: hashing_started.elapsed().saturating_sub(timings.verify),
+        hash_files: progress.files_done,
+        hash_bytes: bytes_read.into_inner(),
+        verify: timings.verify,
+        verify_files: timings.verify_files,
+        verify_bytes: timings.verify_bytes,
+        ..progress.timings
+    };
+    drop(progress);
+    // Files of manifests are not scanned, and only count as copies of the files matching them
+    let unique = if enumeration.collect_unique {
+        hash_map.into_iter()
+            .filter(|(hash, _)| !group_index.contains_key(hash) && !known.contains(hash))
+            .map(|(hash, entry)| FileEntry { hash: hash.to_string(), ..entry })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    (groups, manifest, stopped, unique)
+}
 
-    if !found_duplicates {
-        write_line(&mut output_file, "No duplicate files found.")?;
+/// `file` with its path normalized or redacted as `options` ask for the report
+fn reported_file(file: &FileEntry, options: &ScanOptions) -> FileEntry {
+    let mut file = file.clone();
+    if options.normalize_paths {
+        file.path = nfc_path(&file.path).into_owned();
     }
+    if let Some(salt) = &options.redact_paths {
+        file.path = redact_file(&file.path, salt);
+    }
+    file
+}
 
+/// Drop the groups filtered out by `options` and write the others in the selected format,
+/// with the summary of a scan of `root` whose `progress` tells why it stopped early, if
+/// it did, and the memory its indexes took. Hashdeep manifests list the `hashed` files,
+/// or the members of the groups without them
+pub(crate) fn report_groups(
+    groups: &mut Vec<DuplicateGroup>,
+    hashed: Option<&[FileEntry]>,
+    root: &Path,
+    progress: &ScanProgress,
+    options: &ScanOptions,
+    mut output_file: Option<&mut fs::File>,
+) -> Result<()> {
+    if options.same_dir_only {
+        *groups = groups.iter().flat_map(DuplicateGroup::split_by_directory).collect();
+    }
+    groups.retain(|group| {
+        group.files.len() >= options.min_group_size
+            && group.wasted_bytes() >= options.min_wasted
+            && (!options.cross_dir_only || group.spans_directories())
+    });
+    // The groups returned keep their paths, for the caller to act upon
+    let normalized;
+    let (groups, root) = if options.normalize_paths {
+        normalized = (normalize_groups(groups), nfc_path(root).into_owned());
+        (&normalized.0, normalized.1.as_path())
+    } else {
+        (&*groups, root)
+    };
+    let redacted;
+    let (groups, root) = match &options.redact_paths {
+        Some(salt) => {
+            redacted = (redact_groups(groups, salt), redact_directory(root, salt));
+            (&redacted.0, redacted.1.as_path())
+        }
+        None => (groups, root),
+    };
+    if options.stats_only {
+        let mut summary = summarize(groups, root);
+        summary.stopped = progress.stopped;
+        summary.min_size = options.min_size;
+        return write_stats(&mut output_file, &summary, options.format);
+    }
+    match options.format {
+        OutputFormat::Csv => write_csv(&mut output_file, groups, options.pairs, &options.also_hash)?,
+        OutputFormat::Json => {
+            let mut summary = summarize(groups, root);
+            summary.stopped = progress.stopped;
+            summary.min_size = options.min_size;
+            summary.index_memory = progress.index_memory;
+            summary.spilled = progress.spilled;
+            write_json(&mut output_file, groups, &summary, &options.also_hash)?
+        }
+        OutputFormat::Ncdu => write_ncdu(&mut output_file, groups, root)?,
+        OutputFormat::Hashdeep => {
+            let files = match hashed {
+                Some(hashed) => hashed.iter().map(|file| reported_file(file, options)).collect(),
+                None => group_entries(groups),
+            };
+            write_hashdeep(&mut output_file, &files, root)?
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use crate::duplicate_group::SuggestedAction;
+    use crate::hash_cache::HashCache;
+    use crate::known_hashes::KnownHashes;
+    use crate::walk_filter::WalkFilter;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_min_group_size() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_min_group").tempdir().unwrap();
+        for name in ["a1.txt", "a2.txt", "a3.txt"] {
+            fs::write(tmp_dir.path().join(name), "three copies").unwrap();
+        }
+        for name in ["b1.txt", "b2.txt"] {
+            fs::write(tmp_dir.path().join(name), "two copies").unwrap();
+        }
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 2);
+
+        let options = ScanOptions { min_group_size: 3, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+        assert_eq!(groups[0].size, 12);
+    }
+
+    #[test]
+    fn test_min_wasted() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_min_wasted").tempdir().unwrap();
+        for name in ["small1.txt", "small2.txt"] {
+            fs::write(tmp_dir.path().join(name), "tiny").unwrap();
+        }
+        for name in ["large1.txt", "large2.txt"] {
+            fs::write(tmp_dir.path().join(name), vec![b'x'; 4096]).unwrap();
+        }
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { min_wasted: 4096, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, 4096);
+    }
+
+    #[test]
+    fn test_cross_dir_only() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_cross_dir").tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("backup")).unwrap();
+        for name in ["export-v1.csv", "export-v2.csv"] {
+            fs::write(tmp_dir.path().join(name), "versioned export").unwrap();
+        }
+        fs::write(tmp_dir.path().join("photo.jpg"), "photo taken once").unwrap();
+        fs::write(tmp_dir.path().join("backup/photo.jpg"), "photo taken once").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { min_size: 0, ..Default::default() };
+        assert_eq!(find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap().len(), 2);
+
+        let options = ScanOptions { min_size: 0, cross_dir_only: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].files.iter().all(|file| file.ends_with("photo.jpg")));
+    }
+
+    #[test]
+    fn test_same_dir_only() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_same_dir").tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("backup")).unwrap();
+        for name in ["file.jpg", "file (1).jpg", "backup/file.jpg"] {
+            fs::write(tmp_dir.path().join(name), "downloaded twice").unwrap();
+        }
+        fs::write(tmp_dir.path().join("photo.jpg"), "photo taken once").unwrap();
+        fs::write(tmp_dir.path().join("backup/photo.jpg"), "photo taken once").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { min_size: 0, same_dir_only: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].entries.len(), 2);
+        assert!(groups[0].files.iter().all(|file| file.parent() == Some(tmp_dir.path().canonicalize().unwrap().as_path())));
+    }
+
+    #[test]
+    fn test_min_size() {
+        use std::io::{Read, Seek};
+        let tmp_dir = Builder::new().prefix("find_duplicates_min_size").tempdir().unwrap();
+        for name in ["small1.txt", "small2.txt"] {
+            fs::write(tmp_dir.path().join(name), "tiny").unwrap();
+        }
+        for name in ["large1.txt", "large2.txt"] {
+            fs::write(tmp_dir.path().join(name), vec![b'x'; 1024]).unwrap();
+        }
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { min_size: 1024, format: OutputFormat::Json, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, 1024);
+        let mut json = String::new();
+        output.seek(std::io::SeekFrom::Start(0)).unwrap();
+        output.read_to_string(&mut json).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["summary"]["min_size"], 1024);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_same_file_reported_once() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_same_file").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "copy").unwrap();
+        // Whichever member is found first, the other names of `a.txt` are left out
+        fs::hard_link(tmp_dir.path().join("a.txt"), tmp_dir.path().join("A.TXT")).unwrap();
+        fs::hard_link(tmp_dir.path().join("a.txt"), tmp_dir.path().join("c.txt")).unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert!(!groups[0].entries[0].is_same_file(&groups[0].entries[1]));
+    }
+
+    #[test]
+    fn test_decompress_gzip() {
+        use std::io::Write;
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_gzip").tempdir().unwrap();
+        let content = b"log line\n".repeat(500);
+        fs::write(tmp_dir.path().join("app.log.1"), &content).unwrap();
+        for (name, level) in [("app.log.1.gz", 9), ("copy.log.gz", 1)] {
+            let mut encoder = GzEncoder::new(fs::File::create(tmp_dir.path().join(name)).unwrap(), Compression::new(level));
+            encoder.write_all(&content).unwrap();
+            encoder.finish().unwrap();
+        }
+        let mut output = tempfile::tempfile().unwrap();
+
+        assert!(find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap().is_empty());
+        let options = ScanOptions { decompress: vec![CompressionFormat::Gzip], ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+        assert!(groups[0].hash.starts_with("gz:"));
+        // The members sit side by side on one device, but hold different bytes
+        assert_eq!(groups[0].suggested_action(), SuggestedAction::Delete);
+    }
+
+    #[test]
+    fn test_email_content() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_email").tempdir().unwrap();
+        let maildir = tmp_dir.path().join("Mail");
+        for folder in ["cur", "new", "tmp"] {
+            fs::create_dir_all(maildir.join(folder)).unwrap();
+        }
+        fs::write(maildir.join("cur/1700000000.M1P2.host:2,S"), "Delivered-To: a@example.com\nMessage-ID: <1@example.com>\n\nHello\n").unwrap();
+        fs::write(maildir.join("new/1700000001.M3P4.host"), "Delivered-To: b@example.com\nMessage-ID: <1@example.com>\n\nHello\n").unwrap();
+        fs::write(tmp_dir.path().join("archive.mbox"), "From a@example.com Mon Jan  1 00:00:00 2024\n\
+            Message-ID: <1@example.com>\nStatus: RO\n\nHello\n\n\
+            From b@example.com Mon Jan  1 00:00:00 2024\nMessage-ID: <2@example.com>\n\nHello\n").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        assert!(find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap().is_empty());
+        let options = ScanOptions { email_content: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].hash.starts_with("email:"));
+        assert_eq!(groups[0].files.len(), 3);
+        assert!(groups[0].files.contains(&tmp_dir.path().canonicalize().unwrap().join("archive.mbox/1")));
+    }
+
+    #[test]
+    fn test_known_hashes() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_known").tempdir().unwrap();
+        let (laptop, manifest) = (tmp_dir.path().join("laptop"), tmp_dir.path().join("archive.sha256"));
+        fs::create_dir(&laptop).unwrap();
+        fs::write(laptop.join("a.txt"), "archived").unwrap();
+        fs::write(laptop.join("b.txt"), "not archived").unwrap();
+        fs::write(&manifest, format!("{}  photos/a.txt\n", compute_sha256(&laptop.join("a.txt")).unwrap())).unwrap();
+        let mut known_hashes = KnownHashes::default();
+        known_hashes.add_manifest(&manifest).unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { known_hashes: Some(Arc::new(known_hashes)), ..Default::default() };
+        let groups = find_duplicates(&laptop, &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, vec![manifest.join("photos/a.txt"), laptop.canonicalize().unwrap().join("a.txt")]);
+    }
+
+    #[test]
+    fn test_exclude_extensions() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_exclude").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.log"), "log").unwrap();
+        fs::write(tmp_dir.path().join("b.LOG"), "log").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "text").unwrap();
+        fs::write(tmp_dir.path().join("d.txt"), "text").unwrap();
+        fs::write(tmp_dir.path().join("e"), "none").unwrap();
+        fs::write(tmp_dir.path().join("f"), "none").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { exclude_extensions: Some(vec!["log".to_string()]), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|group| group.size != 3));
+    }
+
+    #[test]
+    fn test_extensions_by_content() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_by_content").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("photo.jpg"), b"\xff\xd8\xff\xe0 jpeg").unwrap();
+        fs::write(tmp_dir.path().join("photo.dat"), b"\xff\xd8\xff\xe0 jpeg").unwrap();
+        fs::write(tmp_dir.path().join("fake.jpg"), "text").unwrap();
+        fs::write(tmp_dir.path().join("fake.dat"), "text").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions {
+            extensions: Some(vec!["jpeg".to_string()]),
+            by_content: true,
+            ..Default::default()
+        };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].size, 9);
+    }
+
+    #[test]
+    fn test_cancelled_scan_reports_nothing_new() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_cancel").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { cancel: Some(Arc::new(AtomicBool::new(true))), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn test_max_runtime_records_stop_reason() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_max_runtime").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions {
+            max_runtime: Some(Duration::ZERO),
+            shared_progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert!(groups.is_empty());
+        assert_eq!(progress.lock().unwrap().stopped, Some(StopReason::MaxRuntime));
+    }
+
+    #[test]
+    fn test_hash_cache_filled() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_cache").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "diff").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let cache = Arc::new(Mutex::new(HashCache::default()));
+        let options = ScanOptions { hash_cache: Some(cache.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(cache.lock().unwrap().len(), 3);
+
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+    }
+
+    #[test]
+    fn test_max_files() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_max_files").tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(tmp_dir.path().join(name), "copy").unwrap();
+        }
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { max_files: Some(2), shared_progress: Some(progress.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(progress.lock().unwrap().stopped, Some(StopReason::MaxFiles));
+
+        let options = ScanOptions { max_files: Some(3), shared_progress: Some(progress.clone()), ..Default::default() };
+        find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(progress.lock().unwrap().stopped, None);
+    }
+
+    #[test]
+    fn test_unique_sizes_not_hashed() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_unique_sizes").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "unique size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { shared_progress: Some(progress.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        let progress = progress.lock().unwrap();
+        assert_eq!((progress.files_total, progress.bytes_total), (2, 8));
+        assert_eq!(progress.files_done, 2);
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_max_bytes").tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(tmp_dir.path().join(name), "copy").unwrap();
+        }
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { max_bytes: Some(8), shared_progress: Some(progress.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(progress.lock().unwrap().stopped, Some(StopReason::MaxBytes));
+
+        // Cached hashes are not read again, so a second session gets further
+        let cache = Arc::new(Mutex::new(HashCache::default()));
+        let options = ScanOptions { max_bytes: Some(8), hash_cache: Some(cache), shared_progress: Some(progress.clone()), ..Default::default() };
+        find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups[0].files.len(), 3);
+        assert_eq!(progress.lock().unwrap().stopped, None);
+    }
+
+    #[test]
+    fn test_largest_first() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_largest_first").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "small").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "small").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "larger copy").unwrap();
+        fs::write(tmp_dir.path().join("d.txt"), "larger copy").unwrap();
+        fs::write(tmp_dir.path().join("e.txt"), "the largest unique file").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        // The budget covers the larger pair only
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { largest_first: true, max_bytes: Some(22), shared_progress: Some(progress.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, 11);
+        assert_eq!(progress.lock().unwrap().stopped, Some(StopReason::MaxBytes));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_entries() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_file_entries").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap();
+        let entries = &groups[0].entries;
+        assert_eq!(entries.iter().map(|entry| &entry.path).collect::<Vec<_>>(), groups[0].files.iter().collect::<Vec<_>>());
+        for (entry, file) in entries.iter().zip(&groups[0].files) {
+            let metadata = fs::metadata(file).unwrap();
+            assert_eq!((entry.size, entry.inode, entry.dev), (4, Some(metadata.ino()), Some(metadata.dev())));
+            assert_eq!(entry.mtime, metadata.modified().ok());
+            assert_eq!(entry.hash, groups[0].hash);
+        }
+    }
+
+    #[test]
+    fn test_redact_paths() {
+        use std::io::{Read, Seek};
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_redact").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("secret.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("private.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { format: OutputFormat::Json, redact_paths: Some("salt".to_string()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert!(groups[0].files.iter().any(|file| file.ends_with("secret.txt")));
+
+        let mut json = String::new();
+        output.rewind().unwrap();
+        output.read_to_string(&mut json).unwrap();
+        assert!(!json.contains("secret") && !json.contains("find_duplicates_redact"));
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["groups"][0]["size"], 4);
+        assert!(report["groups"][0]["files"][0].as_str().unwrap().ends_with(".txt"));
+        assert_eq!(report["summary"]["wasted_bytes"], 4);
+    }
+
+    #[test]
+    fn test_stats_only() {
+        use std::io::{Read, Seek};
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_stats_only").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.jpg"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.jpg"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("c"), "other").unwrap();
+        fs::write(tmp_dir.path().join("d"), "other").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { stats_only: true, ..Default::default() };
+        find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        let mut csv = String::new();
+        output.rewind().unwrap();
+        output.read_to_string(&mut csv).unwrap();
+        assert!(!csv.contains("find_duplicates_stats_only"));
+        assert!(csv.ends_with("EXTENSION,DUPLICATE_FILES,WASTED_BYTES\n\"\",1,5\n\"jpg\",1,4\n\"*\",2,9\n"));
+    }
+
+    #[test]
+    fn test_walk_filter_overrides_extensions() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_walk_filter").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.jpg"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.jpg"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("c.raw"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        // The .raw file is included despite --extensions jpg, and b.jpg is excluded
+        let options = ScanOptions {
+            extensions: Some(vec!["jpg".to_string()]),
+            walk_filter: Some(WalkFilter::new(|path, _| match path.extension().and_then(|ext| ext.to_str()) {
+                Some("raw") => FilterDecision::Include,
+                _ if path.ends_with("b.jpg") => FilterDecision::Exclude,
+                _ => FilterDecision::Default,
+            })),
+            ..Default::default()
+        };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        let mut names: Vec<_> = groups[0].files.iter().map(|file| file.file_name().unwrap().to_owned()).collect();
+        names.sort();
+        assert_eq!(names, ["a.jpg", "c.raw"]);
+    }
+
+    #[test]
+    fn test_prefilter_drops_unique_files() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_prefilter").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a1.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("a2.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "size").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "other size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { prefilter: Some(1024), shared_progress: Some(progress.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(progress.lock().unwrap().files_total, 2);
+    }
+
+    #[test]
+    fn test_unique_files() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_unique").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a1.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("a2.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "diff").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "other size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        for quick_check in [None, Some(QuickCheck::Full)] {
+            let options = ScanOptions { min_size: 0, quick_check, format: OutputFormat::Json, ..Default::default() };
+            let unique = find_unique_files(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+            let names: Vec<_> = unique.iter().map(|file| file.path.file_name().unwrap().to_owned()).collect();
+            assert_eq!(names, ["b.txt", "c.txt"]);
+            // Files of a unique size are never read
+            assert!(unique[1].hash.is_empty());
+        }
+
+        // Files left unwalked could be copies of any file found
+        let options = ScanOptions { min_size: 0, max_files: Some(3), ..Default::default() };
+        assert!(find_unique_files(tmp_dir.path(), &options, Some(&mut output)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_limit_spills_files_found() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_memory_limit").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a1.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("a2.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "other size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { shared_progress: Some(progress.clone()), ..Default::default() };
+        let expected = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert!(progress.lock().unwrap().index_memory > 0);
+        assert!(!progress.lock().unwrap().spilled);
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { memory_limit: Some(1), shared_progress: Some(progress.clone()), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups, expected);
+        assert!(progress.lock().unwrap().spilled);
+        assert_eq!(progress.lock().unwrap().timings.walk_files, 3);
+    }
+
+    #[test]
+    fn test_timings() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_timings").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a1.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("a2.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "size").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "other size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let options = ScanOptions { min_size: 0, shared_progress: Some(progress.clone()), ..Default::default() };
+        find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        let timings = progress.lock().unwrap().timings;
+        assert_eq!((timings.walk_files, timings.walk_bytes), (4, 22));
+        assert_eq!((timings.hash_files, timings.hash_bytes), (3, 12));
+        assert_eq!((timings.verify_files, timings.verify_bytes), (1, 8));
+        assert!(timings.total >= timings.walk + timings.hash + timings.verify + timings.output);
+    }
+
+    #[test]
+    fn test_quick_check_drops_unique_files() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_quick_check").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a1.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("a2.txt"), "same").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "size").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "other size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        for quick_check in [QuickCheck::Sampled(4096), QuickCheck::Full] {
+            let progress = Arc::new(Mutex::new(ScanProgress::default()));
+            let options = ScanOptions { quick_check: Some(quick_check), shared_progress: Some(progress.clone()), ..Default::default() };
+            let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+            assert_eq!(groups.len(), 1);
+            assert_eq!(groups[0].files.len(), 2);
+            assert_eq!(progress.lock().unwrap().files_total, 2);
+        }
+    }
+
+    #[test]
+    fn test_physical_order() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_physical_order").tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("sub")).unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("sub/b.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "unique").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { physical_order: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+    }
+
+    #[test]
+    fn test_also_hash() {
+        use std::io::{Read, Seek};
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_also_hash").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "Hello, world!").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "Hello, world!").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { also_hash: vec![HashAlgorithm::Md5, HashAlgorithm::Sha1], ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups[0].digests[&HashAlgorithm::Md5], "6cd3556deb0da54bca060b4c39479839");
+        assert_eq!(groups[0].digests[&HashAlgorithm::Sha1], "943a702d06f34599aee1f8da8ef9f7296031d699");
+
+        let mut csv = String::new();
+        output.rewind().unwrap();
+        output.read_to_string(&mut csv).unwrap();
+        assert!(csv.contains(",6cd3556deb0da54bca060b4c39479839,943a702d06f34599aee1f8da8ef9f7296031d699,"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_skip_busy() {
+        use std::fs::File;
+        use std::os::unix::io::AsRawFd;
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_skip_busy").tempdir().unwrap();
+        for name in ["a1.txt", "a2.txt", "a3.txt"] {
+            fs::write(tmp_dir.path().join(name), "copy").unwrap();
+        }
+        let writer = File::open(tmp_dir.path().join("a3.txt")).unwrap();
+        unsafe { libc::flock(writer.as_raw_fd(), libc::LOCK_EX) };
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { skip_busy: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(!groups[0].files.contains(&tmp_dir.path().join("a3.txt")));
+    }
+
+    #[test]
+    fn test_sample_above() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_sample_above").tempdir().unwrap();
+        let mut data = vec![0u8; 4 * SAMPLE_SIZE as usize];
+        fs::write(tmp_dir.path().join("a.img"), &data).unwrap();
+        // Between the samples, so only a full hash tells the images apart
+        data[SAMPLE_SIZE as usize + 1] = 1;
+        fs::write(tmp_dir.path().join("b.img"), &data).unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap();
+        assert!(groups.is_empty());
+
+        let options = ScanOptions { sample_above: Some(SAMPLE_SIZE), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].is_probable());
+    }
+
+    #[test]
+    fn test_all_pairs() {
+        use std::io::{Read, Seek};
+        use crate::write_report::PairMode;
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_all_pairs").tempdir().unwrap();
+        for name in ["a1.txt", "a2.txt", "a3.txt", "a4.txt"] {
+            fs::write(tmp_dir.path().join(name), "four copies").unwrap();
+        }
+
+        for (pairs, rows) in [(PairMode::Canonical, 3), (PairMode::All, 6)] {
+            let mut output = tempfile::tempfile().unwrap();
+            let options = ScanOptions { pairs, ..Default::default() };
+            let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+            let mut csv = String::new();
+            output.rewind().unwrap();
+            output.read_to_string(&mut csv).unwrap();
+            let group_column = format!(",{},", groups[0].id());
+            assert_eq!(csv.lines().filter(|line| line.contains(&group_column)).count(), rows);
+        }
+    }
+
+    #[test]
+    fn test_on_duplicate_called_for_each_duplicate() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_on_duplicate").tempdir().unwrap();
+        for name in ["a1.txt", "a2.txt", "a3.txt"] {
+            fs::write(tmp_dir.path().join(name), "three copies").unwrap();
+        }
+        fs::write(tmp_dir.path().join("b.txt"), "unique").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let mut seen = Vec::new();
+        let options = ScanOptions { min_group_size: 4, ..Default::default() };
+        let groups = find_duplicates_with(tmp_dir.path(), &options, Some(&mut output), |group, duplicate| {
+            assert_eq!(group.files.last().map(PathBuf::as_path), Some(duplicate));
+            seen.push(group.files.len());
+        }).unwrap();
+        // Called before the groups are filtered
+        assert!(groups.is_empty());
+        assert_eq!(seen, [2, 3]);
+    }
+
+    #[test]
+    fn test_breadth_first_hashes_shallow_files_first() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_bfs").tempdir().unwrap();
+        fs::create_dir_all(tmp_dir.path().join("a/b/c")).unwrap();
+        fs::write(tmp_dir.path().join("a/b/c/deep.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("a/b/c/deeper.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("top.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("a/middle.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { breadth_first: true, max_files: Some(2), ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        let names: Vec<_> = groups[0].files.iter().map(|file| file.file_name().unwrap()).collect();
+        assert_eq!(names, ["top.txt", "middle.txt"]);
+    }
+
+    #[test]
+    fn test_scan_images() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_scan_images").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("disc.iso"), crate::iso_image::tests::build_image()).unwrap();
+        fs::write(tmp_dir.path().join("readme.txt"), "hello").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_duplicates(tmp_dir.path(), &ScanOptions::default(), Some(&mut output)).unwrap();
+        assert!(groups.is_empty());
+
+        let options = ScanOptions { scan_images: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        let names: Vec<_> = groups[0].files.iter().map(|file| file.strip_prefix(tmp_dir.path().canonicalize().unwrap()).unwrap()).collect();
+        assert!(names.contains(&Path::new("disc.iso/README.TXT")));
+        assert!(names.contains(&Path::new("readme.txt")));
+    }
+
+    #[test]
+    fn test_extracted_copies() {
+        use crate::archive_members::tests::tar_entry;
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_extracted").tempdir().unwrap();
+        let mut tar = tar_entry("notes/a.txt", b"archived", b'0');
+        tar.extend(tar_entry("notes/b.txt", b"archived only", b'0'));
+        tar.extend([0; 1024]);
+        fs::write(tmp_dir.path().join("notes.tar"), tar).unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "archived").unwrap();
+        fs::write(tmp_dir.path().join("a copy.txt"), "archived").unwrap();
+        fs::write(tmp_dir.path().join("c.txt"), "loose").unwrap();
+        fs::write(tmp_dir.path().join("d.txt"), "loose").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { scan_archives: true, ..Default::default() };
+        assert_eq!(find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap().len(), 2);
+        let options = ScanOptions { scan_archives: true, extracted_copies: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+        assert!(groups[0].files[0].ends_with("notes.tar/notes/a.txt"));
+        assert_eq!(groups[0].entries[0].path, groups[0].files[0]);
+    }
+
+    #[test]
+    fn test_skip_network_scans_local_subdirectories() {
+        let tmp_dir = Builder::new().prefix("find_duplicates_skip_network").tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("sub")).unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("sub").join("b.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { skip_network: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_json_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::io::{Read, Seek};
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_non_utf8").tempdir().unwrap();
+        fs::write(tmp_dir.path().join(OsStr::from_bytes(b"caf\xe9.txt")), "copy").unwrap();
+        fs::write(tmp_dir.path().join("cafe.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { format: OutputFormat::Json, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+
+        let mut json = String::new();
+        output.rewind().unwrap();
+        output.read_to_string(&mut json).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let files = report["groups"][0]["files"].as_array().unwrap();
+        assert_eq!(files.iter().filter(|file| file.get("base64").is_some()).count(), 1);
+        assert_eq!(report["groups"][0]["id"], groups[0].id());
+        assert_eq!(report["metadata"]["hash_algorithm"], "sha256");
+        assert_eq!(report["metadata"]["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_hashdeep_manifest() {
+        use std::io::{Read, Seek};
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_hashdeep").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("unique.txt"), "no other file has this size").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { format: OutputFormat::Hashdeep, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+
+        let mut manifest = String::new();
+        output.rewind().unwrap();
+        output.read_to_string(&mut manifest).unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let unique = root.join("unique.txt");
+        let expected = format!("27,{},{}", compute_sha256(&unique).unwrap(), unique.display());
+        assert!(manifest.lines().any(|line| line == expected));
+        assert_eq!(manifest.lines().filter(|line| !line.starts_with('#') && !line.starts_with('%')).count(), 3);
+    }
+
+    #[test]
+    fn test_ncdu_export() {
+        use std::io::{Read, Seek};
+
+        let tmp_dir = Builder::new().prefix("find_duplicates_ncdu").tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("backup")).unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "copy").unwrap();
+        fs::write(tmp_dir.path().join("backup").join("b.txt"), "copy").unwrap();
+        let mut output = tempfile::tempfile().unwrap();
+
+        let options = ScanOptions { format: OutputFormat::Ncdu, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, Some(&mut output)).unwrap();
+
+        let mut json = String::new();
+        output.rewind().unwrap();
+        output.read_to_string(&mut json).unwrap();
+        let export: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!((&export[0], &export[1], &export[2]["progname"]), (&1.into(), &2.into(), &"dupefiles".into()));
+        // Only the copy found second is listed, below its directory
+        fn files(directory: &serde_json::Value) -> Vec<&serde_json::Value> {
+            directory.as_array().unwrap().iter().skip(1)
+                .flat_map(|entry| if entry.is_array() { files(entry) } else { vec![entry] })
+                .collect()
+        }
+        let entries = files(&export[3]);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"], groups[0].files[1].file_name().unwrap().to_str().unwrap());
+        assert_eq!(entries[0]["asize"], 4);
+        assert_eq!(entries[0]["dupefiles_group"], groups[0].id());
+    }
+}
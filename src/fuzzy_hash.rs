@@ -0,0 +1,309 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Result};
+use std::path::Path;
+use std::str::FromStr;
+use crate::localize::message;
+
+/// Bytes the rolling hash looks at, which is also the length of the substring two
+/// signatures must share to be compared
+const ROLLING_WINDOW: usize = 7;
+/// The smallest block size of a signature
+const MIN_BLOCKSIZE: u32 = 3;
+/// The longest signature
+const SPAMSUM_LENGTH: usize = 64;
+const HASH_PRIME: u32 = 0x0100_0193;
+const HASH_INIT: u32 = 0x2802_1967;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A context triggered piecewise hash, in the format of ssdeep, which similar files
+/// share most of: `BLOCKSIZE:SIGNATURE:SIGNATURE2`.
+///
+/// The content is cut into pieces where a rolling hash of the last 7 bytes hits a value
+/// depending on the block size, and each piece adds a character to the signature. An
+/// edit only changes the characters of the pieces it touches, so files differing by a
+/// few edits keep most of their signature. The second signature uses twice the block
+/// size, so files whose sizes differ enough to get different block sizes still compare.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FuzzyHash {
+    /// The block size of the first signature.
+    pub block_size: u32,
+    /// The signature at `block_size`.
+    pub signature: String,
+    /// The signature at twice `block_size`.
+    pub signature2: String,
+}
+
+impl fmt::Display for FuzzyHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.block_size, self.signature, self.signature2)
+    }
+}
+
+impl FromStr for FuzzyHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || message("invalid-fuzzy-hash", &[("value", &s)]);
+        let mut parts = s.splitn(3, ':');
+        let (Some(block_size), Some(signature), Some(signature2)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        Ok(FuzzyHash {
+            block_size: block_size.parse().map_err(|_| invalid())?,
+            signature: signature.to_string(),
+            signature2: signature2.to_string(),
+        })
+    }
+}
+
+/// The rolling hash of the last `ROLLING_WINDOW` bytes
+#[derive(Default)]
+struct RollingHash {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl RollingHash {
+    /// Add a byte, dropping the oldest one, and return the hash of the window
+    fn update(&mut self, byte: u8) -> u32 {
+        let c = byte as u32;
+        let slot = self.n % ROLLING_WINDOW;
+        self.h2 = self.h2.wrapping_sub(self.h1).wrapping_add(ROLLING_WINDOW as u32 * c);
+        self.h1 = self.h1.wrapping_add(c).wrapping_sub(self.window[slot] as u32);
+        self.window[slot] = byte;
+        self.n += 1;
+        self.h3 = (self.h3 << 5) ^ c;
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// A signature being built: the characters of the pieces ended so far, up to `limit`,
+/// and the character of the piece in progress once the limit is reached
+struct Signature {
+    characters: Vec<u8>,
+    last: Option<u8>,
+    limit: usize,
+    hash: u32,
+}
+
+impl Signature {
+    fn new(limit: usize) -> Self {
+        Signature { characters: Vec::new(), last: None, limit, hash: HASH_INIT }
+    }
+
+    /// End the piece in progress; past the limit, the last character keeps changing
+    fn end_piece(&mut self) {
+        let character = BASE64[(self.hash % 64) as usize];
+        if self.characters.len() < self.limit - 1 {
+            self.characters.push(character);
+            self.hash = HASH_INIT;
+        } else {
+            self.last = Some(character);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.characters.extend(self.last);
+        String::from_utf8(self.characters).expect("signatures are base64")
+    }
+}
+
+/// The signatures at `block_size` and twice `block_size` of the content of `reader`
+fn signatures(reader: impl Read, block_size: u32) -> Result<(String, String, usize)> {
+    let mut rolling = RollingHash::default();
+    let mut first = Signature::new(SPAMSUM_LENGTH);
+    let mut second = Signature::new(SPAMSUM_LENGTH / 2);
+    let mut rolled = 0;
+    for byte in BufReader::with_capacity(1024 * 1024, reader).bytes() {
+        let byte = byte?;
+        rolled = rolling.update(byte);
+        first.hash = first.hash.wrapping_mul(HASH_PRIME) ^ byte as u32;
+        second.hash = second.hash.wrapping_mul(HASH_PRIME) ^ byte as u32;
+        if rolled % block_size == block_size - 1 {
+            first.end_piece();
+        }
+        if rolled % (block_size * 2) == block_size * 2 - 1 {
+            second.end_piece();
+        }
+    }
+    // The last piece, unless the content ended exactly at the end of a piece
+    if rolled != 0 {
+        first.last = Some(BASE64[(first.hash % 64) as usize]);
+        second.last = Some(BASE64[(second.hash % 64) as usize]);
+    }
+    let pieces = first.characters.len();
+    Ok((first.finish(), second.finish(), pieces))
+}
+
+/// Computes the fuzzy hash of a file, in the format of ssdeep.
+///
+/// The block size is the smallest power of two times 3 that cuts the file into at
+/// most 64 pieces, halved while the signature has fewer than 32 characters, so the file
+/// may be read more than once.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a `Path` representing the file to hash.
+///
+/// # Returns
+///
+/// * `Result<FuzzyHash>` - The fuzzy hash, or an error if the file cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::fuzzy_hash::compute_fuzzy_hash;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("quote.txt");
+/// fs::write(&path, "Also called fuzzy hashes, Ctph can match inputs that have homologies.").unwrap();
+/// assert_eq!(compute_fuzzy_hash(&path).unwrap().to_string(), "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C");
+/// ```
+pub fn compute_fuzzy_hash(path: &Path) -> Result<FuzzyHash> {
+    let size = fs::metadata(path)?.len();
+    let mut block_size = MIN_BLOCKSIZE;
+    while (block_size as u64) * (SPAMSUM_LENGTH as u64) < size {
+        block_size *= 2;
+    }
+    loop {
+        let (signature, signature2, pieces) = signatures(File::open(path)?, block_size)?;
+        if block_size > MIN_BLOCKSIZE && pieces < SPAMSUM_LENGTH / 2 {
+            block_size /= 2;
+            continue;
+        }
+        return Ok(FuzzyHash { block_size, signature, signature2 });
+    }
+}
+
+/// A signature with every run of more than 3 identical characters cut to 3, as long
+/// runs come from repetitive content and say little about similarity
+fn eliminate_sequences(signature: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::with_capacity(signature.len());
+    for &byte in signature.as_bytes() {
+        let run = result.iter().rev().take(3).filter(|&&previous| previous == byte).count();
+        if result.len() < 3 || run < 3 {
+            result.push(byte);
+        }
+    }
+    result
+}
+
+/// The edit distance of two signatures, counting insertions and deletions as 1 and
+/// substitutions as 2
+fn edit_distance(first: &[u8], second: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=second.len()).collect();
+    for (i, &a) in first.iter().enumerate() {
+        let mut current = vec![i + 1; second.len() + 1];
+        for (j, &b) in second.iter().enumerate() {
+            let substitution = previous[j] + if a == b { 0 } else { 2 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[second.len()]
+}
+
+/// The similarity of two signatures at the same block size, from 0 to 100
+fn score_signatures(first: &[u8], second: &[u8], block_size: u32) -> u32 {
+    if first.len() > SPAMSUM_LENGTH || second.len() > SPAMSUM_LENGTH {
+        return 0;
+    }
+    // Signatures without a common substring as long as the rolling window are unrelated
+    let common = first.windows(ROLLING_WINDOW).any(|window| second.windows(ROLLING_WINDOW).any(|other| other == window));
+    if !common {
+        return 0;
+    }
+    let lengths = (first.len() + second.len()) as u32;
+    let distance = edit_distance(first, second) as u32 * SPAMSUM_LENGTH as u32 / lengths;
+    let distance = distance * 100 / SPAMSUM_LENGTH as u32;
+    if distance >= 100 {
+        return 0;
+    }
+    let score = 100 - distance;
+    // Small block sizes give short signatures, whose matches mean less
+    if block_size >= (99 + ROLLING_WINDOW as u32) / ROLLING_WINDOW as u32 * MIN_BLOCKSIZE {
+        return score;
+    }
+    score.min(block_size / MIN_BLOCKSIZE * first.len().min(second.len()) as u32)
+}
+
+impl FuzzyHash {
+    /// The substrings as long as the rolling window of both signatures, with their block
+    /// size: two hashes have a nonzero `similarity` only when they share one of them, so
+    /// indexing hashes by them finds the pairs worth comparing.
+    pub(crate) fn substrings(&self) -> Vec<(u32, [u8; ROLLING_WINDOW])> {
+        let mut substrings = Vec::new();
+        for (block_size, signature) in [(self.block_size, &self.signature), (self.block_size.wrapping_mul(2), &self.signature2)] {
+            for window in eliminate_sequences(signature).windows(ROLLING_WINDOW) {
+                substrings.push((block_size, window.try_into().expect("windows have the rolling window length")));
+            }
+        }
+        substrings.sort_unstable();
+        substrings.dedup();
+        substrings
+    }
+
+    /// Returns how similar the files of two fuzzy hashes are, from 0 for unrelated files
+    /// to 100 for files that are identical or nearly so, like `ssdeep -d` does.
+    ///
+    /// Hashes whose block sizes differ by more than a factor of two are not compared
+    /// and score 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::fuzzy_hash::FuzzyHash;
+    ///
+    /// let first: FuzzyHash = "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C".parse().unwrap();
+    /// let second: FuzzyHash = "3:AXGBicFlIHBGcL6wCrFQEv:AXGH6xLsr2Cx".parse().unwrap();
+    /// assert_eq!(first.similarity(&second), 22);
+    /// assert_eq!(first.similarity(&first), 100);
+    /// ```
+    pub fn similarity(&self, other: &FuzzyHash) -> u32 {
+        let (a, b) = (self.block_size, other.block_size);
+        if a != b && a != b.wrapping_mul(2) && b != a.wrapping_mul(2) {
+            return 0;
+        }
+        let (first, first2) = (eliminate_sequences(&self.signature), eliminate_sequences(&self.signature2));
+        let (second, second2) = (eliminate_sequences(&other.signature), eliminate_sequences(&other.signature2));
+        if a == b && first == second && first2 == second2 {
+            return 100;
+        }
+        if a == b {
+            score_signatures(&first, &second, a).max(score_signatures(&first2, &second2, a * 2))
+        } else if a == b * 2 {
+            score_signatures(&first, &second2, a)
+        } else {
+            score_signatures(&first2, &second, b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_edited_document() {
+        let tmp_dir = Builder::new().prefix("fuzzy_hash").tempdir().unwrap();
+        let text: String = (0..2000).map(|line| format!("Line {} of the quarterly report, figures and notes.\n", line * 7919 % 10007)).collect();
+        let edited = text.replacen("Line 7919 of", "Line 7919 (revised) of", 1) + "Appendix added later.\n";
+        let unrelated: String = (0..2000).map(|line| format!("{:x}\n", (line as u64 * 2654435761) % 4294967291)).collect();
+        let hash = |name: &str, content: &str| {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            compute_fuzzy_hash(&path).unwrap()
+        };
+        let (original, edited, unrelated) = (hash("a.txt", &text), hash("b.txt", &edited), hash("c.txt", &unrelated));
+
+        assert!(original.similarity(&edited) >= 80, "{} vs {}", original, edited);
+        assert_eq!(original.similarity(&unrelated), 0);
+        assert_eq!(original.to_string().parse::<FuzzyHash>().unwrap(), original);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::file_entry::FileEntry;
+use crate::path_encoding::{escape_path, unescape_path};
+use crate::localize::message;
+
+/// A file hash remembered from a previous scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    modified_secs: i64,
+    modified_nanos: i64,
+    /// The content modes the hash was computed with, as returned by `hash_mode`.
+    mode: String,
+    hash: String,
+    /// Whether the hash covers part of the content only.
+    content_only: bool,
+}
+
+/// Hashes of previously scanned files, keyed by canonical path escaped with `escape_path`
+/// so that paths which are not valid UTF-8 can be saved too.
+///
+/// An entry is only reused while the file keeps the size and modification time it had
+/// when it was hashed, and when the scan uses the same content modes. Saving the cache
+/// when a scan stops early acts as a checkpoint: the next scan with the same cache only
+/// hashes the files that were not reached.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::file_entry::FileEntry;
+/// use dupefiles::hash_cache::HashCache;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("a.txt");
+/// fs::write(&path, "content").unwrap();
+/// let entry = FileEntry::from_path(&path, 7, "");
+///
+/// let mut cache = HashCache::default();
+/// cache.insert(&entry, "", "abc123", false);
+/// assert_eq!(cache.get(&entry, ""), Some(("abc123".to_string(), false)));
+/// assert_eq!(cache.get(&entry, "text"), None);
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HashCache {
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl HashCache {
+    /// Loads a cache saved by `save`, or returns an empty cache if the file does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The cache file.
+    ///
+    /// # Returns
+    ///
+    /// The loaded cache, or an error if the file cannot be read or is not a cache.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents).map_err(|e| {
+                io::Error::new(ErrorKind::InvalidData, message("cache-invalid", &[("file", &path.display()), ("error", &e)]))
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashCache::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the cache to `path`, replacing any previous cache only once it is fully written.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The cache file.
+    ///
+    /// # Returns
+    ///
+    /// Result
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_path = path.with_file_name(format!(".{}.dupefiles-tmp", file_name));
+        fs::write(&temp_path, serde_json::to_vec(self)?)?;
+        fs::rename(&temp_path, path).inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        })
+    }
+
+    /// Returns the hash remembered for the file of `file` and whether it covers part of
+    /// the content only, if the file still has the size and modification time of `file`
+    /// and was hashed with the same content modes.
+    pub fn get(&self, file: &FileEntry, mode: &str) -> Option<(String, bool)> {
+        let (modified_secs, modified_nanos) = unix_time(file.mtime?);
+        self.entries.get(&escape_path(&file.path))
+            .filter(|entry| entry.size == file.size
+                && entry.modified_secs == modified_secs
+                && entry.modified_nanos == modified_nanos
+                && entry.mode == mode)
+            .map(|entry| (entry.hash.clone(), entry.content_only))
+    }
+
+    /// Remembers the hash of the file of `file`, replacing any previous entry for it.
+    /// Files whose modification time is unknown are not remembered.
+    pub fn insert(&mut self, file: &FileEntry, mode: &str, hash: &str, content_only: bool) {
+        let Some(mtime) = file.mtime else { return };
+        let (modified_secs, modified_nanos) = unix_time(mtime);
+        self.entries.insert(escape_path(&file.path), CacheEntry {
+            size: file.size,
+            modified_secs,
+            modified_nanos,
+            mode: mode.to_string(),
+            hash: hash.to_string(),
+            content_only,
+        });
+    }
+
+    /// Number of files in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops the entries that can no longer be reused: those of files that were deleted,
+    /// replaced by something other than a file, or changed since they were hashed.
+    ///
+    /// # Returns
+    ///
+    /// The number of entries dropped.
+    pub fn prune(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|escaped, entry| {
+            unescape_path(escaped).ok()
+                .and_then(|path| fs::metadata(path).ok())
+                .is_some_and(|metadata| metadata.is_file()
+                    && entry.size == metadata.len()
+                    && metadata.modified().ok().map(unix_time) == Some((entry.modified_secs, entry.modified_nanos)))
+        });
+        before - self.entries.len()
+    }
+
+    /// Total size of the files in the cache, as they were when hashed.
+    pub fn total_size(&self) -> u64 {
+        self.entries.values().map(|entry| entry.size).sum()
+    }
+}
+
+/// A time as the seconds and nanoseconds since the Unix epoch of `st_mtime` and
+/// `st_mtime_nsec`, whose nanoseconds are never negative
+pub(crate) fn unix_time(time: SystemTime) -> (i64, i64) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, i64::from(since.subsec_nanos())),
+        Err(e) => {
+            let before = e.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - i64::from(nanos)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_save_and_load() {
+        let tmp_dir = Builder::new().prefix("hash_cache").tempdir().unwrap();
+        let path = tmp_dir.path().join("a.txt");
+        fs::write(&path, "content").unwrap();
+        let entry = FileEntry::from_path(&path, 7, "");
+        let cache_path = tmp_dir.path().join("cache.json");
+
+        let mut cache = HashCache::default();
+        cache.insert(&entry, "", "abc123", false);
+        cache.save(&cache_path).unwrap();
+
+        let loaded = HashCache::load(&cache_path).unwrap();
+        assert_eq!(loaded.get(&entry, ""), Some(("abc123".to_string(), false)));
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_changed_file_is_not_reused() {
+        let tmp_dir = Builder::new().prefix("hash_cache").tempdir().unwrap();
+        let path = tmp_dir.path().join("a.txt");
+        fs::write(&path, "content").unwrap();
+        let mut cache = HashCache::default();
+        cache.insert(&FileEntry::from_path(&path, 7, ""), "", "abc123", false);
+
+        fs::write(&path, "longer content").unwrap();
+        assert_eq!(cache.get(&FileEntry::from_path(&path, 14, ""), ""), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let tmp_dir = Builder::new().prefix("hash_cache").tempdir().unwrap();
+        let kept = tmp_dir.path().join("kept.txt");
+        let changed = tmp_dir.path().join("changed.txt");
+        let deleted = tmp_dir.path().join("deleted.txt");
+        let mut cache = HashCache::default();
+        for path in [&kept, &changed, &deleted] {
+            fs::write(path, "content").unwrap();
+            cache.insert(&FileEntry::from_path(path, 7, ""), "", "abc123", false);
+        }
+        assert_eq!(cache.total_size(), 21);
+
+        fs::write(&changed, "longer content").unwrap();
+        fs::remove_file(&deleted).unwrap();
+        assert_eq!(cache.prune(), 2);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&FileEntry::from_path(&kept, 7, ""), "").is_some());
+    }
+
+    #[test]
+    fn test_unix_time_before_epoch() {
+        use std::time::Duration;
+        assert_eq!(unix_time(UNIX_EPOCH + Duration::from_millis(1500)), (1, 500_000_000));
+        assert_eq!(unix_time(UNIX_EPOCH - Duration::from_millis(1500)), (-2, 500_000_000));
+        assert_eq!(unix_time(UNIX_EPOCH - Duration::from_secs(3)), (-3, 0));
+    }
+
+    #[test]
+    fn test_missing_file_loads_empty() {
+        let tmp_dir = Builder::new().prefix("hash_cache").tempdir().unwrap();
+        assert!(HashCache::load(&tmp_dir.path().join("missing.json")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// The content a hash was computed over, as given by the prefix of its text form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum HashMode {
+    File,
+    Audio,
+    Image,
+    Text,
+    Gzip,
+    Email,
+    Sample,
+}
+
+impl HashMode {
+    fn prefix(self) -> &'static str {
+        match self {
+            HashMode::File => "",
+            HashMode::Audio => "audio:",
+            HashMode::Image => "image:",
+            HashMode::Text => "text:",
+            HashMode::Gzip => "gz:",
+            HashMode::Email => "email:",
+            HashMode::Sample => "sample:",
+        }
+    }
+}
+
+/// A SHA256 hash held in binary form, 33 bytes instead of the 64-character hex string
+/// (plus its heap allocation) used in reports, so maps keyed by hash hold several times
+/// more entries in the same memory.
+///
+/// The `audio:`, `image:`, `text:`, `gz:`, `email:` and `sample:` prefixes of partial hashes are kept, so hashes
+/// computed in different content modes never compare equal. `Display` gives back the
+/// text form.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::hash_key::HashKey;
+///
+/// let hash = "audio:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+/// let key = HashKey::parse(hash).unwrap();
+/// assert_eq!(key.to_string(), hash);
+/// assert_ne!(Some(key), HashKey::parse(&hash["audio:".len()..]));
+/// assert_eq!(HashKey::parse("not a hash"), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HashKey {
+    mode: HashMode,
+    digest: [u8; 32],
+}
+
+impl HashKey {
+    /// Parses the text form of a hash, as returned by `compute_sha256` and the other
+    /// hash functions, with an optional content mode prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash, such as `image:` followed by 64 hexadecimal digits.
+    ///
+    /// # Returns
+    ///
+    /// The hash in binary form, or `None` if it is not a SHA256 hash.
+    pub fn parse(hash: &str) -> Option<Self> {
+        let (mode, hex) = match hash.split_once(':') {
+            Some(("audio", hex)) => (HashMode::Audio, hex),
+            Some(("image", hex)) => (HashMode::Image, hex),
+            Some(("text", hex)) => (HashMode::Text, hex),
+            Some(("gz", hex)) => (HashMode::Gzip, hex),
+            Some(("email", hex)) => (HashMode::Email, hex),
+            Some(("sample", hex)) => (HashMode::Sample, hex),
+            Some(_) => return None,
+            None => (HashMode::File, hash),
+        };
+        if hex.len() != 64 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut digest = [0u8; 32];
+        for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+        }
+        Some(HashKey { mode, digest })
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mode.prefix())?;
+        for byte in self.digest {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 use std::fs;
 use std::path::Path;
-use std::os::unix::fs::MetadataExt;
 use crate::compute_sha256::compute_sha256;
+use crate::file_entry::file_id;
 
-use std::io::Error;
+use std::io::{Error, ErrorKind};
+use crate::localize::message;
 
 
 /// Determines if two files are duplicates based on their content and metadata.
@@ -57,64 +58,74 @@ use std::io::Error;
 /// (i.e., same inode and device ID). This is to distinguish between true duplicates
 /// and hard links.
 pub fn is_duplicate_file(file1: &Path, file2: &Path) -> Result<bool, Error> {
-    if !file1.try_exists().map_err(|e| {
-        eprintln!("Error checking existence of file1: {}", e);
-        e
-    })? || !file2.try_exists().map_err(|e| {
-        eprintln!("Error checking existence of file2: {}", e);
-        e
-    })? {
-        return Ok(false);
-    }
+    // A single stat of each file gives its existence, size and identity
+    let f1metadata = match fs::metadata(file1) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        metadata => metadata.inspect_err(|e| {
+            eprintln!("{}", message("compare-metadata-failed", &[("file", &file1.display()), ("error", e)]));
+        })?,
+    };
+    let f2metadata = match fs::metadata(file2) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        metadata => metadata.inspect_err(|e| {
+            eprintln!("{}", message("compare-metadata-failed", &[("file", &file2.display()), ("error", e)]));
+        })?,
+    };
 
-    let f1size = fs::metadata(file1).map_err(|e| {
-        eprintln!("Error accessing metadata of file1: {}", e);
-        e
-    })?.len();
-    let f2size = fs::metadata(file2).map_err(|e| {
-        eprintln!("Error accessing metadata of file2: {}", e);
-        e
-    })?.len();
-
-    if f1size != f2size {
+    if f1metadata.len() != f2metadata.len() {
         return Ok(false);
     }
-
-    let f1hash = compute_sha256(file1).map_err(|e| {
-        eprintln!("Error computing SHA256 for file1: {}", e);
-        e
-    })?;
-    let f2hash = compute_sha256(file2).map_err(|e| {
-        eprintln!("Error computing SHA256 for file2: {}", e);
-        e
-    })?;
-
-    if f1hash != f2hash {
+    if file_id(&f1metadata).is_some() && file_id(&f1metadata) == file_id(&f2metadata) {
         return Ok(false);
     }
 
-    let f1metadata = fs::metadata(file1).map_err(|e| {
-        eprintln!("Error accessing metadata of file1: {}", e);
-        e
+    same_content(file1, file2)
+}
+
+/// Whether two files have the same SHA256 hash, without looking at their metadata
+pub(crate) fn same_content(file1: &Path, file2: &Path) -> Result<bool, Error> {
+    let f1hash = compute_sha256(file1).inspect_err(|e| {
+        eprintln!("{}", message("compare-hash-failed", &[("file", &file1.display()), ("error", e)]));
     })?;
-    let f2metadata = fs::metadata(file2).map_err(|e| {
-        eprintln!("Error accessing metadata of file2: {}", e);
-        e
+    let f2hash = compute_sha256(file2).inspect_err(|e| {
+        eprintln!("{}", message("compare-hash-failed", &[("file", &file2.display()), ("error", e)]));
     })?;
+    Ok(f1hash == f2hash)
+}
 
-    let f1inode = f1metadata.ino();
-    let f1device_id = f1metadata.dev();
-    let f2inode = f2metadata.ino();
-    let f2device_id = f2metadata.dev();
-
-    if f1device_id == f2device_id && f1inode == f2inode {
-        return Ok(false);
+/// Determines if two paths refer to the same file on the filesystem.
+///
+/// Two paths are the same file when they share the same inode and device ID, as is
+/// the case for hard links.
+///
+/// # Arguments
+///
+/// * `file1` - A reference to the `Path` of the first file to compare.
+/// * `file2` - A reference to the `Path` of the second file to compare.
+///
+/// # Returns
+///
+/// Returns `true` if both paths refer to the same file, or an error if the metadata of
+/// either file cannot be retrieved.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::is_duplicate_file::is_same_file;
+///
+/// assert!(is_same_file(Path::new("Cargo.toml"), Path::new("./Cargo.toml")).unwrap());
+/// ```
+pub fn is_same_file(file1: &Path, file2: &Path) -> Result<bool, Error> {
+    match (file_id(&fs::metadata(file1)?), file_id(&fs::metadata(file2)?)) {
+        (Some(id1), Some(id2)) => Ok(id1 == id2),
+        // Without file identities, the same file resolves to the same canonical path
+        _ => Ok(fs::canonicalize(file1)? == fs::canonicalize(file2)?),
     }
-
-    Ok(true)
 }
 
-#[cfg(test)]
+#[cfg(all(test, unix))]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use tempfile::Builder;
@@ -46,6 +46,7 @@ pub fn is_hidden(path: &Path) -> bool {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
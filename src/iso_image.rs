@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use crate::localize::message;
+
+/// Size of an ISO 9660 logical sector
+const SECTOR_SIZE: u64 = 2048;
+/// Sector of the first volume descriptor
+const FIRST_DESCRIPTOR: u64 = 16;
+/// Directories nested deeper than this are not read, which also stops malformed loops
+const MAX_DEPTH: usize = 64;
+/// Directories larger than this are not read, so a corrupt size cannot exhaust memory
+const MAX_DIRECTORY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A file stored in an ISO 9660 disc image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsoMember {
+    /// The path of the file inside the image, such as `photos/2003/img_001.jpg`.
+    pub path: PathBuf,
+    /// The position of the file's content in the image, in bytes.
+    pub offset: u64,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// Returns whether `path` is an ISO 9660 disc image: a file with the `.iso` extension
+/// holding a volume descriptor where the standard places it.
+///
+/// # Arguments
+///
+/// * `path` - The file to check.
+///
+/// # Returns
+///
+/// `true` if the file can be read with `iso_members`, `false` otherwise.
+pub fn is_iso_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("iso"))
+        && File::open(path)
+            .and_then(|mut file| read_sector(&mut file, FIRST_DESCRIPTOR))
+            .is_ok_and(|sector| &sector[1..6] == b"CD001")
+}
+
+/// Returns whether `path` names a file inside a disc image rather than a file on disk,
+/// that is whether one of its parent directories is a regular file.
+///
+/// # Arguments
+///
+/// * `path` - A path reported by the scan.
+///
+/// # Returns
+///
+/// `true` for paths such as `/data/backup.iso/photos/img_001.jpg`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::iso_image::is_image_member;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let image = dir.path().join("backup.iso");
+/// fs::write(&image, "").unwrap();
+/// assert!(is_image_member(&image.join("photos/img_001.jpg")));
+/// assert!(!is_image_member(&image));
+/// ```
+pub fn is_image_member(path: &Path) -> bool {
+    path.ancestors().skip(1).any(|ancestor| ancestor.is_file())
+}
+
+/// Lists the files stored in an ISO 9660 disc image without mounting it.
+///
+/// The long names of the Joliet extension are used when the image has them, and the
+/// plain ISO 9660 names, without their `;1` version suffix, otherwise. Files split into
+/// several extents, which the standard only needs for files over 4 GiB, are skipped.
+///
+/// # Arguments
+///
+/// * `image` - The disc image to read.
+///
+/// # Returns
+///
+/// Every file in the image, or an error if it is not an ISO 9660 image.
+pub fn iso_members(image: &Path) -> io::Result<Vec<IsoMember>> {
+    let mut file = File::open(image)?;
+    let (root, joliet) = root_directory(&mut file)?;
+
+    let mut members = Vec::new();
+    let mut visited = HashSet::new();
+    let mut directories = vec![(PathBuf::new(), root)];
+    while let Some((path, directory)) = directories.pop() {
+        if !visited.insert(directory.extent) || path.components().count() > MAX_DEPTH || directory.size > MAX_DIRECTORY_SIZE {
+            continue;
+        }
+        let mut data = vec![0; directory.size as usize];
+        file.seek(SeekFrom::Start(directory.extent * SECTOR_SIZE))?;
+        file.read_exact(&mut data)?;
+
+        let mut multi_extent = None;
+        for record in records(&data) {
+            let name = record_name(record, joliet);
+            let flags = record[25];
+            if flags & 0x80 != 0 || multi_extent.as_ref() == Some(&name) {
+                // Every extent but the last has the flag set
+                multi_extent = (flags & 0x80 != 0).then_some(name);
+                continue;
+            }
+            if name.is_empty() {
+                continue; // The "." and ".." entries
+            }
+            let entry = Extent::from_record(record);
+            if flags & 0x02 != 0 {
+                directories.push((path.join(&name), entry));
+            } else {
+                members.push(IsoMember { path: path.join(&name), offset: entry.extent * SECTOR_SIZE, size: entry.size });
+            }
+        }
+    }
+    Ok(members)
+}
+
+/// Computes the SHA256 hash of a file stored in a disc image.
+///
+/// # Arguments
+///
+/// * `image` - The disc image holding the file.
+/// * `member` - The file, as listed by `iso_members`.
+///
+/// # Returns
+///
+/// The SHA256 hash as a hexadecimal string, or an error if the image cannot be read.
+pub fn compute_member_sha256(image: &Path, member: &IsoMember) -> io::Result<String> {
+    let mut file = File::open(image)?;
+    file.seek(SeekFrom::Start(member.offset))?;
+    let mut reader = file.take(member.size);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    if reader.limit() > 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message("iso-truncated", &[])));
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The location and size of a file or directory
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    extent: u64,
+    size: u64,
+}
+
+impl Extent {
+    /// Read the both-endian extent and size fields of a directory record
+    fn from_record(record: &[u8]) -> Self {
+        let extent = u32::from_le_bytes([record[2], record[3], record[4], record[5]]) as u64;
+        let size = u32::from_le_bytes([record[10], record[11], record[12], record[13]]) as u64;
+        Extent { extent, size }
+    }
+}
+
+fn read_sector(file: &mut File, sector: u64) -> io::Result<[u8; SECTOR_SIZE as usize]> {
+    let mut data = [0; SECTOR_SIZE as usize];
+    file.seek(SeekFrom::Start(sector * SECTOR_SIZE))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// The root directory of the Joliet volume when there is one, or of the primary volume,
+/// and whether it is the Joliet one
+fn root_directory(file: &mut File) -> io::Result<(Extent, bool)> {
+    let mut primary = None;
+    for sector in FIRST_DESCRIPTOR.. {
+        let descriptor = read_sector(file, sector)?;
+        if &descriptor[1..6] != b"CD001" {
+            break;
+        }
+        let root = Extent::from_record(&descriptor[156..190]);
+        match descriptor[0] {
+            1 => primary = Some(root),
+            // A supplementary volume whose escape sequence selects UCS-2 is Joliet
+            2 if matches!(&descriptor[88..91], b"%/@" | b"%/C" | b"%/E") => return Ok((root, true)),
+            255 => break,
+            _ => {}
+        }
+    }
+    primary
+        .map(|root| (root, false))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, message("iso-not-image", &[])))
+}
+
+/// The directory records of a directory extent; records never cross a sector boundary,
+/// so a zero length means the rest of the sector is padding
+fn records(data: &[u8]) -> Vec<&[u8]> {
+    let mut records = Vec::new();
+    let mut position = 0;
+    while position < data.len() {
+        let length = data[position] as usize;
+        if length == 0 {
+            position = (position / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+            continue;
+        }
+        if length < 34 || position + length > data.len() {
+            break;
+        }
+        let record = &data[position..position + length];
+        if 33 + record[32] as usize <= length {
+            records.push(record);
+        }
+        position += length;
+    }
+    records
+}
+
+/// The name of a directory record without its version suffix, or an empty name for the
+/// "." and ".." entries
+fn record_name(record: &[u8], joliet: bool) -> String {
+    let raw = &record[33..33 + record[32] as usize];
+    if raw == [0] || raw == [1] {
+        return String::new();
+    }
+    let name = if joliet {
+        let units: Vec<u16> = raw.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(raw).into_owned()
+    };
+    let name = name.split(';').next().unwrap_or_default();
+    // A file name without an extension keeps the separator in plain ISO 9660
+    let name = if joliet { name } else { name.strip_suffix('.').unwrap_or(name) };
+    // Path separators and parent references must not escape the image
+    match name {
+        "." | ".." => "_".to_string(),
+        name => name.replace(['/', '\\'], "_"),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    /// A directory record pointing at `extent`
+    fn record(name: &[u8], extent: u32, size: u32, flags: u8) -> Vec<u8> {
+        let mut record = vec![0u8; 33];
+        record[2..6].copy_from_slice(&extent.to_le_bytes());
+        record[6..10].copy_from_slice(&extent.to_be_bytes());
+        record[10..14].copy_from_slice(&size.to_le_bytes());
+        record[14..18].copy_from_slice(&size.to_be_bytes());
+        record[25] = flags;
+        record[32] = name.len() as u8;
+        record.extend_from_slice(name);
+        if record.len() % 2 == 1 {
+            record.push(0);
+        }
+        record[0] = record.len() as u8;
+        record
+    }
+
+    /// A minimal image with /README.TXT and /DOCS/NOTES.TXT
+    pub(crate) fn build_image() -> Vec<u8> {
+        let mut image = vec![0u8; 22 * SECTOR_SIZE as usize];
+        let sector = |n: usize| n * SECTOR_SIZE as usize;
+
+        // Primary volume descriptor, with the root directory at sector 18
+        image[sector(16)] = 1;
+        image[sector(16) + 1..sector(16) + 6].copy_from_slice(b"CD001");
+        let root = record(&[0], 18, SECTOR_SIZE as u32, 0x02);
+        image[sector(16) + 156..sector(16) + 156 + root.len()].copy_from_slice(&root);
+        // Terminator
+        image[sector(17)] = 255;
+        image[sector(17) + 1..sector(17) + 6].copy_from_slice(b"CD001");
+
+        let mut root_dir = Vec::new();
+        root_dir.extend(record(&[0], 18, SECTOR_SIZE as u32, 0x02));
+        root_dir.extend(record(&[1], 18, SECTOR_SIZE as u32, 0x02));
+        root_dir.extend(record(b"README.TXT;1", 20, 5, 0));
+        root_dir.extend(record(b"DOCS", 19, SECTOR_SIZE as u32, 0x02));
+        image[sector(18)..sector(18) + root_dir.len()].copy_from_slice(&root_dir);
+
+        let mut docs_dir = Vec::new();
+        docs_dir.extend(record(&[0], 19, SECTOR_SIZE as u32, 0x02));
+        docs_dir.extend(record(&[1], 18, SECTOR_SIZE as u32, 0x02));
+        docs_dir.extend(record(b"NOTES.TXT;1", 21, 5, 0));
+        image[sector(19)..sector(19) + docs_dir.len()].copy_from_slice(&docs_dir);
+
+        image[sector(20)..sector(20) + 5].copy_from_slice(b"hello");
+        image[sector(21)..sector(21) + 5].copy_from_slice(b"notes");
+        image
+    }
+
+    #[test]
+    fn test_iso_members() {
+        let tmp_dir = Builder::new().prefix("iso_image").tempdir().unwrap();
+        let image = tmp_dir.path().join("disc.iso");
+        fs::write(&image, build_image()).unwrap();
+
+        assert!(is_iso_image(&image));
+        let mut members = iso_members(&image).unwrap();
+        members.sort_by(|a, b| a.path.cmp(&b.path));
+        let paths: Vec<&Path> = members.iter().map(|member| member.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("DOCS/NOTES.TXT"), Path::new("README.TXT")]);
+
+        let readme = tmp_dir.path().join("readme.txt");
+        fs::write(&readme, "hello").unwrap();
+        assert_eq!(compute_member_sha256(&image, &members[1]).unwrap(), crate::compute_sha256::compute_sha256(&readme).unwrap());
+    }
+
+    #[test]
+    fn test_not_an_image() {
+        let tmp_dir = Builder::new().prefix("iso_image").tempdir().unwrap();
+        let image = tmp_dir.path().join("fake.iso");
+        fs::write(&image, vec![0u8; 40000]).unwrap();
+
+        assert!(!is_iso_image(&image));
+        assert!(iso_members(&image).is_err());
+    }
+}
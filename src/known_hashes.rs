@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use crate::archive_members::{is_archive, member_path};
+use crate::iso_image::is_iso_image;
+use crate::localize::message;
+
+/// The first line of the manifests written by hashdeep
+const HASHDEEP_HEADER: &str = "%%%% HASHDEEP-1.0";
+
+/// A file listed in a checksum manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownFile {
+    /// Where the file is reported: the path of the manifest followed by the path it
+    /// lists, like the files stored in an archive.
+    pub path: PathBuf,
+    /// The SHA256 hash of the file, as a lowercase hexadecimal string.
+    pub sha256: String,
+    /// The size of the file, listed by hashdeep manifests only.
+    pub size: Option<u64>,
+}
+
+/// The files listed in checksum manifests written elsewhere, such as on an offline
+/// archive, which a scan compares with the files it finds without reading them.
+///
+/// `sha256sum` manifests, in the GNU and BSD (`--tag`) formats, and hashdeep manifests
+/// holding a sha256 column are read.
+#[derive(Debug, Clone, Default)]
+pub struct KnownHashes {
+    files: Vec<KnownFile>,
+    sizes: HashSet<u64>,
+    /// Whether some file has no size, so files of any size may match one
+    unsized_files: bool,
+}
+
+impl KnownHashes {
+    /// Adds the files listed in a manifest.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - The manifest, written by `sha256sum` or `hashdeep -c sha256`.
+    ///
+    /// # Returns
+    ///
+    /// An error if the manifest cannot be read, or if a line is neither a comment nor a
+    /// file with its SHA256 hash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use dupefiles::known_hashes::KnownHashes;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let manifest = dir.path().join("archive.sha256");
+    /// fs::write(&manifest, "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03  photos/a.jpg\n").unwrap();
+    ///
+    /// let mut known_hashes = KnownHashes::default();
+    /// known_hashes.add_manifest(&manifest).unwrap();
+    /// assert_eq!(known_hashes.files()[0].path, manifest.join("photos/a.jpg"));
+    /// assert!(known_hashes.may_match(1024));
+    /// ```
+    pub fn add_manifest(&mut self, manifest: &Path) -> Result<()> {
+        let contents = fs::read_to_string(manifest)
+            .with_context(|| message("manifest-read-failed", &[("file", &manifest.display())]))?;
+        let invalid = |number: usize| message("manifest-invalid-line", &[("line", &(number + 1)), ("file", &manifest.display())]);
+        let mut lines = contents.lines().enumerate().peekable();
+        let hashdeep = lines.peek().is_some_and(|(_, line)| line.trim_end() == HASHDEEP_HEADER);
+        // The columns of hashdeep manifests are listed on their second line
+        let mut columns: Vec<String> = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if hashdeep {
+                if let Some(header) = line.strip_prefix("%%%% ") {
+                    columns = header.split(',').map(str::to_string).collect();
+                    continue;
+                }
+            }
+            let (name, sha256, size) = if hashdeep {
+                parse_hashdeep_line(line, &columns).with_context(|| invalid(number))?
+            } else {
+                parse_sha256sum_line(line).with_context(|| invalid(number))?
+            };
+            if !is_sha256(&sha256) {
+                bail!("{}", message("manifest-not-sha256", &[("line", &invalid(number))]));
+            }
+            match size {
+                Some(size) => {
+                    self.sizes.insert(size);
+                }
+                None => self.unsized_files = true,
+            }
+            self.files.push(KnownFile { path: manifest.join(member_path(&name)), sha256: sha256.to_lowercase(), size });
+        }
+        Ok(())
+    }
+
+    /// Returns the files listed, in the order of their manifests.
+    pub fn files(&self) -> &[KnownFile] {
+        &self.files
+    }
+
+    /// Returns whether a file of `size` bytes may match a file listed, which is always
+    /// the case once a manifest without sizes, such as a `sha256sum` one, is added.
+    pub fn may_match(&self, size: u64) -> bool {
+        self.unsized_files || self.sizes.contains(&size)
+    }
+}
+
+/// Returns whether `path` is a file listed in a checksum manifest, as reported by a scan
+/// with `KnownHashes`: a path inside a file that is neither a disc image nor an archive.
+/// Such files are only known by their hash, so an action never keeps one in place of a
+/// copy on disk.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::known_hashes::is_manifest_entry;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (manifest, zip) = (dir.path().join("archive.sha256"), dir.path().join("photos.zip"));
+/// fs::write(&manifest, "").unwrap();
+/// fs::write(&zip, b"PK\x05\x06\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0").unwrap();
+/// assert!(is_manifest_entry(&manifest.join("photos/a.jpg")));
+/// assert!(!is_manifest_entry(&zip.join("a.jpg")));
+/// assert!(!is_manifest_entry(&manifest));
+/// ```
+pub fn is_manifest_entry(path: &Path) -> bool {
+    path.ancestors().skip(1).find(|ancestor| ancestor.is_file())
+        .is_some_and(|container| !is_iso_image(container) && !is_archive(container))
+}
+
+/// Whether a string is a SHA256 hash in hexadecimal
+fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// The name and hash of a line of a `sha256sum` manifest: `HASH  NAME`, `HASH *NAME`
+/// for files hashed in binary mode, or `SHA256 (NAME) = HASH` with `--tag`. Names with
+/// a newline or a backslash are escaped, and their line starts with a backslash
+fn parse_sha256sum_line(line: &str) -> Result<(String, String, Option<u64>)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (name, hash) = if let Some(tagged) = line.strip_prefix("SHA256 (") {
+        let (name, hash) = tagged.rsplit_once(") = ").with_context(|| message("manifest-expected-tagged", &[]))?;
+        (name, hash)
+    } else {
+        let (hash, name) = line.split_once(' ').with_context(|| message("manifest-expected-line", &[]))?;
+        let name = name.strip_prefix([' ', '*']).with_context(|| message("manifest-expected-line", &[]))?;
+        (name, hash)
+    };
+    let name = if escaped { unescape(name) } else { name.to_string() };
+    Ok((name, hash.to_string(), None))
+}
+
+/// A name escaped by `sha256sum`, with `\\` for a backslash and `\n` for a newline
+fn unescape(name: &str) -> String {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The name, hash and size of a line of a hashdeep manifest, whose `columns` are
+/// separated by commas. The file name comes last and may hold commas itself
+fn parse_hashdeep_line(line: &str, columns: &[String]) -> Result<(String, String, Option<u64>)> {
+    let sha256_column = columns.iter().position(|column| column == "sha256")
+        .with_context(|| message("manifest-no-sha256", &[]))?;
+    let values: Vec<&str> = line.splitn(columns.len(), ',').collect();
+    if values.len() != columns.len() || columns.last().map(String::as_str) != Some("filename") {
+        bail!("{}", message("manifest-expected-columns", &[("columns", &columns.join(","))]));
+    }
+    let size = match columns.iter().position(|column| column == "size") {
+        Some(index) => Some(values[index].parse().with_context(|| message("manifest-invalid-size", &[]))?),
+        None => None,
+    };
+    Ok((values[values.len() - 1].to_string(), values[sha256_column].to_string(), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    const HASH: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+    #[test]
+    fn test_hashdeep_manifest() {
+        let tmp_dir = Builder::new().prefix("known_hashes_hashdeep").tempdir().unwrap();
+        let manifest = tmp_dir.path().join("archive.hashdeep");
+        fs::write(&manifest, format!("%%%% HASHDEEP-1.0\n%%%% size,md5,sha256,filename\n\
+            ## Invoked from: /mnt/archive\n## $ hashdeep -c md5,sha256 -r .\n##\n\
+            4,d41d8cd98f00b204e9800998ecf8427e,{},/mnt/archive/a, b.txt\n", HASH.to_uppercase())).unwrap();
+
+        let mut known_hashes = KnownHashes::default();
+        known_hashes.add_manifest(&manifest).unwrap();
+        assert_eq!(known_hashes.files(), [KnownFile { path: manifest.join("mnt/archive/a, b.txt"), sha256: HASH.to_string(), size: Some(4) }]);
+        assert!(known_hashes.may_match(4));
+        assert!(!known_hashes.may_match(5));
+    }
+
+    #[test]
+    fn test_sha256sum_formats() {
+        let tmp_dir = Builder::new().prefix("known_hashes_sha256sum").tempdir().unwrap();
+        let manifest = tmp_dir.path().join("SHA256SUMS");
+        fs::write(&manifest, format!("{HASH}  ./a.txt\r\n{HASH} *b.bin\nSHA256 (c (1).txt) = {HASH}\n\\{HASH}  d\\\\e\\nf\n")).unwrap();
+
+        let mut known_hashes = KnownHashes::default();
+        known_hashes.add_manifest(&manifest).unwrap();
+        let paths: Vec<PathBuf> = known_hashes.files().iter().map(|file| file.path.clone()).collect();
+        assert_eq!(paths, ["a.txt", "b.bin", "c (1).txt", "d\\e\nf"].map(|name| manifest.join(name)));
+
+        fs::write(&manifest, "not a manifest\n").unwrap();
+        assert!(KnownHashes::default().add_manifest(&manifest).is_err());
+    }
+}
@@ -8,13 +8,18 @@
 //! ```no_run
 //! use std::path::Path;
 //! use dupefiles::find_duplicates::find_duplicates;
+//! use dupefiles::scan_options::ScanOptions;
 //! 
 //! # fn main() -> anyhow::Result<()> {
 //! let directory = Path::new("test_data");
-//! let extensions = Some(vec!["txt".to_string()]);
+//! let options = ScanOptions {
+//!     extensions: Some(vec!["txt".to_string()]),
+//!     ..Default::default()
+//! };
 //! let mut output_file = None;
 //! 
-//! find_duplicates(directory, extensions.as_ref(), output_file.as_mut())?;
+//! let groups = find_duplicates(directory, &options, output_file.as_mut())?;
+//! println!("{} duplicate groups found", groups.len());
 //! # Ok(())
 //! # }
 //! ```
@@ -27,6 +32,29 @@
 //! - Supports CSV output format
 //! - Handles symlinks safely
 //! - Provides human-readable file sizes
+//! - Optionally deletes or trashes the duplicates it finds
+//!
+//! # Cargo Features
+//!
+//! The default `cli` feature builds the `dupefiles` command and enables the
+//! `actions` and `reports` features. Applications embedding the scanning engine
+//! only can depend on the core library, which walks trees, hashes files and groups
+//! duplicates (`find_duplicates`, `scan_source`, `hash_cache`, `write_report`, ...):
+//!
+//! ```toml
+//! dupefiles = { version = "1", default-features = false }
+//! ```
+//!
+//! - `actions` - Deleting, trashing and linking duplicates (`actions`), choosing the
+//!   copies kept (`select_keeper`, `protect_list`, `raw_pairs`), handling sidecars
+//!   (`sidecar_files`), confirming (`confirm_action`) and journaling (`action_journal`)
+//! - `reports` - Reading reports back (`read_report`), checking them (`check_report`)
+//!   and exporting treemaps (`treemap`)
+//! - `s3`, `sftp` - Scanning S3 buckets and remote directories over SFTP
+//! - `webhook` - Posting the summary of a scan to a URL when it ends (`webhook`)
+//! - `desktop` - Showing a desktop notification when a scan ends (`desktop_notification`)
+//! - `fuse` - Mounting duplicate groups as a file system of symlinks (`duplicate_mount`)
+//! - `asm` - Assembly SHA256 for CPUs without SHA-NI
 
 pub mod compute_sha256;
 pub mod is_hidden;
@@ -34,4 +62,86 @@ pub mod is_duplicate_file;
 pub mod find_duplicates;
 pub mod debug_message;
 pub mod elapsed_time;
-pub mod human_readable_size;
\ No newline at end of file
+pub mod human_readable_size;
+#[cfg(feature = "actions")]
+pub mod actions;
+#[cfg(feature = "actions")]
+pub mod confirm_action;
+pub mod duplicate_group;
+#[cfg(feature = "actions")]
+pub mod select_keeper;
+#[cfg(feature = "actions")]
+pub mod protect_list;
+#[cfg(feature = "actions")]
+pub mod raw_pairs;
+#[cfg(feature = "actions")]
+pub mod sidecar_files;
+#[cfg(feature = "actions")]
+pub mod action_journal;
+#[cfg(feature = "actions")]
+pub mod exec_hook;
+pub mod scan_options;
+pub mod parse_size;
+pub mod compute_audio_sha256;
+pub mod compute_image_sha256;
+pub mod compute_text_sha256;
+pub mod compute_gzip_sha256;
+pub mod compute_email_sha256;
+pub mod fuzzy_hash;
+pub mod similar_files;
+pub mod detect_file_type;
+pub mod scan_summary;
+pub mod write_report;
+pub mod scan_progress;
+pub mod metrics;
+pub mod parse_duration;
+pub mod hash_cache;
+pub mod path_encoding;
+pub mod unicode_paths;
+pub mod long_path;
+pub mod alternate_streams;
+pub mod clone_file;
+pub mod dedupe_range;
+pub mod shared_extents;
+pub mod network_filesystem;
+pub mod scan_source;
+pub mod iso_image;
+pub mod archive_members;
+pub mod known_hashes;
+pub mod breadth_first;
+pub mod hash_key;
+pub mod prefilter;
+pub mod physical_order;
+pub mod device_scheduler;
+pub mod open_files;
+pub mod memory_usage;
+pub mod cache_hints;
+pub mod quick_check;
+pub mod hash_algorithm;
+pub mod retry;
+pub mod locked_file;
+pub mod run_lock;
+pub mod scan_estimate;
+pub mod walk_filter;
+pub mod filter_expression;
+pub mod file_entry;
+pub mod duplicate_comparator;
+pub mod redact_paths;
+pub mod localize;
+pub mod capabilities;
+#[cfg(feature = "reports")]
+pub mod treemap;
+#[cfg(feature = "reports")]
+pub mod read_report;
+#[cfg(feature = "reports")]
+pub mod check_report;
+#[cfg(feature = "s3")]
+pub mod s3_source;
+#[cfg(feature = "sftp")]
+pub mod sftp_source;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+#[cfg(feature = "desktop")]
+pub mod desktop_notification;
+#[cfg(feature = "fuse")]
+pub mod duplicate_mount;
\ No newline at end of file
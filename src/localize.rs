@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The built-in English messages, also used for any message a translation lacks
+const EN_BUNDLE: &str = include_str!("../locales/en.ftl");
+
+/// Where translated bundles are looked for when `DUPEFILES_LOCALE_DIR` is not set at run
+/// time; packagers can set `DUPEFILES_LOCALE_DIR` at build time to change it
+const LOCALE_DIR: &str = match option_env!("DUPEFILES_LOCALE_DIR") {
+    Some(directory) => directory,
+    None => "/usr/share/dupefiles/locales",
+};
+
+/// The user-facing messages of one language, read from a bundle file.
+///
+/// Bundles use a small subset of the Fluent syntax: one `id = text` message per line,
+/// with `# comments` and blank lines ignored, and `{ $name }` placeholders replaced by
+/// the arguments of the message.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::localize::Bundle;
+///
+/// let bundle = Bundle::parse("# Greetings\ngreeting = Hallo { $name }!\n").unwrap();
+/// assert_eq!(bundle.format("greeting", &[("name", &"Welt")]), Some("Hallo Welt!".to_string()));
+/// assert_eq!(bundle.format("farewell", &[]), None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bundle {
+    messages: HashMap<String, String>,
+}
+
+impl Bundle {
+    /// Parses the text of a bundle.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The bundle, one `id = text` message per line.
+    ///
+    /// # Returns
+    ///
+    /// The bundle, or an error naming the first line that is not a message.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut messages = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, message) = line.split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'id = text'", number + 1))?;
+            messages.insert(id.trim().to_string(), message.trim().to_string());
+        }
+        Ok(Bundle { messages })
+    }
+
+    /// Returns the message `id` with its placeholders replaced by `args`, or `None`
+    /// when the bundle does not have it.
+    pub fn format(&self, id: &str, args: &[(&str, &dyn fmt::Display)]) -> Option<String> {
+        let mut message = self.messages.get(id)?.clone();
+        for (name, value) in args {
+            let value = value.to_string();
+            message = message.replace(&format!("{{ ${} }}", name), &value)
+                .replace(&format!("{{${}}}", name), &value);
+        }
+        Some(message)
+    }
+}
+
+/// The language messages are shown in, from the first of `DUPEFILES_LANG`, `LC_ALL`,
+/// `LC_MESSAGES` and `LANG` that is set, without its encoding and modifier, or `None`
+/// for the C and POSIX locales.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::localize::language_of;
+///
+/// assert_eq!(language_of("pt_BR.UTF-8"), Some("pt_BR".to_string()));
+/// assert_eq!(language_of("de_DE@euro"), Some("de_DE".to_string()));
+/// assert_eq!(language_of("C.UTF-8"), None);
+/// ```
+pub fn language_of(locale: &str) -> Option<String> {
+    let language = locale.split(['.', '@']).next().unwrap_or_default();
+    (!language.is_empty() && language != "C" && language != "POSIX").then(|| language.to_string())
+}
+
+/// Read the bundle of the user's language, trying the bundle of the language without
+/// its region (`pt` for `pt_BR`) when there is none for the region
+fn load_translation() -> Option<Bundle> {
+    let locale = ["DUPEFILES_LANG", "LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())?;
+    let language = language_of(&locale)?;
+    if language == "en" || language.starts_with("en_") {
+        return None;
+    }
+    let directory = env::var_os("DUPEFILES_LOCALE_DIR").map_or_else(|| PathBuf::from(LOCALE_DIR), PathBuf::from);
+    let base = language.split('_').next().unwrap_or_default();
+    [language.as_str(), base].iter()
+        .find_map(|name| load_bundle(&directory.join(format!("{}.ftl", name))))
+}
+
+/// Read a bundle file, warning when it exists but cannot be used
+fn load_bundle(path: &Path) -> Option<Bundle> {
+    let text = fs::read_to_string(path).ok()?;
+    Bundle::parse(&text)
+        .inspect_err(|e| {
+            // `message` is not ready while the translation loads
+            let english = Bundle::parse(EN_BUNDLE).unwrap_or_default();
+            let warning = english.format("translation-ignored", &[("file", &path.display()), ("error", e)]);
+            eprintln!("{}", warning.unwrap_or_default());
+        })
+        .ok()
+}
+
+/// Returns the user-facing message `id` in the user's language, falling back to
+/// English when it is not translated, with its `{ $name }` placeholders replaced by
+/// `args`.
+///
+/// The translation is looked for once, in `<language>.ftl` in the directory named by
+/// the `DUPEFILES_LOCALE_DIR` environment variable, or `/usr/share/dupefiles/locales`.
+///
+/// # Arguments
+///
+/// * `id` - The identifier of the message in the bundles.
+/// * `args` - The values of the placeholders, by name.
+///
+/// # Returns
+///
+/// The message, or `id` itself when no bundle has it.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::localize::message;
+///
+/// assert_eq!(message("summary-by-directory", &[]), "Wasted space by directory:");
+/// ```
+pub fn message(id: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    static BUNDLES: OnceLock<(Bundle, Option<Bundle>)> = OnceLock::new();
+    let (english, translation) = BUNDLES.get_or_init(|| {
+        let english = Bundle::parse(EN_BUNDLE).expect("the built-in en bundle is valid");
+        // Tests compare messages with their English text
+        let translation = if cfg!(test) { None } else { load_translation() };
+        (english, translation)
+    });
+    translation.as_ref()
+        .and_then(|bundle| bundle.format(id, args))
+        .or_else(|| english.format(id, args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_en_bundle_is_complete() {
+        let bundle = Bundle::parse(EN_BUNDLE).unwrap();
+        assert!(!bundle.messages.is_empty());
+        assert!(bundle.messages.values().all(|message| !message.is_empty()));
+        assert_eq!(message("missing-message", &[]), "missing-message");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Bundle::parse("greeting Hallo").unwrap_err(), "line 1: expected 'id = text'");
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::Path;
+
+/// Returns whether another process holds a lock on `path` that keeps it from being read
+/// consistently: an exclusive `flock` lock, or a POSIX write lock on any part of it, as
+/// taken by databases and programs writing the file.
+///
+/// The check briefly takes and releases a shared `flock` lock, and only sees advisory
+/// locks: a file written without locking is not reported. On Windows, where locks are
+/// mandatory, a file is locked when a shared lock cannot be taken on it.
+///
+/// # Arguments
+///
+/// * `path` - The file to check.
+///
+/// # Returns
+///
+/// Whether the file is locked, or an error if it cannot be opened or checked.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::{self, File};
+/// use dupefiles::locked_file::is_locked;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("database.db");
+/// fs::write(&path, "rows").unwrap();
+/// assert!(!is_locked(&path).unwrap());
+///
+/// let writer = File::open(&path).unwrap();
+/// writer.lock().unwrap();
+/// assert!(is_locked(&path).unwrap());
+/// ```
+pub fn is_locked(path: &Path) -> io::Result<bool> {
+    let file = File::open(path)?;
+    #[cfg(unix)]
+    {
+        unix::is_locked(&file)
+    }
+    #[cfg(not(unix))]
+    {
+        // Closing the file releases the shared lock when it is granted
+        match file.try_lock_shared() {
+            Ok(()) => Ok(false),
+            Err(std::fs::TryLockError::WouldBlock) => Ok(true),
+            Err(std::fs::TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// Returns whether the file at `path` no longer has the size and modification time of
+/// `before`, as when it was written while being read, or cannot be queried any more.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::locked_file::changed_since;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("growing.log");
+/// fs::write(&path, "first line").unwrap();
+/// let before = fs::metadata(&path).unwrap();
+/// assert!(!changed_since(&path, &before));
+///
+/// fs::write(&path, "first line, second line").unwrap();
+/// assert!(changed_since(&path, &before));
+/// ```
+pub fn changed_since(path: &Path, before: &Metadata) -> bool {
+    match std::fs::metadata(path) {
+        Ok(after) => after.len() != before.len() || after.modified().ok() != before.modified().ok(),
+        Err(_) => true,
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    pub fn is_locked(file: &File) -> io::Result<bool> {
+        let fd = file.as_raw_fd();
+
+        // Closing the file releases the shared lock when it is granted
+        if unsafe { libc::flock(fd, libc::LOCK_SH | libc::LOCK_NB) } != 0 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::EWOULDBLOCK) => Ok(true),
+                _ => Err(error),
+            };
+        }
+
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = libc::F_RDLCK as _;
+        lock.l_whence = libc::SEEK_SET as _;
+        if unsafe { libc::fcntl(fd, libc::F_GETLK, &mut lock) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // l_type is narrower than F_UNLCK on some platforms
+        Ok(i64::from(lock.l_type) != i64::from(libc::F_UNLCK))
+    }
+}
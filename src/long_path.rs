@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+/// Returns `path` in a form that is not limited to 260 characters on Windows.
+///
+/// On Windows the path is made absolute and given the `\\?\` prefix (`\\?\UNC\` for
+/// network shares), which lifts the legacy `MAX_PATH` limit for the path and for every
+/// path built by joining onto it. Paths that already carry a prefix are left alone. On
+/// other platforms the path is returned unchanged.
+///
+/// # Arguments
+///
+/// * `path` - The path to convert.
+///
+/// # Returns
+///
+/// The converted path.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::long_path::long_path;
+///
+/// # #[cfg(not(windows))]
+/// assert_eq!(long_path(Path::new("/data/a.txt")), Path::new("/data/a.txt"));
+/// ```
+pub fn long_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match absolute.to_str() {
+        Some(absolute) => PathBuf::from(with_long_path_prefix(absolute)),
+        None => absolute,
+    }
+}
+
+/// Add the verbatim prefix to an absolute, normalized Windows path
+fn with_long_path_prefix(path: &str) -> String {
+    let is_drive_absolute = path.as_bytes().get(1) == Some(&b':')
+        && matches!(path.as_bytes().get(2), Some(b'\\') | Some(b'/'));
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        path.to_string()
+    } else if let Some(share) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", share)
+    } else if is_drive_absolute {
+        // Verbatim paths are passed to the file system as is, so only backslashes separate components
+        format!(r"\\?\{}", path.replace('/', r"\"))
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drive_path() {
+        assert_eq!(with_long_path_prefix(r"C:\Users\me/photos"), r"\\?\C:\Users\me\photos");
+    }
+
+    #[test]
+    fn test_unc_path() {
+        assert_eq!(with_long_path_prefix(r"\\server\share\photos"), r"\\?\UNC\server\share\photos");
+    }
+
+    #[test]
+    fn test_prefixed_path_unchanged() {
+        assert_eq!(with_long_path_prefix(r"\\?\C:\photos"), r"\\?\C:\photos");
+        assert_eq!(with_long_path_prefix(r"relative\photos"), r"relative\photos");
+    }
+}
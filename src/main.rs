@@ -1,21 +1,745 @@
-use std::fs::File;
+#![allow(clippy::io_other_error)]
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
 use std::io::{self, ErrorKind};
-use std::path::PathBuf;
-use std::time::Instant;
-use clap::Parser;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use clap::{ArgGroup, Parser, Subcommand};
 use anyhow::Result;
 use std::process;
 use std::thread;
 use std::panic;
 
-use dupefiles::find_duplicates::find_duplicates;
+use dupefiles::capabilities::{capabilities, CapabilitiesFormat};
+use dupefiles::find_duplicates::{find_duplicates, find_unique_files};
+use dupefiles::scan_source::{find_source_duplicates, ScanSource};
+use dupefiles::action_journal::{journaled, read_journal, undo_entry, ActionJournal, JournalStatus, UndoOutcome};
+use dupefiles::actions::{apply_action, Action};
+use dupefiles::clone_file::clone_supported;
+use dupefiles::confirm_action::{confirm, summarize_impact, ImpactSummary};
+use dupefiles::select_keeper::{select_keeper, KeepPolicy};
+use dupefiles::protect_list::ProtectList;
+use dupefiles::raw_pairs::paired_members;
+use dupefiles::sidecar_files::{handle_sidecars, sidecars, Sidecar, SidecarPolicy};
+use dupefiles::exec_hook::run_exec;
+use dupefiles::scan_options::ScanOptions;
+use dupefiles::filter_expression::FilterExpression;
+use dupefiles::scan_estimate::estimate_scan;
+use dupefiles::read_report::read_report;
+use dupefiles::check_report::{check_report, CheckOptions};
+use dupefiles::duplicate_group::DuplicateGroup;
+use dupefiles::is_duplicate_file::{is_duplicate_file, is_same_file};
+use dupefiles::parse_size::parse_size;
+use dupefiles::parse_duration::parse_duration;
+use dupefiles::hash_cache::HashCache;
+use dupefiles::known_hashes::{is_manifest_entry, KnownHashes};
+use dupefiles::long_path::long_path;
+use dupefiles::run_lock::RunLock;
+use dupefiles::iso_image::is_image_member;
+use dupefiles::network_filesystem::is_network_filesystem;
+use dupefiles::open_files::{raise_open_file_limit, OpenFileBudget};
+use dupefiles::scan_summary::summarize;
+use dupefiles::metrics::{serve_metrics, write_prometheus, DaemonMetrics, ScanMetrics};
+#[cfg(feature = "webhook")]
+use dupefiles::webhook::{post_notification, ScanNotification};
+#[cfg(feature = "desktop")]
+use dupefiles::desktop_notification::show_scan_notification;
+#[cfg(feature = "fuse")]
+use dupefiles::duplicate_mount::mount_groups;
+use dupefiles::similar_files::{cluster_similar, find_similar, write_clusters_csv, write_clusters_json, write_similar_csv, write_similar_json, SimilarPair};
+use dupefiles::treemap::{build_treemap, write_treemap_csv, write_treemap_json};
+use dupefiles::write_report::{group_entries, write_csv, write_hashdeep, write_json, write_ncdu, OutputFormat, PairMode};
+use dupefiles::human_readable_size::human_readable_size;
+use dupefiles::localize::message;
+use dupefiles::redact_paths::{random_salt, redact_directory, redact_file, redact_groups};
+use dupefiles::unicode_paths::{nfc_path, normalize_groups};
+use dupefiles::quick_check::QuickCheck;
+use dupefiles::hash_algorithm::HashAlgorithm;
+use dupefiles::compute_gzip_sha256::CompressionFormat;
+use dupefiles::scan_progress::{ProgressFormat, ScanProgress};
+use signal_hook::consts::SIGINT;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Exit code used when the scan is interrupted with Ctrl-C (128 + SIGINT)
+const EXIT_INTERRUPTED: i32 = 130;
 
 /// Duplicate file finder - finds duplicate files in a directory tree
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 #[command(arg_required_else_help = true)]
-struct Args {
-    /// Directory to scan for duplicates
+#[command(after_help = "`dupefiles [OPTIONS] <DIRECTORY>` is a shortcut for `dupefiles scan [OPTIONS] <DIRECTORY>`.")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Print the optional features built in and the SHA256 implementation used on this CPU, then exit; `--capabilities json` describes everything this build supports on this platform
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "text", exclusive = true)]
+    capabilities: Option<CapabilitiesFormat>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Find the duplicate files in a directory tree, the default when no command is given
+    Scan(Box<ScanArgs>),
+    /// Write the duplicates of an earlier report again, in another format or filtered
+    Report(ReportArgs),
+    /// Apply an action to the duplicates of an earlier report without scanning again
+    Clean(CleanArgs),
+    /// Inspect or prune a hash cache written with `scan --cache`
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Check that the files of an older report are unchanged before acting on it
+    #[command(visible_alias = "check")]
+    Verify(VerifyArgs),
+    /// Walk a directory tree and estimate how long a scan would take, hashing only a sample to measure the throughput
+    Estimate(EstimateArgs),
+    /// Reverse the changes recorded in a journal written with --journal, restoring the files removed or hard linked as copies of their own
+    Undo(UndoArgs),
+    /// Show the duplicate groups of a report as a read-only file system, with a directory of symlinks to the copies of each group (requires the fuse feature)
+    Mount(MountArgs),
+}
+
+/// The names of the subcommands and their aliases, which are not taken for a directory to scan
+const COMMAND_NAMES: [&str; 10] = ["scan", "report", "clean", "cache", "verify", "check", "estimate", "undo", "mount", "help"];
+
+/// Insert `scan` in front of the arguments when they do not start with a subcommand, so
+/// `dupefiles [OPTIONS] <DIRECTORY>` keeps scanning `DIRECTORY`
+fn with_default_command(args: Vec<OsString>) -> Vec<OsString> {
+    let first = args.get(1).and_then(|arg| arg.to_str());
+    let top_level = first.is_none_or(|arg| {
+        COMMAND_NAMES.contains(&arg) || ["-h", "--help", "-V", "--version", "--capabilities"].contains(&arg)
+            || arg.starts_with("--capabilities=")
+    });
+    if top_level {
+        return args;
+    }
+    let mut args = args;
+    args.insert(1, OsString::from("scan"));
+    args
+}
+
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    /// Directory to scan for duplicates, s3://bucket/prefix to scan objects in S3 (requires the s3 feature)
+    /// or sftp://user@host/path to scan a remote directory over SSH (requires the sftp feature)
+    #[arg(value_name = "DIRECTORY")]
+    directory: PathBuf,
+
+    /// Optional comma-separated list of file extensions to filter by (e.g., "mp4,jpg")
+    #[arg(short, long)]
+    extensions: Option<String>,
+
+    /// Optional comma-separated list of file extensions to skip (e.g., "tmp,partial,log")
+    #[arg(short = 'x', long)]
+    exclude_extensions: Option<String>,
+
+    /// Match --extensions against the file type detected from magic bytes instead of the file name
+    #[arg(long, requires = "extensions")]
+    by_content: bool,
+
+    /// Only scan the files matching this expression, e.g. "size > 100MiB && ext in (mp4, mkv) && mtime < 2023-01-01"
+    #[arg(long, value_name = "EXPRESSION")]
+    filter: Option<FilterExpression>,
+
+    /// Optional output file path (if not specified, prints to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Also write the wasted space per directory to FILE for treemap visualizers, as CSV when FILE ends in .csv and JSON otherwise
+    #[arg(long, value_name = "FILE")]
+    treemap: Option<PathBuf>,
+
+    /// Also write the pairs of near duplicates, such as edited documents and images saved again, to FILE, as JSON when FILE ends in .json and CSV otherwise; they are never acted on
+    #[arg(long, value_name = "FILE")]
+    similar: Option<PathBuf>,
+
+    /// Lowest similarity of the files written by --similar, from 1 to 100
+    #[arg(long, value_name = "SCORE", requires = "similar", default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..=100))]
+    similarity: u32,
+
+    /// Write --similar as clusters of files similar to a representative file instead of every pair
+    #[arg(long, requires = "similar")]
+    cluster_similar: bool,
+
+    /// Replace every directory and file name in the report and treemap with a salted hash, keeping extensions and sizes, to share them without revealing names
+    #[arg(long)]
+    redact_paths: bool,
+
+    /// Salt of the hashes of --redact-paths (random by default), to redact names alike in several reports
+    #[arg(long, value_name = "SALT", requires = "redact_paths")]
+    redact_salt: Option<String>,
+
+    /// Write paths in the report and treemap in Unicode NFC, so names copied from macOS match those made elsewhere
+    #[arg(long)]
+    normalize_paths: bool,
+
+    /// Output format: csv, json, ncdu (an ncdu export of the wasted copies, for ncdu -f) or hashdeep (a hashdeep manifest of every file hashed)
+    #[arg(short, long, value_name = "FORMAT", default_value = "csv")]
+    format: OutputFormat,
+
+    /// CSV pairs to write: canonical pairs each duplicate with the first copy found, all with every earlier copy
+    #[arg(long, value_name = "MODE", default_value = "canonical")]
+    pairs: PairMode,
+
+    /// Write only the number of duplicates and the space they waste, in total and per extension, with no path, as CSV or JSON
+    #[arg(long, conflicts_with_all = ["treemap", "redact_paths"])]
+    stats_only: bool,
+
+    /// Write the files that have no duplicate anywhere in DIRECTORY instead of the duplicates, as CSV or JSON, to check nothing on a drive is the only copy before wiping it
+    #[arg(long, conflicts_with_all = ["stats_only", "treemap", "similar", "cross_dir_only", "same_dir_only", "delete", "trash", "hardlink", "reflink", "dedupe_ioctl", "exec"])]
+    unique: bool,
+
+    /// Report hashing progress on stderr: json emits one event per second with counts, bytes, current path and ETA
+    #[arg(long, value_name = "FORMAT")]
+    progress: Option<ProgressFormat>,
+
+    /// Print how long each phase of the scan took (walking, hashing, verifying, writing the report) and the files and bytes it went through, as a JSON object on stderr
+    #[arg(long)]
+    timings: bool,
+
+    /// Also write the figures of the scan to FILE in the Prometheus text format, for the textfile collector of node_exporter
+    #[arg(long, value_name = "FILE")]
+    metrics_file: Option<PathBuf>,
+
+    /// Keep running as a daemon, scanning DIRECTORY again every --rescan-interval, and serve the figures of the scans at http://ADDRESS/metrics for Prometheus to scrape
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["delete", "trash", "hardlink", "reflink", "dedupe_ioctl", "exec"])]
+    serve_metrics: Option<SocketAddr>,
+
+    /// How long the daemon waits after a scan before the next one with --serve-metrics (e.g., "30m", "6h")
+    #[arg(long, value_name = "DURATION", default_value = "1h", value_parser = parse_duration, requires = "serve_metrics")]
+    rescan_interval: Duration,
+
+    /// POST a JSON summary of the scan (duplicates found, wasted bytes, report location) to URL when it finishes or fails (requires the webhook feature)
+    #[arg(long, value_name = "URL")]
+    notify_url: Option<String>,
+
+    /// Show a desktop notification with the duplicates found when the scan completes, to switch away during long scans (requires the desktop feature)
+    #[arg(long)]
+    notify_desktop: bool,
+
+    /// Skip files smaller than SIZE (e.g., "64KiB"), which waste little space; 0 scans every non-empty file
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "1KiB")]
+    min_size: u64,
+
+    /// Only report duplicate sets with at least this many copies
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    min_group_size: usize,
+
+    /// Only report duplicate sets wasting at least this much space (e.g., "100MiB")
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    min_wasted: Option<u64>,
+
+    /// Only report duplicate sets spanning several directories, ignoring copies that all live in the same directory
+    #[arg(long, conflicts_with = "same_dir_only")]
+    cross_dir_only: bool,
+
+    /// Only report copies living in the same directory, such as "file (1).jpg" next to "file.jpg", ignoring copies in other directories
+    #[arg(long)]
+    same_dir_only: bool,
+
+    /// Stop the scan after this long (e.g., "90s", "30m", "2h") and report what was found so far
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    max_runtime: Option<Duration>,
+
+    /// Stop after finding this many files to hash and report the duplicates among them
+    #[arg(long, value_name = "N")]
+    max_files: Option<usize>,
+
+    /// Stop after reading SIZE (e.g., "50GiB") to hash files and report the duplicates found so far; hashes reused from --cache are not counted
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_bytes: Option<u64>,
+
+    /// Also compare the NTFS alternate data streams of every file, reported as file:stream (Windows only)
+    #[arg(long)]
+    alternate_streams: bool,
+
+    /// Also compare the files stored in ISO 9660 disc images (.iso), reported as image.iso/path/in/image
+    #[arg(long)]
+    scan_images: bool,
+
+    /// Also compare the files stored in tar and zip archives (.tar, .tar.gz, .tgz, .zip), reported as archive.zip/path/in/archive
+    #[arg(long)]
+    scan_archives: bool,
+
+    /// Only report files on disk whose content is also stored in an archive of the tree, listing the archived copy first so it is kept; implies --scan-archives
+    #[arg(long)]
+    extracted_copies: bool,
+
+    /// Walk the tree breadth-first, so shallow directories are hashed and reported before deep ones
+    #[arg(long)]
+    bfs: bool,
+
+    /// Do not descend into network file systems (NFS, SMB/CIFS, SSHFS, ...) mounted below DIRECTORY
+    #[arg(long)]
+    skip_network: bool,
+
+    /// Drop files whose size and first 4 KiB match no other file using a Bloom filter of this size (e.g., "64MiB") before hashing; walks the tree twice
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    prefilter: Option<u64>,
+
+    /// Keep the files found in memory below about half this size (e.g., "2GiB"), spilling them to a temporary file past it instead of running out of memory
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    memory_limit: Option<u64>,
+
+    /// Hash files in the order of their data on disk, so hard drives read mostly sequentially instead of seeking
+    #[arg(long, conflicts_with = "bfs")]
+    physical_order: bool,
+
+    /// Hash N files at a time on each SSD, in parallel across devices; hard drives are still read one file at a time so they do not thrash
+    #[arg(short = 'j', long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// Hash the largest files sharing their size with another file first, so the biggest duplicates are reported even when the scan stops early
+    #[arg(long, conflicts_with_all = ["bfs", "physical_order"])]
+    largest_first: bool,
+
+    /// Drop every file from the page cache once hashed, so a full-disk scan does not evict the cache of other programs (Linux only)
+    #[arg(long)]
+    drop_cache: bool,
+
+    /// Hash files with direct I/O (O_DIRECT on Linux, F_NOCACHE on macOS), bypassing the page cache on dedicated scans of very large datasets
+    #[arg(long)]
+    direct_io: bool,
+
+    /// Before hashing files of the same size with SHA256, compare them by a CRC32 checksum of their first, middle and last 16 KiB (sampled) or of the whole file (full)
+    #[arg(long, value_name = "MODE")]
+    quick_check: Option<QuickCheck>,
+
+    /// Group files by their first, middle and last SIZE bytes (default "64KiB") and only read whole the files matching another one, to confirm them; same as --quick-check sampled with a chosen sample size
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, num_args = 0..=1, default_missing_value = "64KiB", conflicts_with = "quick_check")]
+    quick: Option<u64>,
+
+    /// Also compute these digests (md5, sha1) while reading each file and report them with every duplicate set
+    #[arg(long, value_name = "ALGORITHMS", value_delimiter = ',', conflicts_with_all = ["audio_content", "image_content", "email_content", "normalize_text", "decompress"])]
+    also_hash: Vec<HashAlgorithm>,
+
+    /// Try reading a file this many more times after a transient error (I/O error, timeout, stale NFS handle) before reporting it as failed
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: u32,
+
+    /// Wait this long before the first retry, doubling the wait before each following one
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "100ms")]
+    retry_delay: Duration,
+
+    /// Skip files locked by another process or modified while being hashed, such as open databases and growing logs
+    #[arg(long)]
+    skip_busy: bool,
+
+    /// Compare files larger than SIZE (e.g., "20GiB") by their size and their first, middle and last MiB only, reporting them as probable duplicates
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with = "also_hash")]
+    sample_above: Option<u64>,
+
+    /// Reuse the hashes of unchanged files from this cache and save the updated cache after the scan
+    #[arg(long, value_name = "FILE")]
+    cache: Option<PathBuf>,
+
+    /// Also compare the files with those listed in this sha256sum or hashdeep manifest, written elsewhere such as on an offline archive, reporting the files already there; may be repeated
+    #[arg(long, value_name = "MANIFEST", conflicts_with_all = ["audio_content", "image_content", "email_content", "normalize_text", "decompress", "sample_above"])]
+    known_hashes: Vec<PathBuf>,
+
+    /// Compare MP3 and FLAC files by their audio frames only, ignoring tags and artwork
+    #[arg(long)]
+    audio_content: bool,
+
+    /// Compare JPEG and PNG files by their image payload only, ignoring EXIF and other metadata
+    #[arg(long)]
+    image_content: bool,
+
+    /// Compare Maildir, .eml and mbox messages by their Message-ID and body only, ignoring the other headers
+    #[arg(long)]
+    email_content: bool,
+
+    /// Compare text files with CRLF and LF line endings treated as equal
+    #[arg(long)]
+    normalize_text: bool,
+
+    /// With --normalize-text, also ignore spaces and tabs at the end of each line
+    #[arg(long, requires = "normalize_text")]
+    trim_trailing_whitespace: bool,
+
+    /// Compare files compressed in these formats (gz) by their decompressed content, so log.1.gz matches log.1
+    #[arg(long, value_name = "FORMATS", value_delimiter = ',', conflicts_with = "normalize_text")]
+    decompress: Vec<CompressionFormat>,
+
+    #[command(flatten)]
+    actions: ActionArgs,
+}
+
+/// The action applied to duplicates and the rules choosing the copies it spares, shared
+/// by scans and `clean`
+#[derive(clap::Args, Debug)]
+struct ActionArgs {
+    /// Permanently delete every duplicate, keeping the copy chosen by --keep
+    #[arg(long, conflicts_with_all = ["trash", "hardlink", "reflink", "dedupe_ioctl"])]
+    delete: bool,
+
+    /// Move every duplicate to the trash, keeping the copy chosen by --keep
+    #[arg(long, conflicts_with_all = ["hardlink", "reflink", "dedupe_ioctl"])]
+    trash: bool,
+
+    /// Replace every duplicate with a hard link to the copy chosen by --keep
+    #[arg(long, conflicts_with_all = ["reflink", "dedupe_ioctl"])]
+    hardlink: bool,
+
+    /// Replace every duplicate with a copy-on-write clone of the copy chosen by --keep (APFS on macOS)
+    #[arg(long, conflicts_with = "dedupe_ioctl")]
+    reflink: bool,
+
+    /// Have the kernel share each duplicate's data with the copy chosen by --keep after verifying it matches (Btrfs/XFS on Linux)
+    #[arg(long)]
+    dedupe_ioctl: bool,
+
+    /// Which copy of each duplicate set to keep: first, newest or oldest (by modification time)
+    #[arg(long, value_name = "POLICY", default_value = "first")]
+    keep: KeepPolicy,
+
+    /// Keep the copy located in this directory; repeat to list directories in priority order
+    #[arg(long, value_name = "DIR")]
+    prefer: Vec<PathBuf>,
+
+    /// Never delete or replace files whose absolute path matches this glob; may be repeated
+    #[arg(long, value_name = "GLOB")]
+    protect: Vec<String>,
+
+    /// Read protect globs from a file, one per line
+    #[arg(long, value_name = "FILE")]
+    protect_file: Option<PathBuf>,
+
+    /// With --delete or --trash, also handle the sidecars (.xmp, .srt, .nfo, .aae) of each duplicate: remove them with it, or move them next to the kept copy
+    #[arg(long, value_name = "POLICY")]
+    sidecars: Option<SidecarPolicy>,
+
+    /// Run this shell command for each duplicate set, with {group} replaced by its paths (without {group}, the set is written to the command's stdin as JSON)
+    #[arg(long, value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// Apply the action without asking for confirmation, except for copies matched by their decoded content
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Print what the action would do to each duplicate without changing any file
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Append every file the action deletes, trashes, replaces or moves to FILE, one JSON object per line with the copy kept and the metadata it had, for `dupefiles undo`
+    #[arg(long, value_name = "FILE")]
+    journal: Option<PathBuf>,
+
+    /// When another dupefiles run is using the same --cache or --journal, wait for it to finish instead of failing
+    #[arg(long)]
+    wait: bool,
+}
+
+impl ActionArgs {
+    /// The action selected, if any
+    fn action(&self) -> Option<Action> {
+        if self.delete {
+            Some(Action::Delete)
+        } else if self.trash {
+            Some(Action::Trash)
+        } else if self.hardlink {
+            Some(Action::Hardlink)
+        } else if self.reflink {
+            Some(Action::Reflink)
+        } else if self.dedupe_ioctl {
+            Some(Action::Dedupe)
+        } else {
+            None
+        }
+    }
+
+    /// Resolve the preferred directories and load the protect globs, so mistakes are
+    /// reported before anything is scanned. Returns `None` without an action
+    fn plan(&self) -> Result<Option<ActionPlan>> {
+        // Taken before scanning, so a run sharing the journal never acts on the same files
+        let journal_lock = match (&self.journal, self.action()) {
+            (Some(journal), Some(_)) if !self.dry_run => Some(lock_shared_file(journal, self.wait)?),
+            _ => None,
+        };
+
+        // Member paths are canonical, so the preferred directories must be too
+        let preferred_dirs = self.prefer.iter().map(|dir| {
+            dir.canonicalize().map_err(|e| {
+                io::Error::new(ErrorKind::NotFound, message("error-preferred-directory", &[("directory", &dir.display()), ("error", &e)]))
+            })
+        }).collect::<Result<Vec<PathBuf>, io::Error>>()?;
+
+        let mut protect_list = ProtectList::default();
+        for pattern in &self.protect {
+            protect_list.add(pattern)?;
+        }
+        if let Some(path) = &self.protect_file {
+            protect_list.add_file(path)?;
+        }
+
+        Ok(self.action().map(|action| ActionPlan {
+            action,
+            keep: self.keep,
+            preferred_dirs,
+            protect_list,
+            raw_pairs: false,
+            sidecars: self.sidecars,
+            yes: self.yes,
+            dry_run: self.dry_run,
+            journal: self.journal.clone(),
+            _journal_lock: journal_lock,
+        }))
+    }
+}
+
+/// Run the `--exec` command for every group, or print what would be run with `dry_run`
+fn run_exec_hooks(command: &str, groups: &[DuplicateGroup], dry_run: bool) {
+    for group in groups {
+        let file = group.files[0].display();
+        if dry_run {
+            println!("{}", message("exec-would", &[("command", &command), ("file", &file)]));
+            continue;
+        }
+        match run_exec(command, group) {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("{}", message("exec-failed", &[("file", &file), ("status", &status)])),
+            Err(e) => eprintln!("{}", message("exec-failed", &[("file", &file), ("status", &e)])),
+        }
+    }
+}
+
+/// An action to apply to duplicates, as given by `ActionArgs`
+struct ActionPlan {
+    action: Action,
+    keep: KeepPolicy,
+    preferred_dirs: Vec<PathBuf>,
+    protect_list: ProtectList,
+    /// Keep both halves of every RAW+JPEG pair, in photo mode (`--image-content`)
+    raw_pairs: bool,
+    /// What happens to the sidecars of the duplicates deleted or trashed
+    sidecars: Option<SidecarPolicy>,
+    yes: bool,
+    dry_run: bool,
+    /// Where every change is recorded
+    journal: Option<PathBuf>,
+    /// Held until the plan is applied, when there is a journal
+    _journal_lock: Option<RunLock>,
+}
+
+/// Take the lock guarding a cache or journal shared between runs, waiting for the run
+/// holding it with `wait`, or failing with an error naming that run otherwise
+fn lock_shared_file(path: &Path, wait: bool) -> Result<RunLock> {
+    let failed = |e: io::Error| io::Error::new(e.kind(), message("error-lock-failed", &[("file", &RunLock::lock_path(path).display()), ("error", &e)]));
+    if let Some(lock) = RunLock::try_acquire(path).map_err(failed)? {
+        return Ok(lock);
+    }
+    let process = RunLock::holder(path).map_or_else(|| "?".to_string(), |pid| pid.to_string());
+    if !wait {
+        anyhow::bail!("{}", message("lock-busy", &[("file", &path.display()), ("process", &process)]));
+    }
+    eprintln!("{}", message("lock-waiting", &[("file", &path.display()), ("process", &process)]));
+    Ok(RunLock::acquire(path).map_err(failed)?)
+}
+
+/// Check that the members of a group still match what the scan found before an action
+/// is applied to any of them: every member of `pairs` with known metadata still has its
+/// size, modification time, device and inode, and with `compare_content` every duplicate
+/// still hashes like the kept copy. Returns why the group must be skipped otherwise
+fn recheck_group(group: &DuplicateGroup, pairs: &[(usize, &Path, &Path)], compare_content: bool) -> Result<(), String> {
+    let file = group.files[0].display();
+    let members = pairs.iter().flat_map(|(_, keeper, duplicate)| [*keeper, *duplicate]);
+    for member in members.collect::<BTreeSet<_>>() {
+        // Files in disc images and archives have no metadata of their own to compare
+        let Some(entry) = group.entries.iter().find(|entry| entry.path == member && entry.inode.is_some()) else {
+            continue;
+        };
+        match entry.is_unchanged() {
+            Ok(true) => {}
+            Ok(false) => return Err(message("plan-skip-group-changed", &[("file", &file), ("changed", &member.display())])),
+            Err(e) => return Err(message("plan-skip-group-unreadable", &[("file", &file), ("changed", &member.display()), ("error", &e)])),
+        }
+    }
+    if compare_content {
+        for (_, keeper, duplicate) in pairs {
+            match is_duplicate_file(keeper, duplicate) {
+                Ok(true) => {}
+                Ok(false) => return Err(message("plan-skip-group-differs", &[
+                    ("file", &file),
+                    ("duplicate", &duplicate.display()),
+                    ("keeper", &keeper.display()),
+                ])),
+                Err(e) => return Err(message("plan-skip-group-unreadable", &[("file", &file), ("changed", &duplicate.display()), ("error", &e)])),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Summarize the impact of an action on `duplicates` and on the sidecars listed for
+/// them, which leave with their file
+fn impact_with_sidecars(duplicates: &[&Path], sidecars: &HashMap<&Path, Vec<Sidecar>>) -> ImpactSummary {
+    let sidecar_paths = duplicates.iter()
+        .flat_map(|duplicate| sidecars.get(duplicate).into_iter().flatten())
+        .map(|sidecar| sidecar.path.as_path());
+    summarize_impact(&duplicates.iter().copied().chain(sidecar_paths).collect::<Vec<_>>())
+}
+
+/// Apply the action of `plan` to the duplicates of `groups` once confirmed on `input`,
+/// sparing the copy kept in each group, the protected files and, in photo mode, the
+/// halves of RAW+JPEG pairs. Copies matched by their decoded content are only deleted or
+/// trashed, and only once confirmed on their own, even with `--yes`. Right before a
+/// group is acted upon, its members are checked again with `recheck_group`, and the
+/// whole group is skipped when any of them changed since it was found
+fn apply_plan(plan: &ActionPlan, groups: &[DuplicateGroup], mut input: impl io::BufRead) -> Result<()> {
+    let action = plan.action;
+    // Pair every unprotected duplicate with the copy that is kept for its group
+    let mut duplicates: Vec<(usize, &Path, &Path)> = Vec::new();
+    for (group_index, group) in groups.iter().enumerate() {
+        if group.is_probable() {
+            eprintln!("{}", message("plan-skip-probable", &[("file", &group.files[0].display())]));
+            continue;
+        }
+        // Members matched by their decoded content hold different bytes, which linking
+        // them would replace with the bytes of the kept copy
+        if group.is_content_match() && !matches!(action, Action::Delete | Action::Trash) {
+            eprintln!("{}", message("plan-skip-content-match", &[("action", &action), ("file", &group.files[0].display())]));
+            continue;
+        }
+        if group.files.iter().all(|file| plan.protect_list.is_protected(file)) {
+            eprintln!("{}", message("plan-skip-protected", &[("file", &group.files[0].display())]));
+            continue;
+        }
+        let paired = if plan.raw_pairs { paired_members(&group.files) } else { Vec::new() };
+        if !paired.is_empty() && paired.len() == group.files.len() {
+            eprintln!("{}", message("plan-skip-raw-pairs", &[("file", &group.files[0].display())]));
+            continue;
+        }
+        // A half of a pair is kept anyway, so the copy kept is chosen among them, and
+        // files of manifests are only known by their hash, so a copy on disk is kept
+        let candidates: Vec<usize> = match paired.is_empty() {
+            true => (0..group.files.len()).collect(),
+            false => paired.clone(),
+        };
+        let candidates: Vec<usize> = candidates.into_iter().filter(|&index| !is_manifest_entry(&group.files[index])).collect();
+        if candidates.is_empty() {
+            eprintln!("{}", message("plan-skip-manifest", &[("file", &group.files[0].display())]));
+            continue;
+        }
+        let choices: Vec<PathBuf> = candidates.iter().map(|&index| group.files[index].clone()).collect();
+        let keeper = candidates[select_keeper(&choices, plan.keep, &plan.preferred_dirs)];
+        // Sharing data with the keeper again would change nothing
+        let shares_data = |file: &PathBuf| {
+            keeper == 0 && matches!(action, Action::Reflink | Action::Dedupe) && group.reflinked.contains(file)
+        };
+        for (index, file) in group.files.iter().enumerate() {
+            // Files in disc images are read-only, but may still be the kept copy
+            if index != keeper && !plan.protect_list.is_protected(file) && !paired.contains(&index) && !shares_data(file) && !is_image_member(file) {
+                duplicates.push((group_index, group.files[keeper].as_path(), file.as_path()));
+            }
+        }
+    }
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+    let targets: Vec<&Path> = duplicates.iter().map(|(_, _, duplicate)| *duplicate).collect();
+    // Sidecars only lose their file when the action removes it
+    let sidecar_policy = plan.sidecars.filter(|_| matches!(action, Action::Delete | Action::Trash));
+    // Listed once, so the sidecars handled are those confirmed
+    let duplicate_sidecars: HashMap<&Path, Vec<Sidecar>> = match sidecar_policy {
+        Some(_) => duplicates.iter().map(|(_, _, duplicate)| {
+            (*duplicate, sidecars(duplicate).unwrap_or_else(|e| {
+                eprintln!("{:#}", e);
+                Vec::new()
+            }))
+        }).collect(),
+        None => HashMap::new(),
+    };
+    let sidecars_of = |duplicate: &Path| duplicate_sidecars.get(duplicate).map_or(&[][..], Vec::as_slice);
+    let impact = |duplicates: &[&Path]| impact_with_sidecars(duplicates, &duplicate_sidecars);
+    if plan.dry_run {
+        for (_, keeper, duplicate) in &duplicates {
+            println!("{}", message("plan-would", &[
+                ("action", &action),
+                ("duplicate", &duplicate.display()),
+                ("keeper", &keeper.display()),
+            ]));
+            for sidecar in sidecars_of(duplicate) {
+                let (key, target) = match sidecar_policy {
+                    Some(SidecarPolicy::Move) => ("plan-would-move-sidecar", keeper.display()),
+                    _ => ("plan-would-remove-sidecar", duplicate.display()),
+                };
+                println!("{}", message(key, &[("action", &action), ("sidecar", &sidecar.path.display()), ("file", &target)]));
+            }
+        }
+        eprint!("{}", message("plan-dry-run", &[("action", &action), ("impact", &impact(&targets))]));
+        return Ok(());
+    }
+    if !plan.yes {
+        eprint!("{}", message("plan-about-to", &[("action", &action), ("impact", &impact(&targets))]));
+        eprint!("{} ", message("plan-confirm", &[]));
+        if !confirm(&mut input)? {
+            eprintln!("{}", message("plan-aborted", &[]));
+            return Ok(());
+        }
+    }
+    // Their bytes differ from the kept copy, so a blanket --yes is not enough
+    let content_matches: Vec<&Path> = duplicates.iter()
+        .filter(|(group_index, _, _)| groups[*group_index].is_content_match())
+        .map(|(_, _, duplicate)| *duplicate)
+        .collect();
+    if !content_matches.is_empty() {
+        eprint!("{}", message("plan-about-to-content-match", &[("action", &action), ("impact", &impact(&content_matches))]));
+        eprint!("{} ", message("plan-confirm", &[]));
+        if !confirm(&mut input)? {
+            eprintln!("{}", message("plan-skip-content-matches", &[]));
+            duplicates.retain(|(group_index, _, _)| !groups[*group_index].is_content_match());
+        }
+    }
+    let mut journal = plan.journal.as_deref().map(ActionJournal::open).transpose()?;
+    for pending in duplicates.chunk_by(|a, b| a.0 == b.0) {
+        let group = &groups[pending[0].0];
+        // Another name of the kept file, such as a different case on a case-insensitive
+        // volume, would take the kept copy with it
+        let pairs: Vec<(usize, &Path, &Path)> = pending.iter()
+            .filter(|(_, keeper, duplicate)| {
+                let same_file = is_same_file(keeper, duplicate).unwrap_or(false);
+                if same_file {
+                    eprintln!("{}", message("plan-skip-same-file", &[("duplicate", &duplicate.display()), ("keeper", &keeper.display())]));
+                }
+                !same_file
+            })
+            .copied()
+            .collect();
+        // Members compared by content only differ in their bytes, files in disc images
+        // cannot be hashed on their own, and the kernel compares the files itself when
+        // deduplicating them
+        let compare_content = !group.hash.contains(':') && action != Action::Dedupe
+            && !pairs.iter().any(|(_, keeper, _)| is_image_member(keeper));
+        if let Err(reason) = recheck_group(group, &pairs, compare_content) {
+            eprintln!("{}", reason);
+            continue;
+        }
+        for (_, keeper, duplicate) in &pairs {
+            let applied = journaled(journal.as_mut(), action.into(), duplicate, keeper, &group.hash, || {
+                apply_action(action, keeper, duplicate)
+            });
+            if let Err(e) = applied {
+                eprintln!("{:#}", e);
+                continue;
+            }
+            if let Some(policy) = sidecar_policy {
+                if let Err(e) = handle_sidecars(policy, action, keeper, sidecars_of(duplicate), journal.as_mut()) {
+                    eprintln!("{:#}", e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+struct EstimateArgs {
+    /// Directory whose scan is estimated
     #[arg(value_name = "DIRECTORY")]
     directory: PathBuf,
 
@@ -23,9 +747,457 @@ struct Args {
     #[arg(short, long)]
     extensions: Option<String>,
 
+    /// Optional comma-separated list of file extensions to skip (e.g., "tmp,partial,log")
+    #[arg(short = 'x', long)]
+    exclude_extensions: Option<String>,
+
+    /// Do not descend into network file systems (NFS, SMB/CIFS, SSHFS, ...) mounted below DIRECTORY
+    #[arg(long)]
+    skip_network: bool,
+
+    /// Only count the files matching this expression, as for scan --filter
+    #[arg(long, value_name = "EXPRESSION")]
+    filter: Option<FilterExpression>,
+
+    /// Skip files smaller than SIZE, as for scan --min-size
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "1KiB")]
+    min_size: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// Report written by an earlier scan, in CSV or JSON format
+    #[arg(value_name = "REPORT")]
+    report: PathBuf,
+
+    /// Only check this many files of the report, chosen at random
+    #[arg(long, value_name = "N")]
+    sample: Option<usize>,
+
+    /// Also hash the files checked and compare them with the hash of their duplicate set
+    #[arg(long)]
+    rehash: bool,
+}
+
+/// Print the files of a report that changed since it was written
+fn verify(args: &VerifyArgs) -> Result<()> {
+    let groups = read_report(&args.report)?;
+    let options = CheckOptions {
+        sample: args.sample,
+        rehash: args.rehash,
+        report_time: fs::metadata(&args.report).and_then(|metadata| metadata.modified()).ok(),
+    };
+    let result = check_report(&groups, &options);
+    for (file, staleness) in &result.stale {
+        println!("{}: {}", file.display(), staleness);
+    }
+    eprintln!("{}", message("verify-summary", &[("stale", &result.stale.len()), ("checked", &result.checked)]));
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+struct ReportArgs {
+    /// Report written by an earlier scan, in CSV or JSON format
+    #[arg(value_name = "REPORT")]
+    report: PathBuf,
+
     /// Optional output file path (if not specified, prints to stdout)
     #[arg(short, long)]
     output: Option<PathBuf>,
+
+    /// Output format: csv, json, ncdu (an ncdu export of the wasted copies, for ncdu -f) or hashdeep (a hashdeep manifest of every file hashed)
+    #[arg(short, long, value_name = "FORMAT", default_value = "csv")]
+    format: OutputFormat,
+
+    /// CSV pairs to write: canonical pairs each duplicate with the first copy found, all with every earlier copy
+    #[arg(long, value_name = "MODE", default_value = "canonical")]
+    pairs: PairMode,
+
+    /// Also write the wasted space per directory to FILE for treemap visualizers, as CSV when FILE ends in .csv and JSON otherwise
+    #[arg(long, value_name = "FILE")]
+    treemap: Option<PathBuf>,
+
+    /// Replace every directory and file name in the report and treemap with a salted hash, keeping extensions and sizes, to share them without revealing names
+    #[arg(long)]
+    redact_paths: bool,
+
+    /// Salt of the hashes of --redact-paths (random by default), to redact names alike in several reports
+    #[arg(long, value_name = "SALT", requires = "redact_paths")]
+    redact_salt: Option<String>,
+
+    /// Write paths in the report and treemap in Unicode NFC, so names copied from macOS match those made elsewhere
+    #[arg(long)]
+    normalize_paths: bool,
+
+    /// Directory the summary, ncdu export and treemap are relative to (defaults to the deepest directory holding every file)
+    #[arg(long, value_name = "DIRECTORY")]
+    root: Option<PathBuf>,
+
+    /// Only report duplicate sets with at least this many copies
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    min_group_size: usize,
+
+    /// Only report duplicate sets wasting at least this much space (e.g., "100MiB")
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    min_wasted: Option<u64>,
+
+    /// Only report duplicate sets spanning several directories, ignoring copies that all live in the same directory
+    #[arg(long, conflicts_with = "same_dir_only")]
+    cross_dir_only: bool,
+
+    /// Only report copies living in the same directory, such as "file (1).jpg" next to "file.jpg", ignoring copies in other directories
+    #[arg(long)]
+    same_dir_only: bool,
+}
+
+/// Write the groups of a report again with the output options of a scan, and its summary
+fn report(args: ReportArgs) -> Result<()> {
+    let mut groups = read_report(&args.report)?;
+    if args.same_dir_only {
+        groups = groups.iter().flat_map(DuplicateGroup::split_by_directory).collect();
+    }
+    let min_wasted = args.min_wasted.unwrap_or(0);
+    groups.retain(|group| {
+        group.files.len() >= args.min_group_size
+            && group.wasted_bytes() >= min_wasted
+            && (!args.cross_dir_only || group.spans_directories())
+    });
+    let root = args.root.unwrap_or_else(|| common_directory(&groups));
+
+    // Reports only name the extra digests they hold, so every digest found is written
+    let also_hash: Vec<HashAlgorithm> = groups.iter()
+        .flat_map(|group| group.digests.keys().copied())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let summary = summarize(&groups, &root);
+    let (groups, root) = if args.normalize_paths {
+        (normalize_groups(&groups), nfc_path(&root).into_owned())
+    } else {
+        (groups, root)
+    };
+    let salt = args.redact_paths.then(|| args.redact_salt.unwrap_or_else(random_salt));
+    let (groups, root) = match &salt {
+        Some(salt) => (redact_groups(&groups, salt), redact_directory(&root, salt)),
+        None => (groups, root),
+    };
+    let mut output_file = args.output.as_ref().map(|path| {
+        File::create(long_path(path)).map_err(|e| {
+            io::Error::new(ErrorKind::Other, message("error-create-output", &[("error", &e)]))
+        })
+    }).transpose()?;
+    let mut output = output_file.as_mut();
+    match args.format {
+        OutputFormat::Csv => write_csv(&mut output, &groups, args.pairs, &also_hash)?,
+        OutputFormat::Json => write_json(&mut output, &groups, &summarize(&groups, &root), &also_hash)?,
+        OutputFormat::Ncdu => write_ncdu(&mut output, &groups, &root)?,
+        OutputFormat::Hashdeep => write_hashdeep(&mut output, &group_entries(&groups), &root)?,
+    }
+    eprint!("{}", summary);
+
+    if let Some(path) = &args.treemap {
+        write_treemap(path, &groups, &root, false, salt.as_deref())?;
+    }
+    Ok(())
+}
+
+/// The deepest directory holding every file of the groups
+fn common_directory(groups: &[DuplicateGroup]) -> PathBuf {
+    let mut files = groups.iter().flat_map(|group| &group.files);
+    let Some(first) = files.next() else {
+        return PathBuf::from("/");
+    };
+    let mut common = first.parent().unwrap_or(first).to_path_buf();
+    for file in files {
+        while !file.starts_with(&common) && common.pop() {}
+    }
+    common
+}
+
+/// Write the treemap of the wasted space of the groups, as CSV when the file ends in .csv and JSON otherwise,
+/// with the paths in Unicode NFC if `normalize` and redacted with `salt` if given
+fn write_treemap(path: &Path, groups: &[DuplicateGroup], root: &Path, normalize: bool, salt: Option<&str>) -> Result<()> {
+    let mut file = File::create(long_path(path)).map_err(|e| {
+        io::Error::new(ErrorKind::Other, message("error-create-treemap", &[("error", &e)]))
+    })?;
+    let normalized;
+    let (groups, root) = if normalize {
+        normalized = (normalize_groups(groups), nfc_path(root).into_owned());
+        (normalized.0.as_slice(), normalized.1.as_path())
+    } else {
+        (groups, root)
+    };
+    let treemap = match salt {
+        Some(salt) => build_treemap(&redact_groups(groups, salt), &redact_directory(root, salt)),
+        None => build_treemap(groups, root),
+    };
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv")) {
+        write_treemap_csv(&mut file, &treemap)
+    } else {
+        write_treemap_json(&mut file, &treemap)
+    }
+}
+
+/// Post the summary of a scan to the webhook of --notify-url, only warning when it cannot,
+/// since the scan itself is done
+#[cfg(feature = "webhook")]
+fn notify_webhook(url: &str, notification: &ScanNotification) {
+    if let Err(e) = post_notification(url, notification) {
+        eprintln!("{:#}", e);
+    }
+}
+
+/// Write the metrics of a scan in the Prometheus text format, through a temporary file
+/// renamed into place so a collector never reads them half written
+fn write_metrics(path: &Path, metrics: &ScanMetrics) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = File::create(long_path(&temporary)).map_err(|e| {
+        io::Error::new(ErrorKind::Other, message("error-create-metrics", &[("error", &e)]))
+    })?;
+    write_prometheus(&mut file, metrics)?;
+    drop(file);
+    fs::rename(long_path(&temporary), long_path(path))?;
+    Ok(())
+}
+
+/// Write the pairs of near duplicates, or their clusters, as JSON when the file ends in .json
+/// and CSV otherwise, with their paths in NFC and redacted like the report
+fn write_similar(path: &Path, pairs: &[SimilarPair], cluster: bool, normalize: bool, salt: Option<&str>) -> Result<()> {
+    let mut file = File::create(long_path(path)).map_err(|e| {
+        io::Error::new(ErrorKind::Other, message("error-create-similar", &[("error", &e)]))
+    })?;
+    let rewrite = |path: &Path| {
+        let path = if normalize { nfc_path(path).into_owned() } else { path.to_path_buf() };
+        salt.map_or_else(|| path.clone(), |salt| redact_file(&path, salt))
+    };
+    let pairs: Vec<SimilarPair> = pairs.iter()
+        .map(|pair| SimilarPair { first: rewrite(&pair.first), second: rewrite(&pair.second), ..pair.clone() })
+        .collect();
+    let json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    match (cluster, json) {
+        (true, true) => write_clusters_json(&mut file, &cluster_similar(&pairs)),
+        (true, false) => write_clusters_csv(&mut file, &cluster_similar(&pairs)),
+        (false, true) => write_similar_json(&mut file, &pairs),
+        (false, false) => write_similar_csv(&mut file, &pairs),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Print how many files a hash cache holds and their total size
+    Info {
+        /// Hash cache file written with `scan --cache`
+        #[arg(value_name = "FILE")]
+        cache: PathBuf,
+    },
+    /// Drop the entries of files deleted or changed since they were hashed
+    Prune {
+        /// Hash cache file written with `scan --cache`
+        #[arg(value_name = "FILE")]
+        cache: PathBuf,
+
+        /// When another dupefiles run is using the cache, wait for it to finish instead of failing
+        #[arg(long)]
+        wait: bool,
+    },
+}
+
+/// Print or prune the entries of a hash cache
+fn cache(command: &CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::Info { cache } => {
+            let path = long_path(cache);
+            if !path.exists() {
+                anyhow::bail!("{}", message("cache-missing", &[("file", &cache.display())]));
+            }
+            let hash_cache = HashCache::load(&path)?;
+            println!("{}", message("cache-info", &[("files", &hash_cache.len()), ("size", &human_readable_size(hash_cache.total_size()))]));
+        }
+        CacheCommand::Prune { cache, wait } => {
+            let path = long_path(cache);
+            if !path.exists() {
+                anyhow::bail!("{}", message("cache-missing", &[("file", &cache.display())]));
+            }
+            let _lock = lock_shared_file(&path, *wait)?;
+            let mut hash_cache = HashCache::load(&path)?;
+            let pruned = hash_cache.prune();
+            hash_cache.save(&path)?;
+            println!("{}", message("cache-pruned", &[("pruned", &pruned), ("left", &hash_cache.len())]));
+        }
+    }
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new("action").required(true).multiple(true).args(["delete", "trash", "hardlink", "reflink", "dedupe_ioctl", "exec"])))]
+struct CleanArgs {
+    /// Report written by an earlier scan, in CSV or JSON format
+    #[arg(value_name = "REPORT")]
+    report: PathBuf,
+
+    #[command(flatten)]
+    actions: ActionArgs,
+}
+
+/// Apply an action to the duplicates of a report, leaving alone the files changed since
+fn clean(args: &CleanArgs) -> Result<()> {
+    let mut plan = args.actions.plan()?;
+    let mut groups = read_report(&args.report)?;
+    // Reports of scans in photo mode hold groups compared by their image payload
+    if let Some(plan) = &mut plan {
+        plan.raw_pairs = groups.iter().any(|group| group.hash.starts_with("image:"));
+    }
+
+    let options = CheckOptions {
+        report_time: fs::metadata(&args.report).and_then(|metadata| metadata.modified()).ok(),
+        ..Default::default()
+    };
+    let result = check_report(&groups, &options);
+    for (file, staleness) in &result.stale {
+        eprintln!("{}", message("clean-skip-stale", &[("file", &file.display()), ("reason", &staleness)]));
+    }
+    let stale: HashSet<&PathBuf> = result.stale.iter().map(|(file, _)| file).collect();
+    for group in &mut groups {
+        // Sharing data with a first member that is gone means nothing
+        if group.files.first().is_some_and(|first| stale.contains(first)) {
+            group.reflinked.clear();
+        }
+        group.files.retain(|file| !stale.contains(file));
+        group.entries.retain(|entry| !stale.contains(&entry.path));
+    }
+    groups.retain(|group| group.files.len() > 1);
+
+    if let Some(command) = &args.actions.exec {
+        run_exec_hooks(command, &groups, args.actions.dry_run);
+    }
+    match &plan {
+        Some(plan) => apply_plan(plan, &groups, io::stdin().lock()),
+        None => Ok(()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct UndoArgs {
+    /// Journal written by --journal
+    #[arg(value_name = "JOURNAL")]
+    journal: PathBuf,
+
+    /// Print what would be restored without changing any file
+    #[arg(long)]
+    dry_run: bool,
+
+    /// When another dupefiles run is using the journal, wait for it to finish instead of failing
+    #[arg(long)]
+    wait: bool,
+}
+
+/// Reverse the changes of a journal, the most recent first, reporting those that cannot be
+fn undo(args: &UndoArgs) -> Result<()> {
+    // A run appending to the journal may still be changing the files it lists
+    let _lock = (!args.dry_run).then(|| lock_shared_file(&args.journal, args.wait)).transpose()?;
+    let entries = read_journal(&args.journal)?;
+    let mut restored = 0;
+    for entry in entries.iter().rev() {
+        let (path, keeper) = (entry.path.display(), entry.keeper.display());
+        match undo_entry(entry, args.dry_run) {
+            Ok(UndoOutcome::Restored) if args.dry_run => println!("{}", message("undo-would", &[("file", &path), ("keeper", &keeper)])),
+            Ok(UndoOutcome::Restored) => {
+                println!("{}", message("undo-restored", &[("file", &path), ("keeper", &keeper)]));
+                restored += 1;
+            }
+            Ok(UndoOutcome::AlreadyUndone) => eprintln!("{}", message("undo-already", &[("file", &path)])),
+            Ok(UndoOutcome::NothingToUndo) => {}
+            Err(e) => eprintln!("{}", message("undo-failed", &[("file", &path), ("error", &format!("{:#}", e))])),
+        }
+    }
+    if !args.dry_run {
+        let changes = entries.iter().filter(|entry| entry.status != JournalStatus::Failed).count();
+        eprintln!("{}", message("undo-summary", &[("restored", &restored), ("changes", &changes)]));
+    }
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+struct MountArgs {
+    /// Report written by an earlier scan, in CSV or JSON format
+    #[arg(value_name = "REPORT")]
+    report: PathBuf,
+
+    /// Empty directory to mount the file system on
+    #[arg(value_name = "MOUNTPOINT")]
+    mountpoint: PathBuf,
+}
+
+/// Show the groups of a report as a file system until Ctrl-C or until it is unmounted
+#[cfg(feature = "fuse")]
+fn mount(args: &MountArgs) -> Result<()> {
+    if !args.mountpoint.is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            message("error-not-directory", &[("directory", &args.mountpoint.display())])
+        ).into());
+    }
+    let groups = read_report(&args.report)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGINT, stop.clone())?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, stop.clone())?;
+    eprintln!("{}", message("mount-ready", &[("groups", &groups.len()), ("mountpoint", &args.mountpoint.display())]));
+    mount_groups(&groups, &args.mountpoint, &stop)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "fuse"))]
+fn mount(_args: &MountArgs) -> Result<()> {
+    anyhow::bail!("{}", message("error-missing-feature", &[("support", &"FUSE"), ("feature", &"fuse")]));
+}
+
+/// Print what a scan of the directory would hash and roughly how long it would take
+fn estimate(args: &EstimateArgs) -> Result<()> {
+    if !args.directory.is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            message("error-not-directory", &[("directory", &args.directory.display())])
+        ).into());
+    }
+    let options = ScanOptions {
+        extensions: args.extensions.as_deref().map(parse_extensions),
+        exclude_extensions: args.exclude_extensions.as_deref().map(parse_extensions),
+        skip_network: args.skip_network,
+        walk_filter: args.filter.clone().map(FilterExpression::walk_filter),
+        min_size: args.min_size,
+        ..Default::default()
+    };
+    print!("{}", estimate_scan(&args.directory, &options)?);
+    Ok(())
+}
+
+/// Split a comma-separated extension list, dropping leading '*' and '.' from each entry
+fn parse_extensions(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim_start_matches('*')
+                  .trim_start_matches('.')
+                  .to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// URL schemes scanned through a `ScanSource` rather than as a local directory
+const REMOTE_SCHEMES: [&str; 2] = ["s3://", "sftp://"];
+
+/// Open the source of an `s3://bucket/prefix` or `sftp://user@host/path` URL
+fn remote_source(url: &str) -> Result<Box<dyn ScanSource>> {
+    if url.starts_with("s3://") {
+        #[cfg(feature = "s3")]
+        return Ok(Box::new(dupefiles::s3_source::S3Source::new(url)?));
+        #[cfg(not(feature = "s3"))]
+        anyhow::bail!("{}", message("error-missing-feature", &[("support", &"S3"), ("feature", &"s3")]));
+    }
+    #[cfg(feature = "sftp")]
+    return Ok(Box::new(dupefiles::sftp_source::SftpSource::new(url)?));
+    #[cfg(not(feature = "sftp"))]
+    anyhow::bail!("{}", message("error-missing-feature", &[("support", &"SFTP"), ("feature", &"sftp")]));
 }
 
 fn measure_elapsed_time<F>(f: F) -> String
@@ -34,7 +1206,7 @@ where
 {
     let start = Instant::now();
     if let Err(e) = f() {
-        eprintln!("Error: {}", e);
+        eprintln!("{}", message("error", &[("error", &e)]));
         process::exit(1);
     }
     let duration = start.elapsed();
@@ -49,6 +1221,26 @@ where
     }
 }
 
+/// Print what this build supports: the version, the optional features enabled and the
+/// SHA256 implementation as text, or every capability as JSON
+fn print_capabilities(format: CapabilitiesFormat) -> Result<()> {
+    let capabilities = capabilities();
+    match format {
+        CapabilitiesFormat::Text => {
+            // The actions and reports features are part of the command line build
+            let features: Vec<&str> = capabilities.features.iter().copied()
+                .filter(|feature| !["actions", "reports"].contains(feature))
+                .collect();
+            println!("dupefiles {}", capabilities.version);
+            let features = if features.is_empty() { message("capabilities-no-features", &[]) } else { features.join(", ") };
+            println!("{}", message("capabilities-features", &[("features", &features)]));
+            println!("{}", message("capabilities-sha256", &[("implementation", &capabilities.sha256)]));
+        }
+        CapabilitiesFormat::Json => println!("{}", serde_json::to_string_pretty(&capabilities)?),
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     // Set up custom panic handler
     panic::set_hook(Box::new(|panic_info| {
@@ -65,57 +1257,571 @@ fn main() -> Result<()> {
 
         let location = panic_info.location().unwrap();
 
-        eprintln!(
-            "thread '{}' panicked at '{}', {}:{}:{}",
-            thread_name,
-            msg,
-            location.file(),
-            location.line(),
-            location.column(),
-        );
+        eprintln!("{}", message("panic", &[
+            ("thread", &thread_name),
+            ("message", &msg),
+            ("file", &location.file()),
+            ("line", &location.line()),
+            ("column", &location.column()),
+        ]));
         process::exit(1);
     }));
 
-    let args = Args::parse();
+    let cli = Cli::parse_from(with_default_command(env::args_os().collect()));
+    if let Some(format) = cli.capabilities {
+        return print_capabilities(format);
+    }
+    match cli.command {
+        Some(Command::Scan(args)) => scan(*args),
+        Some(Command::Report(args)) => report(args),
+        Some(Command::Clean(args)) => clean(&args),
+        Some(Command::Cache(command)) => cache(&command),
+        Some(Command::Verify(args)) => verify(&args),
+        Some(Command::Estimate(args)) => estimate(&args),
+        Some(Command::Undo(args)) => undo(&args),
+        Some(Command::Mount(args)) => mount(&args),
+        None => unreachable!("clap prints the help without a command"),
+    }
+}
+
+/// Scan a directory tree for duplicates, report them and apply the action requested, or
+/// keep scanning it as a daemon serving metrics with --serve-metrics
+fn scan(args: ScanArgs) -> Result<()> {
+    // Print the scan status on SIGUSR1 without interrupting the scan
+    let progress = Arc::new(Mutex::new(ScanProgress::default()));
+    #[cfg(unix)]
+    {
+        let status_progress = progress.clone();
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGUSR1])?;
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                let status = status_progress.lock().unwrap_or_else(|e| e.into_inner()).status_line();
+                eprintln!("{}", status);
+            }
+        });
+    }
+
+    // On the first Ctrl-C stop scanning and report what was found; on the second exit at once
+    let cancel = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(SIGINT, EXIT_INTERRUPTED, cancel.clone())?;
+    signal_hook::flag::register(SIGINT, cancel.clone())?;
 
+    let Some(address) = args.serve_metrics else {
+        return scan_once(&args, &progress, &cancel, None);
+    };
+    let listener = TcpListener::bind(address).map_err(|e| {
+        io::Error::new(e.kind(), message("error-serve-metrics", &[("address", &address), ("error", &e)]))
+    })?;
+    let root = args.directory.canonicalize().unwrap_or_else(|_| args.directory.clone());
+    let metrics = Arc::new(Mutex::new(DaemonMetrics::new(&root)));
+    let served = metrics.clone();
+    thread::spawn(move || {
+        if let Err(e) = serve_metrics(listener, served) {
+            eprintln!("{}", message("metrics-server-failed", &[("error", &e)]));
+        }
+    });
+    eprintln!("{}", message("metrics-serving", &[("address", &address)]));
+    loop {
+        *progress.lock().unwrap_or_else(|e| e.into_inner()) = ScanProgress::default();
+        cancel.store(false, Ordering::Relaxed);
+        if let Err(e) = scan_once(&args, &progress, &cancel, Some(&metrics)) {
+            eprintln!("{}", message("daemon-scan-failed", &[("error", &format!("{:#}", e))]));
+            metrics.lock().unwrap_or_else(|e| e.into_inner()).record_failure();
+        }
+        // A Ctrl-C while waiting exits at once, as there is no scan to report
+        cancel.store(true, Ordering::Relaxed);
+        thread::sleep(args.rescan_interval);
+    }
+}
+
+/// Scan a directory tree once, report the duplicates found and apply the action
+/// requested, adding the figures of the scan to `daemon` when running as one
+fn scan_once(args: &ScanArgs, progress: &Arc<Mutex<ScanProgress>>, cancel: &Arc<AtomicBool>, daemon: Option<&Mutex<DaemonMetrics>>) -> Result<()> {
     let directory = args.directory.as_path();
-    if !directory.exists() {
+    let remote = directory.to_str().is_some_and(|d| REMOTE_SCHEMES.iter().any(|scheme| d.starts_with(scheme)));
+    if !remote && !directory.exists() {
         return Err(io::Error::new(
             ErrorKind::NotFound,
-            format!("Directory does not exist: {}", directory.display())
+            message("error-directory-missing", &[("directory", &directory.display())])
         ).into());
     }
 
-    if !directory.is_dir() {
+    if !remote && !directory.is_dir() {
         return Err(io::Error::new(
             ErrorKind::InvalidInput,
-            format!("Not a directory: {}", directory.display())
+            message("error-not-directory", &[("directory", &directory.display())])
         ).into());
     }
 
     // Convert extensions to Vec<String> if provided
-    let extensions: Option<Vec<String>> = args.extensions.map(|ext| {
-        ext.trim_start_matches('*')
-           .trim_start_matches('.')
-           .split(',')
-           .map(|s| s.trim_start_matches('*')
-                     .trim_start_matches('.')
-                     .to_lowercase())
-           .filter(|s| !s.is_empty())
-           .collect()
-    });
+    let extensions = args.extensions.as_deref().map(parse_extensions);
+    let exclude_extensions = args.exclude_extensions.as_deref().map(parse_extensions);
 
     // Set up output file if specified
-    let mut output_file = args.output.map(|path| {
-        File::create(path).map_err(|e| {
-            io::Error::new(ErrorKind::Other, format!("Failed to create output file: {}", e))
+    #[cfg(feature = "webhook")]
+    let report_path = args.output.clone();
+    let mut output_file = args.output.as_ref().map(|path| {
+        File::create(long_path(path)).map_err(|e| {
+            io::Error::new(ErrorKind::Other, message("error-create-output", &[("error", &e)]))
         })
     }).transpose()?;
+    #[cfg(feature = "webhook")]
+    let report_path = report_path.map(|path| fs::canonicalize(&path).unwrap_or(path));
+    // Fail before scanning rather than after when the treemap cannot be written
+    if let Some(path) = &args.treemap {
+        File::create(long_path(path)).map_err(|e| {
+            io::Error::new(ErrorKind::Other, message("error-create-treemap", &[("error", &e)]))
+        })?;
+    }
+    if let Some(path) = &args.similar {
+        File::create(long_path(path)).map_err(|e| {
+            io::Error::new(ErrorKind::Other, message("error-create-similar", &[("error", &e)]))
+        })?;
+    }
+
+    let action = args.actions.action();
+
+    #[cfg(not(feature = "webhook"))]
+    if args.notify_url.is_some() {
+        anyhow::bail!("{}", message("error-missing-feature", &[("support", &"webhook"), ("feature", &"webhook")]));
+    }
+    #[cfg(not(feature = "desktop"))]
+    if args.notify_desktop {
+        anyhow::bail!("{}", message("error-missing-feature", &[("support", &"desktop notification"), ("feature", &"desktop")]));
+    }
 
+    if remote && args.similar.is_some() {
+        return Err(io::Error::new(ErrorKind::InvalidInput, message("error-local-only", &[("option", &"--similar")])).into());
+    }
+
+    if remote && action.is_some() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            message("error-remote-action", &[])
+        ).into());
+    }
+
+    if args.stats_only && matches!(args.format, OutputFormat::Ncdu | OutputFormat::Hashdeep) {
+        return Err(io::Error::new(ErrorKind::InvalidInput, message("error-csv-json-only", &[("option", &"--stats-only")])).into());
+    }
+
+    if args.unique && matches!(args.format, OutputFormat::Ncdu | OutputFormat::Hashdeep) {
+        return Err(io::Error::new(ErrorKind::InvalidInput, message("error-csv-json-only", &[("option", &"--unique")])).into());
+    }
+
+    if remote && args.unique {
+        return Err(io::Error::new(ErrorKind::InvalidInput, message("error-local-only", &[("option", &"--unique")])).into());
+    }
+
+    if remote && !args.also_hash.is_empty() {
+        return Err(io::Error::new(ErrorKind::InvalidInput, message("error-local-only", &[("option", &"--also-hash")])).into());
+    }
+
+    if remote && args.format == OutputFormat::Hashdeep {
+        return Err(io::Error::new(ErrorKind::InvalidInput, message("error-local-only", &[("option", &"--format hashdeep")])).into());
+    }
+
+    // Check before scanning rather than failing on every duplicate afterwards
+    if action == Some(Action::Reflink) && !clone_supported(directory)? {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            message("error-no-clones", &[("directory", &directory.display())])
+        ).into());
+    }
+
+    let mut plan = args.actions.plan()?;
+    if let Some(plan) = &mut plan {
+        plan.raw_pairs = args.image_content;
+    }
+
+    if !remote && is_network_filesystem(directory).unwrap_or(false) {
+        eprintln!("{}", message("warning-network", &[("directory", &directory.display())]));
+    }
+
+    if args.alternate_streams && !cfg!(windows) {
+        eprintln!("{}", message("warning-alternate-streams", &[]));
+    }
+
+    if remote && args.filter.is_some() {
+        eprintln!("{}", message("warning-filter-remote", &[]));
+    }
+
+    if args.drop_cache && !cfg!(target_os = "linux") {
+        eprintln!("{}", message("warning-drop-cache", &[]));
+    }
+
+    // Shells often leave the soft limit far below what parallel hashing and archives need
+    if let Ok(limit) = raise_open_file_limit() {
+        let slots = OpenFileBudget::new(limit).slots();
+        if slots < args.jobs {
+            eprintln!("{}", message("warning-open-files", &[("limit", &limit), ("slots", &slots), ("jobs", &args.jobs)]));
+        }
+    }
+
+    let content_mode = args.audio_content || args.image_content || args.email_content || args.normalize_text || !args.decompress.is_empty();
+    // Manifests hold the SHA256 hash of whole files
+    if args.format == OutputFormat::Hashdeep && (content_mode || args.sample_above.is_some()) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            message("error-hashdeep-content-mode", &[])
+        ).into());
+    }
+    if args.prefilter.is_some() && content_mode {
+        eprintln!("{}", message("warning-prefilter", &[]));
+    }
+
+    if args.memory_limit.is_some() && content_mode {
+        eprintln!("{}", message("warning-memory-limit", &[]));
+    }
+
+    if (args.quick_check.is_some() || args.quick.is_some()) && content_mode {
+        eprintln!("{}", message("warning-quick", &[]));
+    }
+
+    let cache_path = args.cache.as_deref().map(long_path);
+    // Held until the cache is saved, so runs sharing it do not overwrite each other's hashes
+    let _cache_lock = cache_path.as_deref().map(|path| lock_shared_file(path, args.actions.wait)).transpose()?;
+    let hash_cache = cache_path.as_deref()
+        .map(HashCache::load)
+        .transpose()?
+        .map(|cache| Arc::new(Mutex::new(cache)));
+
+    let mut known_hashes = KnownHashes::default();
+    for manifest in &args.known_hashes {
+        // Files listed are reported below the manifest, which actions then recognize
+        let manifest = manifest.canonicalize().map_err(|e| {
+            io::Error::new(ErrorKind::NotFound, message("error-manifest", &[("file", &manifest.display()), ("error", &e)]))
+        })?;
+        known_hashes.add_manifest(&long_path(&manifest))?;
+    }
+
+    let options = ScanOptions {
+        extensions,
+        exclude_extensions,
+        by_content: args.by_content,
+        min_size: args.min_size,
+        min_group_size: args.min_group_size,
+        min_wasted: args.min_wasted.unwrap_or(0),
+        cross_dir_only: args.cross_dir_only,
+        same_dir_only: args.same_dir_only,
+        audio_content: args.audio_content,
+        image_content: args.image_content,
+        email_content: args.email_content,
+        normalize_text: args.normalize_text,
+        trim_trailing_whitespace: args.trim_trailing_whitespace,
+        decompress: args.decompress.clone(),
+        format: args.format,
+        pairs: args.pairs,
+        progress: args.progress,
+        shared_progress: Some(progress.clone()),
+        cancel: Some(cancel.clone()),
+        max_runtime: args.max_runtime,
+        max_files: args.max_files,
+        max_bytes: args.max_bytes,
+        alternate_streams: args.alternate_streams,
+        scan_images: args.scan_images,
+        scan_archives: args.scan_archives || args.extracted_copies,
+        extracted_copies: args.extracted_copies,
+        breadth_first: args.bfs,
+        skip_network: args.skip_network,
+        prefilter: args.prefilter,
+        memory_limit: args.memory_limit,
+        physical_order: args.physical_order,
+        jobs: args.jobs,
+        largest_first: args.largest_first,
+        drop_cache: args.drop_cache,
+        direct_io: args.direct_io,
+        quick_check: args.quick.map(QuickCheck::Sampled).or(args.quick_check),
+        also_hash: args.also_hash.clone(),
+        retries: args.retries,
+        retry_delay: args.retry_delay,
+        skip_busy: args.skip_busy,
+        sample_above: args.sample_above,
+        hash_cache: hash_cache.clone(),
+        known_hashes: (!args.known_hashes.is_empty()).then(|| Arc::new(known_hashes)),
+        walk_filter: args.filter.clone().map(FilterExpression::walk_filter),
+        stats_only: args.stats_only,
+        redact_paths: args.redact_paths.then(|| args.redact_salt.clone().unwrap_or_else(random_salt)),
+        normalize_paths: args.normalize_paths,
+        comparator: None,
+    };
+
+    let mut groups = Vec::new();
+    let mut unique = None;
+    let mut similar = None;
+    let scan_started = Instant::now();
     let elapsed_time = measure_elapsed_time(|| {
-        find_duplicates(directory, extensions.as_ref(), output_file.as_mut())
+        let scanned = (|| -> Result<()> {
+            groups = if remote {
+                let source = remote_source(&directory.to_string_lossy())?;
+                find_source_duplicates(&*source, &options, output_file.as_mut())?
+            } else if args.unique {
+                unique = Some(find_unique_files(directory, &options, output_file.as_mut())?);
+                Vec::new()
+            } else {
+                find_duplicates(directory, &options, output_file.as_mut())?
+            };
+            // Compared once the duplicates are known, so only one copy of each is hashed
+            if args.similar.is_some() {
+                similar = Some(find_similar(&directory.canonicalize()?, &options, &groups, args.similarity));
+            }
+            Ok(())
+        })();
+        #[cfg(feature = "webhook")]
+        if let (Err(e), Some(url)) = (&scanned, &args.notify_url) {
+            notify_webhook(url, &ScanNotification::failed(directory.to_path_buf(), report_path.clone(), e, scan_started.elapsed()));
+        }
+        scanned
     });
-    eprintln!("Elapsed time: {}", elapsed_time);
+    // Any later Ctrl-C, such as at the confirmation prompt, exits immediately
+    let interrupted = cancel.swap(true, Ordering::Relaxed);
+    let root = if remote { directory.to_path_buf() } else { directory.canonicalize()? };
+    let mut summary = summarize(&groups, &root);
+    let scan_progress = progress.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    summary.stopped = scan_progress.stopped;
+    summary.min_size = options.min_size;
+    summary.index_memory = scan_progress.index_memory;
+    summary.spilled = scan_progress.spilled;
+    match &unique {
+        Some(unique) => {
+            if let Some(reason) = summary.stopped {
+                eprintln!("{}", message("summary-stopped", &[("reason", &reason)]));
+            }
+            eprintln!("{}", message("summary-unique", &[
+                ("files", &unique.len()),
+                ("size", &human_readable_size(unique.iter().map(|file| file.size).sum())),
+            ]));
+        }
+        None => eprint!("{}", summary),
+    }
+    eprintln!("{}", message("scan-elapsed", &[("time", &elapsed_time)]));
+    if args.timings {
+        eprintln!("{}", progress.lock().unwrap_or_else(|e| e.into_inner()).timings.to_json());
+    }
+
+    if let Some(path) = &args.treemap {
+        if let Err(e) = write_treemap(path, &groups, &root, options.normalize_paths, options.redact_paths.as_deref()) {
+            eprintln!("{}", message("treemap-write-failed", &[("file", &path.display()), ("error", &e)]));
+        }
+    }
+    if args.metrics_file.is_some() || daemon.is_some() {
+        let progress = progress.lock().unwrap_or_else(|e| e.into_inner());
+        let metrics = ScanMetrics::new(&root, &summary, &progress, scan_started.elapsed());
+        if let Some(path) = &args.metrics_file {
+            if let Err(e) = write_metrics(path, &metrics) {
+                eprintln!("{}", message("metrics-write-failed", &[("file", &path.display()), ("error", &e)]));
+            }
+        }
+        if let Some(daemon) = daemon {
+            daemon.lock().unwrap_or_else(|e| e.into_inner()).record(metrics);
+        }
+    }
+    #[cfg(feature = "webhook")]
+    if let Some(url) = &args.notify_url {
+        notify_webhook(url, &ScanNotification::finished(root.clone(), report_path.clone(), &summary, scan_started.elapsed()));
+    }
+    #[cfg(feature = "desktop")]
+    if args.notify_desktop {
+        if let Err(e) = show_scan_notification(&root, &summary) {
+            eprintln!("{:#}", e);
+        }
+    }
+    if let (Some(path), Some(pairs)) = (&args.similar, &similar) {
+        eprintln!("{}", message("similar-pairs", &[("pairs", &pairs.len())]));
+        if let Err(e) = write_similar(path, pairs, args.cluster_similar, options.normalize_paths, options.redact_paths.as_deref()) {
+            eprintln!("{}", message("similar-write-failed", &[("file", &path.display()), ("error", &e)]));
+        }
+    }
+
+    // Saved even when the scan stopped early, so the next run resumes where this one stopped
+    if let (Some(path), Some(cache)) = (&cache_path, &hash_cache) {
+        if let Err(e) = cache.lock().unwrap_or_else(|e| e.into_inner()).save(path) {
+            eprintln!("{}", message("cache-save-failed", &[("file", &path.display()), ("error", &e)]));
+        }
+    }
+
+    if interrupted {
+        drop(output_file);
+        process::exit(EXIT_INTERRUPTED);
+    }
+
+    if let Some(command) = &args.actions.exec {
+        run_exec_hooks(command, &groups, args.actions.dry_run);
+    }
+    if let Some(plan) = &plan {
+        apply_plan(plan, &groups, io::stdin().lock())?;
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use dupefiles::compute_sha256::compute_sha256;
+    use tempfile::Builder;
+
+    /// Run `dupefiles` with `args`, as given on the command line
+    fn run(args: &[&OsStr]) -> Result<()> {
+        let args = [OsStr::new("dupefiles")].iter().chain(args).map(OsString::from).collect();
+        match Cli::parse_from(with_default_command(args)).command {
+            Some(Command::Scan(args)) => scan(*args),
+            command => panic!("not a scan: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_decompress_never_hardlinks() {
+        let tmp_dir = Builder::new().prefix("main_decompress").tempdir().unwrap();
+        let logs = tmp_dir.path().join("logs");
+        fs::create_dir(&logs).unwrap();
+        let content = b"log line\n".repeat(500);
+        fs::write(logs.join("app.log.1"), &content).unwrap();
+        let mut encoder = GzEncoder::new(File::create(logs.join("app.log.1.gz")).unwrap(), Compression::default());
+        encoder.write_all(&content).unwrap();
+        encoder.finish().unwrap();
+        let compressed = fs::read(logs.join("app.log.1.gz")).unwrap();
+        let report = tmp_dir.path().join("report.csv");
+
+        run(&[
+            OsStr::new("--decompress"), OsStr::new("gz"), OsStr::new("--hardlink"), OsStr::new("--yes"), OsStr::new("--min-size"), OsStr::new("0"),
+            OsStr::new("--output"), report.as_os_str(), logs.as_os_str(),
+        ]).unwrap();
+        assert_eq!(fs::read(logs.join("app.log.1")).unwrap(), content);
+        assert_eq!(fs::read(logs.join("app.log.1.gz")).unwrap(), compressed);
+        assert!(fs::read_to_string(&report).unwrap().contains("app.log.1.gz"));
+    }
+
+    #[test]
+    fn test_manifest_entries_never_kept() {
+        let tmp_dir = Builder::new().prefix("main_known_hashes").tempdir().unwrap();
+        let (laptop, manifest) = (tmp_dir.path().join("laptop"), tmp_dir.path().join("archive.sha256"));
+        fs::create_dir(&laptop).unwrap();
+        fs::write(laptop.join("a.txt"), "archived".repeat(200)).unwrap();
+        fs::write(laptop.join("b.txt"), "both copies".repeat(200)).unwrap();
+        fs::write(laptop.join("c.txt"), "both copies".repeat(200)).unwrap();
+        let hashes = ["a.txt", "b.txt"].map(|name| format!("{}  {}\n", compute_sha256(&laptop.join(name)).unwrap(), name));
+        fs::write(&manifest, hashes.concat()).unwrap();
+        let mut known_hashes = KnownHashes::default();
+        known_hashes.add_manifest(&manifest).unwrap();
+        let options = ScanOptions { known_hashes: Some(Arc::new(known_hashes)), ..Default::default() };
+        let groups = find_duplicates(&laptop, &options, None).unwrap();
+        assert_eq!(groups.len(), 2);
+        let plan = ActionPlan {
+            action: Action::Delete,
+            keep: KeepPolicy::First,
+            preferred_dirs: Vec::new(),
+            protect_list: ProtectList::default(),
+            raw_pairs: false,
+            sidecars: None,
+            yes: true,
+            dry_run: false,
+            journal: None,
+            _journal_lock: None,
+        };
+
+        apply_plan(&plan, &groups, io::empty()).unwrap();
+        assert!(laptop.join("a.txt").exists());
+        assert_eq!(laptop.join("b.txt").exists() as u8 + laptop.join("c.txt").exists() as u8, 1);
+    }
+
+    #[test]
+    fn test_impact_counts_sidecars() {
+        let tmp_dir = Builder::new().prefix("main_sidecars").tempdir().unwrap();
+        let photo = tmp_dir.path().join("IMG_0001 (1).jpg");
+        fs::write(&photo, "photo").unwrap();
+        fs::write(tmp_dir.path().join("IMG_0001 (1).xmp"), "rating 5").unwrap();
+        let listed = HashMap::from([(photo.as_path(), sidecars(&photo).unwrap())]);
+
+        let impact = impact_with_sidecars(&[&photo], &listed);
+        assert_eq!((impact.files, impact.bytes), (2, 13));
+        let impact = impact_with_sidecars(&[&photo], &HashMap::new());
+        assert_eq!((impact.files, impact.bytes), (1, 5));
+    }
+
+    #[test]
+    fn test_content_matches_need_their_own_confirmation() {
+        let tmp_dir = Builder::new().prefix("main_normalize_text").tempdir().unwrap();
+        let unix = tmp_dir.path().join("notes.txt");
+        let windows = tmp_dir.path().join("notes-windows.txt");
+        fs::write(&unix, "line\n".repeat(500)).unwrap();
+        fs::write(&windows, "line\r\n".repeat(500)).unwrap();
+        let options = ScanOptions { normalize_text: true, ..Default::default() };
+        let groups = find_duplicates(tmp_dir.path(), &options, None).unwrap();
+        assert_eq!(groups.len(), 1);
+        let plan = ActionPlan {
+            action: Action::Delete,
+            keep: KeepPolicy::First,
+            preferred_dirs: Vec::new(),
+            protect_list: ProtectList::default(),
+            raw_pairs: false,
+            sidecars: None,
+            yes: true,
+            dry_run: false,
+            journal: None,
+            _journal_lock: None,
+        };
+
+        apply_plan(&plan, &groups, "\n".as_bytes()).unwrap();
+        assert!(unix.exists() && windows.exists());
+        apply_plan(&plan, &groups, "yes\n".as_bytes()).unwrap();
+        assert_eq!(unix.exists() as u8 + windows.exists() as u8, 1);
+    }
+
+    #[test]
+    fn test_delete_needs_confirmation() {
+        let tmp_dir = Builder::new().prefix("main_delete_confirm").tempdir().unwrap();
+        let (a, b) = (tmp_dir.path().join("a.txt"), tmp_dir.path().join("b.txt"));
+        fs::write(&a, "copy".repeat(500)).unwrap();
+        fs::write(&b, "copy".repeat(500)).unwrap();
+        let groups = find_duplicates(tmp_dir.path(), &ScanOptions::default(), None).unwrap();
+        assert_eq!(groups.len(), 1);
+        let plan = ActionPlan {
+            action: Action::Delete,
+            keep: KeepPolicy::First,
+            preferred_dirs: Vec::new(),
+            protect_list: ProtectList::default(),
+            raw_pairs: false,
+            sidecars: None,
+            yes: false,
+            dry_run: false,
+            journal: None,
+            _journal_lock: None,
+        };
+
+        for answer in ["", "no\n", "y\n"] {
+            apply_plan(&plan, &groups, answer.as_bytes()).unwrap();
+            assert!(a.exists() && b.exists(), "deleted after {:?}", answer);
+        }
+        apply_plan(&plan, &groups, "yes\n".as_bytes()).unwrap();
+        assert_eq!(a.exists() as u8 + b.exists() as u8, 1);
+    }
+
+    #[test]
+    fn test_clean_deletes_from_report() {
+        let tmp_dir = Builder::new().prefix("main_clean_delete").tempdir().unwrap();
+        let photos = tmp_dir.path().join("photos");
+        fs::create_dir(&photos).unwrap();
+        let (old, new) = (photos.join("old.jpg"), photos.join("new.jpg"));
+        fs::write(&old, "photo".repeat(500)).unwrap();
+        fs::write(&new, "photo".repeat(500)).unwrap();
+        File::options().write(true).open(&old).unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(3600)).unwrap();
+        let report = tmp_dir.path().join("report.json");
+        run(&[OsStr::new("--format"), OsStr::new("json"), OsStr::new("--output"), report.as_os_str(), photos.as_os_str()]).unwrap();
+
+        let args = ["dupefiles", "clean"].map(OsString::from).into_iter()
+            .chain([report.as_os_str(), OsStr::new("--delete"), OsStr::new("--keep"), OsStr::new("oldest"), OsStr::new("--yes")].map(OsString::from))
+            .collect();
+        match Cli::parse_from(with_default_command(args)).command {
+            Some(Command::Clean(args)) => clean(&args).unwrap(),
+            command => panic!("not a clean: {:?}", command),
+        }
+        assert!(old.exists());
+        assert!(!new.exists());
+    }
+
+}
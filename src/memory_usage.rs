@@ -0,0 +1,166 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::file_entry::FileEntry;
+use crate::path_encoding::{deserialize_path, serialize_path};
+
+/// The approximate number of bytes `entry` takes in memory, counting its path and hash.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::file_entry::FileEntry;
+/// use dupefiles::memory_usage::entry_memory;
+///
+/// let short = FileEntry::unknown(Path::new("/a"), 1, "");
+/// let long = FileEntry::unknown(Path::new("/data/photos/2024/holiday.jpg"), 1, "");
+/// assert!(entry_memory(&long) > entry_memory(&short));
+/// ```
+pub fn entry_memory(entry: &FileEntry) -> u64 {
+    (size_of::<FileEntry>() + entry.path.capacity() + entry.hash.capacity()) as u64
+}
+
+/// The approximate number of bytes a hash map of `len` entries of `K` and `V` takes in
+/// memory, leaving out what its values point to. Maps hold a control byte per slot
+/// and keep up to an eighth of their slots free.
+pub fn map_memory<K, V>(len: usize) -> u64 {
+    (len * (size_of::<K>() + size_of::<V>() + 1) * 8 / 7) as u64
+}
+
+/// A file on disk holding the entries of the files found by a walk that would not fit
+/// in memory, in the order they were added, to be read back once the walk is done.
+///
+/// The file is created in the temporary directory and removed at once, so it never
+/// outlives the scan, even when the scan is killed.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::file_entry::FileEntry;
+/// use dupefiles::memory_usage::SpillFile;
+///
+/// let mut spill = SpillFile::new().unwrap();
+/// spill.push(&FileEntry::unknown(Path::new("/data/a.txt"), 10, "")).unwrap();
+/// spill.push(&FileEntry::unknown(Path::new("/data/b.txt"), 20, "")).unwrap();
+/// assert_eq!(spill.len(), 2);
+///
+/// let mut sizes = Vec::new();
+/// spill.read_back(|entry| sizes.push(entry.size)).unwrap();
+/// assert_eq!(sizes, vec![10, 20]);
+/// ```
+#[derive(Debug)]
+pub struct SpillFile {
+    writer: BufWriter<File>,
+    len: usize,
+}
+
+/// A file entry as written in a spill file, one JSON object per line
+#[derive(Serialize, Deserialize)]
+struct SpilledEntry {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    path: PathBuf,
+    size: u64,
+    allocated: Option<u64>,
+    mtime: Option<SystemTime>,
+    dev: Option<u64>,
+    inode: Option<u64>,
+    hash: String,
+}
+
+impl SpillFile {
+    /// Creates an empty spill file in the temporary directory.
+    pub fn new() -> io::Result<Self> {
+        // Several scans may run in the same process
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("dupefiles-spill-{}-{}.jsonl", std::process::id(), CREATED.fetch_add(1, Ordering::Relaxed));
+        let path = env::temp_dir().join(name);
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        // The open handle keeps the data until it is dropped
+        fs::remove_file(&path)?;
+        Ok(SpillFile { writer: BufWriter::new(file), len: 0 })
+    }
+
+    /// Appends `entry` to the file.
+    pub fn push(&mut self, entry: &FileEntry) -> io::Result<()> {
+        let spilled = SpilledEntry {
+            path: entry.path.clone(),
+            size: entry.size,
+            allocated: entry.allocated,
+            mtime: entry.mtime,
+            dev: entry.dev,
+            inode: entry.inode,
+            hash: entry.hash.clone(),
+        };
+        serde_json::to_writer(&mut self.writer, &spilled)?;
+        self.writer.write_all(b"\n")?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// How many entries the file holds.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the file holds no entry.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads every entry back in the order they were added, calling `visit` with each.
+    ///
+    /// # Returns
+    ///
+    /// Result, with an error if the file cannot be read back.
+    pub fn read_back(self, mut visit: impl FnMut(FileEntry)) -> io::Result<()> {
+        let mut file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        for line in BufReader::new(file).lines() {
+            let spilled: SpilledEntry = serde_json::from_str(&line?)?;
+            visit(FileEntry {
+                path: spilled.path,
+                size: spilled.size,
+                allocated: spilled.allocated,
+                mtime: spilled.mtime,
+                dev: spilled.dev,
+                inode: spilled.inode,
+                hash: spilled.hash,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_spill_keeps_metadata_and_raw_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "content").unwrap();
+        let entries = vec![
+            FileEntry::from_path(&path, 7, ""),
+            FileEntry::unknown(Path::new(OsStr::from_bytes(b"/data/caf\xe9\n.txt")), 3, "ab"),
+        ];
+
+        let mut spill = SpillFile::new().unwrap();
+        for entry in &entries {
+            spill.push(entry).unwrap();
+        }
+        let mut read = Vec::new();
+        spill.read_back(|entry| read.push(entry)).unwrap();
+        assert_eq!(read, entries);
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use anyhow::Result;
+use crate::path_encoding::escape_path;
+use crate::scan_progress::{ScanError, ScanProgress};
+use crate::scan_summary::{ScanSummary, StopReason};
+
+/// Every kind of `ScanError`, so each has a series even before it first happens
+const ERRORS: [ScanError; 5] = [ScanError::Metadata, ScanError::Listing, ScanError::Hash, ScanError::Busy, ScanError::Compare];
+
+/// How long a scraper may take to send its request before the connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The figures of a scan exported to Prometheus, so dashboards can track how much
+/// duplication each machine of a fleet holds over time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanMetrics {
+    /// The scanned directory, the `root` label of every metric.
+    pub root: PathBuf,
+    /// Files hashed, or skipped while being hashed.
+    pub files_hashed: usize,
+    /// Bytes hashed.
+    pub bytes_hashed: u64,
+    /// Number of duplicate groups found.
+    pub duplicate_groups: usize,
+    /// Number of files duplicating the first-seen member of their group.
+    pub duplicate_files: usize,
+    /// Bytes that would be freed by keeping a single copy of every group.
+    pub wasted_bytes: u64,
+    /// How long the scan took.
+    pub duration: Duration,
+    /// When the scan finished.
+    pub finished: SystemTime,
+    /// Why the scan stopped early, if it did.
+    pub stopped: Option<StopReason>,
+    /// The number of errors of each kind the scan carried on after.
+    pub errors: BTreeMap<ScanError, usize>,
+}
+
+impl ScanMetrics {
+    /// Gathers the metrics of a scan that just finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The scanned directory.
+    /// * `summary` - The summary of the groups found.
+    /// * `progress` - The progress of the scan, as shared through `ScanOptions::shared_progress`.
+    /// * `duration` - How long the scan took.
+    pub fn new(root: &Path, summary: &ScanSummary, progress: &ScanProgress, duration: Duration) -> Self {
+        ScanMetrics {
+            root: root.to_path_buf(),
+            files_hashed: progress.files_done,
+            bytes_hashed: progress.bytes_hashed,
+            duplicate_groups: summary.duplicate_groups,
+            duplicate_files: summary.duplicate_files,
+            wasted_bytes: summary.wasted_bytes,
+            duration,
+            finished: SystemTime::now(),
+            stopped: summary.stopped,
+            errors: progress.errors.clone(),
+        }
+    }
+}
+
+/// Escapes a label value of the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Writes the metrics of a scan in the Prometheus text exposition format, as read from
+/// a `.prom` file by the textfile collector of node_exporter.
+///
+/// Every metric is a gauge describing the last scan of `root`: the files and bytes
+/// hashed, the duplicates found and the space they waste, how long the scan took, when
+/// it finished, whether it completed, and the errors of each kind.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the metrics.
+/// * `metrics` - The metrics of the scan.
+///
+/// # Returns
+///
+/// Result
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use dupefiles::metrics::{write_prometheus, ScanMetrics};
+/// use dupefiles::scan_progress::ScanProgress;
+/// use dupefiles::scan_summary::ScanSummary;
+///
+/// let summary = ScanSummary { duplicate_groups: 2, duplicate_files: 3, wasted_bytes: 4096, ..Default::default() };
+/// let metrics = ScanMetrics::new(Path::new("/data"), &summary, &ScanProgress::new(10, 0), Duration::from_secs(5));
+/// let mut text = Vec::new();
+/// write_prometheus(&mut text, &metrics).unwrap();
+/// let text = String::from_utf8(text).unwrap();
+/// assert!(text.contains("dupefiles_wasted_bytes{root=\"/data\"} 4096\n"));
+/// assert!(text.contains("dupefiles_errors{root=\"/data\",type=\"hash\"} 0\n"));
+/// ```
+pub fn write_prometheus(output: &mut impl Write, metrics: &ScanMetrics) -> Result<()> {
+    let root = escape_label(&escape_path(&metrics.root));
+    let finished = metrics.finished.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+    let gauges: [(&str, &str, f64); 8] = [
+        ("dupefiles_files_hashed", "Files hashed by the last scan.", metrics.files_hashed as f64),
+        ("dupefiles_bytes_hashed", "Bytes hashed by the last scan.", metrics.bytes_hashed as f64),
+        ("dupefiles_duplicate_groups", "Duplicate sets found by the last scan.", metrics.duplicate_groups as f64),
+        ("dupefiles_duplicate_files", "Files duplicating the first copy of their set in the last scan.", metrics.duplicate_files as f64),
+        ("dupefiles_wasted_bytes", "Bytes freed by keeping a single copy of every set found by the last scan.", metrics.wasted_bytes as f64),
+        ("dupefiles_last_scan_duration_seconds", "How long the last scan took.", metrics.duration.as_secs_f64()),
+        ("dupefiles_last_scan_timestamp_seconds", "When the last scan finished, in seconds since the Unix epoch.", finished.as_secs_f64()),
+        ("dupefiles_last_scan_complete", "Whether the last scan hashed every file, rather than stopping early.", if metrics.stopped.is_none() { 1.0 } else { 0.0 }),
+    ];
+    for (name, help, value) in gauges {
+        writeln!(output, "# HELP {} {}", name, help)?;
+        writeln!(output, "# TYPE {} gauge", name)?;
+        writeln!(output, "{}{{root=\"{}\"}} {}", name, root, value)?;
+    }
+    writeln!(output, "# HELP dupefiles_errors Errors the last scan carried on after, by type.")?;
+    writeln!(output, "# TYPE dupefiles_errors gauge")?;
+    for error in ERRORS {
+        let count = metrics.errors.get(&error).copied().unwrap_or(0);
+        writeln!(output, "dupefiles_errors{{root=\"{}\",type=\"{}\"}} {}", root, error.name(), count)?;
+    }
+    Ok(())
+}
+
+/// The figures of every scan run by a daemon, served at `/metrics`: counters adding up
+/// all the scans since it started, and the gauges of the last scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DaemonMetrics {
+    /// The scanned directory, the `root` label of every metric.
+    pub root: PathBuf,
+    /// Scans that finished, even early.
+    pub scans: u64,
+    /// Scans that failed before writing their report.
+    pub failed_scans: u64,
+    /// Files hashed by all the scans.
+    pub files_hashed: u64,
+    /// Bytes hashed by all the scans.
+    pub bytes_hashed: u64,
+    /// The errors of each kind all the scans carried on after.
+    pub errors: BTreeMap<ScanError, u64>,
+    /// The metrics of the last scan that finished.
+    pub last_scan: Option<ScanMetrics>,
+}
+
+impl DaemonMetrics {
+    /// The metrics of a daemon scanning `root`, before its first scan.
+    pub fn new(root: &Path) -> Self {
+        DaemonMetrics { root: root.to_path_buf(), ..Default::default() }
+    }
+
+    /// Adds a scan that just finished to the counters, and makes it the last scan.
+    pub fn record(&mut self, metrics: ScanMetrics) {
+        self.scans += 1;
+        self.files_hashed += metrics.files_hashed as u64;
+        self.bytes_hashed += metrics.bytes_hashed;
+        for (error, count) in &metrics.errors {
+            *self.errors.entry(*error).or_default() += *count as u64;
+        }
+        self.last_scan = Some(metrics);
+    }
+
+    /// Counts a scan that failed before writing its report.
+    pub fn record_failure(&mut self) {
+        self.failed_scans += 1;
+    }
+}
+
+/// Writes the metrics of a daemon in the Prometheus text exposition format: the
+/// counters of all its scans, followed by the gauges of `write_prometheus` for the last
+/// scan once there is one.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the metrics.
+/// * `metrics` - The metrics of the daemon.
+///
+/// # Returns
+///
+/// Result
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use dupefiles::metrics::{write_daemon_prometheus, DaemonMetrics, ScanMetrics};
+/// use dupefiles::scan_progress::ScanProgress;
+/// use dupefiles::scan_summary::ScanSummary;
+///
+/// let mut daemon = DaemonMetrics::new(Path::new("/data"));
+/// let mut progress = ScanProgress::new(1, 100);
+/// progress.finish_file(100);
+/// let scan = ScanMetrics::new(Path::new("/data"), &ScanSummary::default(), &progress, Duration::from_secs(5));
+/// daemon.record(scan.clone());
+/// daemon.record(scan);
+/// let mut text = Vec::new();
+/// write_daemon_prometheus(&mut text, &daemon).unwrap();
+/// let text = String::from_utf8(text).unwrap();
+/// assert!(text.contains("dupefiles_scans_total{root=\"/data\"} 2\n"));
+/// assert!(text.contains("dupefiles_bytes_hashed_total{root=\"/data\"} 200\n"));
+/// assert!(text.contains("dupefiles_bytes_hashed{root=\"/data\"} 100\n"));
+/// ```
+pub fn write_daemon_prometheus(output: &mut impl Write, metrics: &DaemonMetrics) -> Result<()> {
+    let root = escape_label(&escape_path(&metrics.root));
+    let counters: [(&str, &str, u64); 4] = [
+        ("dupefiles_scans_total", "Scans finished since the daemon started, even early.", metrics.scans),
+        ("dupefiles_failed_scans_total", "Scans that failed before writing their report since the daemon started.", metrics.failed_scans),
+        ("dupefiles_files_hashed_total", "Files hashed since the daemon started.", metrics.files_hashed),
+        ("dupefiles_bytes_hashed_total", "Bytes hashed since the daemon started.", metrics.bytes_hashed),
+    ];
+    for (name, help, value) in counters {
+        writeln!(output, "# HELP {} {}", name, help)?;
+        writeln!(output, "# TYPE {} counter", name)?;
+        writeln!(output, "{}{{root=\"{}\"}} {}", name, root, value)?;
+    }
+    writeln!(output, "# HELP dupefiles_errors_total Errors the scans carried on after since the daemon started, by type.")?;
+    writeln!(output, "# TYPE dupefiles_errors_total counter")?;
+    for error in ERRORS {
+        let count = metrics.errors.get(&error).copied().unwrap_or(0);
+        writeln!(output, "dupefiles_errors_total{{root=\"{}\",type=\"{}\"}} {}", root, error.name(), count)?;
+    }
+    if let Some(last_scan) = &metrics.last_scan {
+        write_prometheus(output, last_scan)?;
+    }
+    Ok(())
+}
+
+/// Answers a single HTTP request: the metrics for `GET /metrics`, 404 for anything else
+fn answer_scrape(stream: TcpStream, metrics: &Mutex<DaemonMetrics>) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers are read so the scraper does not see its request cut short
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+    let mut words = request.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let mut body = Vec::new();
+            write_daemon_prometheus(&mut body, &metrics.lock().unwrap_or_else(|e| e.into_inner()))?;
+            ("200 OK", body)
+        }
+        _ => ("404 Not Found", b"Not found; the metrics are served at /metrics\n".to_vec()),
+    };
+    let stream = reader.get_mut();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    Ok(())
+}
+
+/// Serves the metrics of a daemon over HTTP at `/metrics`, for Prometheus to scrape.
+///
+/// Requests are answered one at a time, each on its own connection, with the metrics as
+/// they are when the request arrives. A request that fails is dropped without stopping
+/// the server.
+///
+/// # Arguments
+///
+/// * `listener` - The socket to accept scrapes on.
+/// * `metrics` - The metrics of the daemon, updated by its scans.
+///
+/// # Returns
+///
+/// Never returns unless accepting connections fails.
+pub fn serve_metrics(listener: TcpListener, metrics: Arc<Mutex<DaemonMetrics>>) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept()?;
+        let _ = answer_scrape(stream, &metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_escaped() {
+        let mut progress = ScanProgress::new(3, 300);
+        progress.finish_file(100);
+        progress.count_error(ScanError::Busy);
+        let summary = ScanSummary { stopped: Some(StopReason::MaxRuntime), ..Default::default() };
+        let metrics = ScanMetrics::new(Path::new("/mnt/\"backup\""), &summary, &progress, Duration::from_millis(1500));
+        let mut text = Vec::new();
+        write_prometheus(&mut text, &metrics).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        assert!(text.contains("dupefiles_files_hashed{root=\"/mnt/\\\"backup\\\"\"} 1\n"), "{}", text);
+        assert!(text.contains("dupefiles_bytes_hashed{root=\"/mnt/\\\"backup\\\"\"} 100\n"));
+        assert!(text.contains("dupefiles_last_scan_duration_seconds{root=\"/mnt/\\\"backup\\\"\"} 1.5\n"));
+        assert!(text.contains("dupefiles_last_scan_complete{root=\"/mnt/\\\"backup\\\"\"} 0\n"));
+        assert!(text.contains("dupefiles_errors{root=\"/mnt/\\\"backup\\\"\",type=\"busy\"} 1\n"));
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let metrics = Arc::new(Mutex::new(DaemonMetrics::new(Path::new("/data"))));
+        let served = metrics.clone();
+        std::thread::spawn(move || serve_metrics(listener, served));
+        let scrape = |path: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: text/plain\r\n\r\n", path, address).unwrap();
+            let mut response = String::new();
+            std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+            response
+        };
+
+        let response = scrape("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("dupefiles_scans_total{root=\"/data\"} 0\n"));
+        assert!(!response.contains("dupefiles_wasted_bytes"));
+
+        let mut progress = ScanProgress::new(1, 100);
+        progress.count_error(ScanError::Hash);
+        let summary = ScanSummary { wasted_bytes: 4096, ..Default::default() };
+        metrics.lock().unwrap().record(ScanMetrics::new(Path::new("/data"), &summary, &progress, Duration::from_secs(1)));
+        metrics.lock().unwrap().record_failure();
+        let response = scrape("/metrics");
+        assert!(response.contains("dupefiles_scans_total{root=\"/data\"} 1\n"));
+        assert!(response.contains("dupefiles_failed_scans_total{root=\"/data\"} 1\n"));
+        assert!(response.contains("dupefiles_errors_total{root=\"/data\",type=\"hash\"} 1\n"));
+        assert!(response.contains("dupefiles_wasted_bytes{root=\"/data\"} 4096\n"));
+
+        assert!(scrape("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
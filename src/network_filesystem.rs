@@ -0,0 +1,112 @@
+use std::io;
+use std::path::Path;
+
+/// Returns whether `path` is on a network file system, such as NFS, SMB/CIFS or SSHFS.
+///
+/// On Linux the file system type reported by statfs is compared against the known
+/// network file systems, and FUSE mounts are looked up in `/proc/self/mounts` to tell
+/// SSHFS and other remote FUSE file systems from local ones. On macOS every volume not
+/// flagged as local is a network volume. On other platforms the answer is always `false`.
+///
+/// # Arguments
+///
+/// * `path` - Any path on the file system.
+///
+/// # Returns
+///
+/// Whether the file system is accessed over the network, or an error if it cannot be queried.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::network_filesystem::is_network_filesystem;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// assert!(!is_network_filesystem(dir.path()).unwrap());
+/// ```
+pub fn is_network_filesystem(path: &Path) -> io::Result<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::is_network_filesystem(path)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::is_network_filesystem(path)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = path;
+        Ok(false)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) fn statfs(path: &Path) -> io::Result<libc::statfs> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stats)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    /// Magic numbers of network file systems from linux/magic.h and the file system sources
+    const NETWORK_MAGICS: [u32; 9] = [
+        0x6969,      // NFS
+        0x517B,      // SMB
+        0xFF53_4D42, // CIFS
+        0xFE53_4D42, // SMB2
+        0x5346_414F, // AFS (OpenAFS)
+        0x6B41_4653, // AFS (kAFS)
+        0x00C3_6400, // Ceph
+        0x0102_1997, // 9P
+        0x0BD0_0BD0, // Lustre
+    ];
+    const FUSE_SUPER_MAGIC: u32 = 0x6573_5546;
+    /// FUSE file system types, as listed in /proc/self/mounts, that access remote machines
+    const NETWORK_FUSE_TYPES: [&str; 5] = ["fuse.sshfs", "fuse.rclone", "fuse.s3fs", "fuse.gcsfuse", "fuse.smbnetfs"];
+
+    pub fn is_network_filesystem(path: &Path) -> io::Result<bool> {
+        // f_type is 32 bits wide on some architectures, where the larger magics are negative
+        let magic = super::statfs(path)?.f_type as u32;
+        if magic == FUSE_SUPER_MAGIC {
+            return Ok(fuse_type(path).is_some_and(|fs_type| NETWORK_FUSE_TYPES.contains(&fs_type.as_str())));
+        }
+        Ok(NETWORK_MAGICS.contains(&magic))
+    }
+
+    /// The type of the mount holding `path`, from the mount with the longest matching mount point
+    fn fuse_type(path: &Path) -> Option<String> {
+        let path = path.canonicalize().ok()?;
+        let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+        mounts.lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let (_, mount_point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+                // Spaces and other special characters in mount points are escaped as octal
+                let mount_point = PathBuf::from(mount_point.replace("\\040", " "));
+                path.starts_with(&mount_point).then(|| (mount_point, fs_type.to_string()))
+            })
+            .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+            .map(|(_, fs_type)| fs_type)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::io;
+    use std::path::Path;
+
+    pub fn is_network_filesystem(path: &Path) -> io::Result<bool> {
+        Ok(super::statfs(path)?.f_flags & libc::MNT_LOCAL as u32 == 0)
+    }
+}
@@ -0,0 +1,197 @@
+use std::io;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// File descriptors left for everything but hashing: the standard streams, the report,
+/// the cache and journal with their locks, the directories open during the walk and
+/// the two files compared when confirming a duplicate
+pub const RESERVED_FILES: u64 = 64;
+
+/// The most file descriptors hashing a single file holds at once, such as a file
+/// reopened for direct I/O, or an archive and the member read from it
+pub const FILES_PER_HASH: u64 = 2;
+
+/// The number of files this process may have open at once, its soft `RLIMIT_NOFILE`.
+///
+/// # Returns
+///
+/// The limit, or an error if it cannot be read or the platform has none (Windows).
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::open_files::open_file_limit;
+///
+/// # #[cfg(unix)]
+/// assert!(open_file_limit().unwrap() > 0);
+/// ```
+pub fn open_file_limit() -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        unix::get_limit().map(|(soft, _)| soft)
+    }
+    #[cfg(not(unix))]
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, crate::localize::message("open-files-unsupported", &[])))
+    }
+}
+
+/// Raises the number of files this process may have open at once to the most it is
+/// allowed, its hard `RLIMIT_NOFILE`, as many shells leave the soft limit at 1024 or
+/// less, which parallel hashing and archive scanning can exhaust.
+///
+/// When the hard limit cannot be set, as on macOS where it may be unlimited while
+/// the kernel caps every process lower, the soft limit is left as it was.
+///
+/// # Returns
+///
+/// The limit in force afterwards, or an error if it cannot be read or the platform
+/// has none (Windows).
+pub fn raise_open_file_limit() -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        let (soft, hard) = unix::get_limit()?;
+        if soft < hard && unix::set_limit(hard).is_ok() {
+            return Ok(hard);
+        }
+        Ok(soft)
+    }
+    #[cfg(not(unix))]
+    {
+        open_file_limit()
+    }
+}
+
+/// Returns whether an I/O error comes from running out of file descriptors, for this
+/// process or for the whole system.
+pub fn is_too_many_open_files(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(error.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+    }
+    #[cfg(not(unix))]
+    {
+        // ERROR_TOO_MANY_OPEN_FILES
+        error.raw_os_error() == Some(4)
+    }
+}
+
+/// Bounds how many files are hashed at once so they never need more file descriptors
+/// than the process may open, whatever the number of jobs and devices: each file takes
+/// a slot before it is opened, waiting for one to be freed when they are all taken,
+/// instead of failing with "Too many open files".
+#[derive(Debug)]
+pub struct OpenFileBudget {
+    /// The slots not taken
+    available: Mutex<usize>,
+    /// Notified when a slot is freed
+    freed: Condvar,
+    slots: usize,
+}
+
+impl OpenFileBudget {
+    /// A budget for a process that may open `limit` files at once: `FILES_PER_HASH`
+    /// descriptors a slot, once `RESERVED_FILES` are set aside, and at least one slot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::open_files::OpenFileBudget;
+    ///
+    /// let budget = OpenFileBudget::new(256);
+    /// assert_eq!(budget.slots(), 96);
+    /// let slot = budget.acquire();
+    /// drop(slot);
+    /// assert_eq!(OpenFileBudget::new(16).slots(), 1);
+    /// ```
+    pub fn new(limit: u64) -> Self {
+        let slots = usize::try_from(limit.saturating_sub(RESERVED_FILES) / FILES_PER_HASH).unwrap_or(usize::MAX).max(1);
+        OpenFileBudget { available: Mutex::new(slots), freed: Condvar::new(), slots }
+    }
+
+    /// How many files may be hashed at once.
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Takes a slot, waiting for one to be freed if they are all taken. The slot is
+    /// freed when the returned guard is dropped.
+    pub fn acquire(&self) -> OpenFileSlot<'_> {
+        let mut available = self.lock();
+        while *available == 0 {
+            available = self.freed.wait(available).unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        OpenFileSlot(self)
+    }
+
+    /// Lock the count of slots, recovering it if a thread holding it panicked
+    fn lock(&self) -> MutexGuard<'_, usize> {
+        self.available.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A slot of an `OpenFileBudget`, freed when dropped.
+#[derive(Debug)]
+pub struct OpenFileSlot<'a>(&'a OpenFileBudget);
+
+impl Drop for OpenFileSlot<'_> {
+    fn drop(&mut self) {
+        *self.0.lock() += 1;
+        self.0.freed.notify_one();
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+
+    /// Read the soft and hard limits on open files
+    // rlim_t is u64 on Linux and macOS but signed on some BSDs
+    #[allow(clippy::unnecessary_cast)]
+    pub fn get_limit() -> io::Result<(u64, u64)> {
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((limit.rlim_cur as u64, limit.rlim_max as u64))
+    }
+
+    /// Set the soft limit on open files, keeping the hard one
+    #[allow(clippy::unnecessary_cast)]
+    pub fn set_limit(soft: u64) -> io::Result<()> {
+        let (_, hard) = get_limit()?;
+        let raised = libc::rlimit { rlim_cur: soft as libc::rlim_t, rlim_max: hard as libc::rlim_t };
+        if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &raised) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_slots_bound_concurrency() {
+        let budget = OpenFileBudget::new(RESERVED_FILES + 3 * FILES_PER_HASH);
+        let (open, most_open) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..5 {
+                        let _slot = budget.acquire();
+                        let now = open.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_open.fetch_max(now, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(1));
+                        open.fetch_sub(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert!(most_open.load(Ordering::SeqCst) <= 3);
+    }
+}
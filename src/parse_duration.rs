@@ -0,0 +1,80 @@
+use std::time::Duration;
+use crate::localize::message;
+
+/// Parses a human-written duration such as "90", "45s", "30m", "2h" or "1h30m".
+///
+/// A number without a unit is a number of seconds. Units are case-insensitive:
+/// ms for milliseconds, s for seconds, m for minutes, h for hours and d for days.
+///
+/// # Arguments
+///
+/// * `input` - The duration to parse.
+///
+/// # Returns
+///
+/// The duration, or a message describing why the input is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use dupefiles::parse_duration::parse_duration;
+///
+/// assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 3600)));
+/// assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+/// assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+/// assert!(parse_duration("soon").is_err());
+/// ```
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err(message("invalid-duration", &[("value", &"")]));
+    }
+    let mut rest = trimmed;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().map_err(|_| message("invalid-duration", &[("value", &trimmed)]))?;
+        let unit_len = tail.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let multiplier = match unit.trim().to_lowercase().as_str() {
+            "ms" => 0.001,
+            "" | "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            _ => return Err(message("invalid-duration-unit", &[("value", &trimmed)])),
+        };
+        seconds += number * multiplier;
+        rest = tail;
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days() {
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn test_uppercase_unit() {
+        assert_eq!(parse_duration("45S"), Ok(Duration::from_secs(45)));
+    }
+
+    #[test]
+    fn test_invalid_unit() {
+        assert!(parse_duration("3 weeks").is_err());
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(parse_duration("").is_err());
+    }
+}
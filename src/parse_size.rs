@@ -0,0 +1,66 @@
+use crate::localize::message;
+
+/// Parses a human-written size such as "512", "10K", "1.5MiB" or "2 GB" into bytes.
+///
+/// Suffixes are case-insensitive and always use binary multiples, matching
+/// `human_readable_size`: K, KB and KiB all mean 1024 bytes.
+///
+/// # Arguments
+///
+/// * `input` - The size to parse.
+///
+/// # Returns
+///
+/// The size in bytes, or a message describing why the input is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::parse_size::parse_size;
+///
+/// assert_eq!(parse_size("512"), Ok(512));
+/// assert_eq!(parse_size("10K"), Ok(10 * 1024));
+/// assert_eq!(parse_size("1.5MiB"), Ok(1536 * 1024));
+/// assert_eq!(parse_size("2 GB"), Ok(2 * 1024 * 1024 * 1024));
+/// assert!(parse_size("ten").is_err());
+/// ```
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number.parse().map_err(|_| message("invalid-size", &[("value", &input)]))?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(message("invalid-size-unit", &[("value", &input)])),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tebibyte() {
+        assert_eq!(parse_size("3TiB"), Ok(3 * 1024 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_lowercase_unit() {
+        assert_eq!(parse_size("100mb"), Ok(100 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_invalid_unit() {
+        assert!(parse_size("10 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(parse_size("").is_err());
+    }
+}
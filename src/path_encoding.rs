@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::de::Error as _;
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::localize::message;
+
+/// Escapes a path so that it can be written as text and restored exactly by `unescape_path`.
+///
+/// Backslashes are doubled and bytes that are not part of valid UTF-8 are written as
+/// `\xNN`; everything else is kept as is.
+///
+/// # Arguments
+///
+/// * `path` - The path to escape.
+///
+/// # Returns
+///
+/// The escaped path.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::path_encoding::escape_path;
+///
+/// assert_eq!(escape_path(Path::new("/data/café.txt")), "/data/café.txt");
+/// assert_eq!(escape_path(Path::new("/data/a\\b")), "/data/a\\\\b");
+/// #[cfg(unix)]
+/// {
+///     use std::ffi::OsStr;
+///     use std::os::unix::ffi::OsStrExt;
+///     assert_eq!(escape_path(Path::new(OsStr::from_bytes(b"/data/caf\xe9.txt"))), "/data/caf\\xe9.txt");
+/// }
+/// ```
+pub fn escape_path(path: &Path) -> String {
+    let mut escaped = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        escaped.push_str(&chunk.valid().replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+/// Restores a path escaped by `escape_path`.
+///
+/// # Arguments
+///
+/// * `escaped` - The escaped path.
+///
+/// # Returns
+///
+/// The original path, or a message describing why the input is not a valid escaped path.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::path_encoding::{escape_path, unescape_path};
+///
+/// let path = Path::new("/data/café\\x.txt");
+/// assert_eq!(unescape_path(&escape_path(path)).unwrap(), path);
+/// #[cfg(unix)]
+/// {
+///     use std::ffi::OsStr;
+///     use std::os::unix::ffi::OsStrExt;
+///     let path = Path::new(OsStr::from_bytes(b"/data/caf\xe9\\x.txt"));
+///     assert_eq!(unescape_path(&escape_path(path)).unwrap(), path);
+/// }
+/// ```
+pub fn unescape_path(escaped: &str) -> Result<PathBuf, String> {
+    let invalid = || message("invalid-escaped-path", &[("value", &escaped)]);
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match rest.split_first() {
+            Some((b'\\', tail)) => {
+                bytes.push(b'\\');
+                rest = tail;
+            }
+            Some((b'x', tail)) if tail.len() >= 2 => {
+                let hex = std::str::from_utf8(&tail[..2]).map_err(|_| invalid())?;
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                rest = &tail[2..];
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(bytes_to_path(bytes))
+}
+
+/// The path made of `bytes`: its raw bytes on Unix, and on Windows its encoded bytes
+/// (`OsStr::as_encoded_bytes`), read as UTF-8 where they are not
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+        PathBuf::from(OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// A path as written in JSON: a string when it is valid UTF-8, otherwise an object
+/// holding its raw bytes in base64, so that every path round-trips exactly.
+struct JsonPath<'a>(&'a Path);
+
+impl Serialize for JsonPath<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.to_str() {
+            Some(path) => serializer.serialize_str(path),
+            None => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("base64", &STANDARD.encode(self.0.as_os_str().as_encoded_bytes()))?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// Serializes a path for `#[serde(serialize_with)]`: as a string when it is valid UTF-8,
+/// otherwise as `{"base64": "..."}` holding its raw bytes.
+pub fn serialize_path<P, S>(path: &P, serializer: S) -> Result<S::Ok, S::Error>
+where
+    P: AsRef<Path> + ?Sized,
+    S: Serializer,
+{
+    JsonPath(path.as_ref()).serialize(serializer)
+}
+
+/// Serializes a list of paths like `serialize_path`.
+pub fn serialize_paths<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(paths.len()))?;
+    for path in paths {
+        seq.serialize_element(&JsonPath(path))?;
+    }
+    seq.end()
+}
+
+/// A path as read from JSON, in either form written by `serialize_path`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPathIn {
+    Text(String),
+    Bytes { base64: String },
+}
+
+impl JsonPathIn {
+    fn into_path(self) -> Result<PathBuf, String> {
+        match self {
+            JsonPathIn::Text(path) => Ok(PathBuf::from(path)),
+            JsonPathIn::Bytes { base64 } => STANDARD.decode(&base64)
+                .map(bytes_to_path)
+                .map_err(|e| message("invalid-base64-path", &[("value", &base64), ("error", &e)])),
+        }
+    }
+}
+
+/// Deserializes a path written by `serialize_path`, for `#[serde(deserialize_with)]`.
+pub fn deserialize_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    JsonPathIn::deserialize(deserializer)?.into_path().map_err(D::Error::custom)
+}
+
+/// Deserializes a list of paths written by `serialize_paths`, for `#[serde(deserialize_with)]`.
+pub fn deserialize_paths<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    Vec::<JsonPathIn>::deserialize(deserializer)?
+        .into_iter()
+        .map(|path| path.into_path().map_err(D::Error::custom))
+        .collect()
+}
+
+/// Serializes a map keyed by paths, with each key escaped by `escape_path` since JSON
+/// object keys must be strings.
+pub fn serialize_path_keys<S, V>(map: &BTreeMap<PathBuf, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    V: Serialize,
+{
+    let mut out = serializer.serialize_map(Some(map.len()))?;
+    for (path, value) in map {
+        out.serialize_entry(&escape_path(path), value)?;
+    }
+    out.end()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    fn path_from_bytes(bytes: &[u8]) -> &Path {
+        Path::new(OsStr::from_bytes(bytes))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = path_from_bytes(b"/data/\xff\xfe\\x41/\xc3\xa9t\xc3");
+        assert_eq!(escape_path(path), "/data/\\xff\\xfe\\\\x41/ét\\xc3");
+        assert_eq!(unescape_path(&escape_path(path)).unwrap(), path);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Paths(
+            #[serde(serialize_with = "serialize_paths", deserialize_with = "deserialize_paths")]
+            Vec<PathBuf>,
+        );
+        let paths = vec![PathBuf::from("/data/café.txt"), path_from_bytes(b"/data/caf\xe9.txt").to_path_buf()];
+        let json = serde_json::to_string(&Paths(paths.clone())).unwrap();
+        assert_eq!(serde_json::from_str::<Paths>(&json).unwrap().0, paths);
+    }
+
+    #[test]
+    fn test_unescape_invalid() {
+        assert!(unescape_path("/data/\\q").is_err());
+        assert!(unescape_path("/data/\\x4").is_err());
+    }
+
+    #[test]
+    fn test_json_path() {
+        let valid = serde_json::to_string(&JsonPath(Path::new("/data/a.txt"))).unwrap();
+        assert_eq!(valid, "\"/data/a.txt\"");
+        let invalid = serde_json::to_string(&JsonPath(path_from_bytes(b"/\xff"))).unwrap();
+        assert_eq!(invalid, "{\"base64\":\"L/8=\"}");
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use crate::file_entry::file_id;
+
+/// Returns where the data of `path` starts on disk, as a device number and a position on
+/// that device, so files can be read in the order of their layout on rotational disks.
+///
+/// On Linux the position is the physical offset of the first extent reported by FIEMAP,
+/// and on macOS the device offset of the first byte reported by `F_LOG2PHYS`. When the
+/// file system cannot report it, or the file is empty, inline or not allocated yet, the
+/// inode number is used instead: most file systems allocate inodes and data in roughly
+/// the same order. Where the platform exposes neither, as on Windows, every file is at
+/// position 0 of device 0.
+/// Positions are only comparable between files of the same device.
+///
+/// # Arguments
+///
+/// * `path` - The file to locate.
+///
+/// # Returns
+///
+/// The device number and the position of the file on it, or an error if the file cannot
+/// be opened.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::physical_order::physical_position;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+/// fs::write(&a, "first").unwrap();
+/// fs::write(&b, "second").unwrap();
+///
+/// let (device_a, _) = physical_position(&a).unwrap();
+/// let (device_b, _) = physical_position(&b).unwrap();
+/// assert_eq!(device_a, device_b);
+/// ```
+// Elsewhere there is no position to look up
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(clippy::unnecessary_literal_unwrap))]
+pub fn physical_position(path: &Path) -> io::Result<(u64, u64)> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    #[cfg(target_os = "linux")]
+    let position = linux::first_extent(&file);
+    #[cfg(target_os = "macos")]
+    let position = macos::first_extent(&file);
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let position: Option<u64> = None;
+    let (device, inode) = file_id(&metadata).unwrap_or_default();
+    Ok((device, position.unwrap_or(inode)))
+}
+
+/// Returns whether `path` is on a rotational disk, where reading sequentially and in
+/// large chunks matters most.
+///
+/// On Linux the `queue/rotational` attribute of the device, or of the disk holding the
+/// partition, is read from sysfs. Elsewhere, and for file systems without a block
+/// device such as tmpfs or network shares, the answer is `false`.
+///
+/// # Arguments
+///
+/// * `path` - Any path on the file system.
+///
+/// # Returns
+///
+/// Whether the disk is rotational, or an error if `path` cannot be queried.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::physical_order::is_rotational;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// is_rotational(dir.path()).unwrap();
+/// ```
+pub fn is_rotational(path: &Path) -> io::Result<bool> {
+    let metadata = std::fs::metadata(path)?;
+    Ok(file_id(&metadata).is_some_and(|(device, _)| is_rotational_device(device)))
+}
+
+/// Returns whether the device with number `device`, as found in the `st_dev` of the
+/// files it holds, is a rotational disk, like `is_rotational`.
+pub fn is_rotational_device(device: u64) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        linux::is_rotational(device)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device;
+        false
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::{self, File};
+    use std::path::Path;
+    use std::os::unix::io::AsRawFd;
+
+    /// _IOWR('f', 11, struct fiemap) from linux/fs.h
+    const FS_IOC_FIEMAP: libc::c_ulong = 0xC020_660B;
+    /// Flags of extents without a position of their own: not allocated yet, or stored
+    /// with the metadata
+    const FIEMAP_EXTENT_UNKNOWN: u32 = 0x2;
+    const FIEMAP_EXTENT_DATA_INLINE: u32 = 0x200;
+
+    /// struct fiemap_extent from linux/fiemap.h
+    #[repr(C)]
+    #[derive(Default)]
+    struct FiemapExtent {
+        fe_logical: u64,
+        fe_physical: u64,
+        fe_length: u64,
+        fe_reserved64: [u64; 2],
+        fe_flags: u32,
+        fe_reserved: [u32; 3],
+    }
+
+    /// struct fiemap from linux/fiemap.h, with room for a single extent
+    #[repr(C)]
+    #[derive(Default)]
+    struct Fiemap {
+        fm_start: u64,
+        fm_length: u64,
+        fm_flags: u32,
+        fm_mapped_extents: u32,
+        fm_extent_count: u32,
+        fm_reserved: u32,
+        fm_extents: [FiemapExtent; 1],
+    }
+
+    pub fn is_rotational(device: u64) -> bool {
+        let device = format!("/sys/dev/block/{}:{}", libc::major(device), libc::minor(device));
+        // Partitions have no queue of their own, their disk has
+        ["queue/rotational", "../queue/rotational"].iter()
+            .find_map(|attribute| fs::read_to_string(Path::new(&device).join(attribute)).ok())
+            .is_some_and(|rotational| rotational.trim() == "1")
+    }
+
+    pub fn first_extent(file: &File) -> Option<u64> {
+        let mut map = Fiemap { fm_length: u64::MAX, fm_extent_count: 1, ..Default::default() };
+        if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP as _, &mut map) } != 0 {
+            return None;
+        }
+        let extent = &map.fm_extents[0];
+        let located = extent.fe_flags & (FIEMAP_EXTENT_UNKNOWN | FIEMAP_EXTENT_DATA_INLINE) == 0;
+        (map.fm_mapped_extents > 0 && located).then_some(extent.fe_physical)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+
+    pub fn first_extent(file: &File) -> Option<u64> {
+        let mut location = libc::log2phys { l2p_flags: 0, l2p_contigbytes: 0, l2p_devoffset: 0 };
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_LOG2PHYS, &mut location) } == -1 {
+            return None;
+        }
+        u64::try_from(location.l2p_devoffset).ok()
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+
+/// Bytes read from the start of each file for its partial hash
+const PARTIAL_SIZE: u64 = 4096;
+/// Bits set per key in each Bloom filter
+const HASHES_PER_KEY: u64 = 4;
+
+/// A fixed-size probabilistic filter of the (size, partial hash) keys of files, telling
+/// which files probably have a duplicate without keeping anything per file.
+///
+/// Every file is first added with `insert`, then checked with `may_have_duplicate`.
+/// Two Bloom filters record the keys seen at least once and at least twice, so a file
+/// whose key was only seen once, its own, is certainly unique. A file with a duplicate
+/// is never rejected; a unique file is kept by mistake with a probability that grows as
+/// more files are added to the same memory (around 2% with two bytes per file).
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::prefilter::Prefilter;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b, c) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("c"));
+/// fs::write(&a, "copy").unwrap();
+/// fs::write(&b, "copy").unwrap();
+/// fs::write(&c, "uniq").unwrap();
+///
+/// let mut prefilter = Prefilter::new(1024);
+/// for path in [&a, &b, &c] {
+///     prefilter.insert(path, 4).unwrap();
+/// }
+/// assert!(prefilter.may_have_duplicate(&a, 4).unwrap());
+/// assert!(!prefilter.may_have_duplicate(&c, 4).unwrap());
+/// ```
+pub struct Prefilter {
+    seen: BloomFilter,
+    seen_twice: BloomFilter,
+}
+
+impl Prefilter {
+    /// Creates an empty filter using about `memory` bytes.
+    pub fn new(memory: u64) -> Self {
+        Prefilter { seen: BloomFilter::new(memory / 2), seen_twice: BloomFilter::new(memory / 2) }
+    }
+
+    /// Records a file of `size` bytes.
+    ///
+    /// # Returns
+    ///
+    /// Result, with an error if the start of the file cannot be read.
+    pub fn insert(&mut self, path: &Path, size: u64) -> io::Result<()> {
+        let key = key(path, size)?;
+        if self.seen.contains(key) {
+            self.seen_twice.insert(key);
+        } else {
+            self.seen.insert(key);
+        }
+        Ok(())
+    }
+
+    /// Returns whether another file recorded with `insert` probably has the same size and
+    /// starts with the same bytes as this one, which was recorded too.
+    ///
+    /// # Returns
+    ///
+    /// `false` when the file certainly has no duplicate, or an error if the start of the
+    /// file cannot be read.
+    pub fn may_have_duplicate(&self, path: &Path, size: u64) -> io::Result<bool> {
+        Ok(self.seen_twice.contains(key(path, size)?))
+    }
+}
+
+/// Two independent 64-bit hashes of the size and the SHA256 hash of the first bytes
+fn key(path: &Path, size: u64) -> io::Result<(u64, u64)> {
+    let mut start = Vec::with_capacity(PARTIAL_SIZE as usize);
+    File::open(path)?.take(PARTIAL_SIZE).read_to_end(&mut start)?;
+    let digest = Sha256::digest(&start);
+    let word = |index: usize| u64::from_le_bytes(digest[index * 8..index * 8 + 8].try_into().unwrap_or_default());
+    Ok((word(0) ^ size, word(1) ^ size.rotate_left(32)))
+}
+
+struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new(bytes: u64) -> Self {
+        BloomFilter { bits: vec![0; (bytes / 8).max(1) as usize] }
+    }
+
+    /// The bits of a key, by double hashing
+    fn positions(&self, (h1, h2): (u64, u64)) -> impl Iterator<Item = usize> {
+        let bit_count = self.bits.len() as u64 * 64;
+        (0..HASHES_PER_KEY).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2 | 1)) % bit_count) as usize)
+    }
+
+    fn insert(&mut self, key: (u64, u64)) {
+        for position in self.positions(key) {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    fn contains(&self, key: (u64, u64)) -> bool {
+        self.positions(key).all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+}
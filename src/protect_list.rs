@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use glob::Pattern;
+use crate::unicode_paths::{nfc_path, nfc_str};
+use crate::localize::message;
+
+/// A set of glob patterns naming files that actions must never touch.
+///
+/// Patterns are matched against the absolute, canonical path of each file, and
+/// `*` also matches `/`, so `/archive/*` protects everything below `/archive`.
+/// Patterns and paths are compared in Unicode NFC, so a pattern naming `café` protects
+/// the files named on macOS, which decomposes the `é`, too.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectList {
+    patterns: Vec<Pattern>,
+}
+
+impl ProtectList {
+    /// Adds a glob pattern to the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use dupefiles::protect_list::ProtectList;
+    ///
+    /// let mut protect_list = ProtectList::default();
+    /// protect_list.add("/archive/*").unwrap();
+    /// assert!(protect_list.is_protected(Path::new("/archive/2023/photo.jpg")));
+    /// assert!(!protect_list.is_protected(Path::new("/downloads/photo.jpg")));
+    /// ```
+    pub fn add(&mut self, pattern: &str) -> Result<()> {
+        let pattern = Pattern::new(&nfc_str(pattern))
+            .with_context(|| message("protect-invalid-pattern", &[("pattern", &pattern)]))?;
+        self.patterns.push(pattern);
+        Ok(())
+    }
+
+    /// Adds every pattern listed in a protect-list file.
+    ///
+    /// The file holds one glob pattern per line; blank lines and lines starting
+    /// with `#` are ignored.
+    pub fn add_file(&mut self, path: &Path) -> Result<()> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| message("protect-read-failed", &[("file", &path.display())]))?;
+        for line in contents.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                self.add(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the path matches any pattern in the list.
+    pub fn is_protected(&self, path: &Path) -> bool {
+        let path = nfc_path(path);
+        self.patterns.iter().any(|pattern| pattern.matches_path(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_protect_list_file() {
+        let tmp_dir = Builder::new().prefix("protect_list").tempdir().unwrap();
+        let list_path = tmp_dir.path().join("protect.txt");
+        fs::write(&list_path, "# keep raw photos\n\n*.cr2\n/photos/originals/*\n").unwrap();
+
+        let mut protect_list = ProtectList::default();
+        protect_list.add_file(&list_path).unwrap();
+
+        assert!(protect_list.is_protected(Path::new("/downloads/IMG_0001.cr2")));
+        assert!(protect_list.is_protected(Path::new("/photos/originals/IMG_0001.jpg")));
+        assert!(!protect_list.is_protected(Path::new("/photos/IMG_0001.jpg")));
+    }
+
+    #[test]
+    fn test_decomposed_names() {
+        let mut protect_list = ProtectList::default();
+        protect_list.add("/photos/caf\u{e9}/*").unwrap();
+        assert!(protect_list.is_protected(Path::new("/photos/cafe\u{301}/IMG_0001.jpg")));
+
+        let mut protect_list = ProtectList::default();
+        protect_list.add("*/cafe\u{301}.jpg").unwrap();
+        assert!(protect_list.is_protected(Path::new("/photos/caf\u{e9}.jpg")));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let mut protect_list = ProtectList::default();
+        assert!(protect_list.add("[").is_err());
+    }
+}
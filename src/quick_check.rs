@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+use crate::localize::message;
+
+/// Bytes read at the start, middle and end of a file by the `sampled` check
+pub const DEFAULT_SAMPLE_SIZE: u64 = 16 * 1024;
+
+/// How much of each file the cheap checksum run before SHA256 covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickCheck {
+    /// The first, middle and last this many bytes, enough to tell most different files
+    /// apart while reading a fixed amount of each.
+    Sampled(u64),
+    /// The whole file, read once more but hashed several times faster than with SHA256.
+    Full,
+}
+
+impl FromStr for QuickCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sampled" => Ok(QuickCheck::Sampled(DEFAULT_SAMPLE_SIZE)),
+            "full" => Ok(QuickCheck::Full),
+            _ => Err(message("invalid-quick-check", &[("value", &s)])),
+        }
+    }
+}
+
+/// Computes a CRC32 checksum of a file, over the part of it selected by `mode`.
+///
+/// Files with different checksums certainly differ, so only files of the same size and
+/// checksum need a SHA256 hash. Files no larger than three samples are read whole in
+/// both modes.
+///
+/// # Arguments
+///
+/// * `path` - The file to check.
+/// * `mode` - Whether to read samples of the file or the whole file.
+///
+/// # Returns
+///
+/// The checksum, or an error if the file cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::quick_check::{quick_checksum, QuickCheck};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+/// fs::write(&a, "same size").unwrap();
+/// fs::write(&b, "same SIZE").unwrap();
+/// let sampled = QuickCheck::Sampled(4096);
+/// assert_ne!(quick_checksum(&a, sampled).unwrap(), quick_checksum(&b, sampled).unwrap());
+/// ```
+pub fn quick_checksum(path: &Path, mode: QuickCheck) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = crc32fast::Hasher::new();
+
+    if let QuickCheck::Sampled(sample_size) = mode {
+        if size > sample_size.saturating_mul(3) {
+            let mut sample = vec![0; sample_size as usize];
+            for offset in [0, size / 2 - sample_size / 2, size - sample_size] {
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut sample)?;
+                hasher.update(&sample);
+            }
+            return Ok(hasher.finalize());
+        }
+    }
+
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_sampled_misses_unsampled_bytes() {
+        let tmp_dir = Builder::new().prefix("quick_check").tempdir().unwrap();
+        let (a, b) = (tmp_dir.path().join("a.bin"), tmp_dir.path().join("b.bin"));
+        let mut data = vec![0u8; 100 * 1024];
+        fs::write(&a, &data).unwrap();
+        // Outside the start, middle and end samples
+        data[25 * 1024] = 1;
+        fs::write(&b, &data).unwrap();
+
+        let sampled = QuickCheck::Sampled(DEFAULT_SAMPLE_SIZE);
+        assert_eq!(quick_checksum(&a, sampled).unwrap(), quick_checksum(&b, sampled).unwrap());
+        assert_ne!(quick_checksum(&a, QuickCheck::Full).unwrap(), quick_checksum(&b, QuickCheck::Full).unwrap());
+    }
+}
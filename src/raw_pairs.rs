@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+/// Extensions of the RAW formats cameras write next to a JPEG of the same shot.
+pub const RAW_EXTENSIONS: [&str; 12] = ["arw", "cr2", "cr3", "dng", "nef", "nrw", "orf", "pef", "raf", "raw", "rw2", "srw"];
+
+/// Extensions of the JPEG half of a RAW+JPEG pair.
+const JPEG_EXTENSIONS: [&str; 2] = ["jpg", "jpeg"];
+
+/// Returns `true` if the path has one of the `RAW_EXTENSIONS`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::raw_pairs::is_raw_file;
+///
+/// assert!(is_raw_file(Path::new("/photos/IMG_0001.CR2")));
+/// assert!(!is_raw_file(Path::new("/photos/IMG_0001.JPG")));
+/// ```
+pub fn is_raw_file(path: &Path) -> bool {
+    has_extension(path, &RAW_EXTENSIONS)
+}
+
+/// Whether the extension of the path, in any case, is one of `extensions`
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| extensions.contains(&ext.as_str()))
+}
+
+/// Returns the other half of the RAW+JPEG pair a file belongs to, if any.
+///
+/// Cameras set to record both formats write each shot twice, as `IMG_0001.CR2` and
+/// `IMG_0001.JPG`, in the same directory. For a JPEG file, this is the RAW file next to
+/// it with the same stem; for a RAW file, the JPEG file next to it with the same stem.
+/// Extensions are looked up in lowercase and uppercase.
+///
+/// # Arguments
+///
+/// * `path` - The file.
+///
+/// # Returns
+///
+/// The path of the other half of the pair, or `None` if the file is not part of a pair.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::raw_pairs::pair_partner;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// for name in ["IMG_0001.CR2", "IMG_0001.JPG", "IMG_0002.JPG"] {
+///     fs::write(dir.path().join(name), name).unwrap();
+/// }
+/// assert_eq!(pair_partner(&dir.path().join("IMG_0001.JPG")), Some(dir.path().join("IMG_0001.CR2")));
+/// assert_eq!(pair_partner(&dir.path().join("IMG_0001.CR2")), Some(dir.path().join("IMG_0001.JPG")));
+/// assert_eq!(pair_partner(&dir.path().join("IMG_0002.JPG")), None);
+/// ```
+pub fn pair_partner(path: &Path) -> Option<PathBuf> {
+    let partner_extensions: &[&str] = if has_extension(path, &JPEG_EXTENSIONS) {
+        &RAW_EXTENSIONS
+    } else if is_raw_file(path) {
+        &JPEG_EXTENSIONS
+    } else {
+        return None;
+    };
+    partner_extensions.iter()
+        .flat_map(|ext| [ext.to_string(), ext.to_uppercase()])
+        .map(|ext| path.with_extension(ext))
+        .find(|partner| partner.is_file())
+}
+
+/// Returns the indices of the members of a duplicate group that are half of a RAW+JPEG
+/// pair, which actions must keep so that no pair loses one of its halves.
+///
+/// # Arguments
+///
+/// * `files` - The members of the duplicate group.
+///
+/// # Returns
+///
+/// The indices into `files` of the members with a `pair_partner`, in order.
+pub fn paired_members(files: &[PathBuf]) -> Vec<usize> {
+    (0..files.len()).filter(|&index| pair_partner(&files[index]).is_some()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_paired_members() {
+        let tmp_dir = Builder::new().prefix("raw_pairs").tempdir().unwrap();
+        let (camera, export) = (tmp_dir.path().join("camera"), tmp_dir.path().join("export"));
+        fs::create_dir(&camera).unwrap();
+        fs::create_dir(&export).unwrap();
+        fs::write(camera.join("DSC_0042.nef"), "raw").unwrap();
+        fs::write(camera.join("DSC_0042.jpg"), "jpeg").unwrap();
+        fs::write(export.join("DSC_0042.jpg"), "jpeg").unwrap();
+        fs::write(export.join("DSC_0042.png"), "jpeg").unwrap();
+
+        let files = vec![export.join("DSC_0042.jpg"), camera.join("DSC_0042.jpg"), export.join("DSC_0042.png")];
+        assert_eq!(paired_members(&files), vec![1]);
+        assert_eq!(pair_partner(&camera.join("DSC_0042.nef")), Some(camera.join("DSC_0042.jpg")));
+    }
+}
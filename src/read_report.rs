@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use crate::duplicate_group::DuplicateGroup;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::path_encoding::unescape_path;
+use crate::localize::message;
+
+/// The part of a JSON report needed to restore its groups.
+#[derive(Deserialize)]
+struct JsonReport {
+    groups: Vec<JsonGroup>,
+}
+
+/// A group of a JSON report, whose `id`, member sizes and link flags are not taken for digests
+#[derive(Deserialize)]
+struct JsonGroup {
+    #[serde(default, rename = "id")]
+    _id: Option<String>,
+    #[serde(default, rename = "sizes")]
+    _sizes: Vec<u64>,
+    #[serde(default, rename = "allocated")]
+    _allocated: Vec<Option<u64>>,
+    #[serde(default, rename = "hardlink_possible")]
+    _hardlink_possible: Option<bool>,
+    #[serde(default, rename = "reflink_possible")]
+    _reflink_possible: Option<bool>,
+    #[serde(default, rename = "suggested_action")]
+    _suggested_action: Option<String>,
+    #[serde(flatten)]
+    group: DuplicateGroup,
+}
+
+/// Reads the duplicate groups of a report written by dupefiles in CSV or JSON format.
+///
+/// See `parse_report` for what is restored from each format.
+///
+/// # Arguments
+///
+/// * `path` - The report file.
+///
+/// # Returns
+///
+/// The groups of the report, or an error if it cannot be read or parsed.
+pub fn read_report(path: &Path) -> Result<Vec<DuplicateGroup>> {
+    let text = fs::read_to_string(path).with_context(|| message("report-read-failed", &[("file", &path.display())]))?;
+    parse_report(&text).with_context(|| message("report-parse-failed", &[("file", &path.display())]))
+}
+
+/// Parses the duplicate groups of a CSV or JSON report, telling them apart by their
+/// first character.
+///
+/// JSON reports restore every group exactly. CSV reports are reassembled from their
+/// rows by `GROUP_ID`, so the `hash` of each group only holds its identifier, and the
+/// members sharing their data with the first one are not known. A row joins the group
+/// of its `GROUP_ID` already holding its first file, so the groups of the same content
+/// split by directory with `--same-dir-only` stay apart. Reports of several scans
+/// appended to the same CSV file give a single list of groups.
+///
+/// # Arguments
+///
+/// * `text` - The content of the report.
+///
+/// # Returns
+///
+/// The groups of the report, each listing its first-seen member first, or an error
+/// describing the first line that cannot be parsed.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use dupefiles::read_report::parse_report;
+///
+/// let report = "\
+/// ## dupefiles 1.0.0, hash sha256
+/// DUPE1.NAME,DUPE1.SIZE,DUPE1.HRSIZE,DUPE2.NAME,DUPE2.SIZE,DUPE2.HRSIZE,GROUP_ID
+/// \"/data/a.jpg\",100,\"100 B\",\"/data/b.jpg\",100,\"100 B\",5891b5b522d5df08
+/// \"/data/a.jpg\",100,\"100 B\",\"/data/c.jpg\",100,\"100 B\",5891b5b522d5df08
+/// ";
+/// let groups = parse_report(report).unwrap();
+/// assert_eq!(groups.len(), 1);
+/// assert_eq!(groups[0].files, vec![PathBuf::from("/data/a.jpg"), PathBuf::from("/data/b.jpg"), PathBuf::from("/data/c.jpg")]);
+/// assert_eq!(groups[0].id(), "5891b5b522d5df08");
+/// ```
+pub fn parse_report(text: &str) -> Result<Vec<DuplicateGroup>> {
+    if text.trim_start().starts_with('{') {
+        let report: JsonReport = serde_json::from_str(text)?;
+        return Ok(report.groups.into_iter().map(|group| group.group).collect());
+    }
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    // The group of every member, by group identifier and path
+    let mut group_index: HashMap<(String, PathBuf), usize> = HashMap::new();
+    // The other digests written after `GROUP_ID`, as named by the last header, and the
+    // number of columns it has
+    let mut digest_columns: Vec<HashAlgorithm> = Vec::new();
+    let mut columns = 7;
+    for (number, line) in text.lines().enumerate() {
+        let no_duplicates = line == "No duplicate files found." || line == message("report-no-duplicates", &[]);
+        if line.is_empty() || line.starts_with('#') || no_duplicates {
+            continue;
+        }
+        let fields = split_csv_line(line).ok_or_else(|| anyhow!("{}", message("report-unterminated-field", &[("line", &(number + 1))])))?;
+        if fields[0] == "DUPE1.NAME" {
+            // Reports of older versions have no allocated sizes
+            digest_columns = fields.iter().skip(7)
+                .filter(|name| !name.ends_with(".ALLOCATED"))
+                .map(|name| name.parse())
+                .collect::<Result<_, String>>()
+                .map_err(|e| anyhow!("{}", message("report-line-error", &[("line", &(number + 1)), ("error", &e)])))?;
+            columns = fields.len();
+            continue;
+        }
+        if fields.len() != columns {
+            bail!("{}", message("report-field-count", &[("line", &(number + 1)), ("expected", &columns), ("found", &fields.len())]));
+        }
+        let original = unescape_path(&fields[0]).map_err(|e| anyhow!("{}", message("report-line-error", &[("line", &(number + 1)), ("error", &e)])))?;
+        let duplicate = unescape_path(&fields[3]).map_err(|e| anyhow!("{}", message("report-line-error", &[("line", &(number + 1)), ("error", &e)])))?;
+        let size = fields[1].parse().map_err(|_| anyhow!("{}", message("report-invalid-size", &[("line", &(number + 1)), ("size", &fields[1])])))?;
+
+        let index = *group_index.entry((fields[6].clone(), original.clone())).or_insert_with(|| {
+            groups.push(DuplicateGroup {
+                hash: fields[6].clone(),
+                size,
+                files: Vec::new(),
+                reflinked: Vec::new(),
+                digests: digest_columns.iter().zip(&fields[7..])
+                    .filter(|(_, digest)| !digest.is_empty())
+                    .map(|(algorithm, digest)| (*algorithm, digest.clone()))
+                    .collect(),
+                entries: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        // With every pair written, members appear in many rows
+        for path in [original, duplicate] {
+            if !groups[index].files.contains(&path) {
+                group_index.insert((fields[6].clone(), path.clone()), index);
+                groups[index].files.push(path);
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// Split a CSV line into its fields, unquoting quoted fields, or return `None` when a
+/// quoted field is not closed
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => break,
+                    c => field.push(c),
+                }
+            }
+        }
+        while let Some(c) = chars.next_if(|c| *c != ',') {
+            field.push(c);
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Some(fields);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(split_csv_line("\"a,b\",1,\"say \"\"hi\"\"\",").unwrap(), vec!["a,b", "1", "say \"hi\"", ""]);
+        assert_eq!(split_csv_line("\"open"), None);
+    }
+
+    #[test]
+    fn test_parse_csv_with_digests() {
+        let report = "\
+DUPE1.NAME,DUPE1.SIZE,DUPE1.HRSIZE,DUPE2.NAME,DUPE2.SIZE,DUPE2.HRSIZE,GROUP_ID,MD5,DUPE1.ALLOCATED,DUPE2.ALLOCATED
+\"/data/a\\\\b.txt\",4,\"4 B\",\"/data/c.txt\",4,\"4 B\",8e0b1ed5a3e5b8ab,d8e8fca2dc0f896fd7cb4cb0031ba249,4096,
+";
+        let groups = parse_report(report).unwrap();
+        assert_eq!(groups[0].files, vec![PathBuf::from("/data/a\\b.txt"), PathBuf::from("/data/c.txt")]);
+        assert_eq!(groups[0].size, 4);
+        assert_eq!(groups[0].digests[&HashAlgorithm::Md5], "d8e8fca2dc0f896fd7cb4cb0031ba249");
+    }
+
+    #[test]
+    fn test_parse_csv_groups_split_by_directory() {
+        let report = "\
+DUPE1.NAME,DUPE1.SIZE,DUPE1.HRSIZE,DUPE2.NAME,DUPE2.SIZE,DUPE2.HRSIZE,GROUP_ID
+\"/a/f.jpg\",4,\"4 B\",\"/a/f (1).jpg\",4,\"4 B\",8e0b1ed5a3e5b8ab
+\"/b/f.jpg\",4,\"4 B\",\"/b/f (1).jpg\",4,\"4 B\",8e0b1ed5a3e5b8ab
+\"/b/f.jpg\",4,\"4 B\",\"/b/f (2).jpg\",4,\"4 B\",8e0b1ed5a3e5b8ab
+\"/b/f (1).jpg\",4,\"4 B\",\"/b/f (2).jpg\",4,\"4 B\",8e0b1ed5a3e5b8ab
+";
+        let groups = parse_report(report).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[1].files.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let report = r#"{
+  "metadata": { "tool": "dupefiles", "version": "1.0.0", "hash_algorithm": "sha256", "also_hash": ["sha1"] },
+  "groups": [
+    { "id": "5891b5b5", "hash": "5891b5b5", "size": 6, "files": ["/data/a", { "base64": "L2RhdGEv/w==" }], "reflinked": [], "sha1": "f572d396", "sizes": [6, 6], "allocated": [4096, null] }
+  ],
+  "summary": {}
+}"#;
+        let groups = parse_report(report).unwrap();
+        assert_eq!(groups[0].files[1].as_os_str().len(), 7);
+        assert_eq!(groups[0].digests[&HashAlgorithm::Sha1], "f572d396");
+    }
+}
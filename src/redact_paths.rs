@@ -0,0 +1,125 @@
+use std::collections::hash_map::RandomState;
+use std::ffi::OsStr;
+use std::hash::BuildHasher;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use sha2::{Digest, Sha256};
+use crate::duplicate_group::DuplicateGroup;
+
+/// Hexadecimal digits kept from the hash of each path component
+const REDACTED_LENGTH: usize = 12;
+
+/// Returns a random salt for `redact_directory` and `redact_file`, so the hashes of
+/// common names cannot be looked up in a precomputed table.
+pub fn random_salt() -> String {
+    format!("{:016x}", RandomState::new().hash_one(SystemTime::now()))
+}
+
+/// The salted hash replacing a path component
+fn redact_component(component: &OsStr, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(component.as_encoded_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    hash[..REDACTED_LENGTH].to_string()
+}
+
+/// Replaces every component of a directory path with its salted hash, keeping the
+/// root, so the same directory is redacted alike in every path of a report.
+///
+/// # Arguments
+///
+/// * `path` - The directory.
+/// * `salt` - The salt mixed into every hash; reports redacted with the same salt
+///   redact the same names alike.
+///
+/// # Returns
+///
+/// The redacted path.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::redact_paths::{redact_directory, redact_file};
+///
+/// let directory = redact_directory(Path::new("/home/alice"), "salt");
+/// let file = redact_file(Path::new("/home/alice/passport.jpg"), "salt");
+/// assert!(directory.has_root());
+/// assert!(file.starts_with(&directory));
+/// assert_eq!(file.extension().unwrap(), "jpg");
+/// assert!(!file.to_string_lossy().contains("alice"));
+/// ```
+pub fn redact_directory(path: &Path, salt: &str) -> PathBuf {
+    path.components()
+        .map(|component| match component {
+            Component::Normal(name) => PathBuf::from(redact_component(name, salt)),
+            other => PathBuf::from(other.as_os_str()),
+        })
+        .collect()
+}
+
+/// Replaces every component of a file path with its salted hash, keeping the root and
+/// the extension of the file name. See `redact_directory`.
+pub fn redact_file(path: &Path, salt: &str) -> PathBuf {
+    let (Some(parent), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return redact_directory(path, salt);
+    };
+    let mut name = redact_component(stem, salt);
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    redact_directory(parent, salt).join(name)
+}
+
+/// Returns a copy of the groups with every path replaced by `redact_file`, leaving the
+/// sizes and hashes as they are.
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups.
+/// * `salt` - The salt mixed into every hash.
+///
+/// # Returns
+///
+/// The redacted groups, in the same order.
+pub fn redact_groups(groups: &[DuplicateGroup], salt: &str) -> Vec<DuplicateGroup> {
+    groups.iter()
+        .map(|group| {
+            let mut group = group.clone();
+            for path in group.files.iter_mut().chain(group.reflinked.iter_mut()) {
+                *path = redact_file(path, salt);
+            }
+            for entry in &mut group.entries {
+                entry.path = redact_file(&entry.path, salt);
+            }
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_groups() {
+        let group = DuplicateGroup {
+            hash: "5891b5b5".to_string(),
+            size: 10,
+            files: vec![PathBuf::from("/data/a.tar.gz"), PathBuf::from("/data/README")],
+            reflinked: vec![PathBuf::from("/data/README")],
+            digests: Default::default(),
+            entries: Vec::new(),
+        };
+        let redacted = &redact_groups(std::slice::from_ref(&group), "salt")[0];
+        assert_eq!(redacted.size, 10);
+        assert_eq!(redacted.hash, group.hash);
+        assert_eq!(redacted.files[0].extension().unwrap(), "gz");
+        assert_eq!(redacted.files[1].extension(), None);
+        assert_eq!(redacted.reflinked, vec![redacted.files[1].clone()]);
+        assert_eq!(redacted.files[0].parent(), redacted.files[1].parent());
+        assert_ne!(redact_file(Path::new("/data/README"), "pepper"), redacted.files[1]);
+    }
+}
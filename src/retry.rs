@@ -0,0 +1,110 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+/// Returns whether an I/O error may go away when the operation is tried again, as the
+/// I/O errors and timeouts of flaky network mounts and USB disks do. Missing files and
+/// denied permissions are never transient.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use dupefiles::retry::is_transient;
+///
+/// assert!(is_transient(&io::Error::from(io::ErrorKind::TimedOut)));
+/// assert!(!is_transient(&io::Error::from(io::ErrorKind::NotFound)));
+/// ```
+pub fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+    ) || is_transient_os_error(error)
+}
+
+/// Whether `error` is one of the I/O errors of the operating system that may go away
+fn is_transient_os_error(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(error.raw_os_error(), Some(libc::EIO | libc::ESTALE | libc::EBUSY))
+    }
+    #[cfg(not(unix))]
+    {
+        error.kind() == io::ErrorKind::ResourceBusy
+    }
+}
+
+/// Runs `operation`, running it again up to `retries` times while it fails with a
+/// transient error, waiting `delay` before the first retry and twice as long before each
+/// following one.
+///
+/// # Arguments
+///
+/// * `retries` - The number of times to try again; 0 runs the operation once.
+/// * `delay` - The wait before the first retry.
+/// * `operation` - The I/O to run.
+///
+/// # Returns
+///
+/// The first success, the first error that is not transient, or the last error once
+/// the retries are exhausted.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::time::Duration;
+/// use dupefiles::retry::retry;
+///
+/// let mut failures = 2;
+/// let result = retry(3, Duration::from_millis(1), || {
+///     if failures > 0 {
+///         failures -= 1;
+///         return Err(io::Error::from(io::ErrorKind::TimedOut));
+///     }
+///     Ok("read")
+/// });
+/// assert_eq!(result.unwrap(), "read");
+/// ```
+pub fn retry<T>(retries: u32, delay: Duration, mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = delay;
+    for _ in 0..retries {
+        match operation() {
+            Err(e) if is_transient(&e) => {
+                thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+    operation()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_retries_exhausted() {
+        let mut attempts = 0;
+        let result: io::Result<()> = retry(2, Duration::ZERO, || {
+            attempts += 1;
+            Err(io::Error::from_raw_os_error(libc::EIO))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_permanent_errors_not_retried() {
+        let mut attempts = 0;
+        let result: io::Result<()> = retry(2, Duration::ZERO, || {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(attempts, 1);
+    }
+}
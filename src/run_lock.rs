@@ -0,0 +1,117 @@
+use std::fs::{self, File, TryLockError};
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// An advisory lock on a file shared between runs, such as a hash cache or an action
+/// journal, so two runs at the same time neither overwrite each other's cache nor act
+/// on the same files.
+///
+/// The lock is an exclusive lock (`flock` on Unix, `LockFileEx` on Windows) on a `.lock` file next to the shared file,
+/// which records the process holding it. It is released when the `RunLock` is dropped,
+/// or when the process ends, even if it is killed; the `.lock` file itself is left in
+/// place.
+#[derive(Debug)]
+pub struct RunLock {
+    /// The lock file, kept open for the lock to be held
+    _file: File,
+}
+
+impl RunLock {
+    /// The lock file guarding `path`, `path` with `.lock` appended.
+    pub fn lock_path(path: &Path) -> PathBuf {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        PathBuf::from(lock_path)
+    }
+
+    /// Takes the lock guarding `path` if no other run holds it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The shared file, which need not exist.
+    ///
+    /// # Returns
+    ///
+    /// The lock, `None` if another run holds it, or an error if the lock file cannot
+    /// be created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::run_lock::RunLock;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let cache = dir.path().join("cache.json");
+    /// let lock = RunLock::try_acquire(&cache).unwrap().unwrap();
+    /// assert!(RunLock::try_acquire(&cache).unwrap().is_none());
+    /// assert_eq!(RunLock::holder(&cache), Some(std::process::id()));
+    ///
+    /// drop(lock);
+    /// assert!(RunLock::try_acquire(&cache).unwrap().is_some());
+    /// ```
+    pub fn try_acquire(path: &Path) -> io::Result<Option<RunLock>> {
+        Self::lock(path, false)
+    }
+
+    /// Takes the lock guarding `path`, waiting for the run holding it to release it.
+    pub fn acquire(path: &Path) -> io::Result<RunLock> {
+        Self::lock(path, true)?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::WouldBlock))
+    }
+
+    /// The process ID of the run holding the lock guarding `path`, or of the last run
+    /// that held it, if it is recorded.
+    pub fn holder(path: &Path) -> Option<u32> {
+        fs::read_to_string(Self::lock_path(path)).ok()?.trim().parse().ok()
+    }
+
+    /// Opens the lock file and locks it, waiting for it when `wait` is set, recording this
+    /// process once locked
+    fn lock(path: &Path, wait: bool) -> io::Result<Option<RunLock>> {
+        // Not truncated on opening, which would erase the process ID of the holder
+        let mut file = File::options().read(true).write(true).create(true).truncate(false).open(Self::lock_path(path))?;
+        loop {
+            let locked = if wait { file.lock().map_err(TryLockError::Error) } else { file.try_lock() };
+            match locked {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => return Ok(None),
+                Err(TryLockError::Error(e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Some(RunLock { _file: file }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_acquire_waits_for_release() {
+        let dir = tempfile::Builder::new().prefix("run_lock").tempdir().unwrap();
+        let journal = dir.path().join("journal.jsonl");
+        let held = RunLock::try_acquire(&journal).unwrap().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let waiter = thread::spawn({
+            let journal = journal.clone();
+            move || {
+                let lock = RunLock::acquire(&journal).unwrap();
+                sender.send(()).unwrap();
+                lock
+            }
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(held);
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        drop(waiter.join().unwrap());
+        assert!(!journal.exists());
+    }
+}
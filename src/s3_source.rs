@@ -0,0 +1,141 @@
+use std::io;
+use std::path::PathBuf;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::Client;
+use sha2::{Digest, Sha256};
+use tokio::runtime::Runtime;
+use crate::scan_source::{ScanSource, SourceEntry};
+use crate::localize::message;
+
+/// A `ScanSource` listing the objects of an S3 bucket, or of an S3-compatible object
+/// store, under a prefix.
+///
+/// Credentials, the region and the endpoint are read the usual AWS way: from the
+/// environment (such as `AWS_PROFILE`, `AWS_REGION` and `AWS_ENDPOINT_URL`), the shared
+/// configuration files or the instance metadata. Objects are reported as
+/// `s3://bucket/key`. The ETag of objects uploaded in a single part is the MD5 hash of
+/// their content and is used as their fingerprint, so objects of the same size with
+/// different ETags are not downloaded.
+pub struct S3Source {
+    bucket: String,
+    prefix: String,
+    client: Client,
+    runtime: Runtime,
+}
+
+impl S3Source {
+    /// Connects to the bucket of an `s3://bucket/prefix` URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The bucket and optional key prefix to scan, such as `s3://photos/2023/`.
+    ///
+    /// # Returns
+    ///
+    /// The source, or an error if the URL is not an S3 URL.
+    pub fn new(url: &str) -> io::Result<Self> {
+        let (bucket, prefix) = parse_url(url)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, message("s3-invalid-url", &[("url", &url)])))?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let config = runtime.block_on(aws_config::load_defaults(aws_config::BehaviorVersion::latest()));
+        Ok(S3Source { bucket, prefix, client: Client::new(&config), runtime })
+    }
+
+    fn key<'a>(&self, entry: &'a SourceEntry) -> io::Result<&'a str> {
+        entry.path.to_str()
+            .and_then(|path| path.strip_prefix(&format!("s3://{}/", self.bucket)))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, message("s3-not-in-bucket", &[("file", &entry.path.display())])))
+    }
+}
+
+/// Split an `s3://bucket/prefix` URL into its bucket and prefix
+fn parse_url(url: &str) -> Option<(String, String)> {
+    let location = url.strip_prefix("s3://")?;
+    let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+    (!bucket.is_empty()).then(|| (bucket.to_string(), prefix.to_string()))
+}
+
+/// The ETag of an object when it is the MD5 hash of its content; ETags of multipart
+/// uploads depend on the part size, so they are not comparable
+fn fingerprint(etag: &str) -> Option<String> {
+    let etag = etag.trim_matches('"');
+    (!etag.contains('-')).then(|| etag.to_string())
+}
+
+fn s3_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> io::Error {
+    io::Error::other(DisplayErrorContext(error).to_string())
+}
+
+impl ScanSource for S3Source {
+    fn root(&self) -> PathBuf {
+        PathBuf::from(format!("s3://{}/{}", self.bucket, self.prefix))
+    }
+
+    fn list(&self) -> io::Result<Vec<SourceEntry>> {
+        self.runtime.block_on(async {
+            let mut entries = Vec::new();
+            let mut continuation_token = None;
+            loop {
+                let output = self.client.list_objects_v2()
+                    .bucket(&self.bucket)
+                    .prefix(&self.prefix)
+                    .set_continuation_token(continuation_token)
+                    .send()
+                    .await
+                    .map_err(s3_error)?;
+                for object in output.contents() {
+                    // Keys ending with a slash are the "directories" created by consoles
+                    let Some(key) = object.key().filter(|key| !key.ends_with('/')) else {
+                        continue;
+                    };
+                    entries.push(SourceEntry {
+                        path: PathBuf::from(format!("s3://{}/{}", self.bucket, key)),
+                        size: object.size().unwrap_or(0).max(0) as u64,
+                        fingerprint: object.e_tag().and_then(fingerprint),
+                    });
+                }
+                match output.next_continuation_token() {
+                    Some(token) if output.is_truncated() == Some(true) => continuation_token = Some(token.to_string()),
+                    _ => return Ok(entries),
+                }
+            }
+        })
+    }
+
+    fn hash(&self, entry: &SourceEntry) -> io::Result<String> {
+        let key = self.key(entry)?;
+        self.runtime.block_on(async {
+            let output = self.client.get_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .send()
+                .await
+                .map_err(s3_error)?;
+            let mut body = output.body;
+            let mut hasher = Sha256::new();
+            while let Some(chunk) = body.next().await {
+                hasher.update(chunk.map_err(s3_error)?);
+            }
+            Ok(format!("{:x}", hasher.finalize()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url("s3://bucket"), Some(("bucket".to_string(), String::new())));
+        assert_eq!(parse_url("s3://bucket/photos/2023/"), Some(("bucket".to_string(), "photos/2023/".to_string())));
+        assert_eq!(parse_url("s3:///key"), None);
+        assert_eq!(parse_url("/data"), None);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint("\"9a0364b9e99bb480dd25e1f0284c8555\"").as_deref(), Some("9a0364b9e99bb480dd25e1f0284c8555"));
+        assert_eq!(fingerprint("\"d41d8cd98f00b204e9800998ecf8427e-3\""), None);
+    }
+}
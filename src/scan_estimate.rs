@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use crate::elapsed_time::format_duration;
+use crate::find_duplicates::{hash_mode, scan_root, walk_files};
+use crate::human_readable_size::human_readable_size;
+use crate::scan_options::ScanOptions;
+use crate::localize::message;
+
+/// Bytes hashed from the first candidates to measure the hashing throughput
+const BENCHMARK_BYTES: u64 = 64 * 1024 * 1024;
+
+/// What a scan of a directory tree would have to hash, found by walking the tree
+/// before any scan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanEstimate {
+    /// Number of non-empty files found.
+    pub files: usize,
+    /// Total size of the files found.
+    pub bytes: u64,
+    /// Number of files a scan would hash: those sharing their size with another file,
+    /// or every file when a content mode is enabled.
+    pub candidate_files: usize,
+    /// Total size of the files a scan would hash.
+    pub candidate_bytes: u64,
+    /// Hashing throughput in bytes per second measured on up to 64 MiB of the
+    /// candidates, or `None` when none could be read.
+    pub throughput: Option<f64>,
+}
+
+impl ScanEstimate {
+    /// Roughly how long hashing the candidates would take at the measured throughput.
+    /// Confirming duplicates byte by byte reads them again, so scans of trees with many
+    /// duplicates take longer.
+    pub fn estimated_time(&self) -> Option<Duration> {
+        self.throughput
+            .filter(|throughput| *throughput > 0.0)
+            .map(|throughput| Duration::from_secs_f64(self.candidate_bytes as f64 / throughput))
+    }
+}
+
+impl fmt::Display for ScanEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", message("estimate-files", &[("files", &self.files), ("size", &human_readable_size(self.bytes))]))?;
+        writeln!(f, "{}", message("estimate-candidates", &[
+            ("files", &self.candidate_files),
+            ("size", &human_readable_size(self.candidate_bytes)),
+        ]))?;
+        match (self.throughput, self.estimated_time()) {
+            (Some(throughput), Some(time)) => {
+                writeln!(f, "{}", message("estimate-throughput", &[("throughput", &human_readable_size(throughput as u64))]))?;
+                writeln!(f, "{}", message("estimate-time", &[("time", &format_duration(time))]))
+            }
+            _ => writeln!(f, "{}", message("estimate-time-unknown", &[])),
+        }
+    }
+}
+
+/// Estimates the work of scanning a directory tree with `find_duplicates`.
+///
+/// The tree is walked with the filters of `options` and the files a scan would hash are
+/// counted, then up to 64 MiB of them are hashed to measure the throughput of the disk
+/// and CPU. Files already in the page cache are read faster than the rest, so the time
+/// is a rough lower bound.
+///
+/// # Arguments
+///
+/// * `directory` - The directory whose scan is estimated
+/// * `options` - The `ScanOptions` filtering the files scanned
+///
+/// # Returns
+///
+/// The counts and the throughput measured, or an error if `directory` cannot be walked.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::scan_estimate::estimate_scan;
+/// use dupefiles::scan_options::ScanOptions;
+///
+/// let dir = tempfile::Builder::new().prefix("estimate").tempdir().unwrap();
+/// fs::write(dir.path().join("a.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("b.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("c.txt"), "unique size").unwrap();
+///
+/// let estimate = estimate_scan(dir.path(), &ScanOptions::default()).unwrap();
+/// assert_eq!((estimate.files, estimate.candidate_files), (3, 2));
+/// assert_eq!(estimate.candidate_bytes, 8);
+/// ```
+pub fn estimate_scan(directory: &Path, options: &ScanOptions) -> Result<ScanEstimate> {
+    let root = scan_root(directory)?;
+    let mut estimate = ScanEstimate::default();
+    let mut files: Vec<(PathBuf, u64)> = Vec::new();
+    let mut size_counts: HashMap<u64, usize> = HashMap::new();
+    walk_files(&root, options, Instant::now(), |candidate| {
+        estimate.files += 1;
+        estimate.bytes += candidate.entry.size;
+        *size_counts.entry(candidate.entry.size).or_insert(0) += 1;
+        // Files in disc images cannot be read directly to measure the throughput
+        if candidate.image.is_none() {
+            files.push((candidate.entry.path, candidate.entry.size));
+        }
+        ControlFlow::Continue(())
+    });
+
+    let content_mode = !hash_mode(options).is_empty();
+    for (size, count) in &size_counts {
+        if content_mode || *count > 1 {
+            estimate.candidate_files += count;
+            estimate.candidate_bytes += size * *count as u64;
+        }
+    }
+    files.retain(|(_, size)| content_mode || size_counts[size] > 1);
+    estimate.throughput = measure_throughput(&files);
+    Ok(estimate)
+}
+
+/// Hash up to `BENCHMARK_BYTES` of `files`, returning the bytes hashed per second, or
+/// `None` when nothing could be read
+fn measure_throughput(files: &[(PathBuf, u64)]) -> Option<f64> {
+    let started = Instant::now();
+    let mut hashed = 0;
+    let mut buffer = vec![0; 1024 * 1024];
+    for (path, _) in files {
+        if hashed >= BENCHMARK_BYTES {
+            break;
+        }
+        // Unreadable files are skipped, as a scan would report them and go on
+        let Ok(file) = File::open(path) else {
+            continue;
+        };
+        let mut reader = file.take(BENCHMARK_BYTES - hashed);
+        let mut hasher = Sha256::new();
+        while let Ok(read @ 1..) = reader.read(&mut buffer) {
+            hasher.update(&buffer[..read]);
+            hashed += read as u64;
+        }
+        hasher.finalize();
+    }
+    (hashed > 0).then(|| hashed as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_estimate_content_mode() {
+        let tmp_dir = Builder::new().prefix("scan_estimate_content_mode").tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), "line\r\n").unwrap();
+        fs::write(tmp_dir.path().join("b.txt"), "line\n").unwrap();
+
+        let estimate = estimate_scan(tmp_dir.path(), &ScanOptions::default()).unwrap();
+        assert_eq!(estimate.candidate_files, 0);
+        assert_eq!(estimate.throughput, None);
+        assert_eq!(estimate.estimated_time(), None);
+
+        let options = ScanOptions { normalize_text: true, ..Default::default() };
+        let estimate = estimate_scan(tmp_dir.path(), &options).unwrap();
+        assert_eq!((estimate.candidate_files, estimate.candidate_bytes), (2, 11));
+        assert!(estimate.estimated_time().is_some());
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::compute_gzip_sha256::CompressionFormat;
+use crate::duplicate_comparator::DuplicateComparator;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::hash_cache::HashCache;
+use crate::known_hashes::KnownHashes;
+use crate::quick_check::QuickCheck;
+use crate::write_report::{OutputFormat, PairMode};
+use crate::scan_progress::{ProgressFormat, ScanProgress};
+use crate::walk_filter::WalkFilter;
+
+/// Options controlling which files are scanned, which duplicate groups are reported
+/// and how they are written.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::scan_options::ScanOptions;
+///
+/// let options = ScanOptions {
+///     extensions: Some(vec!["jpg".to_string()]),
+///     min_group_size: 3,
+///     ..Default::default()
+/// };
+/// assert_eq!(options.min_group_size, 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Only scan files with one of these lowercase extensions (e.g., ["mp4", "jpg"]).
+    pub extensions: Option<Vec<String>>,
+    /// Never scan files with one of these lowercase extensions (e.g., ["tmp", "log"]).
+    pub exclude_extensions: Option<Vec<String>>,
+    /// Match `extensions` against the type detected from each file's magic bytes,
+    /// falling back to the file name extension when the type is unknown.
+    pub by_content: bool,
+    /// Skip files smaller than this many bytes, which waste little space however many
+    /// copies they have. Empty files are always skipped. The command defaults to 1 KiB.
+    pub min_size: u64,
+    /// Only report groups with at least this many copies; values below 2 report every group.
+    pub min_group_size: usize,
+    /// Only report groups wasting at least this many bytes (size times extra copies).
+    pub min_wasted: u64,
+    /// Only report groups whose members live in more than one directory, leaving out
+    /// copies kept next to each other on purpose, such as versioned exports.
+    pub cross_dir_only: bool,
+    /// Only report the copies living in the same directory, such as `file (1).jpg` next
+    /// to `file.jpg`: each group is split by directory, and the members alone in their
+    /// directory are left out.
+    pub same_dir_only: bool,
+    /// Compare MP3 and FLAC files by their audio frames only, ignoring tags and artwork.
+    pub audio_content: bool,
+    /// Compare JPEG and PNG files by their image payload only, ignoring EXIF and other metadata.
+    pub image_content: bool,
+    /// Files listed in checksum manifests written elsewhere, compared with the files
+    /// found by their SHA256 hash and reported first in their group, as
+    /// `manifest/path/in/manifest`. They are never read or acted upon.
+    pub known_hashes: Option<Arc<KnownHashes>>,
+    /// Compare email messages, in Maildir folders, `.eml` files and mbox mailboxes, by
+    /// their `Message-ID` and normalized body only, ignoring the other headers. The
+    /// messages of mbox mailboxes are reported as `mailbox/1`, `mailbox/2` and so on.
+    pub email_content: bool,
+    /// Compare text files with CRLF and LF line endings treated as equal.
+    pub normalize_text: bool,
+    /// With `normalize_text`, also ignore spaces and tabs at the end of each line.
+    pub trim_trailing_whitespace: bool,
+    /// Compare files compressed in these formats by their decompressed content, so a
+    /// compressed copy matches the original and copies compressed differently match.
+    /// Other files are then compared by their hash only too.
+    pub decompress: Vec<CompressionFormat>,
+    /// Format of the report written to the output.
+    pub format: OutputFormat,
+    /// Which pairs of members of each group are written in CSV reports.
+    pub pairs: PairMode,
+    /// Report hashing progress on stderr in this format, at most once per second and
+    /// once more when hashing completes.
+    pub progress: Option<ProgressFormat>,
+    /// Progress updated by the scan as it runs, for other threads to report on.
+    pub shared_progress: Option<Arc<Mutex<ScanProgress>>>,
+    /// Once set, the scan stops as soon as possible and reports the groups found so far.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Stop the scan once it has run for this long and report the groups found so far.
+    pub max_runtime: Option<Duration>,
+    /// Only hash the first this many files found, skipping the rest of the tree.
+    pub max_files: Option<usize>,
+    /// Stop the scan once this many bytes were read to hash files and report the groups
+    /// found so far. Hashes reused from `hash_cache` are not counted.
+    pub max_bytes: Option<u64>,
+    /// Also hash the NTFS alternate data streams of every file scanned, reported as
+    /// `file:stream`. Has no effect on platforms other than Windows.
+    pub alternate_streams: bool,
+    /// Also hash the files stored in ISO 9660 disc images (`.iso` files), reported as
+    /// `image.iso/path/in/image`, without mounting the images.
+    pub scan_images: bool,
+    /// Also hash the files stored in tar and zip archives (`.tar`, `.tar.gz`, `.tgz` and
+    /// `.zip` files), reported as `archive.zip/path/in/archive`, without extracting them.
+    pub scan_archives: bool,
+    /// Only report the groups holding both files on disk and files stored in an archive
+    /// or a disc image, such as forgotten extracted copies, listing the stored copies
+    /// first. Needs `scan_archives` or `scan_images` to find any.
+    pub extracted_copies: bool,
+    /// Walk the tree twice, first filling a Bloom filter of this many bytes with the size
+    /// and the hash of the first 4 KiB of every file, then only keeping the files that
+    /// probably have a duplicate, so unique files are neither hashed in full nor kept in
+    /// memory. Ignored when a content mode such as `audio_content` is enabled.
+    pub prefilter: Option<u64>,
+    /// Keep the list of files found while walking below about half this many bytes of
+    /// memory, leaving the rest to the hash indexes: once it grows past that, the files
+    /// are written to a temporary file and only those sharing their size with another
+    /// file are read back. Ignored when a content mode such as `audio_content` is
+    /// enabled, as every file is hashed then.
+    pub memory_limit: Option<u64>,
+    /// Walk the tree level by level, so the files of shallow directories are hashed and
+    /// reported before those of deep ones, which matters when the scan may stop early.
+    pub breadth_first: bool,
+    /// Do not descend into network file systems (NFS, SMB/CIFS, SSHFS, ...) mounted below
+    /// the scanned directory. The directory itself is scanned whatever its file system.
+    pub skip_network: bool,
+    /// Hash files in the order of their data on disk, device by device, instead of the
+    /// walk order, so rotational disks are read mostly sequentially.
+    pub physical_order: bool,
+    /// Hash this many files at a time on each solid-state device, and one at a time on
+    /// each rotational disk, with the files of every device hashed in parallel. With one
+    /// or none, files are hashed one at a time in order.
+    pub jobs: usize,
+    /// Hash the files sharing their size with another file first, largest first, so the
+    /// duplicates wasting the most space are found before a scan stops early.
+    pub largest_first: bool,
+    /// Drop every file from the page cache once it is hashed, so scanning a whole disk
+    /// does not evict the cache of the other programs running on the machine.
+    pub drop_cache: bool,
+    /// Hash files with direct I/O, reading them straight from disk instead of through the
+    /// page cache. Files compared by content only are still read through the cache.
+    pub direct_io: bool,
+    /// Compare files of the same size by a CRC32 checksum of samples or of the whole
+    /// file first, and only hash with SHA256 the files matching another one. Ignored when
+    /// a content mode is enabled.
+    pub quick_check: Option<QuickCheck>,
+    /// Other digests, such as MD5, to compute in the same read as the SHA256 hash of each
+    /// file and report with every group. The hash cache is not used when set, and content
+    /// modes are not supported.
+    pub also_hash: Vec<HashAlgorithm>,
+    /// How many times to try reading the metadata or content of a file again after a
+    /// transient error, such as an I/O error on a flaky network mount.
+    pub retries: u32,
+    /// The wait before the first retry, doubled before each following one.
+    pub retry_delay: Duration,
+    /// Skip files locked by another process, and files modified while they were hashed,
+    /// instead of reporting a hash of content that is changing.
+    pub skip_busy: bool,
+    /// Compare files larger than this many bytes by a hash of their size and of samples
+    /// of their content, making them probable duplicates only.
+    pub sample_above: Option<u64>,
+    /// Hashes reused for unchanged files and updated with every file hashed, for the
+    /// caller to save once the scan is done.
+    pub hash_cache: Option<Arc<Mutex<HashCache>>>,
+    /// Decides which files and directories of a local walk are scanned, on top of or
+    /// instead of the hidden, extension and size filters.
+    pub walk_filter: Option<WalkFilter>,
+    /// Write every path in the report as the salted hashes of its components, keeping
+    /// file extensions, with this salt. Sizes and hashes are written as they are.
+    pub redact_paths: Option<String>,
+    /// Write every path in the report in Unicode Normalization Form C, so names copied
+    /// from macOS, which decomposes accented letters, read like those made elsewhere.
+    pub normalize_paths: bool,
+    /// Write only the aggregate figures of the scan, with no path, instead of the groups.
+    pub stats_only: bool,
+    /// Confirms or refutes that files with equal hashes are duplicates; `None` uses
+    /// `DefaultComparator`.
+    pub comparator: Option<Arc<dyn DuplicateComparator>>,
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::elapsed_time::{format_duration, TimingReport};
+use crate::human_readable_size::human_readable_size;
+use crate::path_encoding::serialize_path;
+use crate::scan_summary::StopReason;
+use crate::localize::message;
+
+/// Minimum time between two progress events.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Format of the progress events reported on stderr while hashing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(message("invalid-progress-format", &[("value", &s)])),
+        }
+    }
+}
+
+/// The phase a scan is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanPhase {
+    /// Walking the directory tree and collecting the files to hash.
+    #[default]
+    Walking,
+    /// Hashing the collected files.
+    Hashing,
+}
+
+/// The errors a scan reports on stderr before carrying on without the file concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScanError {
+    /// The metadata or the target of a symlink could not be read while walking.
+    Metadata,
+    /// The alternate streams of a file, or the files of a disc image or archive, could
+    /// not be listed.
+    Listing,
+    /// A file could not be read to hash it.
+    Hash,
+    /// A file was locked or modified while it was hashed, with `ScanOptions::skip_busy`.
+    Busy,
+    /// A file could not be compared with the first member of its group.
+    Compare,
+}
+
+impl ScanError {
+    /// A short lowercase name of the error, such as `metadata`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScanError::Metadata => "metadata",
+            ScanError::Listing => "listing",
+            ScanError::Hash => "hash",
+            ScanError::Busy => "busy",
+            ScanError::Compare => "compare",
+        }
+    }
+}
+
+/// Tracks how far a scan has progressed.
+///
+/// A scan can be given a shared `ScanProgress` through `ScanOptions::shared_progress`
+/// so that another thread can report on it while the scan runs.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::scan_progress::ScanProgress;
+///
+/// let mut progress = ScanProgress::new(2, 300);
+/// progress.start_file(Path::new("/data/a.txt"));
+/// progress.finish_file(100);
+/// assert_eq!(progress.files_done, 1);
+/// assert!(progress.to_json().contains("\"files_total\":2"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScanProgress {
+    /// The phase the scan is in.
+    pub phase: ScanPhase,
+    /// Number of files hashed so far.
+    pub files_done: usize,
+    /// Number of files to hash, or found so far while walking.
+    pub files_total: usize,
+    /// Bytes hashed so far.
+    pub bytes_hashed: u64,
+    /// Bytes to hash, or found so far while walking.
+    pub bytes_total: u64,
+    /// The file being walked or hashed, or the last one hashed.
+    pub current_path: PathBuf,
+    /// Why the scan stopped early, once it has.
+    pub stopped: Option<StopReason>,
+    /// The number of errors of each kind met so far, while walking and hashing.
+    pub errors: BTreeMap<ScanError, usize>,
+    /// How long each phase took, filled in as the phases end.
+    pub timings: TimingReport,
+    /// The most memory the list of files to hash and the size and hash indexes took at
+    /// once, in bytes, as estimated by `memory_usage::entry_memory` and `map_memory`.
+    pub index_memory: u64,
+    /// Whether the files found while walking were spilled to disk to stay within
+    /// `ScanOptions::memory_limit`.
+    pub spilled: bool,
+    started: Instant,
+    last_report: Option<Instant>,
+}
+
+/// A progress event as written on stderr.
+#[derive(Serialize)]
+struct ProgressEvent<'a> {
+    files_done: usize,
+    files_total: usize,
+    bytes_hashed: u64,
+    bytes_total: u64,
+    #[serde(serialize_with = "serialize_path")]
+    current_path: &'a Path,
+    elapsed_seconds: f64,
+    eta_seconds: Option<f64>,
+}
+
+impl ScanProgress {
+    /// Starts tracking a hashing phase covering `files_total` files and `bytes_total` bytes.
+    pub fn new(files_total: usize, bytes_total: u64) -> Self {
+        ScanProgress {
+            phase: ScanPhase::Hashing,
+            files_done: 0,
+            files_total,
+            bytes_hashed: 0,
+            bytes_total,
+            current_path: PathBuf::new(),
+            stopped: None,
+            errors: BTreeMap::new(),
+            timings: TimingReport::default(),
+            index_memory: 0,
+            spilled: false,
+            started: Instant::now(),
+            last_report: None,
+        }
+    }
+
+    /// Switches from walking to hashing `files_total` files and `bytes_total` bytes,
+    /// keeping the errors met while walking.
+    pub fn start_hashing(&mut self, files_total: usize, bytes_total: u64) {
+        let errors = std::mem::take(&mut self.errors);
+        *self = ScanProgress {
+            errors,
+            timings: self.timings,
+            index_memory: self.index_memory,
+            spilled: self.spilled,
+            ..ScanProgress::new(files_total, bytes_total)
+        };
+    }
+
+    /// Records a file found while walking, which will be hashed later.
+    pub fn found_file(&mut self, path: &Path, size: u64) {
+        self.current_path = path.to_path_buf();
+        self.files_total += 1;
+        self.bytes_total += size;
+    }
+
+    /// Records that a file is about to be hashed.
+    pub fn start_file(&mut self, path: &Path) {
+        self.current_path = path.to_path_buf();
+    }
+
+    /// Records that the current file, of `size` bytes, has been hashed or skipped.
+    pub fn finish_file(&mut self, size: u64) {
+        self.files_done += 1;
+        self.bytes_hashed += size;
+    }
+
+    /// Records that the indexes of the scan take `bytes` of memory, keeping the peak.
+    pub fn note_index_memory(&mut self, bytes: u64) {
+        self.index_memory = self.index_memory.max(bytes);
+    }
+
+    /// Records an error the scan carried on after.
+    pub fn count_error(&mut self, error: ScanError) {
+        *self.errors.entry(error).or_insert(0) += 1;
+    }
+
+    /// Time elapsed since hashing started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Estimated time remaining, extrapolated from the bytes hashed so far.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.bytes_hashed == 0 {
+            return None;
+        }
+        let remaining = self.bytes_total.saturating_sub(self.bytes_hashed) as f64;
+        Some(self.elapsed().mul_f64(remaining / self.bytes_hashed as f64))
+    }
+
+    /// Returns `true`, and restarts the interval, if at least `PROGRESS_INTERVAL` has
+    /// passed since the previous report.
+    pub fn report_due(&mut self) -> bool {
+        let now = Instant::now();
+        let due = self.last_report.is_none_or(|last| now - last >= PROGRESS_INTERVAL);
+        if due {
+            self.last_report = Some(now);
+        }
+        due
+    }
+
+    /// Hashing throughput in bytes per second.
+    pub fn throughput(&self) -> f64 {
+        self.bytes_hashed as f64 / self.elapsed().as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Formats the current progress as a human-readable status line.
+    pub fn status_line(&self) -> String {
+        match self.phase {
+            ScanPhase::Walking => message("status-walking", &[
+                ("files", &self.files_total),
+                ("size", &human_readable_size(self.bytes_total)),
+                ("elapsed", &format_duration(self.elapsed())),
+                ("file", &self.current_path.display()),
+            ]),
+            ScanPhase::Hashing => message("status-hashing", &[
+                ("done", &self.files_done),
+                ("files", &self.files_total),
+                ("hashed", &human_readable_size(self.bytes_hashed)),
+                ("size", &human_readable_size(self.bytes_total)),
+                ("throughput", &human_readable_size(self.throughput() as u64)),
+                ("elapsed", &format_duration(self.elapsed())),
+                ("file", &self.current_path.display()),
+            ]),
+        }
+    }
+
+    /// Formats the current progress as a single-line JSON object.
+    pub fn to_json(&self) -> String {
+        let event = ProgressEvent {
+            files_done: self.files_done,
+            files_total: self.files_total,
+            bytes_hashed: self.bytes_hashed,
+            bytes_total: self.bytes_total,
+            current_path: &self.current_path,
+            elapsed_seconds: self.elapsed().as_secs_f64(),
+            eta_seconds: self.eta().map(|eta| eta.as_secs_f64()),
+        };
+        serde_json::to_string(&event).unwrap_or_default()
+    }
+}
+
+impl Default for ScanProgress {
+    /// Starts tracking a scan in the walking phase.
+    fn default() -> Self {
+        ScanProgress { phase: ScanPhase::Walking, ..ScanProgress::new(0, 0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eta_unknown_before_first_file() {
+        let progress = ScanProgress::new(10, 1000);
+        assert_eq!(progress.eta(), None);
+        assert!(progress.to_json().contains("\"eta_seconds\":null"));
+    }
+
+    #[test]
+    fn test_report_due_once_per_interval() {
+        let mut progress = ScanProgress::new(10, 1000);
+        assert!(progress.report_due());
+        assert!(!progress.report_due());
+    }
+
+    #[test]
+    fn test_status_line() {
+        let mut progress = ScanProgress::default();
+        progress.found_file(Path::new("/data/a.bin"), 2048);
+        assert!(progress.status_line().starts_with("Walking: 1 files found (2.0 KiB)"));
+
+        let mut progress = ScanProgress::new(4, 4096);
+        progress.start_file(Path::new("/data/a.bin"));
+        progress.finish_file(1024);
+        let status = progress.status_line();
+        assert!(status.starts_with("Hashing: 1/4 files, 1.0 KiB/4.0 KiB, "));
+        assert!(status.ends_with("current /data/a.bin"));
+    }
+}
@@ -0,0 +1,325 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use crate::duplicate_comparator::{DefaultComparator, DuplicateComparator};
+use crate::duplicate_group::DuplicateGroup;
+use crate::file_entry::FileEntry;
+use crate::hash_key::HashKey;
+use crate::find_duplicates::{lock_progress, report_groups, stop_reason};
+use crate::scan_options::ScanOptions;
+use crate::scan_progress::{ProgressFormat, ScanError};
+use crate::scan_summary::StopReason;
+use crate::unicode_paths::nfc_str;
+use crate::localize::message;
+
+/// A file or object listed by a `ScanSource`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceEntry {
+    /// The path reported for the entry, such as `s3://bucket/key`.
+    pub path: PathBuf,
+    /// The size of the entry in bytes.
+    pub size: u64,
+    /// A checksum of the content that the source provides for free, such as an S3 ETag,
+    /// or `None` when there is none. Entries of the same size whose fingerprints differ
+    /// are known to differ and are never downloaded; equal fingerprints are only a hint,
+    /// and entries are always hashed before being reported as duplicates.
+    pub fingerprint: Option<String>,
+}
+
+/// A place where duplicates can be searched for other than a local directory tree,
+/// such as an object storage bucket.
+///
+/// A source lists its entries and hashes the content of the ones that may have a
+/// duplicate; `find_source_duplicates` does the rest, so the groups are filtered and
+/// reported exactly like those found by `find_duplicates`.
+pub trait ScanSource {
+    /// The location being scanned, such as `s3://bucket/prefix`, used as the root of
+    /// the per-directory statistics of the summary.
+    fn root(&self) -> PathBuf;
+
+    /// Lists every entry to compare.
+    fn list(&self) -> io::Result<Vec<SourceEntry>>;
+
+    /// Computes the SHA-256 hash of an entry's content as a hexadecimal string.
+    fn hash(&self, entry: &SourceEntry) -> io::Result<String>;
+}
+
+/// Whether an entry must be hashed: only when another entry of the same size may hold
+/// the same content, going by their fingerprints
+fn is_candidate(entry: &SourceEntry, same_size: &[&SourceEntry]) -> bool {
+    same_size.iter().any(|other| {
+        !std::ptr::eq(*other, entry) && match (&entry.fingerprint, &other.fingerprint) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    })
+}
+
+/// Whether `options` filter out an entry by the extension of its path
+pub(crate) fn is_filtered_out(path: &Path, options: &ScanOptions) -> bool {
+    let ext = path.extension().map(|ext| nfc_str(&ext.to_string_lossy().to_lowercase()).into_owned());
+    let listed = |exts: &[String], ext: &str| exts.iter().any(|e| nfc_str(e) == ext);
+    let included = match (&options.extensions, &ext) {
+        (Some(exts), Some(ext)) => listed(exts, ext),
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+    let excluded = match (&options.exclude_extensions, &ext) {
+        (Some(exts), Some(ext)) => listed(exts, ext),
+        _ => false,
+    };
+    !included || excluded
+}
+
+/// Finds the duplicates among the entries of a `ScanSource` and writes them to the
+/// specified output like `find_duplicates` does.
+///
+/// Empty entries are skipped and the `min_size` and extension filters, `min_group_size`, `min_wasted`, `cross_dir_only`,
+/// `max_files`, `max_bytes`, `max_runtime`, `largest_first`, `cancel` and progress options apply. Only entries sharing
+/// their size, and their fingerprint when the source provides one, with another entry
+/// are hashed. Entries with the same SHA-256 hash are reported as duplicates without a
+/// byte-by-byte comparison, and the content modes such as `audio_content` are ignored.
+///
+/// # Arguments
+///
+/// * `source` - The source whose entries are compared
+/// * `options` - The `ScanOptions` filtering the entries scanned and the groups reported,
+///   and selecting the output format
+/// * `output_file` - Optional file to write results to (if None, writes to stdout)
+///
+/// # Returns
+///
+/// The reported duplicate groups, each listing its members in the order they were listed.
+pub fn find_source_duplicates(source: &dyn ScanSource, options: &ScanOptions, output_file: Option<&mut fs::File>) -> Result<Vec<DuplicateGroup>> {
+    let started = Instant::now();
+    let mut stopped = None;
+    let mut file_limit_reached = false;
+
+    let mut entries: Vec<SourceEntry> = source.list()?
+        .into_iter()
+        .filter(|entry| entry.size >= options.min_size.max(1) && !is_filtered_out(&entry.path, options))
+        .collect();
+    let shared_progress = options.shared_progress.clone().unwrap_or_default();
+    let mut progress = lock_progress(&shared_progress);
+    progress.timings.walk = started.elapsed();
+    progress.timings.walk_files = entries.len();
+    progress.timings.walk_bytes = entries.iter().map(|entry| entry.size).sum();
+    drop(progress);
+    if let Some(max_files) = options.max_files.filter(|&max_files| entries.len() > max_files) {
+        entries.truncate(max_files);
+        file_limit_reached = true;
+    }
+
+    let mut by_size: HashMap<u64, Vec<&SourceEntry>> = HashMap::new();
+    for entry in &entries {
+        by_size.entry(entry.size).or_default().push(entry);
+    }
+    let mut candidates: Vec<&SourceEntry> = entries.iter()
+        .filter(|entry| is_candidate(entry, &by_size[&entry.size]))
+        .collect();
+    if options.largest_first {
+        candidates.sort_by_key(|entry| Reverse(entry.size));
+    }
+
+    lock_progress(&shared_progress).start_hashing(candidates.len(), candidates.iter().map(|entry| entry.size).sum());
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut group_index: HashMap<(HashKey, u64), usize> = HashMap::new();
+    let hashing_started = Instant::now();
+    let mut verify = Duration::ZERO;
+    let mut verify_files = 0;
+    let mut bytes_read = 0;
+    let comparator: &dyn DuplicateComparator = options.comparator.as_deref().unwrap_or(&DefaultComparator);
+    for entry in candidates {
+        stopped = stopped.or_else(|| stop_reason(options, started));
+        if options.max_bytes.is_some_and(|max_bytes| bytes_read >= max_bytes) {
+            stopped = stopped.or(Some(StopReason::MaxBytes));
+        }
+        if stopped.is_some() {
+            break;
+        }
+
+        lock_progress(&shared_progress).start_file(&entry.path);
+        let hashed = source.hash(entry);
+        bytes_read += entry.size;
+        let mut progress = lock_progress(&shared_progress);
+        progress.finish_file(entry.size);
+        if options.progress == Some(ProgressFormat::Json) && progress.report_due() {
+            eprintln!("{}", progress.to_json());
+        }
+        drop(progress);
+        let hash = match hashed.and_then(|hash| {
+            HashKey::parse(&hash).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, message("scan-invalid-hash-value", &[("hash", &hash)])))
+        }) {
+            Ok(hash) => hash,
+            Err(e) => {
+                eprintln!("{}", message("scan-hash-failed", &[("file", &entry.path.display()), ("error", &e)]));
+                lock_progress(&shared_progress).count_error(ScanError::Hash);
+                continue;
+            }
+        };
+
+        let file_entry = FileEntry::unknown(&entry.path, entry.size, &hash.to_string());
+        match group_index.get(&(hash, entry.size)) {
+            Some(&index) => {
+                let verify_started = Instant::now();
+                let is_duplicate = comparator.is_duplicate(&groups[index].entries[0], &file_entry);
+                verify += verify_started.elapsed();
+                verify_files += 1;
+                match is_duplicate {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => {
+                        eprintln!("{}", message("scan-compare-failed", &[("error", &e)]));
+                        lock_progress(&shared_progress).count_error(ScanError::Compare);
+                        continue;
+                    }
+                }
+                groups[index].files.push(entry.path.clone());
+                groups[index].entries.push(file_entry);
+            }
+            None => {
+                group_index.insert((hash, entry.size), groups.len());
+                groups.push(DuplicateGroup {
+                    hash: hash.to_string(),
+                    size: entry.size,
+                    files: vec![entry.path.clone()],
+                    reflinked: Vec::new(),
+                    digests: BTreeMap::new(),
+                    entries: vec![file_entry],
+                });
+            }
+        }
+    }
+    groups.retain(|group| group.files.len() > 1);
+
+    if file_limit_reached {
+        stopped = stopped.or(Some(StopReason::MaxFiles));
+    }
+    let mut progress = lock_progress(&shared_progress);
+    progress.stopped = stopped;
+    progress.timings.hash = hashing_started.elapsed().saturating_sub(verify);
+    progress.timings.hash_files = progress.files_done;
+    progress.timings.hash_bytes = bytes_read;
+    // Entries are compared by their hash, so none is read again to verify it
+    progress.timings.verify = verify;
+    progress.timings.verify_files = verify_files;
+    drop(progress);
+    if options.progress == Some(ProgressFormat::Json) {
+        eprintln!("{}", lock_progress(&shared_progress).to_json());
+    }
+
+    let output_started = Instant::now();
+    let progress = lock_progress(&shared_progress).clone();
+    report_groups(&mut groups, None, &source.root(), &progress, options, output_file)?;
+    let mut progress = lock_progress(&shared_progress);
+    progress.timings.output = output_started.elapsed();
+    progress.timings.total = started.elapsed();
+    drop(progress);
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use sha2::{Digest, Sha256};
+
+    /// An in-memory source recording which entries were hashed
+    struct MemorySource {
+        objects: Vec<(&'static str, &'static str, Option<&'static str>)>,
+        hashed: RefCell<Vec<PathBuf>>,
+    }
+
+    impl ScanSource for MemorySource {
+        fn root(&self) -> PathBuf {
+            PathBuf::from("mem://bucket")
+        }
+
+        fn list(&self) -> io::Result<Vec<SourceEntry>> {
+            Ok(self.objects.iter().map(|(key, content, fingerprint)| SourceEntry {
+                path: self.root().join(key),
+                size: content.len() as u64,
+                fingerprint: fingerprint.map(String::from),
+            }).collect())
+        }
+
+        fn hash(&self, entry: &SourceEntry) -> io::Result<String> {
+            self.hashed.borrow_mut().push(entry.path.clone());
+            let (_, content, _) = self.objects.iter()
+                .find(|(key, _, _)| self.root().join(key) == entry.path)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            Ok(format!("{:x}", Sha256::digest(content.as_bytes())))
+        }
+    }
+
+    #[test]
+    fn test_find_source_duplicates() {
+        let source = MemorySource {
+            objects: vec![
+                ("a.txt", "same", None),
+                ("b.txt", "same", None),
+                ("c.txt", "diff", None),
+                ("d.txt", "unique size", None),
+                ("e.txt", "", None),
+            ],
+            hashed: RefCell::new(Vec::new()),
+        };
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_source_duplicates(&source, &ScanOptions::default(), Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, vec![PathBuf::from("mem://bucket/a.txt"), PathBuf::from("mem://bucket/b.txt")]);
+        assert_eq!(source.hashed.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_different_fingerprints_not_hashed() {
+        let source = MemorySource {
+            objects: vec![
+                ("a.txt", "same", Some("1")),
+                ("b.txt", "same", Some("1")),
+                ("c.txt", "diff", Some("2")),
+                ("d.txt", "size", Some("3")),
+            ],
+            hashed: RefCell::new(Vec::new()),
+        };
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_source_duplicates(&source, &ScanOptions::default(), Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        let hashed = source.hashed.borrow();
+        assert_eq!(*hashed, vec![PathBuf::from("mem://bucket/a.txt"), PathBuf::from("mem://bucket/b.txt")]);
+    }
+
+    /// Only objects below the same prefix are duplicates
+    #[derive(Debug)]
+    struct SamePrefix;
+
+    impl DuplicateComparator for SamePrefix {
+        fn is_duplicate(&self, first: &FileEntry, second: &FileEntry) -> io::Result<bool> {
+            Ok(first.path.parent() == second.path.parent())
+        }
+    }
+
+    #[test]
+    fn test_comparator_refutes_duplicates() {
+        let source = MemorySource {
+            objects: vec![
+                ("a/1.txt", "same", None),
+                ("a/2.txt", "same", None),
+                ("b/3.txt", "same", None),
+            ],
+            hashed: RefCell::new(Vec::new()),
+        };
+        let options = ScanOptions { comparator: Some(std::sync::Arc::new(SamePrefix)), ..Default::default() };
+        let mut output = tempfile::tempfile().unwrap();
+
+        let groups = find_source_duplicates(&source, &options, Some(&mut output)).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, vec![PathBuf::from("mem://bucket/a/1.txt"), PathBuf::from("mem://bucket/a/2.txt")]);
+    }
+}
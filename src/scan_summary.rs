@@ -0,0 +1,328 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::clone_file::copy_on_write_supported;
+use crate::duplicate_group::DuplicateGroup;
+use crate::human_readable_size::human_readable_size;
+use crate::localize::message;
+use crate::path_encoding::serialize_path_keys;
+
+/// Aggregate figures describing the duplicates found by a scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScanSummary {
+    /// Number of duplicate groups.
+    pub duplicate_groups: usize,
+    /// Number of files that duplicate the first-seen member of their group.
+    pub duplicate_files: usize,
+    /// Bytes that would be freed by keeping a single copy of every group.
+    pub wasted_bytes: u64,
+    /// Bytes each action would free, as not every duplicate can be hard linked or reflinked.
+    pub savings: ActionSavings,
+    /// Wasted bytes per top-level directory below the scanned directory. Each
+    /// duplicate is counted under the directory that contains it; files directly
+    /// in the scanned directory are counted under the scanned directory itself.
+    #[serde(serialize_with = "serialize_path_keys")]
+    pub wasted_by_directory: BTreeMap<PathBuf, u64>,
+    /// Duplicate files and wasted bytes per lowercase file extension; files without an
+    /// extension are counted under an empty string.
+    pub by_extension: BTreeMap<String, ExtensionStats>,
+    /// Why the scan stopped early, if it did, in which case the figures only cover the
+    /// files scanned before it stopped.
+    pub stopped: Option<StopReason>,
+    /// The `ScanOptions::min_size` of the scan; the figures leave out smaller files.
+    pub min_size: u64,
+    /// The most memory the list of files to hash and the size and hash indexes of the
+    /// scan took at once, in bytes, or 0 when unknown (see `ScanProgress::index_memory`).
+    pub index_memory: u64,
+    /// Whether the files found were spilled to disk to stay within
+    /// `ScanOptions::memory_limit`.
+    pub spilled: bool,
+}
+
+/// Why a scan stopped before every file was hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The scan was cancelled, for example with Ctrl-C.
+    Interrupted,
+    /// The scan ran for longer than `ScanOptions::max_runtime`.
+    MaxRuntime,
+    /// The scan found more files than `ScanOptions::max_files`.
+    MaxFiles,
+    /// The scan hashed as many bytes as `ScanOptions::max_bytes`.
+    MaxBytes,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Interrupted => write!(f, "{}", message("stop-interrupted", &[])),
+            StopReason::MaxRuntime => write!(f, "{}", message("stop-max-runtime", &[])),
+            StopReason::MaxFiles => write!(f, "{}", message("stop-max-files", &[])),
+            StopReason::MaxBytes => write!(f, "{}", message("stop-max-bytes", &[])),
+        }
+    }
+}
+
+/// The bytes each action would free if applied to every duplicate, keeping the first-seen
+/// member of each group.
+///
+/// Hard links and copy-on-write clones can only be made on the file system of the copy
+/// kept, so only the duplicates on the same device as the first member are counted for
+/// them, which needs the `DuplicateGroup::entries` of the group: duplicates whose device
+/// is unknown, such as those of a CSV report or a remote source, only count towards
+/// `delete`, as do those matched by their decoded content, which are never linked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ActionSavings {
+    /// Bytes freed by deleting or trashing the duplicates, the wasted bytes of the summary.
+    pub delete: u64,
+    /// Bytes freed by replacing the duplicates with hard links.
+    pub hardlink: u64,
+    /// Bytes freed by reflinking or deduplicating the duplicates, for those on a file
+    /// system supporting copy-on-write (see `copy_on_write_supported`).
+    pub reflink: u64,
+}
+
+/// Duplicate figures for a single file extension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ExtensionStats {
+    /// Number of files that duplicate the first-seen member of their group.
+    pub duplicate_files: usize,
+    /// Bytes that would be freed by keeping a single copy.
+    pub wasted_bytes: u64,
+}
+
+/// Summarizes duplicate groups found below a directory.
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups, each listing its first-seen member first.
+/// * `root` - The canonical path of the scanned directory.
+///
+/// # Returns
+///
+/// A `ScanSummary` with totals and per-directory and per-extension breakdowns.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use dupefiles::duplicate_group::DuplicateGroup;
+/// use dupefiles::scan_summary::summarize;
+///
+/// let group = DuplicateGroup {
+///     hash: String::new(),
+///     size: 100,
+///     files: vec![PathBuf::from("/data/archive/a.jpg"), PathBuf::from("/data/downloads/a.jpg")],
+///     reflinked: Vec::new(),
+///     digests: Default::default(),
+///     entries: Vec::new(),
+/// };
+/// let summary = summarize(&[group], Path::new("/data"));
+/// assert_eq!(summary.wasted_bytes, 100);
+/// assert_eq!(summary.wasted_by_directory[Path::new("/data/downloads")], 100);
+/// ```
+pub fn summarize(groups: &[DuplicateGroup], root: &Path) -> ScanSummary {
+    let mut summary = ScanSummary::default();
+    // Each file system is only queried once
+    let mut copy_on_write: HashMap<u64, bool> = HashMap::new();
+    for group in groups {
+        summary.duplicate_groups += 1;
+        summary.duplicate_files += group.files.len().saturating_sub(1);
+        summary.wasted_bytes += group.wasted_bytes();
+        let keeper_dev = group.entries.first().and_then(|entry| entry.dev).filter(|_| !group.is_content_match());
+        for duplicate in group.wasting_files() {
+            let on_keeper_device = |dev: &u64| group.entries.iter().any(|entry| &entry.path == duplicate && entry.dev == Some(*dev));
+            if let Some(dev) = keeper_dev.filter(on_keeper_device) {
+                summary.savings.hardlink += group.size;
+                if *copy_on_write.entry(dev).or_insert_with(|| copy_on_write_supported(&group.files[0]).unwrap_or(false)) {
+                    summary.savings.reflink += group.size;
+                }
+            }
+            *summary.wasted_by_directory.entry(top_level_directory(duplicate, root)).or_insert(0) += group.size;
+            let extension = duplicate.extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let stats = summary.by_extension.entry(extension).or_default();
+            stats.duplicate_files += 1;
+            stats.wasted_bytes += group.size;
+        }
+    }
+    summary.savings.delete = summary.wasted_bytes;
+    summary
+}
+
+/// Returns the directory directly below `root` that contains `path`, or `root` itself
+/// for files directly inside it.
+fn top_level_directory(path: &Path, root: &Path) -> PathBuf {
+    match path.strip_prefix(root).ok().and_then(|relative| relative.parent()) {
+        Some(parent) => match parent.components().next() {
+            Some(component) => root.join(component),
+            None => root.to_path_buf(),
+        },
+        None => path.parent().unwrap_or(root).to_path_buf(),
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(reason) = self.stopped {
+            writeln!(f, "{}", message("summary-stopped", &[("reason", &reason)]))?;
+        }
+        writeln!(f, "{}", message("summary-totals", &[
+            ("groups", &self.duplicate_groups),
+            ("files", &self.duplicate_files),
+            ("wasted", &human_readable_size(self.wasted_bytes)),
+        ]))?;
+        if self.duplicate_groups > 0 {
+            writeln!(f, "{}", message("summary-savings", &[
+                ("delete", &human_readable_size(self.savings.delete)),
+                ("hardlink", &human_readable_size(self.savings.hardlink)),
+                ("reflink", &human_readable_size(self.savings.reflink)),
+            ]))?;
+        }
+        if self.min_size > 1 {
+            writeln!(f, "{}", message("summary-min-size", &[("size", &human_readable_size(self.min_size))]))?;
+        }
+        if self.index_memory > 0 {
+            writeln!(f, "{}", message("summary-memory", &[("memory", &human_readable_size(self.index_memory))]))?;
+        }
+        if self.spilled {
+            writeln!(f, "{}", message("summary-spilled", &[]))?;
+        }
+        if self.wasted_by_directory.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "{}", message("summary-by-directory", &[]))?;
+        let mut directories: Vec<_> = self.wasted_by_directory.iter().collect();
+        directories.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        for (directory, bytes) in directories {
+            let percent = *bytes as f64 * 100.0 / self.wasted_bytes.max(1) as f64;
+            writeln!(f, "  {}", message("summary-directory", &[
+                ("directory", &directory.display()),
+                ("wasted", &human_readable_size(*bytes)),
+                ("percent", &format!("{:.1}", percent)),
+            ]))?;
+        }
+        writeln!(f, "{}", message("summary-by-extension", &[]))?;
+        let mut extensions: Vec<_> = self.by_extension.iter().collect();
+        extensions.sort_by(|a, b| b.1.wasted_bytes.cmp(&a.1.wasted_bytes).then_with(|| a.0.cmp(b.0)));
+        for (extension, stats) in extensions {
+            let extension = if extension.is_empty() { message("summary-no-extension", &[]) } else { extension.clone() };
+            writeln!(f, "  {}", message("summary-extension", &[
+                ("extension", &extension),
+                ("files", &stats.duplicate_files),
+                ("wasted", &human_readable_size(stats.wasted_bytes)),
+            ]))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_entry::FileEntry;
+
+    #[test]
+    fn test_top_level_directory() {
+        let root = Path::new("/data");
+        assert_eq!(top_level_directory(Path::new("/data/a/b/c.txt"), root), PathBuf::from("/data/a"));
+        assert_eq!(top_level_directory(Path::new("/data/c.txt"), root), PathBuf::from("/data"));
+        assert_eq!(top_level_directory(Path::new("/other/c.txt"), root), PathBuf::from("/other"));
+    }
+
+    #[test]
+    fn test_summarize_three_copies() {
+        let group = DuplicateGroup {
+            hash: String::new(),
+            size: 10,
+            files: vec![
+                PathBuf::from("/data/keep.txt"),
+                PathBuf::from("/data/x/copy.txt"),
+                PathBuf::from("/data/x/y/copy.txt"),
+            ],
+            reflinked: Vec::new(),
+            digests: BTreeMap::new(),
+            entries: Vec::new(),
+        };
+        let summary = summarize(&[group], Path::new("/data"));
+
+        assert_eq!(summary.duplicate_groups, 1);
+        assert_eq!(summary.duplicate_files, 2);
+        assert_eq!(summary.wasted_bytes, 20);
+        assert_eq!(summary.wasted_by_directory.len(), 1);
+        assert_eq!(summary.wasted_by_directory[Path::new("/data/x")], 20);
+        assert_eq!(summary.by_extension["txt"], ExtensionStats { duplicate_files: 2, wasted_bytes: 20 });
+    }
+
+    #[test]
+    fn test_summarize_by_extension() {
+        let group = DuplicateGroup {
+            hash: String::new(),
+            size: 5,
+            files: vec![
+                PathBuf::from("/data/song.mp3"),
+                PathBuf::from("/data/song.MP3"),
+                PathBuf::from("/data/song"),
+            ],
+            reflinked: Vec::new(),
+            digests: BTreeMap::new(),
+            entries: Vec::new(),
+        };
+        let summary = summarize(&[group], Path::new("/data"));
+
+        assert_eq!(summary.by_extension["mp3"], ExtensionStats { duplicate_files: 1, wasted_bytes: 5 });
+        assert_eq!(summary.by_extension[""], ExtensionStats { duplicate_files: 1, wasted_bytes: 5 });
+    }
+
+    #[test]
+    fn test_summarize_reflinked() {
+        let group = DuplicateGroup {
+            hash: String::new(),
+            size: 10,
+            files: vec![
+                PathBuf::from("/data/keep.txt"),
+                PathBuf::from("/data/x/clone.txt"),
+                PathBuf::from("/data/y/copy.txt"),
+            ],
+            reflinked: vec![PathBuf::from("/data/x/clone.txt")],
+            digests: BTreeMap::new(),
+            entries: Vec::new(),
+        };
+        let summary = summarize(&[group], Path::new("/data"));
+
+        assert_eq!(summary.duplicate_files, 2);
+        assert_eq!(summary.wasted_bytes, 10);
+        assert_eq!(summary.wasted_by_directory.keys().collect::<Vec<_>>(), vec![Path::new("/data/y")]);
+    }
+
+    #[test]
+    fn test_savings_other_device() {
+        let entry = |path: &str, dev, inode| FileEntry {
+            path: PathBuf::from(path),
+            size: 10,
+            allocated: None,
+            mtime: None,
+            dev: Some(dev),
+            inode: Some(inode),
+            hash: String::new(),
+        };
+        let entries = vec![entry("/data/keep.txt", 1, 1), entry("/data/copy.txt", 1, 2), entry("/mnt/usb/copy.txt", 2, 1)];
+        let group = DuplicateGroup {
+            hash: String::new(),
+            size: 10,
+            files: entries.iter().map(|entry| entry.path.clone()).collect(),
+            reflinked: Vec::new(),
+            digests: BTreeMap::new(),
+            entries,
+        };
+        let summary = summarize(std::slice::from_ref(&group), Path::new("/data"));
+        assert_eq!((summary.savings.delete, summary.savings.hardlink), (20, 10));
+
+        // Without entries the devices are unknown
+        let summary = summarize(&[DuplicateGroup { entries: Vec::new(), ..group }], Path::new("/data"));
+        assert_eq!(summary.savings, ActionSavings { delete: 20, hardlink: 0, reflink: 0 });
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+use crate::unicode_paths::starts_with;
+use crate::localize::message;
+
+/// Rule used to choose which member of a duplicate group is kept when an action runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepPolicy {
+    /// Keep the first copy found during the scan.
+    #[default]
+    First,
+    /// Keep the most recently modified copy.
+    Newest,
+    /// Keep the least recently modified copy.
+    Oldest,
+}
+
+impl FromStr for KeepPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(KeepPolicy::First),
+            "newest" => Ok(KeepPolicy::Newest),
+            "oldest" => Ok(KeepPolicy::Oldest),
+            _ => Err(message("invalid-keep-policy", &[("value", &s)])),
+        }
+    }
+}
+
+/// Selects the member of a duplicate group to keep.
+///
+/// When preferred directories are given, the keeper is chosen among the members
+/// located under the first directory in the list that contains any member; the
+/// policy is then applied to those members only. Ties on modification time are broken by choosing the lexicographically smallest
+/// path, so the result does not depend on traversal order. Files whose modification
+/// time cannot be read are only chosen when no other member has one.
+///
+/// # Arguments
+///
+/// * `files` - The members of the duplicate group, in the order they were found.
+/// * `policy` - The `KeepPolicy` to apply.
+/// * `preferred_dirs` - Directories in decreasing order of priority, compared against
+///   the member paths by prefix, in Unicode NFC.
+///
+/// # Returns
+///
+/// The index into `files` of the copy to keep.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use dupefiles::select_keeper::{select_keeper, KeepPolicy};
+///
+/// let files = vec![PathBuf::from("/b/file.txt"), PathBuf::from("/a/file.txt")];
+/// assert_eq!(select_keeper(&files, KeepPolicy::First, &[]), 0);
+/// assert_eq!(select_keeper(&files, KeepPolicy::First, &[PathBuf::from("/a")]), 1);
+/// ```
+pub fn select_keeper(files: &[PathBuf], policy: KeepPolicy, preferred_dirs: &[PathBuf]) -> usize {
+    let in_dir = |dir: &Path| -> Vec<usize> {
+        (0..files.len()).filter(|&index| starts_with(&files[index], dir)).collect()
+    };
+    let indices = preferred_dirs.iter()
+        .map(|dir| in_dir(dir))
+        .find(|indices| !indices.is_empty())
+        .unwrap_or_else(|| (0..files.len()).collect());
+
+    let modified = |path: &PathBuf| -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    };
+    let candidates = indices.iter().map(|&index| (index, &files[index], modified(&files[index])));
+    let chosen = match policy {
+        KeepPolicy::First => return indices.first().copied().unwrap_or(0),
+        KeepPolicy::Newest => candidates.min_by(|a, b| {
+            b.2.is_some().cmp(&a.2.is_some())
+                .then_with(|| b.2.cmp(&a.2))
+                .then_with(|| a.1.cmp(b.1))
+        }),
+        KeepPolicy::Oldest => candidates.min_by(|a, b| {
+            b.2.is_some().cmp(&a.2.is_some())
+                .then_with(|| a.2.cmp(&b.2))
+                .then_with(|| a.1.cmp(b.1))
+        }),
+    };
+    chosen.map(|(index, _, _)| index).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+    use tempfile::Builder;
+
+    fn create_file(path: &PathBuf, modified: SystemTime) {
+        fs::write(path, "duplicate content").unwrap();
+        File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn test_keep_newest_and_oldest() {
+        let tmp_dir = Builder::new().prefix("select_keeper").tempdir().unwrap();
+        let now = SystemTime::now();
+        let old = tmp_dir.path().join("old.txt");
+        let new = tmp_dir.path().join("new.txt");
+        create_file(&old, now - Duration::from_secs(3600));
+        create_file(&new, now);
+        let files = vec![old, new];
+
+        assert_eq!(select_keeper(&files, KeepPolicy::Newest, &[]), 1);
+        assert_eq!(select_keeper(&files, KeepPolicy::Oldest, &[]), 0);
+    }
+
+    #[test]
+    fn test_tie_broken_by_path() {
+        let tmp_dir = Builder::new().prefix("select_keeper_tie").tempdir().unwrap();
+        let modified = SystemTime::now();
+        let b = tmp_dir.path().join("b.txt");
+        let a = tmp_dir.path().join("a.txt");
+        create_file(&b, modified);
+        create_file(&a, modified);
+        let files = vec![b, a];
+
+        assert_eq!(select_keeper(&files, KeepPolicy::Newest, &[]), 1);
+        assert_eq!(select_keeper(&files, KeepPolicy::Oldest, &[]), 1);
+    }
+
+    #[test]
+    fn test_missing_files_are_not_preferred() {
+        let tmp_dir = Builder::new().prefix("select_keeper_missing").tempdir().unwrap();
+        let existing = tmp_dir.path().join("z.txt");
+        create_file(&existing, SystemTime::now());
+        let files = vec![PathBuf::from("/non/existing/a.txt"), existing];
+
+        assert_eq!(select_keeper(&files, KeepPolicy::Oldest, &[]), 1);
+        assert_eq!(select_keeper(&files, KeepPolicy::Newest, &[]), 1);
+    }
+
+    #[test]
+    fn test_preferred_dirs_in_order() {
+        let files = vec![
+            PathBuf::from("/downloads/file.txt"),
+            PathBuf::from("/archive/file.txt"),
+            PathBuf::from("/archive/old/file.txt"),
+        ];
+        let preferred = vec![PathBuf::from("/backup"), PathBuf::from("/archive"), PathBuf::from("/downloads")];
+
+        assert_eq!(select_keeper(&files, KeepPolicy::First, &preferred), 1);
+        assert_eq!(select_keeper(&files, KeepPolicy::First, &[PathBuf::from("/arch")]), 0);
+    }
+
+    #[test]
+    fn test_preferred_dirs_decomposed() {
+        let files = vec![PathBuf::from("/downloads/file.txt"), PathBuf::from("/photos/cafe\u{301}/file.txt")];
+        assert_eq!(select_keeper(&files, KeepPolicy::First, &[PathBuf::from("/photos/caf\u{e9}")]), 1);
+    }
+}
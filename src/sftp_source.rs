@@ -0,0 +1,191 @@
+use std::env;
+use std::io::{self, Read};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+use crate::scan_source::{ScanSource, SourceEntry};
+use crate::localize::message;
+
+/// Private keys tried, in this order, when the SSH agent cannot authenticate
+const IDENTITY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// A `ScanSource` walking a directory tree on a remote server over SFTP.
+///
+/// The server's host key must be listed in `~/.ssh/known_hosts`. Authentication uses
+/// the SSH agent, falling back to the unencrypted default private keys in `~/.ssh`.
+/// Hidden files and directories and symbolic links are skipped, and files are reported
+/// as `sftp://user@host/path`.
+pub struct SftpSource {
+    url: String,
+    authority: String,
+    path: String,
+    sftp: Sftp,
+}
+
+impl SftpSource {
+    /// Connects to the server of an `sftp://[user@]host[:port]/path` URL.
+    ///
+    /// The user defaults to the local user and the port to 22.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The server and directory to scan, such as `sftp://backup@nas/srv/photos`.
+    ///
+    /// # Returns
+    ///
+    /// The source, or an error if the URL is invalid, the host key is unknown or does
+    /// not match, or authentication fails.
+    pub fn new(url: &str) -> io::Result<Self> {
+        let (user, host, port, path) = parse_url(url)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, message("sftp-invalid-url", &[("url", &url)])))?;
+
+        let mut session = Session::new()?;
+        session.set_tcp_stream(TcpStream::connect((host.as_str(), port))?);
+        session.handshake()?;
+        check_host_key(&session, &host, port)?;
+        authenticate(&session, &user)?;
+        let sftp = session.sftp()?;
+
+        let authority = url.trim_start_matches("sftp://").split('/').next().unwrap_or_default().to_string();
+        Ok(SftpSource { url: url.to_string(), authority, path, sftp })
+    }
+
+    fn entry_path(&self, remote_path: &Path) -> PathBuf {
+        PathBuf::from(format!("sftp://{}{}", self.authority, remote_path.display()))
+    }
+
+    fn remote_path(&self, entry: &SourceEntry) -> io::Result<PathBuf> {
+        entry.path.to_str()
+            .and_then(|path| path.strip_prefix(&format!("sftp://{}", self.authority)))
+            .map(PathBuf::from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, message("sftp-not-on-server", &[("file", &entry.path.display())])))
+    }
+}
+
+/// Split an `sftp://[user@]host[:port]/path` URL into its user, host, port and path
+fn parse_url(url: &str) -> Option<(String, String, u16, String)> {
+    let location = url.strip_prefix("sftp://")?;
+    let (authority, path) = match location.find('/') {
+        Some(index) => location.split_at(index),
+        None => (location, "/"),
+    };
+    let (user, host_port) = match authority.rsplit_once('@') {
+        Some((user, host_port)) => (user.to_string(), host_port),
+        None => (env::var("USER").ok()?, authority),
+    };
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (host_port, 22),
+    };
+    (!host.is_empty() && !user.is_empty()).then(|| (user, host.to_string(), port, path.to_string()))
+}
+
+/// Refuse servers whose host key is not the one recorded in ~/.ssh/known_hosts
+fn check_host_key(session: &Session, host: &str, port: u16) -> io::Result<()> {
+    let (key, _) = session.host_key()
+        .ok_or_else(|| io::Error::other(message("sftp-no-host-key", &[])))?;
+    let mut known_hosts = session.known_hosts()?;
+    if let Some(home) = env::var_os("HOME") {
+        // A missing known_hosts file leaves every host unknown
+        let _ = known_hosts.read_file(&Path::new(&home).join(".ssh/known_hosts"), KnownHostFileKind::OpenSSH);
+    }
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            message("sftp-host-key-mismatch", &[("host", &host)]),
+        )),
+        CheckResult::NotFound => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            message("sftp-host-unknown", &[("host", &host)]),
+        )),
+        CheckResult::Failure => Err(io::Error::other(message("sftp-host-key-check-failed", &[]))),
+    }
+}
+
+/// Authenticate with the SSH agent, then with the default private keys
+fn authenticate(session: &Session, user: &str) -> io::Result<()> {
+    if session.userauth_agent(user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+    if let Some(home) = env::var_os("HOME") {
+        for name in IDENTITY_FILES {
+            let key = Path::new(&home).join(".ssh").join(name);
+            if key.exists() && session.userauth_pubkey_file(user, None, &key, None).is_ok() && session.authenticated() {
+                return Ok(());
+            }
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::PermissionDenied, message("sftp-auth-failed", &[("user", &user)])))
+}
+
+impl ScanSource for SftpSource {
+    fn root(&self) -> PathBuf {
+        PathBuf::from(&self.url)
+    }
+
+    fn list(&self) -> io::Result<Vec<SourceEntry>> {
+        let mut entries = Vec::new();
+        let mut directories = vec![PathBuf::from(&self.path)];
+        while let Some(directory) = directories.pop() {
+            let listing = match self.sftp.readdir(&directory) {
+                Ok(listing) => listing,
+                Err(e) if directory == Path::new(&self.path) => return Err(e.into()),
+                Err(e) => {
+                    eprintln!("{}", message("scan-directory-unreadable", &[("directory", &self.entry_path(&directory).display()), ("error", &e)]));
+                    continue;
+                }
+            };
+            for (path, stat) in listing {
+                if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+                    continue;
+                }
+                if stat.is_dir() {
+                    directories.push(path);
+                } else if stat.is_file() {
+                    entries.push(SourceEntry {
+                        path: self.entry_path(&path),
+                        size: stat.size.unwrap_or(0),
+                        fingerprint: None,
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn hash(&self, entry: &SourceEntry) -> io::Result<String> {
+        let mut file = self.sftp.open(&self.remote_path(entry)?)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 1024 * 1024];
+        loop {
+            let bytes_read = file.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("sftp://backup@nas:2222/srv/photos"),
+            Some(("backup".to_string(), "nas".to_string(), 2222, "/srv/photos".to_string()))
+        );
+        assert_eq!(
+            parse_url("sftp://backup@nas"),
+            Some(("backup".to_string(), "nas".to_string(), 22, "/".to_string()))
+        );
+        assert_eq!(parse_url("sftp://backup@:22/srv"), None);
+        assert_eq!(parse_url("sftp://backup@nas:ssh/srv"), None);
+        assert_eq!(parse_url("s3://bucket"), None);
+    }
+}
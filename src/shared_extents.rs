@@ -0,0 +1,140 @@
+use std::io;
+use std::path::Path;
+
+/// Returns whether two files already share all their data on disk, as reflinked copies
+/// and files deduplicated by the kernel do.
+///
+/// The physical extents of both files are compared with the FIEMAP ioctl, so this only
+/// detects sharing on Linux file systems that report extents (such as Btrfs and XFS).
+/// Files whose extents cannot be compared are reported as not sharing, as they are on
+/// other platforms.
+///
+/// # Arguments
+///
+/// * `file1` - The first file.
+/// * `file2` - The second file.
+///
+/// # Returns
+///
+/// Whether every extent of `file1` is also an extent of `file2` at the same offset, or
+/// an error if either file cannot be opened.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::shared_extents::shares_extents;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (a, b) = (dir.path().join("a.bin"), dir.path().join("b.bin"));
+/// fs::write(&a, vec![1u8; 8192]).unwrap();
+/// fs::write(&b, vec![1u8; 8192]).unwrap();
+/// assert!(!shares_extents(&a, &b).unwrap());
+/// ```
+pub fn shares_extents(file1: &Path, file2: &Path) -> io::Result<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        let (extents1, extents2) = (linux::extents(file1)?, linux::extents(file2)?);
+        Ok(matches!((extents1, extents2), (Some(e1), Some(e2)) if !e1.is_empty() && e1 == e2))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (file1, file2);
+        Ok(false)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    /// _IOWR('f', 11, struct fiemap) from linux/fs.h
+    const FS_IOC_FIEMAP: u64 = 0xC020_660B;
+    /// Flush delayed allocations so that every extent has a physical location
+    const FIEMAP_FLAG_SYNC: u32 = 0x0001;
+    const FIEMAP_EXTENT_LAST: u32 = 0x0001;
+    /// Extent flags for which the physical location does not identify the data
+    const FIEMAP_EXTENT_UNRELIABLE: u32 = 0x0002 | 0x0004 | 0x0008 | 0x0200 | 0x0400;
+    /// Extents requested per ioctl call
+    const BATCH_SIZE: usize = 64;
+
+    /// struct fiemap_extent from linux/fiemap.h
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct FiemapExtent {
+        fe_logical: u64,
+        fe_physical: u64,
+        fe_length: u64,
+        fe_reserved64: [u64; 2],
+        fe_flags: u32,
+        fe_reserved: [u32; 3],
+    }
+
+    /// struct fiemap from linux/fiemap.h, followed by room for a batch of extents
+    #[repr(C)]
+    struct Fiemap {
+        fm_start: u64,
+        fm_length: u64,
+        fm_flags: u32,
+        fm_mapped_extents: u32,
+        fm_extent_count: u32,
+        fm_reserved: u32,
+        fm_extents: [FiemapExtent; BATCH_SIZE],
+    }
+
+    /// Returns the (logical, physical, length) extents of a file, or `None` if the file
+    /// system cannot report them or any extent has no reliable physical location.
+    pub fn extents(path: &Path) -> io::Result<Option<Vec<(u64, u64, u64)>>> {
+        let file = File::open(path)?;
+        let mut extents = Vec::new();
+        let mut start = 0;
+        loop {
+            let mut map: Fiemap = unsafe { std::mem::zeroed() };
+            map.fm_start = start;
+            map.fm_length = u64::MAX - start;
+            map.fm_flags = FIEMAP_FLAG_SYNC;
+            map.fm_extent_count = BATCH_SIZE as u32;
+            if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP as _, &mut map) } != 0 {
+                return Ok(None);
+            }
+            let mapped = &map.fm_extents[..map.fm_mapped_extents as usize];
+            for extent in mapped {
+                if extent.fe_flags & FIEMAP_EXTENT_UNRELIABLE != 0 {
+                    return Ok(None);
+                }
+                extents.push((extent.fe_logical, extent.fe_physical, extent.fe_length));
+            }
+            match mapped.last() {
+                Some(last) if last.fe_flags & FIEMAP_EXTENT_LAST == 0 => start = last.fe_logical + last.fe_length,
+                _ => return Ok(Some(extents)),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_file_shares_extents_with_itself() {
+        let tmp_dir = Builder::new().prefix("shared_extents").tempdir().unwrap();
+        let path = tmp_dir.path().join("a.bin");
+        fs::write(&path, vec![7u8; 65536]).unwrap();
+        // tmpfs and some other file systems do not report extents at all
+        if linux::extents(&path).unwrap().is_some() {
+            assert!(shares_extents(&path, &path).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let tmp_dir = Builder::new().prefix("shared_extents").tempdir().unwrap();
+        assert!(shares_extents(&tmp_dir.path().join("missing"), &tmp_dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::{bail, Context, Result};
+use crate::action_journal::{journaled, ActionJournal, JournalAction};
+use crate::actions::{apply_action, Action};
+use crate::compute_sha256::compute_sha256;
+use crate::is_duplicate_file::is_duplicate_file;
+use crate::localize::message;
+
+/// Extensions of the metadata files media applications keep next to a file: XMP
+/// sidecars of photo editors, subtitles, media center descriptions and the edits of
+/// Apple Photos.
+pub const SIDECAR_EXTENSIONS: [&str; 4] = ["xmp", "srt", "nfo", "aae"];
+
+/// What happens to the sidecars of a duplicate that an action removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidecarPolicy {
+    /// Delete or trash the sidecars with the duplicate.
+    Remove,
+    /// Rename the sidecars after the kept copy, next to it, unless it has its own.
+    Move,
+}
+
+impl FromStr for SidecarPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "remove" => Ok(SidecarPolicy::Remove),
+            "move" => Ok(SidecarPolicy::Move),
+            _ => Err(message("invalid-sidecar-policy", &[("value", &s)])),
+        }
+    }
+}
+
+/// A sidecar of a file, and the part of its name following the name of the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sidecar {
+    /// The path of the sidecar.
+    pub path: PathBuf,
+    /// The end of its name, such as `.xmp` for `IMG_0001.xmp`, `.CR2.xmp` for
+    /// `IMG_0001.CR2.xmp` or `.en.srt` for `movie.en.srt`.
+    pub suffix: String,
+}
+
+/// Whether a file name ends with one of the `SIDECAR_EXTENSIONS`, in any case
+fn is_sidecar_name(name: &str) -> bool {
+    Path::new(name).extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| SIDECAR_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Lists the sidecars of a file: the files next to it with one of the
+/// `SIDECAR_EXTENSIONS`, named after the file (`IMG_0001.CR2.xmp`) or after its stem
+/// (`IMG_0001.xmp`, `movie.en.srt`).
+///
+/// A sidecar named after the stem is shared by every file of that stem, such as both
+/// halves of a RAW+JPEG pair, so it is only listed when no other file has that stem.
+///
+/// # Arguments
+///
+/// * `path` - The file.
+///
+/// # Returns
+///
+/// The sidecars of the file, sorted by path, or an error if its directory cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::sidecar_files::sidecars;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// for name in ["movie.mkv", "movie.en.srt", "movie.nfo", "movies.nfo"] {
+///     fs::write(dir.path().join(name), name).unwrap();
+/// }
+/// let found = sidecars(&dir.path().join("movie.mkv")).unwrap();
+/// let suffixes: Vec<&str> = found.iter().map(|sidecar| sidecar.suffix.as_str()).collect();
+/// assert_eq!(suffixes, [".en.srt", ".nfo"]);
+/// ```
+pub fn sidecars(path: &Path) -> Result<Vec<Sidecar>> {
+    let (Some(directory), Some(name)) = (path.parent(), path.file_name().and_then(|name| name.to_str())) else {
+        return Ok(Vec::new());
+    };
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
+    let mut found = Vec::new();
+    let mut stem_shared = false;
+    for entry in fs::read_dir(directory).with_context(|| message("sidecar-list-failed", &[("directory", &directory.display())]))? {
+        let entry = entry?;
+        let Ok(sibling) = entry.file_name().into_string() else {
+            continue;
+        };
+        let Some(suffix) = sibling.strip_prefix(stem).filter(|suffix| suffix.starts_with('.')) else {
+            continue;
+        };
+        if !is_sidecar_name(&sibling) {
+            stem_shared |= sibling != name;
+            continue;
+        }
+        let named_after_file = sibling.len() > name.len() && sibling.starts_with(name) && sibling[name.len()..].starts_with('.');
+        found.push((Sidecar { path: entry.path(), suffix: suffix.to_string() }, named_after_file));
+    }
+    let mut found: Vec<Sidecar> = found.into_iter()
+        .filter(|(_, named_after_file)| *named_after_file || !stem_shared)
+        .map(|(sidecar, _)| sidecar)
+        .collect();
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+/// The path a sidecar takes when it follows `keeper`, named after its stem like it was
+/// named after the stem of the duplicate
+fn moved_path(keeper: &Path, sidecar: &Sidecar) -> PathBuf {
+    let stem = keeper.file_stem().unwrap_or_default().to_string_lossy();
+    keeper.with_file_name(format!("{}{}", stem, sidecar.suffix))
+}
+
+/// Handles the sidecars of a duplicate removed by `action`, so they do not outlive it.
+///
+/// With `SidecarPolicy::Remove`, the sidecars are deleted or trashed like the duplicate.
+/// With `SidecarPolicy::Move`, each sidecar is renamed after the kept copy, next to it;
+/// when the kept copy already has an identical sidecar, the duplicate's is removed, and
+/// when it has a different one, the duplicate's is left in place and reported.
+///
+/// # Arguments
+///
+/// * `policy` - The `SidecarPolicy` to apply.
+/// * `action` - The action that removed the duplicate, `Action::Delete` or `Action::Trash`.
+/// * `keeper` - The path of the copy that is kept.
+/// * `sidecars` - The sidecars of the duplicate, listed by `sidecars` before it was removed.
+/// * `journal` - The journal recording each sidecar removed or moved, with its own hash,
+///   or `None`.
+///
+/// # Returns
+///
+/// An error naming the first sidecar that could not be handled; the others are still
+/// handled.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::actions::Action;
+/// use dupefiles::sidecar_files::{handle_sidecars, sidecars, SidecarPolicy};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (keeper, duplicate) = (dir.path().join("IMG_0001.jpg"), dir.path().join("IMG_0001 (1).jpg"));
+/// fs::write(&keeper, "photo").unwrap();
+/// fs::write(&duplicate, "photo").unwrap();
+/// fs::write(dir.path().join("IMG_0001 (1).xmp"), "rating 5").unwrap();
+///
+/// let found = sidecars(&duplicate).unwrap();
+/// fs::remove_file(&duplicate).unwrap();
+/// handle_sidecars(SidecarPolicy::Move, Action::Delete, &keeper, &found, None).unwrap();
+/// assert_eq!(fs::read_to_string(dir.path().join("IMG_0001.xmp")).unwrap(), "rating 5");
+/// ```
+pub fn handle_sidecars(
+    policy: SidecarPolicy,
+    action: Action,
+    keeper: &Path,
+    sidecars: &[Sidecar],
+    mut journal: Option<&mut ActionJournal>,
+) -> Result<()> {
+    let mut first_error = None;
+    for sidecar in sidecars {
+        let handled = match policy {
+            SidecarPolicy::Remove => journaled_sidecar(journal.as_deref_mut(), action.into(), &sidecar.path, keeper, || {
+                apply_action(action, keeper, &sidecar.path)
+            }),
+            SidecarPolicy::Move => move_sidecar(action, keeper, sidecar, journal.as_deref_mut()),
+        };
+        if let Err(e) = handled {
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Makes a change to a sidecar with `change`, recording it in `journal`, when there is
+/// one, with the hash of the sidecar
+fn journaled_sidecar(
+    journal: Option<&mut ActionJournal>,
+    action: JournalAction,
+    sidecar: &Path,
+    keeper: &Path,
+    change: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let hash = if journal.is_some() { compute_sha256(sidecar)? } else { String::new() };
+    journaled(journal, action, sidecar, keeper, &hash, change)
+}
+
+/// Renames a sidecar after the kept copy, or removes it when the kept copy already has
+/// the same one
+fn move_sidecar(action: Action, keeper: &Path, sidecar: &Sidecar, journal: Option<&mut ActionJournal>) -> Result<()> {
+    let target = moved_path(keeper, sidecar);
+    if target.exists() {
+        if !is_duplicate_file(&target, &sidecar.path)? {
+            bail!("{}", message("sidecar-target-differs", &[("sidecar", &sidecar.path.display()), ("target", &target.display())]));
+        }
+        return journaled_sidecar(journal, action.into(), &sidecar.path, &target, || {
+            apply_action(action, &target, &sidecar.path)
+        });
+    }
+    journaled_sidecar(journal, JournalAction::Move, &sidecar.path, &target, || {
+        if fs::rename(&sidecar.path, &target).is_err() {
+            // Another file system
+            fs::copy(&sidecar.path, &target)
+                .and_then(|_| fs::remove_file(&sidecar.path))
+                .with_context(|| message("sidecar-move-failed", &[("sidecar", &sidecar.path.display()), ("target", &target.display())]))?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_stem_sidecar_shared_by_pair() {
+        let tmp_dir = Builder::new().prefix("sidecar_pair").tempdir().unwrap();
+        for name in ["IMG_0001.CR2", "IMG_0001.JPG", "IMG_0001.xmp", "IMG_0001.JPG.xmp"] {
+            fs::write(tmp_dir.path().join(name), name).unwrap();
+        }
+
+        let found = sidecars(&tmp_dir.path().join("IMG_0001.JPG")).unwrap();
+        assert_eq!(found, vec![Sidecar { path: tmp_dir.path().join("IMG_0001.JPG.xmp"), suffix: ".JPG.xmp".to_string() }]);
+    }
+
+    #[test]
+    fn test_move_keeps_differing_sidecar() {
+        let tmp_dir = Builder::new().prefix("sidecar_move").tempdir().unwrap();
+        let (keeper, duplicate) = (tmp_dir.path().join("keep/movie.mkv"), tmp_dir.path().join("copy/film.mkv"));
+        for (path, content) in [(&keeper, "video"), (&duplicate, "video")] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        fs::write(tmp_dir.path().join("keep/movie.nfo"), "<movie/>").unwrap();
+        fs::write(tmp_dir.path().join("copy/film.nfo"), "<movie>edited</movie>").unwrap();
+        fs::write(tmp_dir.path().join("copy/film.srt"), "1\n00:00:01,000 --> 00:00:02,000\nHi\n").unwrap();
+
+        let found = sidecars(&duplicate).unwrap();
+        apply_action(Action::Delete, &keeper, &duplicate).unwrap();
+        assert!(handle_sidecars(SidecarPolicy::Move, Action::Delete, &keeper, &found, None).is_err());
+        assert!(tmp_dir.path().join("keep/movie.srt").exists());
+        assert!(!tmp_dir.path().join("copy/film.srt").exists());
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("keep/movie.nfo")).unwrap(), "<movie/>");
+        assert!(tmp_dir.path().join("copy/film.nfo").exists());
+    }
+}
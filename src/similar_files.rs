@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use anyhow::Result;
+use serde::Serialize;
+use crate::duplicate_group::DuplicateGroup;
+use crate::find_duplicates::walk_files;
+use crate::fuzzy_hash::{compute_fuzzy_hash, FuzzyHash};
+use crate::path_encoding::{escape_path, serialize_path};
+use crate::scan_options::ScanOptions;
+use crate::localize::message;
+
+/// Two files that are not duplicates but whose fuzzy hashes are similar, such as a
+/// document and a slightly edited copy, or an image and a copy saved again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimilarPair {
+    /// The similarity of the files, from 1 to 100, as given by `FuzzyHash::similarity`.
+    pub score: u32,
+    /// The file found first.
+    #[serde(serialize_with = "serialize_path")]
+    pub first: PathBuf,
+    /// The size of the file found first, in bytes.
+    pub first_size: u64,
+    /// The other file.
+    #[serde(serialize_with = "serialize_path")]
+    pub second: PathBuf,
+    /// The size of the other file, in bytes.
+    pub second_size: u64,
+}
+
+/// A file of a `SimilarCluster`, with its similarity to the representative.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimilarMember {
+    /// The path of the file.
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    /// The size of the file, in bytes.
+    pub size: u64,
+    /// The similarity of the file to the representative of its cluster, from 1 to 100.
+    pub score: u32,
+}
+
+/// Files similar to one of them, the representative, which stands for the cluster in
+/// place of every pair the files make.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimilarCluster {
+    /// The file the others are similar to.
+    #[serde(serialize_with = "serialize_path")]
+    pub representative: PathBuf,
+    /// The size of the representative, in bytes.
+    pub size: u64,
+    /// The other files, the most similar first.
+    pub members: Vec<SimilarMember>,
+}
+
+/// Finds the files below `root` that are near duplicates of each other, by comparing
+/// their fuzzy hashes.
+///
+/// The files are walked with the filters of `options`, like a scan. Only the first
+/// copy of each duplicate group is hashed, since its other copies would be reported
+/// with the same files again; files stored in disc images and archives are left out.
+///
+/// # Arguments
+///
+/// * `root` - The canonical path of the scanned directory.
+/// * `options` - The options of the scan.
+/// * `groups` - The duplicate groups found by the scan.
+/// * `min_score` - The lowest similarity reported, from 1 to 100.
+///
+/// # Returns
+///
+/// The pairs of files at least `min_score` similar, the most similar first. Files that
+/// cannot be read are reported on stderr and skipped.
+pub fn find_similar(root: &Path, options: &ScanOptions, groups: &[DuplicateGroup], min_score: u32) -> Vec<SimilarPair> {
+    let copies: HashSet<&Path> = groups.iter()
+        .flat_map(|group| group.files.iter().skip(1))
+        .map(PathBuf::as_path)
+        .collect();
+    let mut files: Vec<(PathBuf, u64, FuzzyHash)> = Vec::new();
+    walk_files(root, options, Instant::now(), |candidate| {
+        if candidate.image.is_none() && !copies.contains(candidate.entry.path.as_path()) {
+            match compute_fuzzy_hash(&candidate.entry.path) {
+                Ok(hash) => files.push((candidate.entry.path, candidate.entry.size, hash)),
+                Err(e) => eprintln!("{}", message("scan-fuzzy-hash-failed", &[("file", &candidate.entry.path.display()), ("error", &e)])),
+            }
+        }
+        ControlFlow::Continue(())
+    });
+
+    // Only files sharing a substring of their signatures can be similar
+    let mut index: HashMap<(u32, [u8; 7]), Vec<usize>> = HashMap::new();
+    for (position, (_, _, hash)) in files.iter().enumerate() {
+        for substring in hash.substrings() {
+            index.entry(substring).or_default().push(position);
+        }
+    }
+    let mut compared: HashSet<(usize, usize)> = HashSet::new();
+    let mut pairs = Vec::new();
+    for positions in index.values() {
+        for (i, &first) in positions.iter().enumerate() {
+            for &second in &positions[i + 1..] {
+                if !compared.insert((first, second)) {
+                    continue;
+                }
+                let score = files[first].2.similarity(&files[second].2);
+                if score >= min_score.max(1) {
+                    pairs.push(SimilarPair {
+                        score,
+                        first: files[first].0.clone(),
+                        first_size: files[first].1,
+                        second: files[second].0.clone(),
+                        second_size: files[second].1,
+                    });
+                }
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.first.cmp(&b.first)).then_with(|| a.second.cmp(&b.second)));
+    pairs
+}
+
+/// Groups pairs of similar files into clusters, so that a set of many versions of the
+/// same photo or document is reported once rather than as every pair it makes.
+///
+/// The file similar to the most others becomes the representative of a cluster holding
+/// every file similar to it that is not in a cluster yet; the next file similar to the
+/// most others not in a cluster starts the next one, and so on. Every member is thus
+/// similar to its representative, while files similar to each other through a chain of
+/// slightly different versions only are not merged.
+///
+/// # Arguments
+///
+/// * `pairs` - The pairs found by `find_similar`.
+///
+/// # Returns
+///
+/// The clusters, the largest first, each with at least one member besides its
+/// representative.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use dupefiles::similar_files::{cluster_similar, SimilarPair};
+///
+/// let pair = |score, first: &str, second: &str| SimilarPair {
+///     score,
+///     first: PathBuf::from(first),
+///     first_size: 100,
+///     second: PathBuf::from(second),
+///     second_size: 100,
+/// };
+/// let pairs = [pair(90, "/a.jpg", "/b.jpg"), pair(80, "/a.jpg", "/c.jpg"), pair(70, "/b.jpg", "/c.jpg"), pair(60, "/c.jpg", "/d.jpg")];
+/// let clusters = cluster_similar(&pairs);
+/// assert_eq!(clusters.len(), 1);
+/// assert_eq!(clusters[0].representative, PathBuf::from("/c.jpg"));
+/// let members: Vec<(&str, u32)> = clusters[0].members.iter().map(|m| (m.path.to_str().unwrap(), m.score)).collect();
+/// assert_eq!(members, [("/a.jpg", 80), ("/b.jpg", 70), ("/d.jpg", 60)]);
+/// ```
+pub fn cluster_similar(pairs: &[SimilarPair]) -> Vec<SimilarCluster> {
+    let mut neighbors: HashMap<&Path, (u64, Vec<SimilarMember>)> = HashMap::new();
+    for pair in pairs {
+        for (file, size, other, other_size) in [
+            (&pair.first, pair.first_size, &pair.second, pair.second_size),
+            (&pair.second, pair.second_size, &pair.first, pair.first_size),
+        ] {
+            neighbors.entry(file.as_path()).or_insert_with(|| (size, Vec::new())).1
+                .push(SimilarMember { path: other.clone(), size: other_size, score: pair.score });
+        }
+    }
+    let mut files: Vec<&Path> = neighbors.keys().copied().collect();
+    files.sort_by(|a, b| neighbors[b].1.len().cmp(&neighbors[a].1.len()).then_with(|| a.cmp(b)));
+
+    let mut clustered: HashSet<&Path> = HashSet::new();
+    let mut clusters = Vec::new();
+    for file in files {
+        if clustered.contains(file) {
+            continue;
+        }
+        let (size, similar) = &neighbors[file];
+        let mut members: Vec<SimilarMember> = similar.iter()
+            .filter(|member| !clustered.contains(member.path.as_path()))
+            .cloned()
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        members.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+        clustered.insert(file);
+        clustered.extend(similar.iter().map(|member| member.path.as_path()));
+        clusters.push(SimilarCluster { representative: file.to_path_buf(), size: *size, members });
+    }
+    clusters.sort_by(|a, b| b.members.len().cmp(&a.members.len()).then_with(|| a.representative.cmp(&b.representative)));
+    clusters
+}
+
+/// Writes near duplicates as CSV, one row per pair of files.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the rows.
+/// * `pairs` - The pairs found by `find_similar`.
+///
+/// # Returns
+///
+/// Result
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use dupefiles::similar_files::{write_similar_csv, SimilarPair};
+///
+/// let pair = SimilarPair {
+///     score: 88,
+///     first: PathBuf::from("/docs/report.odt"),
+///     first_size: 20480,
+///     second: PathBuf::from("/docs/report (final).odt"),
+///     second_size: 20992,
+/// };
+/// let mut csv = Vec::new();
+/// write_similar_csv(&mut csv, &[pair]).unwrap();
+/// assert_eq!(String::from_utf8(csv).unwrap(), "SCORE,FILE1.NAME,FILE1.SIZE,FILE2.NAME,FILE2.SIZE\n\
+///     88,\"/docs/report.odt\",20480,\"/docs/report (final).odt\",20992\n");
+/// ```
+pub fn write_similar_csv(output: &mut impl Write, pairs: &[SimilarPair]) -> Result<()> {
+    writeln!(output, "SCORE,FILE1.NAME,FILE1.SIZE,FILE2.NAME,FILE2.SIZE")?;
+    for pair in pairs {
+        writeln!(output, "{},\"{}\",{},\"{}\",{}", pair.score,
+            escape_path(&pair.first).replace('"', "\"\""), pair.first_size,
+            escape_path(&pair.second).replace('"', "\"\""), pair.second_size)?;
+    }
+    Ok(())
+}
+
+/// Writes near duplicates as a JSON array of pairs of files.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the document.
+/// * `pairs` - The pairs found by `find_similar`.
+///
+/// # Returns
+///
+/// Result
+pub fn write_similar_json(output: &mut impl Write, pairs: &[SimilarPair]) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *output, pairs)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Writes clusters of near duplicates as CSV, one row per file: the representative of
+/// each cluster first, with an empty score, then its members with their similarity to it.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the rows.
+/// * `clusters` - The clusters made by `cluster_similar`.
+///
+/// # Returns
+///
+/// Result
+pub fn write_clusters_csv(output: &mut impl Write, clusters: &[SimilarCluster]) -> Result<()> {
+    writeln!(output, "CLUSTER,NAME,SIZE,SCORE")?;
+    for (number, cluster) in clusters.iter().enumerate() {
+        writeln!(output, "{},\"{}\",{},", number + 1, escape_path(&cluster.representative).replace('"', "\"\""), cluster.size)?;
+        for member in &cluster.members {
+            writeln!(output, "{},\"{}\",{},{}", number + 1, escape_path(&member.path).replace('"', "\"\""), member.size, member.score)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes clusters of near duplicates as a JSON array of clusters.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the document.
+/// * `clusters` - The clusters made by `cluster_similar`.
+///
+/// # Returns
+///
+/// Result
+pub fn write_clusters_json(output: &mut impl Write, clusters: &[SimilarCluster]) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *output, clusters)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder;
+    use crate::find_duplicates::find_duplicates;
+
+    #[test]
+    fn test_chain_not_merged() {
+        let pair = |first: &str, second: &str| SimilarPair {
+            score: 90,
+            first: PathBuf::from(first),
+            first_size: 10,
+            second: PathBuf::from(second),
+            second_size: 10,
+        };
+        let pairs = [pair("/a", "/b"), pair("/b", "/c"), pair("/c", "/d"), pair("/d", "/e")];
+        let mut csv = Vec::new();
+        write_clusters_csv(&mut csv, &cluster_similar(&pairs)).unwrap();
+
+        assert_eq!(String::from_utf8(csv).unwrap(), "\
+CLUSTER,NAME,SIZE,SCORE
+1,\"/b\",10,
+1,\"/a\",10,90
+1,\"/c\",10,90
+2,\"/d\",10,
+2,\"/e\",10,90
+");
+    }
+
+    #[test]
+    fn test_find_similar_skips_duplicates() {
+        let tmp_dir = Builder::new().prefix("similar_files").tempdir().unwrap();
+        let text: String = (0..500).map(|line| format!("Paragraph {} of the draft, still to be reviewed.\n", line * 31 % 997)).collect();
+        fs::write(tmp_dir.path().join("draft.txt"), &text).unwrap();
+        fs::write(tmp_dir.path().join("draft copy.txt"), &text).unwrap();
+        fs::write(tmp_dir.path().join("draft v2.txt"), text.replace("Paragraph 62 of", "Paragraph 62 (rewritten) of")).unwrap();
+        fs::write(tmp_dir.path().join("other.txt"), "unrelated ".repeat(2000)).unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+
+        let options = ScanOptions::default();
+        let groups = find_duplicates(&root, &options, None).unwrap();
+        let pairs = find_similar(&root, &options, &groups, 50);
+        assert_eq!(pairs.len(), 1);
+        let (edited, original) = if pairs[0].first.ends_with("draft v2.txt") {
+            (&pairs[0].first, &pairs[0].second)
+        } else {
+            (&pairs[0].second, &pairs[0].first)
+        };
+        assert_eq!(edited, &root.join("draft v2.txt"));
+        assert_eq!(original, &groups[0].files[0]);
+        assert!(pairs[0].score >= 80);
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::Serialize;
+use crate::duplicate_group::DuplicateGroup;
+use crate::path_encoding::{escape_path, serialize_path};
+
+/// A directory of the treemap of where duplicates are, with the space they waste in it
+/// and below it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TreemapNode {
+    /// The name of the directory, or the whole scanned path for the root.
+    pub name: String,
+    /// The path of the directory.
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    /// Bytes wasted by the duplicates in the directory and its subdirectories.
+    pub wasted_bytes: u64,
+    /// Bytes wasted by the duplicates directly in the directory, for visualizers that
+    /// add up the values of the children themselves.
+    pub direct_wasted_bytes: u64,
+    /// Number of duplicates in the directory and its subdirectories.
+    pub duplicate_files: usize,
+    /// The subdirectories holding duplicates, the most wasteful first.
+    pub children: Vec<TreemapNode>,
+}
+
+/// Duplicates counted directly in a directory and in its subdirectories, before the totals are known
+#[derive(Default)]
+struct DirectoryCounts {
+    wasted_bytes: u64,
+    duplicate_files: usize,
+    children: BTreeMap<OsString, DirectoryCounts>,
+}
+
+impl DirectoryCounts {
+    fn into_node(self, name: String, path: PathBuf) -> TreemapNode {
+        let mut children: Vec<TreemapNode> = self.children.into_iter()
+            .map(|(name, counts)| {
+                let child_path = path.join(&name);
+                counts.into_node(escape_path(Path::new(&name)), child_path)
+            })
+            .collect();
+        children.sort_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes).then_with(|| a.name.cmp(&b.name)));
+        TreemapNode {
+            name,
+            wasted_bytes: self.wasted_bytes + children.iter().map(|child| child.wasted_bytes).sum::<u64>(),
+            direct_wasted_bytes: self.wasted_bytes,
+            duplicate_files: self.duplicate_files + children.iter().map(|child| child.duplicate_files).sum::<usize>(),
+            path,
+            children,
+        }
+    }
+}
+
+/// Builds the treemap of the directories below `root` holding duplicates.
+///
+/// Like the summary, every member of a group but the first-seen one counts as wasted,
+/// except the copies sharing their data with it. Directories without duplicates below
+/// them are left out.
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups, each listing its first-seen member first.
+/// * `root` - The canonical path of the scanned directory.
+///
+/// # Returns
+///
+/// The node of `root`, holding the subdirectories with duplicates.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use dupefiles::duplicate_group::DuplicateGroup;
+/// use dupefiles::treemap::build_treemap;
+///
+/// let group = DuplicateGroup {
+///     hash: String::new(),
+///     size: 100,
+///     files: vec![PathBuf::from("/data/a.jpg"), PathBuf::from("/data/photos/2023/a.jpg")],
+///     reflinked: Vec::new(),
+///     digests: Default::default(),
+///     entries: Vec::new(),
+/// };
+/// let treemap = build_treemap(&[group], Path::new("/data"));
+/// assert_eq!((treemap.wasted_bytes, treemap.direct_wasted_bytes), (100, 0));
+/// assert_eq!(treemap.children[0].name, "photos");
+/// assert_eq!(treemap.children[0].children[0].path, Path::new("/data/photos/2023"));
+/// ```
+pub fn build_treemap(groups: &[DuplicateGroup], root: &Path) -> TreemapNode {
+    let mut tree = DirectoryCounts::default();
+    for group in groups {
+        for duplicate in group.files.iter().skip(1).filter(|duplicate| !group.reflinked.contains(duplicate)) {
+            // Duplicates outside the scanned directory are counted directly in it
+            let directory = duplicate.parent()
+                .and_then(|parent| parent.strip_prefix(root).ok())
+                .unwrap_or(Path::new(""));
+            let counts = directory.iter()
+                .fold(&mut tree, |counts, name| counts.children.entry(name.to_os_string()).or_default());
+            counts.wasted_bytes += group.size;
+            counts.duplicate_files += 1;
+        }
+    }
+    tree.into_node(escape_path(root), root.to_path_buf())
+}
+
+/// Writes a treemap as a single JSON document nesting every directory in its parent's
+/// `children`, as read by `d3.hierarchy` and similar.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the document.
+/// * `treemap` - The treemap built by `build_treemap`.
+///
+/// # Returns
+///
+/// Result
+pub fn write_treemap_json(output: &mut impl Write, treemap: &TreemapNode) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *output, treemap)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Writes a treemap as CSV, one row per directory giving its path and that of its
+/// parent, as read by the treemaps of Plotly and Google Charts. The parent of the root
+/// is empty.
+///
+/// # Arguments
+///
+/// * `output` - Where to write the rows.
+/// * `treemap` - The treemap built by `build_treemap`.
+///
+/// # Returns
+///
+/// Result
+pub fn write_treemap_csv(output: &mut impl Write, treemap: &TreemapNode) -> Result<()> {
+    writeln!(output, "PATH,PARENT,NAME,WASTED_BYTES,DIRECT_WASTED_BYTES,DUPLICATE_FILES")?;
+    write_csv_rows(output, treemap, "")
+}
+
+/// Write the row of `node` and those of its descendants, parents first
+fn write_csv_rows(output: &mut impl Write, node: &TreemapNode, parent: &str) -> Result<()> {
+    let path = escape_path(&node.path).replace('"', "\"\"");
+    writeln!(output, "\"{}\",\"{}\",\"{}\",{},{},{}", path, parent, node.name.replace('"', "\"\""),
+        node.wasted_bytes, node.direct_wasted_bytes, node.duplicate_files)?;
+    for child in &node.children {
+        write_csv_rows(output, child, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_treemap_csv() {
+        let group = DuplicateGroup {
+            hash: String::new(),
+            size: 10,
+            files: vec![
+                PathBuf::from("/data/keep.txt"),
+                PathBuf::from("/data/x/copy.txt"),
+                PathBuf::from("/data/x/y/copy.txt"),
+                PathBuf::from("/data/z/clone.txt"),
+            ],
+            reflinked: vec![PathBuf::from("/data/z/clone.txt")],
+            digests: BTreeMap::new(),
+            entries: Vec::new(),
+        };
+        let treemap = build_treemap(&[group], Path::new("/data"));
+        let mut csv = Vec::new();
+        write_treemap_csv(&mut csv, &treemap).unwrap();
+
+        assert_eq!(String::from_utf8(csv).unwrap(), "\
+PATH,PARENT,NAME,WASTED_BYTES,DIRECT_WASTED_BYTES,DUPLICATE_FILES
+\"/data\",\"\",\"/data\",20,0,2
+\"/data/x\",\"/data\",\"x\",20,10,2
+\"/data/x/y\",\"/data/x\",\"y\",10,10,1
+");
+    }
+}
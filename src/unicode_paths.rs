@@ -0,0 +1,121 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use crate::duplicate_group::DuplicateGroup;
+
+/// Returns text in Unicode Normalization Form C, where accented letters are written as
+/// one character, as on Linux and Windows, rather than as a letter followed by its
+/// accent, as macOS names files.
+///
+/// # Examples
+///
+/// ```
+/// use dupefiles::unicode_paths::nfc_str;
+///
+/// assert_eq!(nfc_str("cafe\u{301}.jpg"), "caf\u{e9}.jpg");
+/// assert_eq!(nfc_str("caf\u{e9}.jpg"), "caf\u{e9}.jpg");
+/// ```
+pub fn nfc_str(text: &str) -> Cow<'_, str> {
+    if is_nfc(text) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.nfc().collect())
+    }
+}
+
+/// Returns a path in Unicode Normalization Form C, see `nfc_str`. Paths that are not
+/// valid Unicode are returned as they are.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::unicode_paths::nfc_path;
+///
+/// assert_eq!(nfc_path(Path::new("/photos/cafe\u{301}.jpg")), Path::new("/photos/caf\u{e9}.jpg"));
+/// ```
+pub fn nfc_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str().map(nfc_str) {
+        Some(Cow::Owned(normalized)) => Cow::Owned(PathBuf::from(normalized)),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Returns whether two paths name the same path once both are in Unicode Normalization
+/// Form C, so a name copied from macOS matches the same name typed on Linux.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::unicode_paths::same_path;
+///
+/// assert!(same_path(Path::new("/photos/cafe\u{301}.jpg"), Path::new("/photos/caf\u{e9}.jpg")));
+/// assert!(!same_path(Path::new("/photos/cafe.jpg"), Path::new("/photos/caf\u{e9}.jpg")));
+/// ```
+pub fn same_path(first: &Path, second: &Path) -> bool {
+    first == second || nfc_path(first) == nfc_path(second)
+}
+
+/// Returns whether `path` is `directory` or below it once both are in Unicode
+/// Normalization Form C, like `Path::starts_with`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use dupefiles::unicode_paths::starts_with;
+///
+/// assert!(starts_with(Path::new("/photos/cafe\u{301}/a.jpg"), Path::new("/photos/caf\u{e9}")));
+/// assert!(!starts_with(Path::new("/photos/cafe/a.jpg"), Path::new("/photos/caf\u{e9}")));
+/// ```
+pub fn starts_with(path: &Path, directory: &Path) -> bool {
+    path.starts_with(directory) || nfc_path(path).starts_with(nfc_path(directory))
+}
+
+/// Returns a copy of the groups with every path in Unicode Normalization Form C, so
+/// reports of trees copied between macOS and other systems name files alike.
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups.
+///
+/// # Returns
+///
+/// The normalized groups, in the same order.
+pub fn normalize_groups(groups: &[DuplicateGroup]) -> Vec<DuplicateGroup> {
+    groups.iter()
+        .map(|group| {
+            let mut group = group.clone();
+            for path in group.files.iter_mut().chain(group.reflinked.iter_mut()) {
+                *path = nfc_path(path).into_owned();
+            }
+            for entry in &mut group.entries {
+                entry.path = nfc_path(&entry.path).into_owned();
+            }
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_groups() {
+        let group = DuplicateGroup {
+            hash: "5891b5b5".to_string(),
+            size: 10,
+            files: vec![PathBuf::from("/data/cafe\u{301}.jpg"), PathBuf::from("/data/caf\u{e9} (1).jpg")],
+            reflinked: vec![PathBuf::from("/data/cafe\u{301}.jpg")],
+            digests: Default::default(),
+            entries: Vec::new(),
+        };
+        let normalized = &normalize_groups(std::slice::from_ref(&group))[0];
+        assert_eq!(normalized.files[0], Path::new("/data/caf\u{e9}.jpg"));
+        assert_eq!(normalized.files[1], group.files[1]);
+        assert_eq!(normalized.reflinked, vec![normalized.files[0].clone()]);
+        assert_eq!(normalized.hash, group.hash);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::Arc;
+
+/// What a `WalkFilter` decides for a file or directory met by the walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// Apply the built-in filters: hidden paths, `extensions`, `exclude_extensions`
+    /// and files below `min_size` are skipped as without a filter.
+    Default,
+    /// Scan the file even if it is hidden, small or filtered out by its extension. For
+    /// a directory, descend into it; its files are decided on their own.
+    Include,
+    /// Skip the file, or the directory and everything below it.
+    Exclude,
+}
+
+/// The predicate wrapped by `WalkFilter`
+type FilterFn = dyn Fn(&Path, &Metadata) -> FilterDecision + Send + Sync;
+
+/// A predicate deciding which files and directories a local scan walks, on top of or
+/// instead of the built-in filters, for policies `ScanOptions` does not cover.
+///
+/// The predicate is called with the path and metadata of every directory below the
+/// scanned one and of every file, after symbolic links are followed. Remote sources,
+/// alternate streams and the files in disc images are not passed to it.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use dupefiles::find_duplicates::find_duplicates;
+/// use dupefiles::scan_options::ScanOptions;
+/// use dupefiles::walk_filter::{FilterDecision, WalkFilter};
+///
+/// let dir = tempfile::Builder::new().prefix("walk_filter").tempdir().unwrap();
+/// fs::create_dir(dir.path().join("node_modules")).unwrap();
+/// fs::write(dir.path().join("a.txt"), "copy").unwrap();
+/// fs::write(dir.path().join("node_modules/b.txt"), "copy").unwrap();
+/// fs::write(dir.path().join(".c.txt"), "copy").unwrap();
+///
+/// let options = ScanOptions {
+///     walk_filter: Some(WalkFilter::new(|path, metadata| {
+///         if metadata.is_dir() && path.ends_with("node_modules") {
+///             FilterDecision::Exclude
+///         } else if path.ends_with(".c.txt") {
+///             FilterDecision::Include
+///         } else {
+///             FilterDecision::Default
+///         }
+///     })),
+///     ..Default::default()
+/// };
+/// let mut output = tempfile::tempfile().unwrap();
+/// let groups = find_duplicates(dir.path(), &options, Some(&mut output)).unwrap();
+/// assert_eq!(groups[0].files.len(), 2);
+/// assert!(groups[0].files.iter().all(|file| !file.starts_with(dir.path().join("node_modules"))));
+/// ```
+#[derive(Clone)]
+pub struct WalkFilter(Arc<FilterFn>);
+
+impl WalkFilter {
+    /// Wraps a predicate so it can be set in `ScanOptions::walk_filter`.
+    pub fn new<F>(filter: F) -> Self
+    where
+        F: Fn(&Path, &Metadata) -> FilterDecision + Send + Sync + 'static,
+    {
+        WalkFilter(Arc::new(filter))
+    }
+
+    /// Returns the decision of the predicate for `path`.
+    pub fn decide(&self, path: &Path, metadata: &Metadata) -> FilterDecision {
+        (self.0)(path, metadata)
+    }
+}
+
+impl fmt::Debug for WalkFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("WalkFilter")
+    }
+}
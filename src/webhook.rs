@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use serde::{Serialize, Serializer};
+use crate::path_encoding::serialize_path;
+use crate::scan_summary::{ScanSummary, StopReason};
+use crate::localize::message;
+
+/// How long a webhook may take to answer before the notification is given up
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the scan a notification is about ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanEvent {
+    /// The scan ended and its report was written, even if it stopped early.
+    ScanFinished,
+    /// The scan failed before writing its report.
+    ScanFailed,
+}
+
+/// The summary of a scan posted to a webhook when it ends, for automation to start
+/// follow-up jobs such as opening a ticket or launching a cleanup.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanNotification {
+    /// How the scan ended.
+    pub event: ScanEvent,
+    /// The version of dupefiles.
+    pub version: &'static str,
+    /// The scanned directory, or the URL of a remote source.
+    #[serde(serialize_with = "serialize_path")]
+    pub directory: PathBuf,
+    /// Where the report was written, or `None` when it was written to stdout.
+    #[serde(serialize_with = "serialize_optional_path")]
+    pub report: Option<PathBuf>,
+    /// Number of duplicate groups found.
+    pub duplicate_groups: usize,
+    /// Number of files duplicating the first-seen member of their group.
+    pub duplicate_files: usize,
+    /// Bytes that would be freed by keeping a single copy of every group.
+    pub wasted_bytes: u64,
+    /// Why the scan stopped early, if it did.
+    pub stopped: Option<StopReason>,
+    /// How long the scan ran, in seconds.
+    pub elapsed_seconds: f64,
+    /// Why the scan failed, for `ScanEvent::ScanFailed`.
+    pub error: Option<String>,
+}
+
+/// Serializes an optional path like `serialize_path`, or as `null`
+fn serialize_optional_path<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => serialize_path(path, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl ScanNotification {
+    /// The notification of a scan that ended with `summary`.
+    pub fn finished(directory: PathBuf, report: Option<PathBuf>, summary: &ScanSummary, elapsed: Duration) -> Self {
+        ScanNotification {
+            event: ScanEvent::ScanFinished,
+            version: env!("CARGO_PKG_VERSION"),
+            directory,
+            report,
+            duplicate_groups: summary.duplicate_groups,
+            duplicate_files: summary.duplicate_files,
+            wasted_bytes: summary.wasted_bytes,
+            stopped: summary.stopped,
+            elapsed_seconds: elapsed.as_secs_f64(),
+            error: None,
+        }
+    }
+
+    /// The notification of a scan that failed with `error`.
+    pub fn failed(directory: PathBuf, report: Option<PathBuf>, error: &anyhow::Error, elapsed: Duration) -> Self {
+        ScanNotification {
+            event: ScanEvent::ScanFailed,
+            error: Some(format!("{:#}", error)),
+            ..ScanNotification::finished(directory, report, &ScanSummary::default(), elapsed)
+        }
+    }
+}
+
+/// Posts a notification to a webhook as a JSON object.
+///
+/// # Arguments
+///
+/// * `url` - The `http://` or `https://` URL of the webhook.
+/// * `notification` - The notification to post.
+///
+/// # Returns
+///
+/// An error if the webhook cannot be reached within 30 seconds or answers with an
+/// error status.
+pub fn post_notification(url: &str, notification: &ScanNotification) -> Result<()> {
+    let body = serde_json::to_string(notification)?;
+    ureq::post(url)
+        .timeout(TIMEOUT)
+        .set("Content-Type", "application/json")
+        .set("User-Agent", concat!("dupefiles/", env!("CARGO_PKG_VERSION")))
+        .send_string(&body)
+        .with_context(|| message("webhook-failed", &[("url", &url)]))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_post_notification() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/dupefiles", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let summary = ScanSummary { duplicate_groups: 1, duplicate_files: 2, wasted_bytes: 4096, ..Default::default() };
+        let notification = ScanNotification::finished(PathBuf::from("/data"), None, &summary, Duration::from_secs(2));
+        post_notification(&url, &notification).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(body["event"], "scan_finished");
+        assert_eq!(body["directory"], "/data");
+        assert_eq!(body["report"], serde_json::Value::Null);
+        assert_eq!(body["wasted_bytes"], 4096);
+    }
+}
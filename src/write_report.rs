@@ -0,0 +1,532 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::duplicate_group::{DuplicateGroup, SuggestedAction};
+use crate::file_entry::{allocated_size, FileEntry};
+use crate::hash_algorithm::HashAlgorithm;
+use crate::human_readable_size::human_readable_size;
+use crate::scan_summary::{ActionSavings, ExtensionStats, ScanSummary, StopReason};
+use crate::path_encoding::{escape_path, serialize_path};
+use crate::localize::message;
+
+/// Format of the report written by `find_duplicates`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One CSV row per pair of duplicates, as selected by `PairMode`.
+    #[default]
+    Csv,
+    /// A single JSON document holding every group and the scan summary.
+    Json,
+    /// An ncdu JSON export of the wasted copies, for browsing with `ncdu -f`.
+    Ncdu,
+    /// A hashdeep audit manifest listing the size and SHA256 hash of every file hashed.
+    Hashdeep,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ncdu" => Ok(OutputFormat::Ncdu),
+            "hashdeep" => Ok(OutputFormat::Hashdeep),
+            _ => Err(message("invalid-output-format", &[("value", &s)])),
+        }
+    }
+}
+
+/// Which pairs of a duplicate group are written as CSV rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PairMode {
+    /// One row per duplicate, paired with the first-seen member of its group.
+    #[default]
+    Canonical,
+    /// One row per pair of members, each duplicate paired with every member found
+    /// before it, so a group of N copies gives N * (N - 1) / 2 rows.
+    All,
+}
+
+impl FromStr for PairMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "canonical" => Ok(PairMode::Canonical),
+            "all" => Ok(PairMode::All),
+            _ => Err(message("invalid-pair-mode", &[("value", &s)])),
+        }
+    }
+}
+
+/// Write a line either to the output file or stdout
+fn write_line(output_file: &mut Option<&mut fs::File>, line: &str) -> Result<()> {
+    match output_file {
+        Some(file) => writeln!(file, "{}", line)?,
+        None => println!("{}", line),
+    }
+    Ok(())
+}
+
+/// What produced a report, so reports made with different algorithms or versions are not
+/// mixed up when compared or merged later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportMetadata {
+    /// The name of the program, `dupefiles`.
+    pub tool: &'static str,
+    /// The version of the program.
+    pub version: &'static str,
+    /// The algorithm of the group hashes, `sha256`. Hashes of part of the content only
+    /// carry the prefix of their content mode, such as `audio:`.
+    pub hash_algorithm: &'static str,
+    /// The other digests reported with each group.
+    pub also_hash: Vec<HashAlgorithm>,
+}
+
+impl ReportMetadata {
+    /// Describes a report of this version of dupefiles, with the other digests `also_hash`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dupefiles::hash_algorithm::HashAlgorithm;
+    /// use dupefiles::write_report::ReportMetadata;
+    ///
+    /// let metadata = ReportMetadata::new(&[HashAlgorithm::Md5]);
+    /// assert_eq!(metadata.to_string(), format!("dupefiles {}, hash sha256, also md5", env!("CARGO_PKG_VERSION")));
+    /// ```
+    pub fn new(also_hash: &[HashAlgorithm]) -> Self {
+        ReportMetadata {
+            tool: "dupefiles",
+            version: env!("CARGO_PKG_VERSION"),
+            hash_algorithm: "sha256",
+            also_hash: also_hash.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for ReportMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}, hash {}", self.tool, self.version, self.hash_algorithm)?;
+        if !self.also_hash.is_empty() {
+            let names: Vec<&str> = self.also_hash.iter().map(|algorithm| algorithm.name()).collect();
+            write!(f, ", also {}", names.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// The apparent size and the space allocated on disk of every member, from the entries
+/// of the scan or else from the files as they are now. Members compared by content only
+/// may differ in size; those that cannot be queried take the size of the group and an
+/// unknown allocation
+fn member_sizes(group: &DuplicateGroup) -> Vec<(u64, Option<u64>)> {
+    group.files.iter().enumerate()
+        .map(|(index, path)| match group.entries.get(index) {
+            Some(entry) => (entry.size, entry.allocated),
+            None => fs::metadata(path).map_or((group.size, None), |metadata| (metadata.len(), allocated_size(&metadata))),
+        })
+        .collect()
+}
+
+/// Escape a path for a quoted CSV field, doubling quotes so the path round-trips exactly
+fn csv_path(path: &Path) -> String {
+    escape_path(path).replace('"', "\"\"")
+}
+
+/// Writes duplicate groups as CSV, one row per duplicate paired with the first-seen
+/// member of its group, or with every member found before it.
+///
+/// The header row is only written the first time this function is called, so the
+/// results of several scans can be appended to the same output. It is the first line,
+/// so CSV tools read it as such, and the `ReportMetadata` is only written in JSON. The
+/// space allocated on disk to both files of each row ends it, in the `DUPE1.ALLOCATED`
+/// and `DUPE2.ALLOCATED` columns, empty when unknown.
+///
+/// # Arguments
+///
+/// * `output_file` - Optional file to write to (if None, writes to stdout)
+/// * `groups` - The duplicate groups to write.
+/// * `pairs` - The `PairMode` selecting which pairs of members are written.
+/// * `also_hash` - The other digests of each group written after its identifier, one
+///   column each.
+///
+/// # Returns
+///
+/// Result
+pub fn write_csv(
+    output_file: &mut Option<&mut fs::File>,
+    groups: &[DuplicateGroup],
+    pairs: PairMode,
+    also_hash: &[HashAlgorithm],
+) -> Result<()> {
+    static mut HEADER_PRINTED_ONCE: bool = false;
+
+    // Write CSV header if needed
+    unsafe {
+        if !HEADER_PRINTED_ONCE {
+            let mut header = "DUPE1.NAME,DUPE1.SIZE,DUPE1.HRSIZE,DUPE2.NAME,DUPE2.SIZE,DUPE2.HRSIZE,GROUP_ID".to_string();
+            for algorithm in also_hash {
+                header.push_str(&format!(",{}", algorithm.name().to_uppercase()));
+            }
+            header.push_str(",DUPE1.ALLOCATED,DUPE2.ALLOCATED");
+            write_line(output_file, &header)?;
+            HEADER_PRINTED_ONCE = true;
+        }
+    }
+
+    for group in groups {
+        let sizes = member_sizes(group);
+        let allocated = |index: usize| sizes[index].1.map_or(String::new(), |bytes| bytes.to_string());
+        // The identifier and other digests of the group end every row
+        let mut group_columns = group.id();
+        for algorithm in also_hash {
+            group_columns.push_str(&format!(",{}", group.digests.get(algorithm).map_or("", String::as_str)));
+        }
+        for (index, duplicate) in group.files.iter().enumerate().skip(1) {
+            let originals = match pairs {
+                PairMode::Canonical => 0..1,
+                PairMode::All => 0..index,
+            };
+            for original in originals {
+                let output = format!("\"{}\",{},\"{}\",\"{}\",{},\"{}\",{},{},{}",
+                    csv_path(&group.files[original]), sizes[original].0, human_readable_size(sizes[original].0),
+                    csv_path(duplicate), sizes[index].0, human_readable_size(sizes[index].0), group_columns,
+                    allocated(original), allocated(index));
+                write_line(output_file, &output)?;
+            }
+        }
+    }
+
+    if groups.is_empty() {
+        write_line(output_file, &message("report-no-duplicates", &[]))?;
+    }
+    Ok(())
+}
+
+/// The JSON report document.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    metadata: ReportMetadata,
+    groups: Vec<JsonGroup<'a>>,
+    summary: &'a ScanSummary,
+}
+
+/// A duplicate group of the JSON report, led by its identifier and followed by the
+/// apparent and allocated size of each member, whether its duplicates can be linked and
+/// the action suggested for them
+#[derive(Serialize)]
+pub(crate) struct JsonGroup<'a> {
+    id: String,
+    #[serde(flatten)]
+    group: &'a DuplicateGroup,
+    sizes: Vec<u64>,
+    allocated: Vec<Option<u64>>,
+    hardlink_possible: Option<bool>,
+    reflink_possible: Option<bool>,
+    suggested_action: SuggestedAction,
+}
+
+impl<'a> JsonGroup<'a> {
+    pub(crate) fn new(group: &'a DuplicateGroup) -> Self {
+        let (sizes, allocated) = member_sizes(group).into_iter().unzip();
+        JsonGroup {
+            id: group.id(),
+            group,
+            sizes,
+            allocated,
+            hardlink_possible: group.hardlink_possible(),
+            reflink_possible: group.reflink_possible(),
+            suggested_action: group.suggested_action(),
+        }
+    }
+}
+
+/// Writes the `ReportMetadata`, duplicate groups and the scan summary as a single JSON
+/// document. Each group is led by an `id` field holding `DuplicateGroup::id`, the
+/// `GROUP_ID` of CSV reports, followed by the `sizes` and `allocated` arrays holding
+/// the apparent size and the space allocated on disk of each file, in the order of
+/// `files`; allocations are `null` when unknown. It ends with the
+/// `DuplicateGroup::hardlink_possible` and `DuplicateGroup::reflink_possible` flags,
+/// `null` when unknown, and the `DuplicateGroup::suggested_action`.
+///
+/// # Arguments
+///
+/// * `output_file` - Optional file to write to (if None, writes to stdout)
+/// * `groups` - The duplicate groups to write.
+/// * `summary` - The `ScanSummary` of the groups.
+/// * `also_hash` - The other digests of each group.
+///
+/// # Returns
+///
+/// Result
+pub fn write_json(
+    output_file: &mut Option<&mut fs::File>,
+    groups: &[DuplicateGroup],
+    summary: &ScanSummary,
+    also_hash: &[HashAlgorithm],
+) -> Result<()> {
+    let metadata = ReportMetadata::new(also_hash);
+    let groups = groups.iter().map(JsonGroup::new).collect();
+    let json = serde_json::to_string_pretty(&JsonReport { metadata, groups, summary })?;
+    write_line(output_file, &json)
+}
+
+/// The JSON document of `write_stats`
+#[derive(Serialize)]
+struct JsonStats<'a> {
+    metadata: ReportMetadata,
+    duplicate_groups: usize,
+    duplicate_files: usize,
+    wasted_bytes: u64,
+    savings: ActionSavings,
+    by_extension: &'a BTreeMap<String, ExtensionStats>,
+    stopped: Option<StopReason>,
+    min_size: u64,
+}
+
+/// Writes the aggregate figures of a scan without any path, for collecting duplication
+/// metrics from many machines without their file listings.
+///
+/// As JSON, the document holds the `ReportMetadata`, the totals, the figures per
+/// extension and why the scan stopped early, if it did. As CSV, a row per extension
+/// gives its duplicate files and wasted bytes, after the header row, and a last row for
+/// the `*` extension gives the totals.
+///
+/// # Arguments
+///
+/// * `output_file` - Optional file to write to (if None, writes to stdout)
+/// * `summary` - The `ScanSummary` of the scan; its `wasted_by_directory` is left out.
+/// * `format` - `OutputFormat::Csv` or `OutputFormat::Json`.
+///
+/// # Returns
+///
+/// Result, or an error for `OutputFormat::Ncdu` and `OutputFormat::Hashdeep`, which
+/// describe paths only.
+pub fn write_stats(output_file: &mut Option<&mut fs::File>, summary: &ScanSummary, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            let stats = JsonStats {
+                metadata: ReportMetadata::new(&[]),
+                duplicate_groups: summary.duplicate_groups,
+                duplicate_files: summary.duplicate_files,
+                wasted_bytes: summary.wasted_bytes,
+                savings: summary.savings,
+                by_extension: &summary.by_extension,
+                stopped: summary.stopped,
+                min_size: summary.min_size,
+            };
+            write_line(output_file, &serde_json::to_string_pretty(&stats)?)
+        }
+        OutputFormat::Csv => {
+            write_line(output_file, "EXTENSION,DUPLICATE_FILES,WASTED_BYTES")?;
+            for (extension, stats) in &summary.by_extension {
+                write_line(output_file, &format!("\"{}\",{},{}", extension.replace('"', "\"\""), stats.duplicate_files, stats.wasted_bytes))?;
+            }
+            write_line(output_file, &format!("\"*\",{},{}", summary.duplicate_files, summary.wasted_bytes))
+        }
+        OutputFormat::Ncdu => anyhow::bail!("{}", message("stats-format-unsupported", &[("format", &"ncdu")])),
+        OutputFormat::Hashdeep => anyhow::bail!("{}", message("stats-format-unsupported", &[("format", &"hashdeep")])),
+    }
+}
+
+/// The JSON document of `write_unique`
+#[derive(Serialize)]
+struct JsonUnique<'a> {
+    metadata: ReportMetadata,
+    files: Vec<JsonUniqueFile<'a>>,
+    stopped: Option<StopReason>,
+}
+
+/// A file of the JSON document of `write_unique`
+#[derive(Serialize)]
+struct JsonUniqueFile<'a> {
+    #[serde(serialize_with = "serialize_path")]
+    path: &'a Path,
+    size: u64,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    hash: &'a str,
+}
+
+/// Writes the files that have no duplicate, as found by `find_unique_files`.
+///
+/// As JSON, the document holds the `ReportMetadata`, the files with their size and
+/// their hash when they were hashed, and why the scan stopped early, if it did. As CSV,
+/// a row per file gives its path, size and hash, empty when the file was not hashed,
+/// after the header row.
+///
+/// # Arguments
+///
+/// * `output_file` - Optional file to write to (if None, writes to stdout)
+/// * `files` - The unique files.
+/// * `stopped` - Why the scan stopped early, if it did.
+/// * `format` - `OutputFormat::Csv` or `OutputFormat::Json`.
+///
+/// # Returns
+///
+/// Result, or an error for `OutputFormat::Ncdu` and `OutputFormat::Hashdeep`.
+pub fn write_unique(
+    output_file: &mut Option<&mut fs::File>,
+    files: &[FileEntry],
+    stopped: Option<StopReason>,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            let files = files.iter()
+                .map(|file| JsonUniqueFile { path: &file.path, size: file.size, hash: &file.hash })
+                .collect();
+            let unique = JsonUnique { metadata: ReportMetadata::new(&[]), files, stopped };
+            write_line(output_file, &serde_json::to_string_pretty(&unique)?)
+        }
+        OutputFormat::Csv => {
+            write_line(output_file, "NAME,SIZE,HRSIZE,HASH")?;
+            for file in files {
+                write_line(output_file, &format!("\"{}\",{},\"{}\",{}",
+                    csv_path(&file.path), file.size, human_readable_size(file.size), file.hash))?;
+            }
+            Ok(())
+        }
+        OutputFormat::Ncdu => anyhow::bail!("{}", message("unique-format-unsupported", &[("format", &"ncdu")])),
+        OutputFormat::Hashdeep => anyhow::bail!("{}", message("unique-format-unsupported", &[("format", &"hashdeep")])),
+    }
+}
+
+/// The wasted copies below a directory of an ncdu export
+#[derive(Default)]
+struct NcduDirectory {
+    files: Vec<Value>,
+    directories: BTreeMap<String, NcduDirectory>,
+}
+
+impl NcduDirectory {
+    /// The directory as an ncdu JSON array: its info block followed by its entries
+    fn into_value(self, name: String) -> Value {
+        let mut entries = vec![json!({ "name": name })];
+        entries.extend(self.files);
+        for (name, directory) in self.directories {
+            entries.push(directory.into_value(name));
+        }
+        Value::Array(entries)
+    }
+}
+
+/// Writes the wasted copies of the duplicate groups as an ncdu JSON export, so the space
+/// they waste can be browsed with `ncdu -f`.
+///
+/// Every member of a group but the first-seen one is listed below `root` with its size,
+/// except those sharing their data with the first member, so directory totals add up to
+/// the wasted space. Each file also carries the identifier of its group and the path of
+/// the first member in the `dupefiles_group` and `dupefiles_original` fields, which ncdu
+/// ignores.
+///
+/// # Arguments
+///
+/// * `output_file` - Optional file to write to (if None, writes to stdout)
+/// * `groups` - The duplicate groups to write.
+/// * `root` - The scanned directory, the top of the exported tree.
+///
+/// # Returns
+///
+/// Result
+pub fn write_ncdu(output_file: &mut Option<&mut fs::File>, groups: &[DuplicateGroup], root: &Path) -> Result<()> {
+    let mut tree = NcduDirectory::default();
+    for group in groups {
+        let sizes = member_sizes(group);
+        for (index, duplicate) in group.files.iter().enumerate().skip(1).filter(|(_, duplicate)| !group.reflinked.contains(duplicate)) {
+            let relative = duplicate.strip_prefix(root).unwrap_or(duplicate);
+            let mut names: Vec<String> = relative.iter().map(|name| escape_path(Path::new(name))).collect();
+            let Some(name) = names.pop() else {
+                continue;
+            };
+            let directory = names.into_iter().fold(&mut tree, |directory, name| directory.directories.entry(name).or_default());
+            // Files that cannot be queried any more, or that are not local, count their size
+            let (size, allocated) = sizes[index];
+            directory.files.push(json!({
+                "name": name,
+                "asize": size,
+                "dsize": allocated.unwrap_or(size),
+                "dupefiles_group": group.id(),
+                "dupefiles_original": escape_path(&group.files[0]),
+            }));
+        }
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let export = json!([
+        1,
+        2,
+        { "progname": "dupefiles", "progver": env!("CARGO_PKG_VERSION"), "timestamp": timestamp },
+        tree.into_value(escape_path(root)),
+    ]);
+    write_line(output_file, &serde_json::to_string(&export)?)
+}
+
+/// Returns an entry for every member of the duplicate groups, with the hash of its group,
+/// for `write_hashdeep` to list the files of a report.
+pub fn group_entries(groups: &[DuplicateGroup]) -> Vec<FileEntry> {
+    groups.iter()
+        .flat_map(|group| {
+            let sizes = member_sizes(group);
+            group.files.iter().zip(sizes).map(|(path, (size, _))| FileEntry::unknown(path, size, &group.hash))
+        })
+        .collect()
+}
+
+/// Writes files with their size and SHA256 hash as a hashdeep audit manifest, so a scan
+/// doubles as a manifest generator for `hashdeep -a -k`, forensic and backup tools
+/// reading that format, and `--known-hashes`.
+///
+/// The manifest starts with the `%%%% HASHDEEP-1.0` and `%%%% size,sha256,filename`
+/// headers, then `##` comment lines naming `root` and the `ReportMetadata`, followed by a
+/// `size,sha256,filename` line per file. Files whose hash is not the SHA256 hash of their
+/// whole content, such as those compared by content only or by samples, are left out.
+///
+/// # Arguments
+///
+/// * `output_file` - Optional file to write to (if None, writes to stdout)
+/// * `files` - The files to list, with their size and hash.
+/// * `root` - The scanned directory.
+///
+/// # Returns
+///
+/// Result
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::path::Path;
+/// use dupefiles::file_entry::FileEntry;
+/// use dupefiles::write_report::write_hashdeep;
+///
+/// let hash = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+/// let files = [FileEntry::unknown(Path::new("/data/a, b.txt"), 4, hash), FileEntry::unknown(Path::new("/data/c.mp3"), 9, "audio:00")];
+/// let mut output = tempfile::NamedTempFile::new().unwrap();
+/// write_hashdeep(&mut Some(output.as_file_mut()), &files, Path::new("/data")).unwrap();
+///
+/// let manifest = fs::read_to_string(output.path()).unwrap();
+/// assert!(manifest.starts_with("%%%% HASHDEEP-1.0\n%%%% size,sha256,filename\n## Invoked from: /data\n"));
+/// assert!(manifest.ends_with(&format!("##\n4,{},/data/a, b.txt\n", hash)));
+/// ```
+pub fn write_hashdeep(output_file: &mut Option<&mut fs::File>, files: &[FileEntry], root: &Path) -> Result<()> {
+    write_line(output_file, "%%%% HASHDEEP-1.0")?;
+    write_line(output_file, "%%%% size,sha256,filename")?;
+    write_line(output_file, &format!("## Invoked from: {}", escape_path(root)))?;
+    write_line(output_file, &format!("## {}", ReportMetadata::new(&[])))?;
+    write_line(output_file, "##")?;
+    for file in files {
+        if file.hash.len() == 64 && file.hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            write_line(output_file, &format!("{},{},{}", file.size, file.hash, escape_path(&file.path)))?;
+        }
+    }
+    Ok(())
+}